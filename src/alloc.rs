@@ -1,11 +1,16 @@
 //! builtin lisp data structures.
+use crate::buffer::BUFFERS;
 use crate::core::cons::Cons;
-use crate::core::gc::Context;
+use crate::core::env::{Env, INTERNED_SYMBOLS, sym};
+use crate::core::gc::{Context, Rt};
 use crate::core::object::{
-    ByteFn, ByteString, FnArgs, Gc, IntoObject, LispVec, NIL, Object, RecordBuilder, Symbol,
+    ByteFn, ByteString, FnArgs, Function, Gc, IntoObject, LispBigInt, LispBuffer, LispFloat,
+    LispHashTable, LispString, LispVec, NIL, Object, ObjectType, RecordBuilder, Symbol, SymbolCell,
 };
 use anyhow::{Result, ensure};
+use rune_core::macros::{call, list, root};
 use rune_macros::defun;
+use std::cell::Cell;
 
 #[defun]
 pub(crate) fn list<'ob>(objects: &[Object<'ob>], cx: &'ob Context) -> Object<'ob> {
@@ -39,6 +44,8 @@ pub(crate) fn make_closure<'ob>(
             constants.into_obj(cx).untag(),
             prototype.args,
             prototype.depth,
+            prototype.docstring(),
+            prototype.interactive(),
         )
         .into_obj(cx))
     }
@@ -51,13 +58,14 @@ pub(crate) fn make_byte_code<'ob>(
     byte_code: &'ob ByteString,
     constants: &'ob LispVec,
     depth: usize,
-    _docstring: Option<Object>,
-    _interactive_spec: Option<Object>,
+    docstring: Option<Object>,
+    interactive_spec: Option<Object>,
     _elements: &[Object],
     cx: &'ob Context,
 ) -> Result<&'ob ByteFn> {
     unsafe {
-        let bytefn = ByteFn::make(byte_code, constants, FnArgs::from_arg_spec(arglist)?, depth);
+        let args = FnArgs::from_arg_spec(arglist)?;
+        let bytefn = ByteFn::make(byte_code, constants, args, depth, docstring, interactive_spec);
         Ok(bytefn.into_obj(cx).untag())
     }
 }
@@ -80,6 +88,22 @@ fn record<'ob>(type_: Object<'ob>, slots: &[Object<'ob>], cx: &'ob Context) -> R
     RecordBuilder(record)
 }
 
+defsym!(FINALIZER);
+
+/// Make a finalizer that will run FUNCTION.
+/// FUNCTION will be called after garbage collection when the returned
+/// finalizer object becomes unreachable. FUNCTION is run once per finalizer
+/// object, and errors it signals are reported but otherwise ignored.
+#[defun]
+fn make_finalizer<'ob>(function: Function<'ob>, cx: &'ob Context) -> Object<'ob> {
+    let mut record = cx.vec_with_capacity(2);
+    record.push(sym::FINALIZER.into());
+    record.push(function.into());
+    let finalizer = RecordBuilder(record).into_obj(cx);
+    cx.track_finalizer(finalizer.untag());
+    finalizer.into()
+}
+
 #[defun]
 fn purecopy(obj: Object) -> Object {
     obj
@@ -90,25 +114,212 @@ fn make_symbol<'ob>(name: &str, cx: &'ob Context) -> Symbol<'ob> {
     Symbol::new_uninterned(name, cx)
 }
 
+/// Reclaim storage for Lisp objects no longer needed.
+/// Returns info on amount of space in use:
+///  ((CONSES SIZE USED FREE) (SYMBOLS SIZE USED FREE) (STRINGS SIZE USED FREE)
+///   (STRING-BYTES 1 USED) (VECTORS SIZE USED) (VECTOR-SLOTS SIZE USED FREE)
+///   (FLOATS SIZE USED FREE) (HASH-TABLES SIZE USED) (BIGINTS SIZE USED)
+///   (BUFFERS SIZE USED))
+/// SIZE is the number of bytes of each object, USED is the number of live
+/// objects and FREE is the number of free slots. Since the collector copies
+/// live objects into a new space, FREE is always 0.
 #[defun]
-fn garbage_collect(cx: &mut Context) -> bool {
+fn garbage_collect<'ob>(env: &mut Rt<Env>, cx: &'ob mut Context) -> Result<Object<'ob>> {
     cx.garbage_collect(true);
-    true
+    after_garbage_collect(env, cx);
+    let stats = cx.gc_stats();
+    let symbols = INTERNED_SYMBOLS.lock().unwrap().symbols().count() + stats.symbols;
+    let buffers = BUFFERS.lock().unwrap().len();
+    Ok(list![
+        list![sym::CONSES, size_of::<Cons>(), stats.conses, 0; cx],
+        list![sym::SYMBOLS, size_of::<SymbolCell>(), symbols, 0; cx],
+        list![sym::STRINGS, size_of::<LispString>(), stats.strings, 0; cx],
+        list![sym::STRING_BYTES, 1, stats.string_bytes; cx],
+        list![sym::VECTORS, size_of::<LispVec>(), stats.vectors; cx],
+        list![sym::VECTOR_SLOTS, size_of::<Object>(), stats.vector_slots, 0; cx],
+        list![sym::FLOATS, size_of::<LispFloat>(), stats.floats, 0; cx],
+        list![sym::HASH_TABLES, size_of::<LispHashTable>(), stats.hash_tables; cx],
+        list![sym::BIGINTS, size_of::<LispBigInt>(), stats.bigints; cx],
+        list![sym::BUFFERS, size_of::<LispBuffer>(), buffers; cx];
+        cx
+    ])
+}
+
+thread_local! {
+    /// Set while `post-gc-hook` is running, so that collections done by the
+    /// hook don't run it again.
+    static IN_POST_GC_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run the functions of the finalizers that the last collection found
+/// unreachable.
+fn run_finalizers(env: &mut Rt<Env>, cx: &mut Context) {
+    let finalizers = cx.take_finalizers();
+    root!(finalizers, cx);
+    for i in 0..finalizers.len() {
+        let Ok(func) = finalizers[i].try_as() else { continue };
+        if let Err(e) = call!(func; env, cx) {
+            eprintln!("finalizer failed: {e}");
+        }
+    }
 }
 
+/// Update the GC variables, run finalizers and run `post-gc-hook` after a
+/// collection.
+fn after_garbage_collect(env: &mut Rt<Env>, cx: &mut Context) {
+    env.vars.insert(sym::GCS_DONE, cx.add(cx.gcs_done()));
+    env.vars.insert(sym::GC_ELAPSED, cx.add(cx.gc_elapsed().as_secs_f64()));
+    run_finalizers(env, cx);
+    if IN_POST_GC_HOOK.with(Cell::get) {
+        return;
+    }
+    if env.vars.get(sym::POST_GC_HOOK).is_none_or(|x| x.bind(cx).is_nil()) {
+        return;
+    }
+    let Some(func) = sym::RUN_HOOKS.func(cx) else { return };
+    root!(func, cx);
+    IN_POST_GC_HOOK.with(|x| x.set(true));
+    if let Err(e) = call!(func, Object::from(sym::POST_GC_HOOK); env, cx) {
+        eprintln!("post-gc-hook failed: {e}");
+    }
+    IN_POST_GC_HOOK.with(|x| x.set(false));
+}
+
+/// Collect garbage if needed at a safe point in the evaluator. The limits of
+/// the collector are read from `gc-cons-threshold` and `gc-cons-percentage`.
+/// When built with the `gc_stress` feature, this also applies the current
+/// value of `gc-stress`.
+pub(crate) fn maybe_garbage_collect(env: &mut Rt<Env>, cx: &mut Context) -> Result<()> {
+    if cfg!(feature = "gc_stress")
+        && let Some(value) = env.vars.get(sym::GC_STRESS)
+    {
+        let interval = match value.bind(cx).untag() {
+            ObjectType::NIL => None,
+            ObjectType::Int(n) if n > 0 => Some(n as usize),
+            _ => Some(1),
+        };
+        cx.set_gc_stress(interval);
+    }
+    if !cx.gc_pending() {
+        return Ok(());
+    }
+    let threshold = match env.vars.get(sym::GC_CONS_THRESHOLD).map(|x| x.bind(cx).untag()) {
+        Some(ObjectType::Int(n)) => n.max(0) as usize,
+        _ => Context::DEFAULT_CONS_THRESHOLD,
+    };
+    let percentage = match env.vars.get(sym::GC_CONS_PERCENTAGE).map(|x| x.bind(cx).untag()) {
+        Some(ObjectType::Float(x)) => **x,
+        Some(ObjectType::Int(n)) => n as f64,
+        _ => Context::DEFAULT_CONS_PERCENTAGE,
+    };
+    cx.set_gc_cons_threshold(threshold, percentage);
+    let gcs_done = cx.gcs_done();
+    cx.garbage_collect(false);
+    if cx.gcs_done() != gcs_done {
+        after_garbage_collect(env, cx);
+    }
+    Ok(())
+}
+
+// If non-nil, force a garbage collection after every N allocations and verify
+// that no root points to freed memory. t means every allocation. This only has
+// an effect when built with the `gc_stress` feature.
+defvar!(GC_STRESS, cfg!(feature = "gc_stress"));
+defvar!(GC_CONS_THRESHOLD, 800_000);
+defvar!(GC_CONS_PERCENTAGE, 0.1);
+defvar!(GC_ELAPSED, 0.0);
+defvar!(GCS_DONE, 0);
+defvar!(POST_GC_HOOK);
+defsym!(CONSES);
+defsym!(SYMBOLS);
+defsym!(STRINGS);
+defsym!(VECTORS);
+defsym!(VECTOR_SLOTS);
+defsym!(FLOATS);
+defsym!(HASH_TABLES);
+defsym!(BIGINTS);
+defsym!(BUFFERS);
+
 #[cfg(test)]
 mod test {
     use proptest::prelude::*;
-    use rune_core::macros::root;
+    use rune_core::macros::{rebind, root};
 
     use crate::{
         assert_elprop,
         core::{env::intern, gc::RootSet, object::ObjectType},
+        interpreter::assert_lisp,
         library::elprop::arb_byte,
     };
 
     use super::*;
 
+    #[test]
+    fn test_garbage_collect_stats() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        root!(env, new(Env), cx);
+        let stats = rebind!(garbage_collect(env, cx).unwrap());
+        let names: Vec<_> = stats
+            .as_list()
+            .unwrap()
+            .map(|x| {
+                let ObjectType::Cons(entry) = x.unwrap().untag() else { unreachable!() };
+                entry.car().to_string()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "conses",
+                "symbols",
+                "strings",
+                "string-bytes",
+                "vectors",
+                "vector-slots",
+                "floats",
+                "hash-tables",
+                "bigints",
+                "buffers"
+            ]
+        );
+        let gcs_done = env.vars.get(sym::GCS_DONE).unwrap().bind(cx);
+        assert_eq!(gcs_done, cx.gcs_done() as i64);
+    }
+
+    #[test]
+    fn test_finalizer() {
+        assert_lisp(
+            // The nil drops the finalizer from the value of the progn
+            "(progn (setq finalized nil)
+                    (make-finalizer #'(lambda () (setq finalized t)))
+                    nil
+                    (garbage-collect)
+                    finalized)",
+            "t",
+        );
+    }
+
+    #[test]
+    fn test_post_gc_hook() {
+        assert_lisp(
+            "(progn (setq gc-hook-ran nil)
+                    (setq post-gc-hook (list #'(lambda () (setq gc-hook-ran t))))
+                    (garbage-collect)
+                    (setq post-gc-hook nil)
+                    gc-hook-ran)",
+            "t",
+        );
+        // An error in the hook is reported instead of signaled
+        assert_lisp(
+            "(progn (setq post-gc-hook (list #'(lambda () (error \"hook\"))))
+                    (garbage-collect)
+                    (setq post-gc-hook nil)
+                    'done)",
+            "done",
+        );
+    }
+
     #[test]
     fn build_record() {
         let roots = &RootSet::default();
@@ -1,5 +1,7 @@
 //! Arithmetic operators.
-use crate::core::object::{Gc, IntoObject, Number, NumberType, ObjectType};
+use crate::core::error::{Type, TypeError};
+use crate::core::object::{Gc, IntoObject, Number, NumberType, Object, ObjectType};
+use anyhow::Result;
 use float_cmp::ApproxEq;
 use num_bigint::BigInt;
 use num_traits::{FromPrimitive, ToPrimitive, Zero};
@@ -145,20 +147,20 @@ impl Rem for NumberValue {
     }
 }
 
-impl PartialEq<i64> for Number<'_> {
+impl PartialEq<i64> for NumberValue {
     fn eq(&self, other: &i64) -> bool {
-        match self.val() {
-            NumberValue::Int(num) => num == *other,
-            NumberValue::Float(num) => num == *other as f64,
-            NumberValue::Big(num) => num == BigInt::from(*other),
+        match self {
+            NumberValue::Int(num) => num == other,
+            NumberValue::Float(num) => *num == *other as f64,
+            NumberValue::Big(num) => *num == BigInt::from(*other),
         }
     }
 }
 
-impl PartialEq<f64> for Number<'_> {
+impl PartialEq<f64> for NumberValue {
     fn eq(&self, other: &f64) -> bool {
-        match self.val() {
-            NumberValue::Int(num) => num as f64 == *other,
+        match self {
+            NumberValue::Int(num) => *num as f64 == *other,
             NumberValue::Float(num) => num.approx_eq(*other, (f64::EPSILON, 2)),
             NumberValue::Big(num) => {
                 num.to_f64().is_some_and(|n| n.approx_eq(*other, (f64::EPSILON, 2)))
@@ -167,18 +169,36 @@ impl PartialEq<f64> for Number<'_> {
     }
 }
 
-impl PartialEq<BigInt> for Number<'_> {
+impl PartialEq<BigInt> for NumberValue {
     fn eq(&self, other: &BigInt) -> bool {
-        match self.val() {
-            NumberValue::Int(num) => BigInt::from(num) == *other,
+        match self {
+            NumberValue::Int(num) => BigInt::from(*num) == *other,
             NumberValue::Float(num) => {
-                other.to_f64().is_some_and(|n| n.approx_eq(num, (f64::EPSILON, 2)))
+                other.to_f64().is_some_and(|n| n.approx_eq(*num, (f64::EPSILON, 2)))
             } // TODO: Check
-            NumberValue::Big(num) => num == *other,
+            NumberValue::Big(num) => num == other,
         }
     }
 }
 
+impl PartialEq<i64> for Number<'_> {
+    fn eq(&self, other: &i64) -> bool {
+        self.val() == *other
+    }
+}
+
+impl PartialEq<f64> for Number<'_> {
+    fn eq(&self, other: &f64) -> bool {
+        self.val() == *other
+    }
+}
+
+impl PartialEq<BigInt> for Number<'_> {
+    fn eq(&self, other: &BigInt) -> bool {
+        self.val() == *other
+    }
+}
+
 impl PartialOrd for NumberValue {
     fn partial_cmp(&self, other: &NumberValue) -> Option<std::cmp::Ordering> {
         match self {
@@ -203,99 +223,147 @@ impl PartialOrd for NumberValue {
     }
 }
 
+/// The value of a number or marker argument. Markers are converted to their
+/// position.
+fn number_value(obj: Object) -> Result<NumberValue> {
+    match obj.untag() {
+        ObjectType::Int(x) => Ok(NumberValue::Int(x)),
+        ObjectType::Float(x) => Ok(NumberValue::Float(**x)),
+        ObjectType::BigInt(x) => Ok(NumberValue::Big((**x).clone())),
+        ObjectType::Marker(m) => Ok(NumberValue::Int(m.lisp_position()? as i64)),
+        x => Err(TypeError::new(Type::NumberOrMarker, x).into()),
+    }
+}
+
+fn fold_numbers(
+    init: NumberValue,
+    numbers: &[Object],
+    f: fn(NumberValue, NumberValue) -> NumberValue,
+) -> Result<NumberValue> {
+    numbers.iter().try_fold(init, |acc, x| Ok(f(acc, number_value(*x)?)))
+}
+
 #[defun(name = "+")]
-pub(crate) fn add(vars: &[Number]) -> NumberValue {
-    vars.iter().fold(NumberValue::Int(0), |acc, x| acc + x.val())
+pub(crate) fn add(vars: &[Object]) -> Result<NumberValue> {
+    fold_numbers(NumberValue::Int(0), vars, Add::add)
 }
 
 #[defun(name = "-")]
-pub(crate) fn sub(number: Option<Number>, numbers: &[Number]) -> NumberValue {
+pub(crate) fn sub(number: Option<Object>, numbers: &[Object]) -> Result<NumberValue> {
     match number {
         Some(num) => {
-            let num = num.val();
-            if numbers.is_empty() {
-                -num
-            } else {
-                numbers.iter().fold(num, |acc, x| acc - x.val())
-            }
+            let num = number_value(num)?;
+            if numbers.is_empty() { Ok(-num) } else { fold_numbers(num, numbers, Sub::sub) }
         }
-        None => NumberValue::Int(0),
+        None => Ok(NumberValue::Int(0)),
     }
 }
 
 #[defun(name = "*")]
-pub(crate) fn mul(numbers: &[Number]) -> NumberValue {
-    numbers.iter().fold(NumberValue::Int(1), |acc, x| acc * x.val())
+pub(crate) fn mul(numbers: &[Object]) -> Result<NumberValue> {
+    fold_numbers(NumberValue::Int(1), numbers, Mul::mul)
 }
 
 #[defun(name = "/")]
-pub(crate) fn div(number: Number, divisors: &[Number]) -> NumberValue {
-    divisors.iter().fold(number.val(), |acc, x| acc / x.val())
+pub(crate) fn div(number: Object, divisors: &[Object]) -> Result<NumberValue> {
+    fold_numbers(number_value(number)?, divisors, Div::div)
 }
 
 #[defun(name = "1+")]
-pub(crate) fn add_one(number: Number) -> NumberValue {
-    number.val() + NumberValue::Int(1)
+pub(crate) fn add_one(number: Object) -> Result<NumberValue> {
+    Ok(number_value(number)? + NumberValue::Int(1))
 }
 
 #[defun(name = "1-")]
-pub(crate) fn sub_one(number: Number) -> NumberValue {
-    number.val() - NumberValue::Int(1)
+pub(crate) fn sub_one(number: Object) -> Result<NumberValue> {
+    Ok(number_value(number)? - NumberValue::Int(1))
 }
 
-#[defun(name = "=")]
-pub(crate) fn num_eq(number: Number, numbers: &[Number]) -> bool {
-    match number.val() {
-        NumberValue::Int(num) => numbers.iter().all(|&x| x == num),
-        NumberValue::Float(num) => numbers.iter().all(|&x| x == num),
-        NumberValue::Big(num) => numbers.iter().all(|&x| x == num),
+fn num_val_eq(lhs: &NumberValue, rhs: &NumberValue) -> bool {
+    match rhs {
+        NumberValue::Int(num) => lhs == num,
+        NumberValue::Float(num) => lhs == num,
+        NumberValue::Big(num) => lhs == num,
     }
 }
 
-#[defun(name = "/=")]
-pub(crate) fn num_ne(number: Number, numbers: &[Number]) -> bool {
-    match number.val() {
-        NumberValue::Int(num) => numbers.iter().all(|&x| x != num),
-        NumberValue::Float(num) => numbers.iter().all(|&x| x != num),
-        NumberValue::Big(num) => numbers.iter().all(|&x| x != num),
+#[defun(name = "=")]
+pub(crate) fn num_eq(number: Object, numbers: &[Object]) -> Result<bool> {
+    let number = number_value(number)?;
+    for x in numbers {
+        if !num_val_eq(&number_value(*x)?, &number) {
+            return Ok(false);
+        }
     }
+    Ok(true)
 }
 
-fn cmp(number: Number, numbers: &[Number], cmp: fn(&NumberValue, &NumberValue) -> bool) -> bool {
-    numbers
-        .iter()
-        .try_fold(number.val(), |acc, &x| cmp(&acc, &x.val()).then_some(NumberValue::Int(0)))
-        .is_some()
+#[defun(name = "/=")]
+pub(crate) fn num_ne(number: Object, numbers: &[Object]) -> Result<bool> {
+    let number = number_value(number)?;
+    for x in numbers {
+        if num_val_eq(&number_value(*x)?, &number) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Check that `cmp` holds between each pair of adjacent numbers.
+fn cmp(
+    number: Object,
+    numbers: &[Object],
+    cmp: fn(&NumberValue, &NumberValue) -> bool,
+) -> Result<bool> {
+    let mut prev = number_value(number)?;
+    for x in numbers {
+        let next = number_value(*x)?;
+        if !cmp(&prev, &next) {
+            return Ok(false);
+        }
+        prev = next;
+    }
+    Ok(true)
 }
 
 #[defun(name = "<")]
-pub(crate) fn less_than(number: Number, numbers: &[Number]) -> bool {
+pub(crate) fn less_than(number: Object, numbers: &[Object]) -> Result<bool> {
     cmp(number, numbers, NumberValue::lt)
 }
 
 #[defun(name = "<=")]
-pub(crate) fn less_than_or_eq(number: Number, numbers: &[Number]) -> bool {
+pub(crate) fn less_than_or_eq(number: Object, numbers: &[Object]) -> Result<bool> {
     cmp(number, numbers, NumberValue::le)
 }
 
 #[defun(name = ">")]
-pub(crate) fn greater_than(number: Number, numbers: &[Number]) -> bool {
+pub(crate) fn greater_than(number: Object, numbers: &[Object]) -> Result<bool> {
     cmp(number, numbers, NumberValue::gt)
 }
 
 #[defun(name = ">=")]
-pub(crate) fn greater_than_or_eq(number: Number, numbers: &[Number]) -> bool {
+pub(crate) fn greater_than_or_eq(number: Object, numbers: &[Object]) -> Result<bool> {
     cmp(number, numbers, NumberValue::ge)
 }
 
+/// Convert an integer or marker argument to an integer. Markers are converted
+/// to their position.
+pub(crate) fn int_or_marker(obj: Object) -> Result<i64> {
+    match obj.untag() {
+        ObjectType::Int(x) => Ok(x),
+        ObjectType::Marker(m) => Ok(m.lisp_position()? as i64),
+        x => Err(TypeError::new(Type::IntegerOrMarker, x).into()),
+    }
+}
+
 #[defun]
-pub(crate) fn logior(ints_or_markers: &[Gc<i64>]) -> i64 {
-    ints_or_markers.iter().fold(0, |acc, x| acc | x.untag())
+pub(crate) fn logior(ints_or_markers: &[Object]) -> Result<i64> {
+    ints_or_markers.iter().try_fold(0, |acc, x| Ok(acc | int_or_marker(*x)?))
 }
 
 #[defun]
-fn logand(int_or_markers: &[Gc<i64>]) -> i64 {
-    int_or_markers.iter().fold(-1, |accum, x| accum & x.untag())
+fn logand(int_or_markers: &[Object]) -> Result<i64> {
+    int_or_markers.iter().try_fold(-1, |accum, x| Ok(accum & int_or_marker(*x)?))
 }
 
 #[defun(name = "mod")]
@@ -304,31 +372,26 @@ pub(crate) fn modulo(x: Number, y: Number) -> NumberValue {
 }
 
 #[defun(name = "%")]
-pub(crate) fn remainder(x: i64, y: i64) -> i64 {
-    // TODO: Handle markers
-    x % y
+pub(crate) fn remainder(x: Object, y: Object) -> Result<i64> {
+    Ok(int_or_marker(x)? % int_or_marker(y)?)
 }
 
-#[expect(clippy::trivially_copy_pass_by_ref)]
-fn max_val(x: NumberValue, y: &Number) -> NumberValue {
-    let y = y.val();
+fn max_val(x: NumberValue, y: NumberValue) -> NumberValue {
     if x > y { x } else { y }
 }
 
-#[expect(clippy::trivially_copy_pass_by_ref)]
-fn min_val(x: NumberValue, y: &Number) -> NumberValue {
-    let y = y.val();
+fn min_val(x: NumberValue, y: NumberValue) -> NumberValue {
     if x < y { x } else { y }
 }
 
 #[defun]
-pub(crate) fn max(number_or_marker: Number, number_or_markers: &[Number]) -> NumberValue {
-    number_or_markers.iter().fold(number_or_marker.val(), max_val)
+pub(crate) fn max(number_or_marker: Object, number_or_markers: &[Object]) -> Result<NumberValue> {
+    fold_numbers(number_value(number_or_marker)?, number_or_markers, max_val)
 }
 
 #[defun]
-pub(crate) fn min(number_or_marker: Number, number_or_markers: &[Number]) -> NumberValue {
-    number_or_markers.iter().fold(number_or_marker.val(), min_val)
+pub(crate) fn min(number_or_marker: Object, number_or_markers: &[Object]) -> Result<NumberValue> {
+    fold_numbers(number_value(number_or_marker)?, number_or_markers, min_val)
 }
 
 #[cfg(test)]
@@ -340,25 +403,25 @@ mod test {
     fn test_add() {
         let roots = &RootSet::default();
         let cx = &Context::new(roots);
-        assert_eq!(add(&[]), NumberValue::Int(0));
-        assert_eq!(add(&[7.into(), 13.into()]), NumberValue::Int(20));
-        assert_eq!(add(&[1.into(), cx.add_as(2.5)]), NumberValue::Float(3.5));
-        assert_eq!(add(&[0.into(), (-1).into()]), NumberValue::Int(-1));
+        assert_eq!(add(&[]).unwrap(), NumberValue::Int(0));
+        assert_eq!(add(&[7.into(), 13.into()]).unwrap(), NumberValue::Int(20));
+        assert_eq!(add(&[1.into(), cx.add(2.5)]).unwrap(), NumberValue::Float(3.5));
+        assert_eq!(add(&[0.into(), (-1).into()]).unwrap(), NumberValue::Int(-1));
     }
 
     #[test]
     fn test_sub() {
-        assert_eq!(sub(None, &[]), NumberValue::Int(0));
-        assert_eq!(sub(Some(7.into()), &[]), NumberValue::Int(-7));
-        assert_eq!(sub(Some(7.into()), &[13.into()]), NumberValue::Int(-6));
-        assert_eq!(sub(Some(0.into()), &[(-1).into()]), NumberValue::Int(1));
+        assert_eq!(sub(None, &[]).unwrap(), NumberValue::Int(0));
+        assert_eq!(sub(Some(7.into()), &[]).unwrap(), NumberValue::Int(-7));
+        assert_eq!(sub(Some(7.into()), &[13.into()]).unwrap(), NumberValue::Int(-6));
+        assert_eq!(sub(Some(0.into()), &[(-1).into()]).unwrap(), NumberValue::Int(1));
     }
 
     #[test]
     fn test_mul() {
-        assert_eq!(mul(&[]), NumberValue::Int(1));
-        assert_eq!(mul(&[7.into(), 13.into()]), NumberValue::Int(91));
-        assert_eq!(mul(&[(-1).into(), 1.into()]), NumberValue::Int(-1));
+        assert_eq!(mul(&[]).unwrap(), NumberValue::Int(1));
+        assert_eq!(mul(&[7.into(), 13.into()]).unwrap(), NumberValue::Int(91));
+        assert_eq!(mul(&[(-1).into(), 1.into()]).unwrap(), NumberValue::Int(-1));
     }
 
     #[test]
@@ -366,8 +429,8 @@ mod test {
         let roots = &RootSet::default();
         let cx = &Context::new(roots);
 
-        assert_eq!(div(cx.add_as(12.0), &[]), NumberValue::Float(12.0));
-        assert_eq!(div(12.into(), &[5.into(), 2.into()]), NumberValue::Int(1));
+        assert_eq!(div(cx.add(12.0), &[]).unwrap(), NumberValue::Float(12.0));
+        assert_eq!(div(12.into(), &[5.into(), 2.into()]).unwrap(), NumberValue::Int(1));
     }
 
     #[test]
@@ -375,23 +438,23 @@ mod test {
         let roots = &RootSet::default();
         let cx = &Context::new(roots);
         let int1 = 1.into();
-        let float1 = cx.add_as(1.0);
-        let float1_1 = cx.add_as(1.1);
+        let float1 = cx.add(1.0);
+        let float1_1 = cx.add(1.1);
 
-        assert!(num_eq(int1, &[]));
-        assert!(num_eq(int1, &[cx.add_as(1.0)]));
-        assert!(num_eq(float1, &[1.into()]));
-        assert!(!num_eq(float1, &[1.into(), 1.into(), float1_1]));
+        assert!(num_eq(int1, &[]).unwrap());
+        assert!(num_eq(int1, &[cx.add(1.0)]).unwrap());
+        assert!(num_eq(float1, &[1.into()]).unwrap());
+        assert!(!num_eq(float1, &[1.into(), 1.into(), float1_1]).unwrap());
     }
 
     #[test]
     fn test_cmp() {
         let roots = &RootSet::default();
         let cx = &Context::new(roots);
-        assert!(less_than(1.into(), &[]));
-        assert!(less_than(1.into(), &[cx.add_as(1.1)]));
-        assert!(!less_than(cx.add_as(1.0), &[1.into()]));
-        assert!(less_than(cx.add_as(1.0), &[cx.add_as(1.1), 2.into(), cx.add_as(2.1)]));
+        assert!(less_than(1.into(), &[]).unwrap());
+        assert!(less_than(1.into(), &[cx.add(1.1)]).unwrap());
+        assert!(!less_than(cx.add(1.0), &[1.into()]).unwrap());
+        assert!(less_than(cx.add(1.0), &[cx.add(1.1), 2.into(), cx.add(2.1)]).unwrap());
     }
 
     #[test]
@@ -399,19 +462,18 @@ mod test {
         let roots = &RootSet::default();
         let cx = &Context::new(roots);
         assert_eq!(
-            max(cx.add_as(1.0), &[cx.add_as(2.1), cx.add_as(1.1), cx.add_as(1.0)]),
-            cx.add_as(2.1).val()
+            max(cx.add(1.0), &[cx.add(2.1), cx.add(1.1), cx.add(1.0)]).unwrap(),
+            NumberValue::Float(2.1)
         );
         assert_eq!(
-            min(cx.add_as(1.1), &[cx.add_as(1.0), cx.add_as(2.1), cx.add_as(1.0)]),
-            cx.add_as(1.0).val()
+            min(cx.add(1.1), &[cx.add(1.0), cx.add(2.1), cx.add(1.0)]).unwrap(),
+            NumberValue::Float(1.0)
         );
     }
 
     #[test]
     fn test_other() {
-        let roots = &RootSet::default();
-        let cx = &Context::new(roots);
-        assert_eq!(logand(&[258.into_obj(cx), 255.into_obj(cx)]), 2);
+        assert_eq!(logand(&[258.into(), 255.into()]).unwrap(), 2);
+        assert_eq!(logior(&[1.into(), 4.into()]).unwrap(), 5);
     }
 }
@@ -1,11 +1,13 @@
 //! Buffer operations.
 use crate::{
     core::{
-        env::{Env, INTERNED_SYMBOLS},
+        cons::Cons,
+        env::{Env, INTERNED_SYMBOLS, sym},
         error::{Type, TypeError},
         gc::{Context, Rt},
-        object::{Gc, LispBuffer, NIL, Object, ObjectType, OptionalFlag},
+        object::{Gc, LispBuffer, NIL, Object, ObjectType, OpenBuffer, OptionalFlag, Symbol},
     },
+    fileio,
     fns::slice_into_list,
 };
 use anyhow::{Result, bail};
@@ -29,7 +31,16 @@ pub(crate) fn set_buffer<'ob>(
     Ok(cx.add(buffer))
 }
 
-fn resolve_buffer<'ob>(buffer_or_name: Object, cx: &'ob Context) -> Result<&'ob LispBuffer> {
+/// Return the current buffer as a Lisp object.
+#[defun]
+pub(crate) fn current_buffer<'ob>(env: &Rt<Env>, cx: &'ob Context) -> &'ob LispBuffer {
+    env.current_buffer.get().lisp_buffer(cx)
+}
+
+pub(crate) fn resolve_buffer<'ob>(
+    buffer_or_name: Object,
+    cx: &'ob Context,
+) -> Result<&'ob LispBuffer> {
     match buffer_or_name.untag() {
         ObjectType::Buffer(b) => Ok(b),
         ObjectType::String(name) => {
@@ -182,13 +193,21 @@ fn unique_buffer_name(name: &str, ignore: Option<&str>, buffer_list: &BufferMap)
 fn kill_buffer(buffer_or_name: Option<Object>, cx: &Context, env: &mut Rt<Env>) -> bool {
     match buffer_or_name {
         Some(buffer) => match resolve_buffer(buffer, cx) {
-            Ok(b) => env.with_buffer_mut(b, |b| b.kill()).unwrap_or(false),
+            Ok(b) => {
+                let killed = env.with_buffer_mut(b, |b| b.kill()).unwrap_or(false);
+                if killed {
+                    env.current_buffer.untrack(b);
+                }
+                killed
+            }
             Err(_) => false,
         },
         None => {
             let killed = env.current_buffer.get_mut().kill();
             // todo, we need to select a new buffer
             env.current_buffer.release();
+            let buffer = env.current_buffer.buf_ref;
+            env.current_buffer.untrack(buffer);
             killed
         }
     }
@@ -200,10 +219,42 @@ fn buffer_base_buffer(_buffer: OptionalFlag) -> bool {
     false
 }
 
+/// Return the buffer visiting file FILENAME (a string).
+/// The buffer's `buffer-file-name' must match exactly the expansion of FILENAME.
+/// If there is no such live buffer, return nil.
 #[defun]
-fn get_file_buffer(_filename: &str) -> bool {
-    // TODO: implement file buffers
-    false
+fn get_file_buffer<'ob>(filename: &str, env: &Rt<Env>, cx: &'ob Context) -> Result<Object<'ob>> {
+    let filename = fileio::expand_file_name(filename, None, env, cx)?;
+    // Don't hold the lock while visiting the buffers
+    let buffers: Vec<&LispBuffer> = BUFFERS.lock().unwrap().values().copied().collect();
+    for buffer in buffers {
+        // Buffers that can't be opened aren't visiting anything
+        let visiting = env.with_buffer(buffer, |b| {
+            b.local_var(sym::BUFFER_FILE_NAME).is_some_and(
+                |x| matches!(x.untag(), ObjectType::String(s) if s.as_ref() == filename),
+            )
+        });
+        if visiting.unwrap_or(false) {
+            return Ok(cx.add(buffer));
+        }
+    }
+    Ok(NIL)
+}
+
+/// Return name of file BUFFER is visiting, or nil if none.
+/// No argument or nil as argument means use the current buffer.
+#[defun]
+fn buffer_file_name<'ob>(
+    buffer: Option<Gc<&LispBuffer>>,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let name = |b: &OpenBuffer| b.local_var(sym::BUFFER_FILE_NAME).map(|x| cx.bind(x));
+    let name = match buffer {
+        Some(buffer) => env.with_buffer(buffer.untag(), name)?,
+        None => name(env.current_buffer.get()),
+    };
+    Ok(name.unwrap_or(NIL))
 }
 
 #[defun]
@@ -217,6 +268,44 @@ fn buffer_list<'ob>(_frame: OptionalFlag, cx: &'ob Context) -> Object<'ob> {
     slice_into_list(&buffer_list, None, cx)
 }
 
+/// Return the value of VARIABLE in BUFFER.
+/// If VARIABLE does not have a buffer-local binding in BUFFER, the value is
+/// the default binding of the variable.
+#[defun]
+fn buffer_local_value<'ob>(
+    variable: Symbol,
+    buffer: Gc<&LispBuffer>,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let local = env.with_buffer(buffer.untag(), |b| b.local_var(variable).map(|x| cx.bind(x)))?;
+    match local.or_else(|| env.vars.get(variable).map(|x| x.bind(cx))) {
+        Some(value) => Ok(value),
+        None => bail!("Void variable: {variable}"),
+    }
+}
+
+/// Return an alist of variables that are buffer-local in BUFFER.
+/// Most elements look like (SYMBOL . VALUE), describing one variable. BUFFER
+/// defaults to the current buffer.
+#[defun]
+fn buffer_local_variables<'ob>(
+    buffer: Option<Gc<&LispBuffer>>,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let to_alist = |b: &OpenBuffer| -> Vec<Object<'ob>> {
+        let vars = b.local_vars().into_iter();
+        vars.map(|(var, value)| Cons::new(cx.bind(var), cx.bind(value), cx).into())
+            .collect()
+    };
+    let alist = match buffer {
+        Some(buffer) => env.with_buffer(buffer.untag(), to_alist)?,
+        None => to_alist(env.current_buffer.get()),
+    };
+    Ok(slice_into_list(&alist, None, cx))
+}
+
 // TODO: buffer local
 defvar!(FILL_COLUMN, 70);
 defvar!(INDENT_TABS_MODE);
@@ -227,12 +316,13 @@ defvar!(TAB_WIDTH, 8);
 defvar!(TRUNCATE_LINES);
 defvar!(WORD_WRAP);
 defvar!(BIDI_DISPLAY_REORDERING);
-defvar!(BUFFER_FILE_NAME);
+defvar!(BUFFER_FILE_TRUENAME);
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::core::gc::RootSet;
+    use rune_core::macros::root;
 
     #[test]
     fn test_gen_new_buffer_name() {
@@ -269,4 +359,18 @@ mod test {
         let buffer = get_buffer_create(cx.add("test_create_buffer"), Some(NIL), cx).unwrap();
         assert!(matches!(buffer.untag(), ObjectType::Buffer(_)));
     }
+
+    #[test]
+    fn test_kill_buffer_untracks() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        root!(env, new(Env), cx);
+        let buffer = get_buffer_create(cx.add("test_kill_buffer_untracks"), Some(NIL), cx).unwrap();
+        let ObjectType::Buffer(b) = buffer.untag() else { unreachable!() };
+        env.current_buffer.track(b);
+        assert!(env.current_buffer.is_tracked(b));
+        assert!(kill_buffer(Some(buffer), cx, env));
+        assert!(!env.current_buffer.is_tracked(b));
+        cx.garbage_collect(true);
+    }
 }
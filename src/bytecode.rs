@@ -1,15 +1,14 @@
 //! The main bytecode interpeter.
-use crate::core::cons::Cons;
-use crate::core::env::{CallFrame, Env, sym};
+use crate::core::env::{ArgSlice, CallFrame, Env, Handler as EnvHandler, HandlerType};
 use crate::core::gc::{Context, IntoRoot, Rt, Rto, Slot};
 use crate::core::object::{
-    ByteFn, ByteString, FnArgs, Function, FunctionType, Gc, LispVec, NIL, Object, ObjectType,
-    Symbol, WithLifetime,
+    ByteFn, ByteString, FnArgs, Function, FunctionType, Gc, LispString, LispVec, NIL, Object,
+    ObjectType, Symbol,
 };
 use crate::data::LispError;
-use crate::eval::{ErrorType, EvalError, EvalResult};
+use crate::eval::{EvalError, EvalResult};
 use anyhow::{Result, bail};
-use rune_core::macros::{bail_err, rebind, root};
+use rune_core::macros::{rebind, root};
 use rune_macros::{Trace, defun};
 
 mod opcode;
@@ -79,31 +78,18 @@ impl ProgramCounter {
     }
 }
 
-#[derive(Debug, Trace)]
-/// A handler for a condition-case. These are stored in a vector in the VM and
-/// added/removed via bytecodes.
-struct Handler<'ob> {
-    #[no_trace]
+/// Where execution resumes when a `condition-case` or `catch` handler pushed by
+/// the VM receives a non-local exit. The handler itself is in
+/// [`Env::handlers`](crate::core::env::Env) at `index`. These are stored in a
+/// vector in the VM and added/removed via bytecodes.
+#[derive(Debug)]
+struct Handler {
     jump_code: u16,
-    #[no_trace]
     stack_size: usize,
-    #[no_trace]
     stack_frame: usize,
-    condition: Slot<Object<'ob>>,
-}
-
-impl<'new> IntoRoot<Handler<'new>> for Handler<'_> {
-    unsafe fn into_root(self) -> Handler<'new> {
-        self.with_lifetime()
-    }
-}
-
-impl<'old, 'new> WithLifetime<'new> for Handler<'old> {
-    type Out = Handler<'new>;
-
-    unsafe fn with_lifetime(self) -> Self::Out {
-        std::mem::transmute::<Handler<'old>, Handler<'new>>(self)
-    }
+    /// The depth of the binding stack when the handler was pushed
+    binding_depth: usize,
+    index: usize,
 }
 
 /// The bytecode VM. This hold all the current call frames and handlers. The
@@ -116,8 +102,13 @@ struct VM<'brw, 'env, 'rt> {
     /// The current function being executed. Saved to ensure it is preserved by
     /// the garbage collector.
     func: Slot<&'rt ByteFn>,
-    /// All currently active condition-case handlers
-    handlers: Vec<Handler<'rt>>,
+    /// All currently active condition-case and catch handlers
+    #[no_trace]
+    handlers: Vec<Handler>,
+    /// The depth of the binding stack when the VM was entered. Bindings past
+    /// this are undone when an error exits the VM.
+    #[no_trace]
+    binding_depth: usize,
     /// The runtime environment
     #[no_trace]
     env: &'brw mut Rt<Env<'env>>,
@@ -133,8 +124,7 @@ impl<'ob> RootedVM<'_, '_, '_> {
     fn varref(&mut self, idx: u16, cx: &'ob Context) -> Result<()> {
         let symbol = self.get_const(idx as usize, cx);
         if let ObjectType::Symbol(sym) = symbol.untag() {
-            let Some(var) = self.env.vars.get(sym) else { bail!("Void Variable: {sym}") };
-            let var = var.bind(cx);
+            let Some(var) = self.env.var(sym, cx) else { bail!("Void Variable: {sym}") };
             self.env.stack.push(var);
             Ok(())
         } else {
@@ -185,6 +175,22 @@ impl<'ob> RootedVM<'_, '_, '_> {
         }
     }
 
+    /// Push a handler for the condition or tag on top of the stack. The jump
+    /// target is the argument of the current op.
+    fn push_handler(&mut self, kind: HandlerType, cx: &'ob Context) {
+        // pop before getting stack size
+        let tag = self.env.stack.pop(cx);
+        let handler = Handler {
+            jump_code: self.pc.arg2(),
+            stack_size: self.env.stack.len(),
+            stack_frame: self.env.stack.current_frame(),
+            binding_depth: self.env.binding_depth(),
+            index: self.env.handlers.len(),
+        };
+        self.handlers.push(handler);
+        self.env.handlers.push(EnvHandler::new(kind, tag));
+    }
+
     #[inline(always)]
     fn debug_enabled() -> bool {
         cfg!(test) || (cfg!(feature = "debug_bytecode") && crate::debug::debug_enabled())
@@ -238,6 +244,7 @@ impl<'ob> RootedVM<'_, '_, '_> {
             self.env
                 .stack
                 .push_bytecode_frame(frame_start, next_fn.depth, prev_fn, pc_offset);
+            self.env.stack.set_frame_function(func.into());
             self.prepare_lisp_args(next_fn, arg_cnt, &name, cx)?;
         } else {
             // Otherwise, call the function directly.
@@ -246,7 +253,7 @@ impl<'ob> RootedVM<'_, '_, '_> {
             let result = func.call(&mut frame, Some(&name), cx)?;
             drop(frame); // removes the arguments from the stack
             self.env.stack.top().set(result);
-            cx.garbage_collect(false);
+            crate::alloc::maybe_garbage_collect(self.env, cx)?;
         }
         Ok(())
     }
@@ -257,40 +264,22 @@ impl<'ob> RootedVM<'_, '_, '_> {
                 Ok(x) => return Ok(rebind!(x, cx)),
                 Err(e) => e,
             };
-
-            // we will fix this once we can handle different error types
-            #[expect(clippy::never_loop)]
-            while let Some(handler) = self.handlers.bind_mut(cx).pop() {
-                match handler.condition.untag() {
-                    ObjectType::Symbol(sym::ERROR) => {}
-                    ObjectType::Cons(conditions) => {
-                        for condition in conditions {
-                            let condition = condition?;
-                            // TODO: Handle different error symbols
-                            if condition != sym::DEBUG && condition != sym::ERROR {
-                                bail_err!("non-error conditions {condition} not yet supported")
-                            }
-                        }
-                    }
-                    x => bail_err!("Invalid condition handler: {x}"),
-                }
-
-                let error = if let EvalError { error: ErrorType::Signal(id), .. } = err {
-                    let Some((sym, data)) = self.env.get_exception(id) else {
-                        unreachable!("Exception not found")
-                    };
-                    Cons::new(sym, data, cx)
-                } else {
-                    // TODO: Need to remove the anyhow branch once
-                    // full errors are implemented
-                    Cons::new(sym::ERROR, format!("{err}"), cx)
-                };
+            let err = crate::eval::signal_error(err, self.env, cx);
+            // Pop the handlers that the error unwinds past
+            while let Some(handler) = self.handlers.pop() {
+                self.env.handlers.truncate(handler.index);
+                if !err.is_handled_by(handler.index) {
+                    continue;
+                }
+                let value = err.handler_value(self.env, cx);
+                self.env.unbind_to(handler.binding_depth, cx);
                 self.unwind(handler.stack_frame, cx);
                 self.env.stack.truncate(handler.stack_size);
-                self.env.stack.push(Object::from(error));
+                self.env.stack.push(value);
                 self.pc.goto(handler.jump_code);
                 continue 'main;
             }
+            self.env.unbind_to(self.binding_depth, cx);
             return Err(err);
         }
     }
@@ -298,7 +287,7 @@ impl<'ob> RootedVM<'_, '_, '_> {
     #[expect(clippy::too_many_lines)]
     /// The main bytecode execution loop.
     fn execute_bytecode(&mut self, cx: &'ob mut Context) -> EvalResult<'ob> {
-        use crate::{alloc, arith, data, fns};
+        use crate::{alloc, arith, buffer, cmds, data, editfns, fns, indent, marker, syntax};
         use opcode::OpCode as op;
         loop {
             let op = match self.pc.next().try_into() {
@@ -410,19 +399,10 @@ impl<'ob> RootedVM<'_, '_, '_> {
                 }
                 op::PopHandler => {
                     self.handlers.pop();
+                    self.env.handlers.pop();
                 }
-                op::PushCondtionCase => {
-                    // pop before getting stack size
-                    let condition = self.env.stack.pop(cx);
-                    let handler = Handler {
-                        jump_code: self.pc.arg2(),
-                        stack_size: self.env.stack.len(),
-                        stack_frame: self.env.stack.current_frame(),
-                        condition: Slot::new(condition),
-                    };
-                    self.handlers.push(handler);
-                }
-                op::PushCatch => todo!("PushCatch bytecode"),
+                op::PushCondtionCase => self.push_handler(HandlerType::ConditionCase, cx),
+                op::PushCatch => self.push_handler(HandlerType::Catch, cx),
                 op::Nth => {
                     let list = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
@@ -534,100 +514,220 @@ impl<'ob> RootedVM<'_, '_, '_> {
                     let value = data::get(top, prop, self.env, cx);
                     self.env.stack.top().set(value);
                 }
-                op::Substring => todo!("Substring bytecode"),
-                op::Concat2 => todo!("Concat2 bytecode"),
-                op::Concat3 => todo!("Concat3 bytecode"),
-                op::Concat4 => todo!("Concat4 bytecode"),
+                op::Substring => {
+                    let to = Object::try_from_option(self.env.stack.pop(cx))?;
+                    let from = Object::try_from_option(self.env.stack.pop(cx))?;
+                    let top = self.env.stack.top();
+                    let string: Gc<&LispString> = top.bind_as(cx)?;
+                    top.set(fns::substring(string.untag(), from, to, cx)?);
+                }
+                op::Concat2 => {
+                    let a2 = self.env.stack.pop(cx);
+                    let top = self.env.stack.top();
+                    top.set(fns::concat(&[top.bind(cx), a2], cx)?);
+                }
+                op::Concat3 => {
+                    let a3 = self.env.stack.pop(cx);
+                    let a2 = self.env.stack.pop(cx);
+                    let top = self.env.stack.top();
+                    top.set(fns::concat(&[top.bind(cx), a2, a3], cx)?);
+                }
+                op::Concat4 => {
+                    let a4 = self.env.stack.pop(cx);
+                    let a3 = self.env.stack.pop(cx);
+                    let a2 = self.env.stack.pop(cx);
+                    let top = self.env.stack.top();
+                    top.set(fns::concat(&[top.bind(cx), a2, a3, a4], cx)?);
+                }
                 op::Sub1 => {
                     let top = self.env.stack.top();
-                    top.set(cx.add(arith::sub_one(top.bind_as(cx)?)));
+                    top.set(cx.add(arith::sub_one(top.bind(cx))?));
                 }
                 op::Add1 => {
                     let top = self.env.stack.top();
-                    top.set(cx.add(arith::add_one(top.bind_as(cx)?)));
+                    top.set(cx.add(arith::add_one(top.bind(cx))?));
                 }
                 op::EqlSign => {
                     let rhs = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    top.set::<Object>(arith::num_eq(top.bind_as(cx)?, &[rhs.try_into()?]).into());
+                    top.set::<Object>(arith::num_eq(top.bind(cx), &[rhs])?.into());
                 }
                 op::GreaterThan => {
                     let v1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    top.set(arith::greater_than(top.bind_as(cx)?, &[v1.try_into()?]));
+                    top.set(arith::greater_than(top.bind(cx), &[v1])?);
                 }
                 op::LessThan => {
                     let v1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    top.set(arith::less_than(top.bind_as(cx)?, &[v1.try_into()?]));
+                    top.set(arith::less_than(top.bind(cx), &[v1])?);
                 }
                 op::LessThanOrEqual => {
                     let v1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    top.set(arith::less_than_or_eq(top.bind_as(cx)?, &[v1.try_into()?]));
+                    top.set(arith::less_than_or_eq(top.bind(cx), &[v1])?);
                 }
                 op::GreaterThanOrEqual => {
-                    let v1 = &[self.env.stack.pop(cx).try_into()?];
+                    let v1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    top.set(arith::greater_than_or_eq(top.bind_as(cx)?, v1));
+                    top.set(arith::greater_than_or_eq(top.bind(cx), &[v1])?);
+                }
+                op::Diff => {
+                    let arg1 = self.env.stack.pop(cx);
+                    let top = self.env.stack.top();
+                    top.set(cx.add(arith::sub(Some(top.bind(cx)), &[arg1])?));
                 }
-                op::Diff => todo!("Diff bytecode"),
                 op::Negate => {
                     let top = self.env.stack.top();
-                    top.set(cx.add(arith::sub(top.bind_as(cx)?, &[])));
+                    top.set(cx.add(arith::sub(Some(top.bind(cx)), &[])?));
                 }
                 op::Plus => {
                     let arg1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    let args = &[top.bind_as(cx)?, arg1.try_into()?];
-                    top.set(cx.add(arith::add(args)));
+                    top.set(cx.add(arith::add(&[top.bind(cx), arg1])?));
                 }
                 op::Max => {
                     let arg1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    let args = &[arg1.try_into()?];
-                    top.set(cx.add(arith::max(top.bind_as(cx)?, args)));
+                    top.set(cx.add(arith::max(top.bind(cx), &[arg1])?));
                 }
                 op::Min => {
                     let arg1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    let args = &[arg1.try_into()?];
-                    top.set(cx.add(arith::min(top.bind_as(cx)?, args)));
+                    top.set(cx.add(arith::min(top.bind(cx), &[arg1])?));
                 }
                 op::Multiply => {
                     let arg1 = self.env.stack.pop(cx);
                     let top = self.env.stack.top();
-                    let args = &[top.bind_as(cx)?, arg1.try_into()?];
-                    top.set(cx.add(arith::mul(args)));
-                }
-                op::Point => todo!("Point bytecode"),
-                op::GotoChar => todo!("GotoChar bytecode"),
-                op::Insert => todo!("Insert bytecode"),
-                op::PointMax => todo!("PointMax bytecode"),
-                op::PointMin => todo!("PointMin bytecode"),
-                op::CharAfter => todo!("CharAfter bytecode"),
-                op::FollowingChar => todo!("FollowingChar bytecode"),
-                op::PrecedingChar => todo!("PrecedingChar bytecode"),
-                op::CurrentColumn => todo!("CurrentColumn bytecode"),
-                op::IndentTo => todo!("IndentTo bytecode"),
-                op::EndOfLineP => todo!("EndOfLineP bytecode"),
-                op::EndOfBufferP => todo!("EndOfBufferP bytecode"),
-                op::BeginningOfLineP => todo!("BeginningOfLineP bytecode"),
-                op::BeginningOfBufferP => todo!("BeginningOfBufferP bytecode"),
-                op::CurrentBuffer => todo!("CurrentBuffer bytecode"),
-                op::SetBuffer => todo!("SetBuffer bytecode"),
-                op::SaveCurrentBuffer1 => todo!("SaveCurrentBuffer1 bytecode"),
-                op::ForwardChar => todo!("ForwardChar bytecode"),
-                op::ForwardWord => todo!("ForwardWord bytecode"),
-                op::SkipCharsForward => todo!("SkipCharsForward bytecode"),
-                op::SkipCharsBackward => todo!("SkipCharsBackward bytecode"),
-                op::ForwardLine => todo!("ForwardLine bytecode"),
-                op::CharSyntax => todo!("CharSyntax bytecode"),
-                op::BufferSubstring => todo!("BufferSubstring bytecode"),
-                op::DeleteRegion => todo!("DeleteRegion bytecode"),
-                op::NarrowToRegion => todo!("NarrowToRegion bytecode"),
-                op::Widen => todo!("Widen bytecode"),
-                op::EndOfLine => todo!("EndOfLine bytecode"),
+                    top.set(cx.add(arith::mul(&[top.bind(cx), arg1])?));
+                }
+                op::Point => {
+                    let point = editfns::point(self.env);
+                    self.env.stack.push(cx.add(point));
+                }
+                op::GotoChar => {
+                    let position = self.env.stack.top().bind(cx);
+                    editfns::goto_char(position, self.env)?;
+                }
+                op::Insert => {
+                    editfns::insert(ArgSlice::new(1), self.env, cx)?;
+                    self.env.stack.top().set(NIL);
+                }
+                op::PointMax => {
+                    let max = editfns::point_max(self.env)?;
+                    self.env.stack.push(cx.add(max));
+                }
+                op::PointMin => {
+                    let min = editfns::point_min(self.env);
+                    self.env.stack.push(cx.add(min));
+                }
+                op::CharAfter => {
+                    let pos = self.env.stack.top().bind(cx).try_into()?;
+                    let chr = editfns::char_after(pos, self.env);
+                    self.env.stack.top().set(cx.add(chr));
+                }
+                op::FollowingChar => {
+                    let chr = editfns::following_char(self.env);
+                    self.env.stack.push(cx.add(chr));
+                }
+                op::PrecedingChar => {
+                    let chr = editfns::preceding_char(self.env);
+                    self.env.stack.push(cx.add(chr));
+                }
+                op::CurrentColumn => {
+                    let column = indent::current_column(self.env, cx);
+                    self.env.stack.push(cx.add(column));
+                }
+                op::IndentTo => {
+                    let column = self.env.stack.top().bind(cx).try_into()?;
+                    let column = indent::indent_to(column, None, self.env, cx)?;
+                    self.env.stack.top().set(cx.add(column));
+                }
+                op::EndOfLineP => {
+                    let eolp = editfns::eolp(self.env);
+                    self.env.stack.push(cx.add(eolp));
+                }
+                op::EndOfBufferP => {
+                    let eobp = editfns::eobp(self.env);
+                    self.env.stack.push(cx.add(eobp));
+                }
+                op::BeginningOfLineP => {
+                    let bolp = editfns::bolp(self.env);
+                    self.env.stack.push(cx.add(bolp));
+                }
+                op::BeginningOfBufferP => {
+                    let bobp = editfns::bobp(self.env);
+                    self.env.stack.push(cx.add(bobp));
+                }
+                op::CurrentBuffer => {
+                    let current = buffer::current_buffer(self.env, cx);
+                    self.env.stack.push(cx.add(current));
+                }
+                op::SetBuffer => {
+                    let top = self.env.stack.top().bind(cx);
+                    let current = buffer::set_buffer(top, self.env, cx)?;
+                    self.env.stack.top().set(current);
+                }
+                op::SaveCurrentBuffer1 => self.env.save_current_buffer(),
+                op::ForwardChar => {
+                    let n = Object::try_from_option(self.env.stack.top().bind(cx))?;
+                    cmds::forward_char(n, self.env, cx)?;
+                    self.env.stack.top().set(NIL);
+                }
+                op::ForwardWord => {
+                    let arg = Object::try_from_option(self.env.stack.top().bind(cx))?;
+                    let found = syntax::forward_word(arg, self.env, cx);
+                    self.env.stack.top().set(cx.add(found));
+                }
+                op::SkipCharsForward => {
+                    let lim = self.env.stack.pop(cx).try_into()?;
+                    let string = self.env.stack.top().bind_as(cx)?;
+                    let moved = syntax::skip_chars_forward(string, lim, self.env);
+                    self.env.stack.top().set(cx.add(moved));
+                }
+                op::SkipCharsBackward => {
+                    let lim = self.env.stack.pop(cx).try_into()?;
+                    let string = self.env.stack.top().bind_as(cx)?;
+                    let moved = syntax::skip_chars_backward(string, lim, self.env);
+                    self.env.stack.top().set(cx.add(moved));
+                }
+                op::ForwardLine => {
+                    let n = Object::try_from_option(self.env.stack.top().bind(cx))?;
+                    let shortage = cmds::forward_line(n, self.env);
+                    self.env.stack.top().set(cx.add(shortage));
+                }
+                op::CharSyntax => {
+                    let character = self.env.stack.pop(cx).try_into()?;
+                    let syntax = syntax::char_syntax(character, self.env, cx);
+                    self.env.stack.push(cx.add(syntax));
+                }
+                op::BufferSubstring => {
+                    let end = self.env.stack.pop(cx).try_into()?;
+                    let start = self.env.stack.top().bind(cx).try_into()?;
+                    let string = editfns::buffer_substring(start, end, self.env, cx)?;
+                    self.env.stack.top().set(string);
+                }
+                op::DeleteRegion => {
+                    let end = self.env.stack.pop(cx).try_into()?;
+                    let start = self.env.stack.top().bind(cx).try_into()?;
+                    editfns::delete_region(start, end, self.env, cx)?;
+                    self.env.stack.top().set(NIL);
+                }
+                op::NarrowToRegion => {
+                    let end = self.env.stack.pop(cx).try_into()?;
+                    let start = self.env.stack.top().bind(cx).try_into()?;
+                    editfns::narrow_to_region(start, end, self.env)?;
+                    self.env.stack.top().set(NIL);
+                }
+                op::Widen => {
+                    editfns::widen(self.env);
+                    self.env.stack.push(NIL);
+                }
+                op::EndOfLine => {
+                    let n = Object::try_from_option(self.env.stack.top().bind(cx))?;
+                    cmds::end_of_line(n, self.env);
+                    self.env.stack.top().set(NIL);
+                }
                 op::ConstantN2 => {
                     let idx = self.pc.arg2();
                     let cnst = self.get_const(idx.into(), cx);
@@ -668,6 +768,15 @@ impl<'ob> RootedVM<'_, '_, '_> {
                     }
                 }
                 op::Return => {
+                    // Frames called from the interpreter or a builtin are
+                    // handled by `Function::call`
+                    let bytecode_caller = self.env.stack.prev_bytecode_frame().is_some();
+                    if bytecode_caller && self.env.stack.debug_on_exit() {
+                        let top = self.env.stack.top().bind(cx);
+                        root!(top, cx);
+                        let value = crate::eval::debug_on_exit(top, self.env, cx)?;
+                        self.env.stack.top().set(value);
+                    }
                     if let Some((f, offset)) = self.env.stack.prev_bytecode_frame() {
                         self.set_current_frame(f.bind(cx), offset);
                         let top = self.env.stack.top().bind(cx);
@@ -698,10 +807,16 @@ impl<'ob> RootedVM<'_, '_, '_> {
                     let top = self.env.stack[0].bind(cx);
                     self.env.stack.push(top);
                 }
-                op::SaveExcursion => todo!("SaveExcursion bytecode"),
-                op::SaveRestriction => todo!("SaveRestriction bytecode"),
+                op::SaveExcursion => self.env.save_excursion(cx),
+                op::SaveRestriction => self.env.save_restriction(cx),
                 op::UnwindProtect => todo!("UnwindProtect bytecode"),
-                op::SetMarker => todo!("SetMarker bytecode"),
+                op::SetMarker => {
+                    let buffer = Object::try_from_option(self.env.stack.pop(cx))?;
+                    let position = self.env.stack.pop(cx);
+                    let marker = self.env.stack.top().bind(cx).try_into()?;
+                    let marker = marker::set_marker(marker, Some(position), buffer, self.env)?;
+                    self.env.stack.top().set::<Object>(marker.into());
+                }
                 op::MatchBeginning => todo!("MatchBeginning bytecode"),
                 op::MatchEnd => todo!("MatchEnd bytecode"),
                 op::Upcase => todo!("Upcase bytecode"),
@@ -779,7 +894,13 @@ impl<'ob> RootedVM<'_, '_, '_> {
                     self.env.stack.top().set(list);
                 }
                 op::ConcatN => todo!("ConcatN bytecode"),
-                op::InsertN => todo!("InsertN bytecode"),
+                op::InsertN => {
+                    let arg_cnt = usize::from(self.pc.arg1());
+                    editfns::insert(ArgSlice::new(arg_cnt), self.env, cx)?;
+                    let len = self.env.stack.len();
+                    self.env.stack.truncate(len - arg_cnt);
+                    self.env.stack.push(NIL);
+                }
                 op::Switch => {
                     let ObjectType::HashTable(table) = self.env.stack.pop(cx).untag() else {
                         unreachable!("switch table was not a hash table")
@@ -919,17 +1040,26 @@ pub(crate) fn call<'ob>(
     let vm = VM {
         pc: ProgramCounter::new(func.codes()),
         func: Slot::new(func),
-        env: frame,
         handlers: Vec::new(),
+        binding_depth: frame.binding_depth(),
+        env: frame,
     };
     root!(vm, cx);
+    let frame_idx = vm.env.stack.current_frame();
     vm.prepare_lisp_args(func, arg_cnt, name, cx)?;
-    vm.run(cx).map_err(|e| e.add_trace(name, vm.env.stack.current_args()))
+    vm.run(cx).map_err(|e| {
+        // Drop the frames and temporaries of the calls that the error exits
+        vm.env.stack.unwind_frames(frame_idx);
+        vm.env.stack.truncate_to_args();
+        e.add_trace(name, vm.env.stack.current_args())
+    })
 }
 
 #[cfg(test)]
 mod test {
     use crate::core::{
+        cons::Cons,
+        env::{intern, sym},
         gc::RootSet,
         object::{HashTable, IntoObject},
     };
@@ -997,6 +1127,20 @@ mod test {
         })
     }
 
+    /// Read the printed form of a function produced by `byte-compile`, like
+    /// the ones found in `.elc` files. The tests that use this were compiled
+    /// with the bundled bytecomp from the lambda in the comment above each.
+    macro_rules! compiled_bytecode {
+        ($name:ident, $printed:expr, $cx:expr $(,)?) => {
+            let cx1: &Context = $cx;
+            let ObjectType::ByteFn(bytecode) = read($printed, cx1).untag() else {
+                panic!("not a byte-code function: {}", $printed)
+            };
+            root!(bytecode, cx1);
+            let $name = bytecode;
+        };
+    }
+
     fn check_bytecode_internal(
         args: &mut Rt<Vec<Slot<Object>>>,
         bytecode: &Rto<&ByteFn>,
@@ -1012,6 +1156,23 @@ mod test {
         assert_eq!(val, expect);
     }
 
+    fn read<'ob>(string: &str, cx: &'ob Context) -> Object<'ob> {
+        crate::reader::read(string, cx).unwrap().0
+    }
+
+    /// Evaluate `form` with the interpreter, with `f` bound to `func`.
+    fn check_interpreted(form: &str, func: &Rto<&ByteFn>, expect: &str, cx: &mut Context) {
+        root!(env, new(Env), cx);
+        let binding = Cons::new(intern("f", cx), func.bind(cx), cx);
+        let lexical = list![binding; cx];
+        root!(lexical, cx);
+        let form = read(form, cx);
+        root!(form, cx);
+        let val = rebind!(crate::interpreter::eval(form, Some(lexical), env, cx).unwrap(), cx);
+        let expect = read(expect, cx);
+        assert_eq!(val, expect);
+    }
+
     #[test]
     fn test_basic() {
         use OpCode::*;
@@ -1264,4 +1425,359 @@ mod test {
         root!(inner, cx);
         check_bytecode!(outer, [inner], 7, cx);
     }
+
+    #[test]
+    fn test_catch() {
+        use OpCode as O;
+
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+
+        // (lambda (f) (catch 'done (funcall f) 7))
+        make_bytecode!(
+            outer,
+            257,
+            [
+                O::Constant0,
+                O::PushCatch,
+                0x09,
+                0x0,
+                O::Duplicate,
+                O::Call0,
+                O::Discard,
+                O::Constant1,
+                O::PopHandler,
+                O::Return
+            ],
+            [intern("done", cx), 7],
+            cx
+        );
+        let throws = read("(closure (t) nil (throw 'done 5))", cx);
+        root!(throws, cx);
+        check_bytecode!(outer, [throws], 5, cx);
+        let returns = read("(closure (t) nil (catch 'other 3))", cx);
+        root!(returns, cx);
+        check_bytecode!(outer, [returns], 7, cx);
+    }
+
+    #[test]
+    fn test_interpreted_handlers() {
+        use OpCode as O;
+
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+
+        // (lambda (f) (condition-case err (funcall f) (my-error (cdr err))))
+        make_bytecode!(
+            outer,
+            257,
+            [
+                O::Constant0,
+                O::PushCondtionCase,
+                0x08,
+                0x0,
+                O::Duplicate,
+                O::Call0,
+                O::PopHandler,
+                O::Return,
+                O::Cdr,
+                O::Return
+            ],
+            [read("(my-error)", cx)],
+            cx
+        );
+        // The error passes through an interpreted `condition-case' that
+        // doesn't match it, after the `handler-bind' handler has seen it
+        let signals = read(
+            "(closure (t) nil
+               (condition-case nil
+                   (handler-bind-1 #'(lambda () (signal 'my-error (list 1 2)))
+                                   '(my-error) #'(lambda (e) (setcar (cdr e) 'seen)))
+                 (arith-error 'wrong)))",
+            cx,
+        );
+        root!(signals, cx);
+        let expect = read("(seen 2)", cx);
+        root!(expect, cx);
+        check_bytecode!(outer, [signals], expect, cx);
+    }
+
+    #[test]
+    fn test_bytecode_in_interpreted_handlers() {
+        use OpCode as O;
+
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+
+        // (lambda () (throw 'done 9))
+        make_bytecode!(
+            throws,
+            0,
+            [O::Constant0, O::Constant1, O::Constant2, O::Call2, O::Return],
+            [sym::THROW, intern("done", cx), 9],
+            cx
+        );
+        check_interpreted("(catch 'done (funcall f) 1)", throws, "9", cx);
+        check_interpreted("(catch 'done (catch 'other (funcall f)) 1)", throws, "9", cx);
+
+        // (lambda () (car 1))
+        make_bytecode!(signals, 0, [O::Constant0, O::Car, O::Return], [1], cx);
+        check_interpreted(
+            "(condition-case nil (funcall f) (arith-error 'wrong) (error 'caught))",
+            signals,
+            "caught",
+            cx,
+        );
+        check_interpreted("(ignore-error-wrapper f)", signals, "nil", cx);
+
+        // (lambda () 5)
+        make_bytecode!(returns, 0, [O::Constant0, O::Return], [5], cx);
+        check_interpreted(
+            "(condition-case v (funcall f) (:success (list 'ok v)) (error 'failed))",
+            returns,
+            "(ok 5)",
+            cx,
+        );
+    }
+
+    #[test]
+    fn test_backtrace_frame() {
+        use OpCode as O;
+
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+
+        // (lambda (a) (backtrace-frame 1))
+        make_bytecode!(
+            inner,
+            257,
+            [O::Constant0, O::Constant1, O::Call1, O::Return],
+            [sym::BACKTRACE_FRAME, 1],
+            cx
+        );
+
+        // (lambda (x) (funcall x 5))
+        make_bytecode!(outer, 257, [O::Duplicate, O::Constant0, O::Call1, O::Return], [5], cx);
+        let expect = list![sym::TRUE, inner.bind(cx), 5; cx];
+        root!(expect, cx);
+        let inner = cx.add(inner.bind(cx));
+        root!(inner, cx);
+        check_bytecode!(outer, [inner], expect, cx);
+    }
+
+    #[test]
+    fn test_string_ops() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+
+        // (lambda (a b) (concat a b))
+        compiled_bytecode!(bytecode, r#"#[514 "\001\001P\207" [] 4]"#, cx);
+        check_bytecode!(bytecode, ["foo", "bar"], "foobar", cx);
+
+        // (lambda (a b c) (concat a b c))
+        compiled_bytecode!(bytecode, r#"#[771 "\002\002\002Q\207" [] 6]"#, cx);
+        check_bytecode!(bytecode, ["a", "b", "c"], "abc", cx);
+
+        // (lambda (a b c d) (concat a b c d))
+        compiled_bytecode!(bytecode, r#"#[1028 "\003\003\003\003R\207" [] 8]"#, cx);
+        check_bytecode!(bytecode, ["a", "b", "c", "d"], "abcd", cx);
+
+        // (lambda (s) (substring s 1 -1))
+        compiled_bytecode!(bytecode, r#"#[257 "\211\300\301O\207" [1 -1] 4]"#, cx);
+        check_bytecode!(bytecode, ["hello"], "ell", cx);
+
+        // (lambda (s n) (substring s n))
+        compiled_bytecode!(bytecode, r#"#[514 "\001\001\300O\207" [nil] 5]"#, cx);
+        check_bytecode!(bytecode, ["hello", 2], "llo", cx);
+
+        // (lambda (x y) (- x y))
+        compiled_bytecode!(bytecode, r#"#[514 "\001\001Z\207" [] 4]"#, cx);
+        check_bytecode!(bytecode, [5, 3], 2, cx);
+        check_bytecode!(bytecode, [1.5, 1], 0.5, cx);
+    }
+
+    #[test]
+    fn test_buffer_ops() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+
+        // (lambda () (insert "hello") (point))
+        compiled_bytecode!(bytecode, r#"#[0 "\300c\210`\207" ["hello"] 1]"#, cx);
+        check_bytecode!(bytecode, [], 6, cx);
+
+        // (lambda (n) (insert "hello") (goto-char n) (point))
+        compiled_bytecode!(bytecode, r#"#[257 "\300c\210\211b\210`\207" ["hello"] 2]"#, cx);
+        check_bytecode!(bytecode, [3], 3, cx);
+        check_bytecode!(bytecode, [10], 6, cx);
+
+        // (lambda () (insert "hello") (list (point-min) (point-max)))
+        compiled_bytecode!(bytecode, r#"#[0 "\300c\210edD\207" ["hello"] 2]"#, cx);
+        let expect = read("(1 6)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+
+        // (lambda (n) (insert "hello") (char-after n))
+        compiled_bytecode!(bytecode, r#"#[257 "\300c\210\211f\207" ["hello"] 2]"#, cx);
+        check_bytecode!(bytecode, [2], 'e', cx);
+        check_bytecode!(bytecode, [6], false, cx);
+        check_bytecode!(bytecode, [false], false, cx);
+
+        // (lambda () (insert "hello") (goto-char 2) (list (preceding-char) (following-char)))
+        compiled_bytecode!(bytecode, r#"#[0 "\300c\210\301b\210hgD\207" ["hello" 2] 2]"#, cx);
+        let expect = read("(104 101)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+
+        // (lambda () (insert "a\nbc") (list (current-column) (indent-to 4) (current-column)))
+        compiled_bytecode!(bytecode, r#"#[0 "\300c\210i\301jiE\207" ["a\nbc" 4] 3]"#, cx);
+        let expect = read("(2 4 4)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+
+        // (lambda (n) (insert "a\nb") (goto-char n) (list (bobp) (bolp) (eolp) (eobp)))
+        compiled_bytecode!(bytecode, r#"#[257 "\300c\210\211b\210onlmF\207" ["a\nb"] 5]"#, cx);
+        for (pos, expect) in [
+            (1, "(t t nil nil)"),
+            (2, "(nil nil t nil)"),
+            (3, "(nil t nil nil)"),
+            (4, "(nil nil t t)"),
+        ] {
+            let expect = read(expect, cx);
+            root!(expect, cx);
+            check_bytecode!(bytecode, [pos], expect, cx);
+        }
+    }
+
+    #[test]
+    fn test_motion_ops() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+
+        // (lambda ()
+        //   (insert "foo bar\nbaz")
+        //   (goto-char 1)
+        //   (list (forward-word) (point) (forward-char 2) (point) (forward-line) (point)))
+        compiled_bytecode!(
+            bytecode,
+            r#"#[0 "\300c\210\301b\210\302v`\303u`\302y`\257\006\207" ["foo bar\nbaz" 1 nil 2] 6]"#,
+            cx
+        );
+        let expect = read("(t 4 nil 6 0 9)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+
+        // (lambda ()
+        //   (insert "abc123")
+        //   (goto-char 1)
+        //   (list (skip-chars-forward "a-z") (point) (skip-chars-backward "^1" 2) (point)))
+        compiled_bytecode!(
+            bytecode,
+            r#"#[0 "\300c\210\301b\210\302\303w`\304\305x`F\207" ["abc123" 1 "a-z" nil "^1" 2] 4]"#,
+            cx
+        );
+        let expect = read("(3 4 -2 2)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+
+        // (lambda (c) (char-syntax c))
+        compiled_bytecode!(bytecode, r#"#[257 "\211z\207" [] 2]"#, cx);
+        check_bytecode!(bytecode, ['a'], 'w', cx);
+        check_bytecode!(bytecode, [' '], ' ', cx);
+    }
+
+    #[test]
+    fn test_current_buffer_ops() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+        let name = "test_current_buffer_ops";
+        crate::buffer::get_buffer_create(cx.add(name), Some(NIL), cx).unwrap();
+
+        // (lambda (name) (eq (set-buffer name) (current-buffer)))
+        compiled_bytecode!(bytecode, r#"#[257 "\211qp=\207" [] 3]"#, cx);
+        check_bytecode!(bytecode, [name], true, cx);
+
+        // (lambda (name)
+        //   (list (save-current-buffer (set-buffer name) (insert "abc") (point))
+        //         (point)))
+        compiled_bytecode!(bytecode, r#"#[257 "r\211q\210\300c\210`)`D\207" ["abc"] 3]"#, cx);
+        let expect = read("(4 1)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [name], expect, cx);
+    }
+
+    #[test]
+    fn test_excursion_ops() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+
+        // (lambda ()
+        //   (insert "hello")
+        //   (list (save-excursion (goto-char 1) (insert "<") (point)) (point)))
+        compiled_bytecode!(
+            bytecode,
+            r#"#[0 "\300c\210\212\301b\210\302c\210`)`D\207" ["hello" 1 "<"] 2]"#,
+            cx
+        );
+        let expect = read("(2 7)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+
+        // (lambda ()
+        //   (insert "hello world")
+        //   (list (save-restriction
+        //           (narrow-to-region 1 6)
+        //           (list (buffer-substring (point-min) (point-max)) (point)))
+        //         (point-max)
+        //         (progn (narrow-to-region 7 12) (widen) (point-min))))
+        compiled_bytecode!(
+            bytecode,
+            r#"#[0 "\300c\210\214\301\302}\210ed{`)Dd\303\304}\210~\210eE\207" ["hello world" 1 6 7 12] 4]"#,
+            cx
+        );
+        let expect = read(r#"(("hello" 6) 12 1)"#, cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+
+        // (lambda (x)
+        //   (insert "hello")
+        //   (condition-case nil
+        //       (save-restriction
+        //         (save-excursion
+        //           (narrow-to-region 2 4)
+        //           (delete-region 2 3)
+        //           (car x)))
+        //     (error (list (point) (point-min) (point-max)))))
+        compiled_bytecode!(
+            bytecode,
+            r#"#[257 "\300c\210\3011\026\000\214\212\302\303}\210\302\304|\210\211@*0\207\210`edE\207" ["hello" (error) 2 4 3] 4]"#,
+            cx
+        );
+        let expect = read("(5 1 5)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [1], expect, cx);
+
+        // (lambda ()
+        //   (insert "ab\n" "cd")
+        //   (goto-char 1)
+        //   (end-of-line)
+        //   (list (point)
+        //         (marker-position (set-marker (make-marker) (point)))
+        //         (progn (end-of-line 2) (point))))
+        compiled_bytecode!(
+            bytecode,
+            r#"#[0 "\300\301\261\002\210\302b\210\303\177\210`\304\305 `\303\223!\306\177\210`E\207" ["ab\n" "cd" 1 nil marker-position make-marker 2] 5]"#,
+            cx
+        );
+        let expect = read("(3 3 6)", cx);
+        root!(expect, cx);
+        check_bytecode!(bytecode, [], expect, cx);
+    }
 }
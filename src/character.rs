@@ -1,9 +1,12 @@
 //! Character and string utilities.
 use crate::core::{
     gc::Context,
-    object::{Gc, Object, OptionalFlag, int_to_char},
+    object::{
+        Gc, Object, ObjectType, OptionalFlag, char_to_int, char_to_raw_byte, int_to_char,
+        raw_byte_to_char,
+    },
 };
-use anyhow::Result;
+use anyhow::{Result, bail};
 use rune_macros::defun;
 
 #[defun]
@@ -19,7 +22,9 @@ fn max_char(unicode: OptionalFlag) -> usize {
 
 #[defun]
 fn characterp(obj: Object) -> bool {
-    char::try_from(obj).is_ok()
+    // Characters above Unicode other than raw bytes can't be stored in a
+    // string, but they are still characters
+    matches!(obj.untag(), ObjectType::Int(0..=0x3F_FFFF))
 }
 
 #[defun]
@@ -35,7 +40,7 @@ fn make_string<'ob>(
     multibyte: OptionalFlag,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    if multibyte.is_some() {
+    if multibyte.is_some() || init > 0x7F {
         let chr = int_to_char(i64::try_from(init)?)?;
         let size = chr.len_utf8();
         let mut string = cx.string_with_capacity(length * size);
@@ -49,3 +54,18 @@ fn make_string<'ob>(
         Ok(cx.add(string))
     }
 }
+
+#[defun]
+fn unibyte_char_to_multibyte(ch: i64) -> Result<i64> {
+    let Ok(byte) = u8::try_from(ch) else { bail!("Not a unibyte character: {ch}") };
+    Ok(char_to_int(raw_byte_to_char(byte)))
+}
+
+#[defun]
+fn multibyte_char_to_unibyte(ch: char) -> i64 {
+    match char_to_raw_byte(ch) {
+        Some(byte) => i64::from(byte),
+        None if ch.is_ascii() => i64::from(ch as u8),
+        None => -1,
+    }
+}
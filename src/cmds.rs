@@ -0,0 +1,137 @@
+//! Simple built-in editing commands.
+use crate::core::{
+    cons::Cons,
+    env::{Env, sym},
+    gc::{Context, Rt},
+};
+use crate::data::LispError;
+use anyhow::Result;
+use rune_macros::defun;
+
+/// Move point N characters forward (backward if N is negative).
+/// On reaching end or beginning of buffer, stop and signal error.
+/// If N is omitted or nil, move point 1 character forward.
+#[defun]
+pub(crate) fn forward_char(n: Option<i64>, env: &mut Rt<Env>, cx: &Context) -> Result<()> {
+    let buf = env.current_buffer.get_mut();
+    let (begv, zv) = (buf.begv() as i64, buf.zv() as i64);
+    let target = (buf.text.cursor().chars() as i64).saturating_add(n.unwrap_or(1));
+    buf.text.set_cursor(target.clamp(begv, zv) as usize);
+    if target < begv {
+        Err(LispError::new(Cons::new1(sym::BEGINNING_OF_BUFFER, cx)).into())
+    } else if target > zv {
+        Err(LispError::new(Cons::new1(sym::END_OF_BUFFER, cx)).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Move N lines forward (backward if N is negative).
+/// Precisely, if point is on line I, move to the start of line I + N.
+/// If there isn't room, go as far as possible (no error).
+///
+/// Returns the count of lines left to move. If moving forward,
+/// that is N minus number of lines moved; if backward, N plus number
+/// moved.
+///
+/// With positive N, a non-empty line at the end counts as one line
+/// successfully moved (for the return value). This means in an empty
+/// line at the end of the buffer, or at the end of a line that lacks a
+/// trailing newline, the return value is N - 1.
+#[defun]
+pub(crate) fn forward_line(n: Option<i64>, env: &mut Rt<Env>) -> i64 {
+    let count = n.unwrap_or(1);
+    let buf = env.current_buffer.get_mut();
+    let (begv, zv) = (buf.begv(), buf.zv());
+    let start = buf.text.cursor().chars();
+    let mut pos = start;
+    let mut shortage;
+    if count > 0 {
+        shortage = count;
+        while shortage > 0 && pos < zv {
+            if buf.text.char_at(pos) == Some('\n') {
+                shortage -= 1;
+            }
+            pos += 1;
+        }
+    } else {
+        // Moving back to the start of the current line takes one extra
+        // newline
+        shortage = 1 - count;
+        while pos > begv {
+            if buf.text.char_at(pos - 1) == Some('\n') {
+                shortage -= 1;
+                if shortage == 0 {
+                    break;
+                }
+            }
+            pos -= 1;
+        }
+    }
+    buf.text.set_cursor(pos);
+    if shortage > 0
+        && (count <= 0 || (zv > begv && pos != start && buf.text.char_at(pos - 1) != Some('\n')))
+    {
+        shortage -= 1;
+    }
+    if count <= 0 { -shortage } else { shortage }
+}
+
+/// Move point to end of current line (in the logical order).
+/// With argument N not nil or 1, move forward N - 1 lines first.
+/// If point reaches the beginning or end of buffer, it stops there.
+#[defun]
+pub(crate) fn end_of_line(n: Option<i64>, env: &mut Rt<Env>) {
+    let n = n.unwrap_or(1);
+    if n != 1 {
+        forward_line(Some(n - 1), env);
+    }
+    let buf = env.current_buffer.get_mut();
+    let zv = buf.zv();
+    let mut pos = buf.text.cursor().chars();
+    while pos < zv && buf.text.char_at(pos) != Some('\n') {
+        pos += 1;
+    }
+    buf.text.set_cursor(pos);
+}
+
+defsym!(BEGINNING_OF_BUFFER);
+defsym!(END_OF_BUFFER);
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_forward_char() {
+        assert_lisp("(progn (insert \"hello\") (goto-char 1) (forward-char 2) (point))", "3");
+        assert_lisp("(progn (insert \"hello\") (forward-char -1) (point))", "5");
+        assert_lisp(
+            "(progn (insert \"hello\") (condition-case nil (forward-char) (end-of-buffer (point))))",
+            "6",
+        );
+        assert_lisp(
+            "(progn (insert \"hello\") (condition-case nil (forward-char -10) (beginning-of-buffer (point))))",
+            "1",
+        );
+    }
+
+    #[test]
+    fn test_forward_line() {
+        assert_lisp(
+            "(progn (insert \"a\\nb\\nc\") (goto-char 1) (list (forward-line) (point)))",
+            "(0 3)",
+        );
+        assert_lisp(
+            "(progn (insert \"a\\nb\\nc\") (goto-char 1) (list (forward-line 5) (point)))",
+            "(2 6)",
+        );
+        assert_lisp(
+            "(progn (insert \"a\\nb\\n\") (goto-char 1) (list (forward-line 5) (point)))",
+            "(3 5)",
+        );
+        assert_lisp("(progn (insert \"a\\nbc\") (list (forward-line 0) (point)))", "(0 3)");
+        assert_lisp("(progn (insert \"a\\nbc\") (list (forward-line -1) (point)))", "(0 1)");
+        assert_lisp("(progn (insert \"a\\nbc\") (list (forward-line -3) (point)))", "(-2 1)");
+    }
+}
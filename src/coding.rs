@@ -0,0 +1,630 @@
+//! Coding systems for converting between text and bytes.
+use crate::{
+    core::{
+        env::{CurrentBuffer, Env, intern, sym},
+        error::{Type, TypeError},
+        gc::{Context, Rt},
+        object::{
+            NIL, Object, ObjectType, Position, TRUE, char_to_raw_byte, push_utf8_raw,
+            raw_byte_to_char,
+        },
+    },
+    editfns,
+    eval::EvalError,
+    fns,
+};
+use anyhow::{Result, bail};
+use rune_macros::defun;
+use std::borrow::Cow;
+
+/// How the end of a line is represented in encoded text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Eol {
+    /// A line feed
+    Unix,
+    /// A carriage return followed by a line feed
+    Dos,
+    /// A carriage return
+    Mac,
+}
+
+impl Eol {
+    const ALL: [Eol; 3] = [Eol::Unix, Eol::Dos, Eol::Mac];
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Eol::Unix => "-unix",
+            Eol::Dos => "-dos",
+            Eol::Mac => "-mac",
+        }
+    }
+
+    /// Determine the end of line convention from the first line ending in
+    /// `bytes`.
+    fn detect(bytes: &[u8]) -> Option<Self> {
+        let idx = bytes.iter().position(|&x| x == b'\r' || x == b'\n')?;
+        match &bytes[idx..] {
+            [b'\r', b'\n', ..] => Some(Eol::Dos),
+            [b'\r', ..] => Some(Eol::Mac),
+            _ => Some(Eol::Unix),
+        }
+    }
+}
+
+/// The character encoding of a coding system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Detect the encoding when decoding
+    Undecided,
+    Utf8,
+    /// UTF-8 with a leading byte order mark
+    Utf8WithSignature,
+    Latin1,
+    Ascii,
+    /// Bytes are decoded as is, but line endings are still converted
+    RawText,
+    /// Bytes are decoded as is, with no conversion at all
+    NoConversion,
+}
+
+/// The base names of the coding systems. The first name of each kind is the
+/// canonical one.
+const NAMES: &[(&str, Kind)] = &[
+    ("undecided", Kind::Undecided),
+    ("utf-8", Kind::Utf8),
+    ("utf-8-with-signature", Kind::Utf8WithSignature),
+    ("iso-latin-1", Kind::Latin1),
+    ("iso-8859-1", Kind::Latin1),
+    ("latin-1", Kind::Latin1),
+    ("us-ascii", Kind::Ascii),
+    ("raw-text", Kind::RawText),
+    ("no-conversion", Kind::NoConversion),
+    ("binary", Kind::NoConversion),
+];
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+impl Kind {
+    fn name(self) -> &'static str {
+        NAMES.iter().find(|(_, kind)| *kind == self).unwrap().0
+    }
+
+    /// Return the kinds that can decode `bytes`, best first.
+    fn detect(bytes: &[u8]) -> Vec<Self> {
+        if bytes.is_ascii() {
+            return vec![Kind::Undecided];
+        }
+        let mut kinds = Vec::new();
+        if std::str::from_utf8(bytes).is_ok() {
+            if bytes.starts_with(BOM) {
+                kinds.push(Kind::Utf8WithSignature);
+            }
+            kinds.push(Kind::Utf8);
+        }
+        kinds.extend([Kind::Latin1, Kind::RawText, Kind::NoConversion]);
+        kinds
+    }
+}
+
+/// A coding system. The end of line convention is `None` when it is detected
+/// while decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CodingSystem {
+    kind: Kind,
+    eol: Option<Eol>,
+}
+
+impl CodingSystem {
+    pub(crate) const UNDECIDED: Self = Self { kind: Kind::Undecided, eol: None };
+    pub(crate) const UTF_8_UNIX: Self = Self { kind: Kind::Utf8, eol: Some(Eol::Unix) };
+
+    fn from_name(name: &str) -> Option<Self> {
+        let lookup = |name: &str| NAMES.iter().find(|(x, _)| *x == name).map(|(_, kind)| *kind);
+        if let Some(kind) = lookup(name) {
+            let eol = (kind == Kind::NoConversion).then_some(Eol::Unix);
+            return Some(Self { kind, eol });
+        }
+        let eol = Eol::ALL.into_iter().find(|eol| name.ends_with(eol.suffix()))?;
+        let kind = lookup(&name[..name.len() - eol.suffix().len()])?;
+        // no-conversion has no end of line variants
+        (kind != Kind::NoConversion).then_some(Self { kind, eol: Some(eol) })
+    }
+
+    /// Return the coding system named by `coding`, or `None` if it is nil.
+    /// Signal `coding-system-error` if it doesn't name a coding system.
+    pub(crate) fn from_object(coding: Object, env: &mut Rt<Env>) -> Result<Option<Self>> {
+        match coding.untag() {
+            ObjectType::NIL => Ok(None),
+            ObjectType::Symbol(name) => match Self::from_name(name.name()) {
+                Some(coding) => Ok(Some(coding)),
+                None => Err(EvalError::signal(sym::CODING_SYSTEM_ERROR.into(), coding, env).into()),
+            },
+            _ => Err(TypeError::new(Type::Symbol, coding).into()),
+        }
+    }
+
+    fn name(self) -> String {
+        match self.eol {
+            Some(eol) if self.kind != Kind::NoConversion => {
+                format!("{}{}", self.kind.name(), eol.suffix())
+            }
+            _ => self.kind.name().to_owned(),
+        }
+    }
+
+    pub(crate) fn to_object<'ob>(self, cx: &'ob Context) -> Object<'ob> {
+        intern(&self.name(), cx).into()
+    }
+
+    /// Decode `bytes` into text. Returns the text and the coding system that
+    /// was used once the encoding and end of line convention were detected.
+    /// Bytes that are not valid in the encoding become raw byte characters,
+    /// so decoding and then encoding gives back the same bytes.
+    pub(crate) fn decode(self, bytes: &[u8]) -> (String, Self) {
+        let kind = match self.kind {
+            Kind::Undecided => Kind::detect(bytes)[0],
+            kind => kind,
+        };
+        let eol = self.eol.or_else(|| Eol::detect(bytes));
+        let mut bytes = Cow::Borrowed(bytes);
+        match eol {
+            Some(Eol::Dos) if kind != Kind::NoConversion => {
+                let mut converted = Vec::with_capacity(bytes.len());
+                let mut iter = bytes.iter().peekable();
+                while let Some(&byte) = iter.next() {
+                    if byte != b'\r' || iter.peek() != Some(&&b'\n') {
+                        converted.push(byte);
+                    }
+                }
+                bytes = Cow::Owned(converted);
+            }
+            Some(Eol::Mac) if kind != Kind::NoConversion => {
+                let converted = bytes.iter().map(|&x| if x == b'\r' { b'\n' } else { x });
+                bytes = Cow::Owned(converted.collect());
+            }
+            _ => {}
+        }
+
+        let mut text = String::with_capacity(bytes.len());
+        match kind {
+            Kind::Undecided | Kind::Utf8 | Kind::Utf8WithSignature => {
+                let mut bytes = &*bytes;
+                if kind == Kind::Utf8WithSignature {
+                    bytes = bytes.strip_prefix(BOM).unwrap_or(bytes);
+                }
+                push_utf8_raw(&mut text, bytes);
+            }
+            Kind::Latin1 => text.extend(bytes.iter().map(|&x| x as char)),
+            Kind::Ascii | Kind::RawText | Kind::NoConversion => {
+                text.extend(bytes.iter().map(|&x| raw_byte_to_char(x)));
+            }
+        }
+        (text, Self { kind, eol })
+    }
+
+    /// Encode `text` into bytes. Raw byte characters are written as the bytes
+    /// they represent, and characters that can't be encoded become `?`.
+    pub(crate) fn encode(self, text: &str) -> Vec<u8> {
+        let text = match self.eol {
+            Some(Eol::Dos) => Cow::Owned(text.replace('\n', "\r\n")),
+            Some(Eol::Mac) => Cow::Owned(text.replace('\n', "\r")),
+            _ => Cow::Borrowed(text),
+        };
+        let mut bytes = Vec::with_capacity(text.len());
+        if self.kind == Kind::Utf8WithSignature {
+            bytes.extend_from_slice(BOM);
+        }
+        let mut buf = [0; 4];
+        for chr in text.chars() {
+            if let Some(byte) = char_to_raw_byte(chr) {
+                bytes.push(byte);
+                continue;
+            }
+            match self.kind {
+                Kind::Latin1 => bytes.push(u8::try_from(chr).unwrap_or(b'?')),
+                Kind::Ascii => bytes.push(if chr.is_ascii() { chr as u8 } else { b'?' }),
+                _ => bytes.extend_from_slice(chr.encode_utf8(&mut buf).as_bytes()),
+            }
+        }
+        bytes
+    }
+}
+
+/// Return the bytes of a string. Multibyte strings are taken as UTF-8, with
+/// raw byte characters standing for themselves.
+fn string_bytes(string: Object<'_>) -> Result<Cow<'_, [u8]>> {
+    match string.untag() {
+        ObjectType::String(s) => {
+            let text: &str = s;
+            if text.chars().any(|x| char_to_raw_byte(x).is_some()) {
+                Ok(Cow::Owned(CodingSystem::UTF_8_UNIX.encode(text)))
+            } else {
+                Ok(Cow::Borrowed(text.as_bytes()))
+            }
+        }
+        ObjectType::ByteString(s) => Ok(Cow::Borrowed(s.inner())),
+        _ => Err(TypeError::new(Type::String, string).into()),
+    }
+}
+
+fn set_last_coding_system(coding: CodingSystem, env: &mut Rt<Env>, cx: &Context) -> Result<()> {
+    env.set_var(sym::LAST_CODING_SYSTEM_USED, coding.to_object(cx))
+}
+
+/// Return t if OBJECT is nil or a coding system.
+#[defun]
+fn coding_system_p(object: Object) -> bool {
+    match object.untag() {
+        ObjectType::NIL => true,
+        ObjectType::Symbol(name) => CodingSystem::from_name(name.name()).is_some(),
+        _ => false,
+    }
+}
+
+/// Check validity of CODING-SYSTEM.
+/// If valid, return CODING-SYSTEM, else signal a `coding-system-error' error.
+#[defun]
+fn check_coding_system<'ob>(coding_system: Object<'ob>, env: &mut Rt<Env>) -> Result<Object<'ob>> {
+    CodingSystem::from_object(coding_system, env)?;
+    Ok(coding_system)
+}
+
+/// Return eol-type of CODING-SYSTEM.
+/// An eol-type is an integer 0, 1, 2, or a vector of coding systems.
+///
+/// Integer values 0, 1, and 2 indicate a format of end-of-line; LF, CRLF,
+/// and CR respectively.
+///
+/// A vector value indicates that a format of end-of-line should be
+/// detected automatically.  Nth element of the vector is the subsidiary
+/// coding system whose eol-type is N.
+#[defun]
+fn coding_system_eol_type<'ob>(
+    coding_system: Object,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let Some(coding) = CodingSystem::from_object(coding_system, env)? else { return Ok(NIL) };
+    Ok(match coding.eol {
+        Some(eol) => cx.add(Eol::ALL.iter().position(|x| *x == eol).unwrap()),
+        None => {
+            let variants: Vec<_> = Eol::ALL
+                .into_iter()
+                .map(|eol| CodingSystem { eol: Some(eol), ..coding }.to_object(cx))
+                .collect();
+            cx.add(variants)
+        }
+    })
+}
+
+/// Decode STRING which is encoded in CODING-SYSTEM, and return the result.
+///
+/// Optional third arg NOCOPY non-nil means it is OK to return STRING itself
+/// if the decoding operation is trivial.
+///
+/// Optional fourth arg BUFFER non-nil means that the decoded text is
+/// inserted in that buffer after point (point does not move).  In this
+/// case, the return value is the length of the decoded text.
+///
+/// This function sets `last-coding-system-used' to the precise coding system
+/// used (which may be different from CODING-SYSTEM if CODING-SYSTEM is
+/// not fully specified.)
+#[defun]
+fn decode_coding_string<'ob>(
+    string: Object<'ob>,
+    coding_system: Object,
+    nocopy: Option<Object>,
+    buffer: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let Some(coding) = CodingSystem::from_object(coding_system, env)? else {
+        return unconverted_string(string, nocopy, buffer, env, cx);
+    };
+    let bytes = string_bytes(string)?;
+    let (text, used) = coding.decode(&bytes);
+    set_last_coding_system(used, env, cx)?;
+    if let Some(buffer) = buffer.filter(|x| !x.is_nil()) {
+        let len = text.chars().count();
+        insert_after_point(buffer, cx.add(text), env, cx)?;
+        return Ok(cx.add(len));
+    }
+    if nocopy.is_some_and(|x| !x.is_nil()) && text.is_ascii() && text.as_bytes() == &*bytes {
+        return Ok(string);
+    }
+    Ok(cx.add(text))
+}
+
+/// Encode STRING to CODING-SYSTEM, and return the result.
+///
+/// Optional third arg NOCOPY non-nil means it is OK to return STRING
+/// itself if the encoding operation is trivial.
+///
+/// Optional fourth arg BUFFER non-nil means that the encoded text is
+/// inserted in that buffer after point (point does not move).  In this
+/// case, the return value is the length of the encoded text.
+///
+/// This function sets `last-coding-system-used' to the precise coding system
+/// used (which may be different from CODING-SYSTEM if CODING-SYSTEM is
+/// not fully specified.)
+#[defun]
+fn encode_coding_string<'ob>(
+    string: Object<'ob>,
+    coding_system: Object,
+    nocopy: Option<Object>,
+    buffer: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let Some(coding) = CodingSystem::from_object(coding_system, env)? else {
+        return unconverted_string(string, nocopy, buffer, env, cx);
+    };
+    let (bytes, trivial) = match string.untag() {
+        ObjectType::String(s) => {
+            let bytes = coding.encode(s);
+            let trivial = s.is_ascii() && bytes == s.as_bytes();
+            (bytes, trivial)
+        }
+        // Unibyte strings are already encoded
+        ObjectType::ByteString(s) => (s.to_vec(), true),
+        _ => bail!(TypeError::new(Type::String, string)),
+    };
+    set_last_coding_system(coding, env, cx)?;
+    if let Some(buffer) = buffer.filter(|x| !x.is_nil()) {
+        let len = bytes.len();
+        insert_after_point(buffer, cx.add(bytes), env, cx)?;
+        return Ok(cx.add(len));
+    }
+    if nocopy.is_some_and(|x| !x.is_nil()) && trivial {
+        return Ok(string);
+    }
+    Ok(cx.add(bytes))
+}
+
+/// The result of decoding or encoding STRING with a nil coding system, which
+/// doesn't change it. See `decode-coding-string` for the arguments.
+fn unconverted_string<'ob>(
+    string: Object<'ob>,
+    nocopy: Option<Object>,
+    buffer: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    if let Some(buffer) = buffer.filter(|x| !x.is_nil()) {
+        let len = fns::length(string)?;
+        insert_after_point(buffer, string, env, cx)?;
+        return Ok(cx.add(len));
+    }
+    if nocopy.is_some_and(|x| !x.is_nil()) {
+        return Ok(string);
+    }
+    fns::copy_sequence(string, cx)
+}
+
+/// Insert `text` after point in `buffer`, without moving point. This is where
+/// the coding functions put their result when they are given a buffer.
+fn insert_after_point(buffer: Object, text: Object, env: &mut Rt<Env>, cx: &Context) -> Result<()> {
+    let buffer = crate::buffer::resolve_buffer(buffer, cx)?;
+    // Fail here if the buffer was killed, instead of when it is made current
+    env.with_buffer(buffer, |_| ())?;
+    let current = env.current_buffer.get().lisp_buffer(cx);
+    env.set_buffer(buffer);
+    let point = env.current_buffer.get().text.cursor().chars();
+    let result = editfns::insert_object(&mut env.current_buffer, text, cx);
+    env.current_buffer.get_mut().text.set_cursor(point);
+    env.set_buffer(current);
+    result
+}
+
+/// Decode the current region from the specified coding system.
+///
+/// What's meant by "decoding" is transforming bytes into text
+/// (characters).  If, for instance, you have a region that contains data
+/// that represents the two bytes #xc2 #xa9, after calling this function
+/// with the utf-8 coding system, the region will contain the single
+/// character ?\N{COPYRIGHT SIGN}.
+///
+/// When called from a program, takes four arguments:
+///         START, END, CODING-SYSTEM, and DESTINATION.
+/// START and END are buffer positions.
+///
+/// Optional 4th arguments DESTINATION specifies where the decoded text goes.
+/// If nil, the region between START and END is replaced by the decoded text.
+/// If buffer, the decoded text is inserted in that buffer after point (point
+/// does not move).
+/// If t, the decoded text is returned.
+///
+/// This function sets `last-coding-system-used' to the precise coding system
+/// used (which may be different from CODING-SYSTEM if CODING-SYSTEM is
+/// not fully specified.)
+/// It returns the length of the decoded text.
+#[defun]
+fn decode_coding_region<'ob>(
+    Position(start): Position,
+    Position(end): Position,
+    coding_system: Object,
+    destination: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let (start, end) = if start <= end { (start, end) } else { (end, start) };
+    let (a, b) = env.current_buffer.get().slice_with_gap(start, end)?;
+    let region = format!("{a}{b}");
+    let coding = CodingSystem::from_object(coding_system, env)?;
+    let coding = coding.unwrap_or(CodingSystem { kind: Kind::NoConversion, eol: Some(Eol::Unix) });
+    let bytes = CodingSystem::UTF_8_UNIX.encode(&region);
+    let (text, used) = coding.decode(&bytes);
+    set_last_coding_system(used, env, cx)?;
+    let len = text.chars().count();
+    match destination {
+        Some(x) if x == TRUE => return Ok(cx.add(text)),
+        Some(buffer) if !buffer.is_nil() => insert_after_point(buffer, cx.add(text), env, cx)?,
+        _ => replace_region(&mut env.current_buffer, start, end, text, cx)?,
+    }
+    Ok(cx.add(len))
+}
+
+/// Replace the text between the 1-based positions `start` and `end` with
+/// `text`, keeping point after the region where it was.
+fn replace_region(
+    buffer: &mut CurrentBuffer,
+    start: usize,
+    end: usize,
+    text: String,
+    cx: &Context,
+) -> Result<()> {
+    let point = buffer.get().text.cursor().chars() + 1;
+    let len = text.chars().count();
+    editfns::delete_text(buffer, start, end, cx)?;
+    buffer.get_mut().text.set_cursor(start - 1);
+    editfns::insert_object(buffer, cx.add(text), cx)?;
+    let point = if point >= end { point - (end - start) + len } else { point.min(start) };
+    buffer.get_mut().text.set_cursor(point - 1);
+    Ok(())
+}
+
+/// Detect coding system of the text in STRING.
+/// Return a list of possible coding systems ordered by priority.
+/// The coding systems to try and their priorities follows what
+/// the function `coding-system-priority-list' (which see) returns.
+///
+/// If only ASCII characters are found (except for such ISO-2022 control
+/// characters as ESC), it returns a list of single element `undecided'
+/// or its subsidiary coding system according to a detected end-of-line
+/// format.
+///
+/// If optional argument HIGHEST is non-nil, return the coding system of
+/// highest priority.
+#[defun]
+fn detect_coding_string<'ob>(
+    string: Object,
+    highest: Option<Object>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let bytes = string_bytes(string)?;
+    let eol = Eol::detect(&bytes);
+    let coding: Vec<_> = Kind::detect(&bytes)
+        .into_iter()
+        .map(|kind| {
+            let eol = if kind == Kind::NoConversion { Some(Eol::Unix) } else { eol };
+            CodingSystem { kind, eol }.to_object(cx)
+        })
+        .collect();
+    if highest.is_some_and(|x| !x.is_nil()) {
+        Ok(coding[0])
+    } else {
+        Ok(crate::fns::slice_into_list(&coding, None, cx))
+    }
+}
+
+defsym!(CODING_SYSTEM_ERROR);
+defvar!(CODING_SYSTEM_FOR_READ);
+defvar!(CODING_SYSTEM_FOR_WRITE);
+defvar!(LAST_CODING_SYSTEM_USED);
+defvar!(BUFFER_FILE_CODING_SYSTEM);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_coding_system_names() {
+        let utf8_dos = CodingSystem { kind: Kind::Utf8, eol: Some(Eol::Dos) };
+        assert_eq!(CodingSystem::from_name("utf-8-dos"), Some(utf8_dos));
+        assert_eq!(utf8_dos.name(), "utf-8-dos");
+        assert_eq!(CodingSystem::from_name("latin-1").unwrap().name(), "iso-latin-1");
+        assert_eq!(CodingSystem::from_name("binary").unwrap().name(), "no-conversion");
+        assert_eq!(CodingSystem::from_name("no-conversion-dos"), None);
+        assert_eq!(CodingSystem::from_name("utf-9"), None);
+        assert_lisp("(list (coding-system-p 'utf-8-mac) (coding-system-p nil))", "(t t)");
+        assert_lisp("(coding-system-p 'utf-9)", "nil");
+        assert_lisp("(coding-system-eol-type 'latin-1-dos)", "1");
+        assert_lisp(
+            "(coding-system-eol-type 'us-ascii)",
+            "[us-ascii-unix us-ascii-dos us-ascii-mac]",
+        );
+        assert_lisp(
+            "(condition-case nil (check-coding-system 'utf-9) (error 'invalid))",
+            "invalid",
+        );
+    }
+
+    #[test]
+    fn test_decode_encode() {
+        let utf8 = CodingSystem::from_name("utf-8").unwrap();
+        let (text, used) = utf8.decode(b"a\xC3\xA9\xFF\r\nb");
+        assert_eq!(text, format!("a\u{E9}{}\nb", raw_byte_to_char(0xFF)));
+        assert_eq!(used.name(), "utf-8-dos");
+        assert_eq!(used.encode(&text), b"a\xC3\xA9\xFF\r\nb");
+
+        let latin1 = CodingSystem::from_name("latin-1-mac").unwrap();
+        assert_eq!(latin1.decode(b"\xE9\r").0, "\u{E9}\n");
+        assert_eq!(latin1.encode("\u{E9}\u{3B1}\n"), b"\xE9?\r");
+
+        let signature = CodingSystem::from_name("utf-8-with-signature").unwrap();
+        assert_eq!(signature.decode(b"\xEF\xBB\xBFab").0, "ab");
+        assert_eq!(signature.encode("ab"), b"\xEF\xBB\xBFab");
+
+        let raw = CodingSystem::from_name("raw-text-unix").unwrap();
+        assert_eq!(raw.decode(b"\xC3\xA9\r\n").0.chars().count(), 4);
+
+        // U+10FFAB is one of the code points used for raw bytes, so its
+        // encoding is kept as four raw bytes
+        let bytes = b"\xF4\x8F\xBE\xAB\xF4\x8F\xBC\x85\xFF";
+        let (text, used) = utf8.decode(bytes);
+        assert_eq!(text.chars().count(), 6);
+        assert_eq!(text.chars().nth(4), Some('\u{10FF05}'));
+        assert_eq!(used.encode(&text), bytes);
+        assert_eq!(
+            CodingSystem::UNDECIDED.decode(b"\xEF\xBB\xBFa").1.name(),
+            "utf-8-with-signature"
+        );
+        assert_eq!(CodingSystem::UNDECIDED.decode(b"a\xE9").1.name(), "iso-latin-1");
+    }
+
+    #[test]
+    fn test_coding_string() {
+        assert_lisp(
+            "(let ((s (unibyte-string #xF4 #x8F #xBE #xAB ?a))) (equal (encode-coding-string (decode-coding-string s 'utf-8) 'utf-8) s))",
+            "t",
+        );
+        assert_lisp(
+            r#"(list (decode-coding-string (unibyte-string #xC3 #xA9 13 10) 'utf-8) last-coding-system-used)"#,
+            "(\"\u{E9}\n\" utf-8-dos)",
+        );
+        assert_lisp(
+            r#"(string-equal (encode-coding-string "é\n" 'utf-8-dos) (unibyte-string #xC3 #xA9 13 10))"#,
+            "t",
+        );
+        assert_lisp(r#"(encode-coding-string "abc" nil)"#, r#""abc""#);
+        assert_lisp(r#"(detect-coding-string "a\r\nb")"#, "(undecided-dos)");
+        assert_lisp(
+            r#"(detect-coding-string (unibyte-string #xC3 #xA9 10))"#,
+            "(utf-8-unix iso-latin-1-unix raw-text-unix no-conversion)",
+        );
+        assert_lisp(r#"(detect-coding-string (unibyte-string #xE9) t)"#, "iso-latin-1");
+        assert_lisp(r#"(progn (insert "aéb") (decode-coding-region 2 3 'latin-1 t))"#, "\"Ã©\"");
+        assert_lisp(
+            r#"(progn (insert "aéb") (list (decode-coding-region 2 3 'latin-1) (point) (point-max)))"#,
+            "(2 5 5)",
+        );
+    }
+
+    #[test]
+    fn test_coding_destination() {
+        assert_lisp(r#"(let ((s "abc")) (eq (decode-coding-string s 'utf-8 t) s))"#, "t");
+        assert_lisp(r#"(let ((s "abc")) (eq (decode-coding-string s 'utf-8) s))"#, "nil");
+        assert_lisp(r#"(let ((s "abc")) (eq (encode-coding-string s nil t) s))"#, "t");
+        assert_lisp(r#"(let ((s "abc")) (eq (encode-coding-string s nil) s))"#, "nil");
+        assert_lisp(r#"(let ((s "é")) (eq (encode-coding-string s 'utf-8 t) s))"#, "nil");
+        assert_lisp(
+            r#"(let ((buf (get-buffer-create "coding-string-dest"))) (list (decode-coding-string (unibyte-string #xC3 #xA9 ?x) 'utf-8 nil buf) (point-max) (progn (set-buffer buf) (list (point) (buffer-substring 1 (point-max))))))"#,
+            r#"(2 1 (1 "éx"))"#,
+        );
+        assert_lisp(
+            r#"(let ((buf (get-buffer-create "coding-region-dest"))) (insert "aéb") (list (decode-coding-region 2 3 'latin-1 buf) (point-max) (progn (set-buffer buf) (buffer-substring 1 (point-max)))))"#,
+            r#"(2 4 "Ã©")"#,
+        );
+    }
+}
@@ -97,6 +97,7 @@ impl Cons {
 
     pub(crate) fn set_car(&self, new_car: Object) -> Result<()> {
         if self.0.mutable {
+            self.0.field_barrier(&self.0.car);
             unsafe { self.0.car.as_mut().set(new_car) }
             Ok(())
         } else {
@@ -106,6 +107,7 @@ impl Cons {
 
     pub(crate) fn set_cdr(&self, new_cdr: Object) -> Result<()> {
         if self.0.mutable {
+            self.0.field_barrier(&self.0.cdr);
             unsafe { self.0.cdr.as_mut().set(new_cdr) }
             Ok(())
         } else {
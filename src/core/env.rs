@@ -1,11 +1,18 @@
-use super::gc::{Context, ObjectMap, Rto, Slot};
-use super::object::{LispBuffer, Object, OpenBuffer, Symbol, WithLifetime};
+use super::cons::Cons;
+use super::gc::{Context, GcState, IntoRoot, ObjectMap, RootedDeref, Rt, Rto, Slot, Trace};
+use super::object::{
+    Gc, LispBuffer, LispMarker, MarkerInner, NIL, Object, ObjectType, OpenBuffer, Symbol,
+    WithLifetime,
+};
 use anyhow::{Result, anyhow};
+use rune_core::hashmap::HashSet;
 use rune_macros::Trace;
 use std::cell::OnceCell;
 
+mod handler;
 mod stack;
 mod symbol_map;
+pub(crate) use handler::*;
 pub(crate) use stack::*;
 pub(crate) use symbol_map::*;
 
@@ -14,21 +21,84 @@ type PropertyMap<'a> = ObjectMap<Slot<Symbol<'a>>, Vec<(Slot<Symbol<'a>>, Slot<O
 pub(crate) struct Env<'a> {
     pub(crate) vars: ObjectMap<Slot<Symbol<'a>>, Slot<Object<'a>>>,
     pub(crate) props: PropertyMap<'a>,
-    pub(crate) catch_stack: Vec<Slot<Object<'a>>>,
+    /// The active `catch`, `condition-case` and `handler-bind` handlers,
+    /// innermost last.
+    pub(crate) handlers: Vec<Handler<'a>>,
     exception: (Slot<Object<'a>>, Slot<Object<'a>>),
     #[no_trace]
     exception_id: u32,
-    binding_stack: Vec<(Slot<Symbol<'a>>, Option<Slot<Object<'a>>>)>,
+    binding_stack: Vec<Binding<'a>>,
     pub(crate) match_data: Slot<Object<'a>>,
-    #[no_trace]
+    /// The standard syntax table, created the first time it is needed.
+    pub(crate) standard_syntax_table: Slot<Object<'a>>,
     pub(crate) current_buffer: CurrentBuffer<'a>,
     pub(crate) stack: LispStack<'a>,
 }
 
+/// A dynamic binding made by `let` or a function call. When the binding is
+/// undone the variable is restored to `value`.
+#[derive(Debug, Trace)]
+struct Binding<'a> {
+    var: Slot<Symbol<'a>>,
+    /// `None` if the variable was void before it was bound
+    value: Option<Slot<Object<'a>>>,
+    /// The buffer whose buffer-local value was bound, or `None` if the default
+    /// value was bound. A buffer without a value is the one to make current
+    /// again, saved by `save-current-buffer`.
+    #[no_trace]
+    buffer: Option<&'a LispBuffer>,
+    #[no_trace]
+    kind: BindingKind,
+}
+
+/// The state that is restored when a [`Binding`] is undone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingKind {
+    /// A variable, or the current buffer for `save-current-buffer`
+    Var,
+    /// The current buffer and point for `save-excursion`. The value is a
+    /// marker at point.
+    Excursion,
+    /// The restriction of the buffer for `save-restriction`. The value is a
+    /// cons of markers at the bounds, or nil if the buffer was not narrowed.
+    Restriction,
+}
+
+impl<'a> Binding<'a> {
+    fn new(var: Symbol<'a>, value: Option<Object<'a>>, buffer: Option<&'a LispBuffer>) -> Self {
+        Self { var: Slot::new(var), value: value.map(Slot::new), buffer, kind: BindingKind::Var }
+    }
+
+    fn saved_buffer(buffer: &'a LispBuffer) -> Self {
+        Self::new(sym::NIL, None, Some(buffer))
+    }
+
+    fn saved_state(kind: BindingKind, value: Object<'a>, buffer: &'a LispBuffer) -> Self {
+        Self { kind, ..Self::new(sym::NIL, Some(value), Some(buffer)) }
+    }
+}
+
+impl<'new> IntoRoot<Binding<'new>> for Binding<'_> {
+    unsafe fn into_root(self) -> Binding<'new> {
+        self.with_lifetime()
+    }
+}
+
+impl<'old, 'new> WithLifetime<'new> for Binding<'old> {
+    type Out = Binding<'new>;
+
+    unsafe fn with_lifetime(self) -> Self::Out {
+        std::mem::transmute::<Binding<'old>, Binding<'new>>(self)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct CurrentBuffer<'a> {
     buffer: OnceCell<OpenBuffer<'a>>,
     pub(crate) buf_ref: &'a LispBuffer,
+    /// Buffers that hold objects from this thread's heap, such as
+    /// buffer-local values or overlays. They are traced from here.
+    tracked_buffers: HashSet<&'a LispBuffer>,
 }
 
 impl Default for CurrentBuffer<'_> {
@@ -40,7 +110,7 @@ impl Default for CurrentBuffer<'_> {
             unsafe { global.create_buffer(&name).with_lifetime() }
         };
         crate::buffer::BUFFERS.lock().unwrap().insert(name, buffer);
-        Self { buffer: Default::default(), buf_ref: buffer }
+        Self { buffer: Default::default(), buf_ref: buffer, tracked_buffers: HashSet::default() }
     }
 }
 
@@ -71,6 +141,80 @@ impl<'a> CurrentBuffer<'a> {
     pub(crate) fn release(&mut self) {
         self.buffer.take();
     }
+
+    /// Like [`Self::get`], but returns `None` if the buffer has been killed.
+    fn try_get(&self) -> Option<&OpenBuffer<'a>> {
+        if let Some(buffer) = self.buffer.get() {
+            return Some(buffer);
+        }
+        let locked = unsafe { self.buf_ref.lock().ok()?.with_lifetime() };
+        Some(self.buffer.get_or_init(|| locked))
+    }
+
+    /// The buffer-local value of `var` in the current buffer.
+    pub(crate) fn local_var(&self, var: Symbol) -> Option<Object<'_>> {
+        self.try_get()?.local_var(var)
+    }
+
+    pub(crate) fn has_local_var(&self, var: Symbol) -> bool {
+        self.try_get().is_some_and(|x| x.has_local_var(var))
+    }
+
+    /// Set the buffer-local value of `var` in the current buffer. All
+    /// buffer-local variables have to be set through here so that they get
+    /// traced.
+    pub(crate) fn set_local_var(&mut self, var: Symbol, value: Object) {
+        self.track(self.buf_ref);
+        self.get_mut().set_local_var(var, value);
+    }
+
+    /// Trace `buffer` along with the rest of this thread's roots. This has to
+    /// be called before storing objects from this thread's heap in a buffer.
+    pub(crate) fn track(&mut self, buffer: &LispBuffer) {
+        let buffer = unsafe { buffer.with_lifetime() };
+        self.tracked_buffers.insert(buffer);
+    }
+
+    /// Stop tracing `buffer`. Called when it is killed, since a killed buffer
+    /// doesn't hold any objects.
+    pub(crate) fn untrack(&mut self, buffer: &LispBuffer) {
+        self.tracked_buffers.remove(buffer);
+    }
+
+    /// Whether `buffer` is traced from here.
+    #[cfg(test)]
+    pub(crate) fn is_tracked(&self, buffer: &LispBuffer) -> bool {
+        self.tracked_buffers.contains(buffer)
+    }
+}
+
+impl Trace for CurrentBuffer<'_> {
+    fn trace(&self, state: &mut GcState) {
+        for buffer in &self.tracked_buffers {
+            match self.buffer.get() {
+                // The current buffer is already locked by us
+                Some(current) if *buffer == self.buf_ref => current.trace(state),
+                // killed buffers don't hold any objects
+                _ => {
+                    if let Ok(buffer) = buffer.lock() {
+                        buffer.trace(state);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl RootedDeref for CurrentBuffer<'_> {
+    type Target = Self;
+
+    fn rooted_deref(rooted: &Rt<Self>) -> &Self::Target {
+        unsafe { &*(rooted as *const Rt<Self>).cast::<Self::Target>() }
+    }
+
+    fn rooted_derefmut(rooted: &mut Rt<Self>) -> &mut Self::Target {
+        unsafe { &mut *(rooted as *mut Rt<Self>).cast::<Self::Target>() }
+    }
 }
 
 impl PartialEq<LispBuffer> for CurrentBuffer<'_> {
@@ -81,7 +225,33 @@ impl PartialEq<LispBuffer> for CurrentBuffer<'_> {
 
 // RootedEnv created by #[derive(Trace)]
 impl<'a> RootedEnv<'a> {
+    /// The value of `var` in the current buffer. This is the buffer-local value
+    /// if there is one, otherwise the default value.
+    pub(crate) fn var<'ob>(&self, var: Symbol, cx: &'ob Context) -> Option<Object<'ob>> {
+        match self.current_buffer.local_var(var) {
+            Some(value) => Some(cx.bind(value)),
+            None => self.vars.get(var).map(|x| x.bind(cx)),
+        }
+    }
+
+    /// Set the value of `var` in the current buffer. If the variable is
+    /// buffer-local (or automatically becomes buffer-local when set) only the
+    /// current buffer sees the new value.
     pub(crate) fn set_var(&mut self, sym: Symbol, value: Object) -> Result<()> {
+        if sym.is_const() {
+            Err(anyhow!("Attempt to set a constant symbol: {sym}"))
+        } else if sym.is_local_if_set() || self.current_buffer.has_local_var(sym) {
+            self.current_buffer.set_local_var(sym, value);
+            Ok(())
+        } else {
+            self.vars.insert(sym, value);
+            Ok(())
+        }
+    }
+
+    /// Set the default value of `sym`, which is seen by all buffers that don't
+    /// have their own buffer-local value.
+    pub(crate) fn set_default(&mut self, sym: Symbol, value: Object) -> Result<()> {
         if sym.is_const() {
             Err(anyhow!("Attempt to set a constant symbol: {sym}"))
         } else {
@@ -90,6 +260,25 @@ impl<'a> RootedEnv<'a> {
         }
     }
 
+    /// Give `var` a buffer-local value in the current buffer. The local starts
+    /// out with the default value, or nil if `var` is void.
+    pub(crate) fn make_local_var(&mut self, var: Symbol, cx: &Context) -> Result<()> {
+        if var.is_const() {
+            return Err(anyhow!("Symbol {var} may not be buffer-local"));
+        }
+        if !self.current_buffer.has_local_var(var) {
+            let value = self.vars.get(var).map_or(NIL, |x| x.bind(cx));
+            self.current_buffer.set_local_var(var, value);
+        }
+        Ok(())
+    }
+
+    /// Remove the buffer-local value of `var` from the current buffer, so that
+    /// it sees the default value again.
+    pub(crate) fn kill_local_var(&mut self, var: Symbol) {
+        self.current_buffer.get_mut().kill_local_var(var);
+    }
+
     pub(crate) fn set_prop(&mut self, symbol: Symbol, propname: Symbol, value: Object) {
         match self.props.get_mut(symbol) {
             Some(plist) => match plist.iter_mut().find(|x| x.0 == propname) {
@@ -114,36 +303,158 @@ impl<'a> RootedEnv<'a> {
     }
 
     pub(crate) fn varbind(&mut self, var: Symbol, value: Object, cx: &Context) {
-        let prev_value = self.vars.get(var).map(|x| x.bind(cx));
-        self.binding_stack.push((var, prev_value));
-        self.vars.insert(var, value);
+        // If the variable is buffer-local we only bind the value in the current
+        // buffer
+        if let Some(prev_value) = self.current_buffer.local_var(var) {
+            let prev_value = cx.bind(prev_value);
+            let buffer = self.current_buffer.buf_ref;
+            self.binding_stack.push(Binding::new(var, Some(prev_value), Some(buffer)));
+            self.current_buffer.set_local_var(var, value);
+        } else {
+            let prev_value = self.vars.get(var).map(|x| x.bind(cx));
+            self.binding_stack.push(Binding::new(var, prev_value, None));
+            self.vars.insert(var, value);
+        }
+    }
+
+    /// Save the current buffer so that it is made current again by the next
+    /// `unbind`. This is how `save-current-buffer` is run by the VM.
+    pub(crate) fn save_current_buffer(&mut self) {
+        let buffer = self.current_buffer.buf_ref;
+        self.binding_stack.push(Binding::saved_buffer(buffer));
+    }
+
+    /// Save the current buffer and point so that they are restored by the
+    /// next `unbind`. This is how `save-excursion` is run.
+    pub(crate) fn save_excursion(&mut self, cx: &Context) {
+        let marker: Gc<&LispMarker> = cx.add_as(MarkerInner::new());
+        let buf = self.current_buffer.get_mut();
+        buf.set_marker(marker.untag(), buf.text.cursor().chars());
+        let buffer = self.current_buffer.buf_ref;
+        self.binding_stack.push(Binding::saved_state(
+            BindingKind::Excursion,
+            marker.into(),
+            buffer,
+        ));
+    }
+
+    /// Save the restriction of the current buffer so that it is restored by
+    /// the next `unbind`. This is how `save-restriction` is run.
+    pub(crate) fn save_restriction(&mut self, cx: &Context) {
+        let buf = self.current_buffer.get_mut();
+        // The bounds are saved as markers so they stay correct if the body
+        // edits the buffer outside of them.
+        let saved: Object = if buf.is_narrowed() {
+            let (beg, end) = (buf.begv(), buf.zv());
+            let beg_marker: Gc<&LispMarker> = cx.add_as(MarkerInner::new());
+            let end_marker: Gc<&LispMarker> = cx.add_as(MarkerInner::new());
+            end_marker.untag().set_insertion_type(true);
+            buf.set_marker(beg_marker.untag(), beg);
+            buf.set_marker(end_marker.untag(), end);
+            Cons::new(beg_marker, end_marker, cx).into()
+        } else {
+            NIL
+        };
+        let buffer = self.current_buffer.buf_ref;
+        self.binding_stack
+            .push(Binding::saved_state(BindingKind::Restriction, saved, buffer));
+    }
+
+    /// The number of bindings that have not been undone yet.
+    pub(crate) fn binding_depth(&self) -> usize {
+        self.binding_stack.len()
+    }
+
+    /// Undo the bindings made after the binding stack was `depth` deep. This
+    /// is used when a non-local exit leaves the code that made them.
+    pub(crate) fn unbind_to(&mut self, depth: usize, cx: &Context) {
+        let count = self.binding_stack.len().saturating_sub(depth);
+        self.unbind(u16::try_from(count).unwrap_or(u16::MAX), cx);
     }
 
     pub(crate) fn unbind(&mut self, count: u16, cx: &Context) {
         for _ in 0..count {
-            match self.binding_stack.bind_mut(cx).pop() {
-                Some((sym, val)) => match val {
-                    Some(val) => self.vars.insert(*sym, *val),
-                    None => self.vars.remove(*sym),
-                },
-                None => panic!("Binding stack was empty"),
+            let Some(binding) = self.binding_stack.bind_mut(cx).pop() else {
+                panic!("Binding stack was empty")
+            };
+            let var = *binding.var;
+            match (binding.kind, binding.buffer, binding.value) {
+                (BindingKind::Excursion, Some(buffer), Some(marker)) => {
+                    self.restore_excursion(buffer, *marker);
+                }
+                (BindingKind::Restriction, Some(buffer), Some(saved)) => {
+                    self.restore_restriction(buffer, *saved);
+                }
+                (_, Some(buffer), Some(value)) => {
+                    // Only restore the buffer-local value if the buffer is
+                    // still live and the variable was not killed in the
+                    // meantime
+                    let value = *value;
+                    if *self.current_buffer == *buffer {
+                        if self.current_buffer.has_local_var(var) {
+                            self.current_buffer.set_local_var(var, value);
+                        }
+                    } else if let Ok(mut buffer) = buffer.lock()
+                        && buffer.has_local_var(var)
+                    {
+                        buffer.set_local_var(var, value);
+                    }
+                }
+                (_, Some(buffer), None) => {
+                    // A killed buffer can't be made current again
+                    if self.with_buffer(buffer, |_| {}).is_ok() {
+                        self.set_buffer(buffer);
+                    }
+                }
+                (_, None, Some(value)) => self.vars.insert(var, *value),
+                (_, None, None) => self.vars.remove(var),
             }
         }
     }
 
+    fn restore_excursion(&mut self, buffer: &LispBuffer, marker: Object) {
+        let ObjectType::Marker(marker) = marker.untag() else {
+            unreachable!("save-excursion should save a marker")
+        };
+        // A killed buffer can't be made current again
+        if self.with_buffer(buffer, |_| {}).is_ok() {
+            self.set_buffer(buffer);
+            if let Some(point) = marker.position() {
+                self.current_buffer.get_mut().text.set_cursor(point);
+            }
+        }
+        marker.detach();
+    }
+
+    fn restore_restriction(&mut self, buffer: &LispBuffer, saved: Object) {
+        // If the buffer was killed there is nothing to restore
+        let _ = self.with_buffer_mut(buffer, |buf| match saved.untag() {
+            ObjectType::Cons(bounds) => {
+                let beg: &LispMarker = bounds.car().try_into().unwrap();
+                let end: &LispMarker = bounds.cdr().try_into().unwrap();
+                if let (Some(b), Some(e)) = (beg.position(), end.position()) {
+                    buf.restrict(b, e);
+                }
+                beg.detach();
+                end.detach();
+            }
+            _ => buf.widen(),
+        });
+    }
+
     pub(crate) fn defvar(&mut self, var: Symbol, value: Object) -> Result<()> {
         // TOOD: Handle `eval-sexp` on defvar, which should always update the
         // value
         if self.vars.get(var).is_none() {
-            self.set_var(var, value)?;
+            self.set_default(var, value)?;
             var.make_special();
         }
 
         // If this variable was unbound previously in the binding stack,
         // we will bind it to the new value
         for binding in &mut *self.binding_stack {
-            if binding.0 == var && binding.1.is_none() {
-                binding.1.set(Some(value));
+            if binding.var == var && binding.buffer.is_none() && binding.value.is_none() {
+                binding.value.set(Some(value));
             }
         }
         Ok(())
@@ -153,7 +464,8 @@ impl<'a> RootedEnv<'a> {
         if buffer == self.current_buffer.buf_ref {
             return;
         }
-        self.current_buffer.set(buffer);
+        // Deref explicitly so this doesn't resolve to `Rt::set`
+        (*self.current_buffer).set(buffer);
     }
 
     pub(crate) fn with_buffer<T>(
@@ -161,7 +473,7 @@ impl<'a> RootedEnv<'a> {
         buffer: &LispBuffer,
         mut func: impl FnMut(&OpenBuffer) -> T,
     ) -> Result<T> {
-        if self.current_buffer == *buffer {
+        if *self.current_buffer == *buffer {
             Ok(func(self.current_buffer.get()))
         } else {
             let buffer = buffer.lock()?;
@@ -174,7 +486,7 @@ impl<'a> RootedEnv<'a> {
         buffer: &LispBuffer,
         mut func: impl FnMut(&mut OpenBuffer) -> T,
     ) -> Result<T> {
-        if self.current_buffer == *buffer {
+        if *self.current_buffer == *buffer {
             Ok(func(self.current_buffer.get_mut()))
         } else {
             let mut buffer = buffer.lock()?;
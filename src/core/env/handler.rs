@@ -0,0 +1,66 @@
+//! Handlers for non-local exits.
+//!
+//! `catch`, `condition-case` and `handler-bind` establish their handlers on a
+//! single stack in the [`Env`](super::Env), whether they are run by the
+//! interpreter or the bytecode VM. When an error is signaled or a tag is thrown
+//! this stack is searched for the handler that will receive it. The error then
+//! unwinds the Rust stack until it reaches the code that pushed that handler.
+//! Each evaluator pops the handlers it pushed as the error passes through, so
+//! the index of a handler identifies it while the error is in flight.
+use crate::core::{
+    gc::{IntoRoot, Slot},
+    object::{NIL, Object, WithLifetime},
+};
+use rune_macros::Trace;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HandlerType {
+    /// A `catch` for the tag
+    Catch,
+    /// One clause of a `condition-case`
+    ConditionCase,
+    /// A `handler-bind` handler. The function is called with the error before
+    /// the stack is unwound and the search continues if it returns.
+    HandlerBind,
+    /// Hides the handlers from the given index up while a `handler-bind`
+    /// function runs, so errors in the function are not sent back to it.
+    SkipConditions(usize),
+}
+
+#[derive(Debug, Trace)]
+pub(crate) struct Handler<'a> {
+    #[no_trace]
+    pub(crate) kind: HandlerType,
+    /// The tag of a `catch` or the conditions that are handled
+    pub(crate) tag: Slot<Object<'a>>,
+    /// The function of a `handler-bind` handler
+    pub(crate) func: Slot<Object<'a>>,
+}
+
+impl<'a> Handler<'a> {
+    pub(crate) fn new(kind: HandlerType, tag: Object<'a>) -> Self {
+        Self { kind, tag: Slot::new(tag), func: Slot::new(NIL) }
+    }
+
+    pub(crate) fn handler_bind(conditions: Object<'a>, func: Object<'a>) -> Self {
+        Self {
+            kind: HandlerType::HandlerBind,
+            tag: Slot::new(conditions),
+            func: Slot::new(func),
+        }
+    }
+}
+
+impl<'new> IntoRoot<Handler<'new>> for Handler<'_> {
+    unsafe fn into_root(self) -> Handler<'new> {
+        self.with_lifetime()
+    }
+}
+
+impl<'old, 'new> WithLifetime<'new> for Handler<'old> {
+    type Out = Handler<'new>;
+
+    unsafe fn with_lifetime(self) -> Self::Out {
+        std::mem::transmute::<Handler<'old>, Handler<'new>>(self)
+    }
+}
@@ -18,6 +18,8 @@ pub(crate) struct LispStack<'a> {
     vec: Vec<Slot<Object<'a>>>,
     #[no_trace]
     current: Frame,
+    /// The function called by the current frame, or `nil` if it is not known.
+    current_func: Slot<Object<'a>>,
     frames: Vec<FrameStore<'a>>,
 }
 
@@ -34,11 +36,13 @@ struct Frame {
     /// second is boolean indicating if the last argument is a cons with the
     /// remaining variadic arguments.
     arg_cnt: (u16, bool),
+    /// Call the debugger when this frame returns. Set by `backtrace-debug`.
+    debug_on_exit: bool,
 }
 
 impl Default for Frame {
     fn default() -> Self {
-        Self { start: 0, end: usize::MAX, arg_cnt: (0, false) }
+        Self { start: 0, end: usize::MAX, arg_cnt: (0, false), debug_on_exit: false }
     }
 }
 
@@ -53,6 +57,7 @@ struct ByteFrame<'a> {
 struct FrameStore<'a> {
     #[no_trace]
     frame: Frame,
+    func: Slot<Object<'a>>,
     bytecode: Option<ByteFrame<'a>>,
 }
 
@@ -63,12 +68,18 @@ impl<'new> IntoRoot<FrameStore<'new>> for FrameStore<'_> {
 }
 
 impl<'ob> FrameStore<'ob> {
-    fn new(frame: Frame) -> Self {
-        Self { frame, bytecode: None }
+    fn new(frame: Frame, func: Object<'ob>) -> Self {
+        Self { frame, func: Slot::new(func), bytecode: None }
     }
 
-    fn new_bytecode(frame: Frame, func: &'ob ByteFn, pc_offset: usize) -> Self {
-        Self { frame, bytecode: Some(ByteFrame { func: Slot::new(func), pc_offset }) }
+    fn new_bytecode(
+        frame: Frame,
+        func: Object<'ob>,
+        byte_fn: &'ob ByteFn,
+        pc_offset: usize,
+    ) -> Self {
+        let bytecode = ByteFrame { func: Slot::new(byte_fn), pc_offset };
+        Self { frame, func: Slot::new(func), bytecode: Some(bytecode) }
     }
 }
 
@@ -80,6 +91,24 @@ impl<'old, 'new> WithLifetime<'new> for FrameStore<'old> {
     }
 }
 
+/// A live call frame, as seen by the debugger.
+pub(crate) struct BacktraceFrame<'brw, 'a> {
+    /// The function that was called. This is the symbol if it was called by
+    /// name.
+    pub(crate) func: &'brw Rto<Object<'a>>,
+    pub(crate) args: &'brw [Rto<Object<'a>>],
+    /// The stack slots used by the frame after its arguments. Bytecode
+    /// functions keep their local variables here.
+    pub(crate) locals: &'brw [Rto<Object<'a>>],
+    /// True if the last argument is a list of the `&rest` arguments.
+    pub(crate) rest: bool,
+    /// True if the debugger will be called when the frame returns.
+    pub(crate) debug_on_exit: bool,
+    /// The number of frames below this one. Used to refer back to the frame
+    /// with [`set_debug_on_exit`](RootedLispStack::set_debug_on_exit).
+    pub(crate) depth: usize,
+}
+
 /// Type representing a slice of arguments on the stack. Used to avoid
 /// allocations and copies when calling functions.
 #[derive(Copy, Clone)]
@@ -139,7 +168,10 @@ impl<'a> RootedLispStack<'a> {
     ) {
         assert!(start <= self.len());
         assert!(self.current.start <= start);
-        self.frames.push(FrameStore::new_bytecode(self.current, func, pc));
+        // SAFETY: The function is moved directly into the rooted frame store
+        let caller = unsafe { self.current_func.bind_unchecked() };
+        self.frames.push(FrameStore::new_bytecode(self.current, caller, func, pc));
+        self.current_func.set(NIL);
         let end = start + depth;
         // allocate space so that we don't have to reallocate later. This will
         // also let us do unchecked pushes later.
@@ -154,7 +186,10 @@ impl<'a> RootedLispStack<'a> {
         assert!(arg_cnt <= self.len());
         let start = self.len() - arg_cnt;
         assert!(self.current.start <= start);
-        self.frames.push(FrameStore::new(self.current));
+        // SAFETY: The function is moved directly into the rooted frame store
+        let caller = unsafe { self.current_func.bind_unchecked() };
+        self.frames.push(FrameStore::new(self.current, caller));
+        self.current_func.set(NIL);
         self.current =
             Frame { start, arg_cnt: (u16::try_from(arg_cnt).unwrap(), false), ..Frame::default() };
     }
@@ -163,10 +198,69 @@ impl<'a> RootedLispStack<'a> {
     /// previous one
     pub(crate) fn pop_frame(&mut self) {
         self.vec.truncate(self.current.start);
-        self.current = self.frames.last().unwrap().frame;
+        let prev = self.frames.last().unwrap();
+        self.current = prev.frame;
+        self.current_func.set(&prev.func);
         self.frames.pop();
     }
 
+    /// Record the function called by the current frame. The first function
+    /// recorded is kept, so that a function called through a symbol shows up
+    /// under that name.
+    pub(crate) fn set_frame_function(&mut self, func: Object) {
+        if self.current_func == NIL {
+            self.current_func.set(func);
+        }
+    }
+
+    /// Return the live call frames from innermost to outermost. Frames whose
+    /// function was never recorded are skipped.
+    pub(crate) fn backtrace(&self) -> Vec<BacktraceFrame<'_, 'a>> {
+        let mut backtrace = Vec::new();
+        let mut end = self.vec.len();
+        // The stack frame that was pushed when the current frame was created.
+        // It records if the frame was called from bytecode and therefore
+        // starts with the function slot.
+        let mut pushed = self.frames.len();
+        let mut frame = self.current;
+        let mut func = &self.current_func;
+        while pushed > 0 {
+            let caller = &self.frames[pushed - 1];
+            if *func != NIL {
+                let offset = usize::from(caller.bytecode.is_some());
+                let start = (frame.start + offset).min(end);
+                let arg_end = (start + frame.arg_cnt.0 as usize).min(end);
+                backtrace.push(BacktraceFrame {
+                    func,
+                    args: &self.vec[start..arg_end],
+                    locals: &self.vec[arg_end..end],
+                    rest: frame.arg_cnt.1,
+                    debug_on_exit: frame.debug_on_exit,
+                    depth: pushed,
+                });
+            }
+            end = frame.start;
+            frame = caller.frame;
+            func = &caller.func;
+            pushed -= 1;
+        }
+        backtrace
+    }
+
+    /// Set if the debugger is called when the frame at `depth` returns.
+    pub(crate) fn set_debug_on_exit(&mut self, depth: usize, flag: bool) {
+        if depth == self.frames.len() {
+            self.current.debug_on_exit = flag;
+        } else {
+            self.frames[depth].frame.debug_on_exit = flag;
+        }
+    }
+
+    /// True if the debugger should be called when the current frame returns.
+    pub(crate) fn debug_on_exit(&self) -> bool {
+        self.current.debug_on_exit
+    }
+
     pub(crate) fn get_bytecode_frame(&self, idx: usize) -> Option<(&Rto<&'a ByteFn>, usize)> {
         let frame = self.frames.get(idx)?;
         let bytecode = frame.bytecode.as_ref()?;
@@ -188,10 +282,17 @@ impl<'a> RootedLispStack<'a> {
             return; /* no frames to unwind */
         }
         assert!(frame < self.current_frame());
-        self.current = self.frames[frame].frame;
+        let prev = &self.frames[frame];
+        self.current = prev.frame;
+        self.current_func.set(&prev.func);
         self.frames.truncate(frame);
     }
 
+    /// Remove everything in the current frame above its arguments.
+    pub(crate) fn truncate_to_args(&mut self) {
+        self.vec.truncate(self.current.start + usize::from(self.current.arg_cnt.0));
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.vec.len()
     }
@@ -75,6 +75,11 @@ impl SymbolMap {
     pub(crate) fn get(&self, name: &str) -> Option<Symbol<'_>> {
         self.map.get(name)
     }
+
+    /// All symbols that have been interned, in no particular order.
+    pub(crate) fn symbols(&self) -> impl Iterator<Item = Symbol<'_>> {
+        self.map.map.values().map(|x| unsafe { x.with_lifetime() })
+    }
 }
 
 // This file includes all symbol definitions. Generated by build.rs
@@ -21,6 +21,10 @@ pub(crate) enum Type {
     Buffer,
     CharTable,
     BigInt,
+    Marker,
+    IntegerOrMarker,
+    NumberOrMarker,
+    Overlay,
 }
 
 /// Error provided if object was the wrong type
@@ -1,8 +1,10 @@
+mod barrier;
 mod root;
 mod trace;
 #[macro_use]
 mod context;
 mod heap;
+mod verify;
 pub(crate) use context::*;
 pub(crate) use heap::*;
 pub(crate) use root::*;
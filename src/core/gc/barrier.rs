@@ -0,0 +1,85 @@
+//! The write barrier of the generational collector.
+//!
+//! A minor collection only traces the nursery, so any pointer from an old
+//! object to a young one needs to be recorded when it is written. Otherwise the
+//! young object would look unreachable. These writes are logged in a
+//! sequential store buffer that is used as extra roots by the next minor
+//! collection and cleared after every collection.
+//!
+//! Cons cells log the field that was written. Vectors, records, hash tables and
+//! the other containers hand out mutable views that can write any element, so
+//! they log the whole object instead. Variables and symbol properties live in
+//! the `Env` and buffer locals live in the buffer, which are both always
+//! traced as roots, so `set` and `put` don't need a barrier.
+use super::{GcHeap, GcState, Trace};
+use crate::core::object::ObjCell;
+use std::cell::RefCell;
+
+enum Remembered {
+    Field(*const ObjCell),
+    Object(*const dyn Trace),
+}
+
+thread_local! {
+    static REMEMBERED_SET: RefCell<Vec<Remembered>> = const { RefCell::new(Vec::new()) };
+}
+
+fn remember(entry: Remembered) {
+    REMEMBERED_SET.with(|set| {
+        let mut set = set.borrow_mut();
+        // Mutating the same object in a loop is common, so skip the entry if
+        // it was the last one logged.
+        let duplicate = match (set.last(), &entry) {
+            (Some(Remembered::Field(a)), Remembered::Field(b)) => std::ptr::eq(*a, *b),
+            (Some(Remembered::Object(a)), Remembered::Object(b)) => std::ptr::addr_eq(*a, *b),
+            _ => false,
+        };
+        if !duplicate {
+            set.push(entry);
+        }
+    });
+}
+
+impl<T> GcHeap<T> {
+    /// Record that `field`, which is part of this object, was written.
+    pub(in crate::core) fn field_barrier(&self, field: &ObjCell) {
+        if self.is_old() {
+            remember(Remembered::Field(field));
+        }
+    }
+}
+
+impl<T: Trace + 'static> GcHeap<T> {
+    /// Record that this object may have been written.
+    pub(in crate::core) fn write_barrier(&self) {
+        if self.is_old() {
+            remember(Remembered::Object(self as &dyn Trace));
+        }
+    }
+}
+
+/// Trace everything that was written into the old generation since the last
+/// collection.
+pub(super) fn trace_remembered_set(state: &mut GcState) {
+    let set = REMEMBERED_SET.with(|set| set.take());
+    for entry in set {
+        // SAFETY: Old objects are only freed by a major collection, which
+        // clears the remembered set.
+        unsafe {
+            match entry {
+                Remembered::Field(field) => (*field).trace(state),
+                Remembered::Object(object) => (*object).trace(state),
+            }
+        }
+    }
+}
+
+pub(super) fn clear_remembered_set() {
+    REMEMBERED_SET.with(|set| set.borrow_mut().clear());
+}
+
+/// The number of entries in the remembered set.
+#[cfg(test)]
+pub(super) fn remembered_set_len() -> usize {
+    REMEMBERED_SET.with(|set| set.borrow().len())
+}
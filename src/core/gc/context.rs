@@ -1,13 +1,22 @@
 use super::GcState;
+use super::GcStats;
+use super::Slot;
 use super::Trace;
+use super::barrier;
+use super::set_minor_collection;
+use super::verify::{self, FromSpace};
 use crate::core::object::GcString;
 use crate::core::object::LispHashTable;
+use crate::core::object::LispMarker;
+use crate::core::object::LispString;
+use crate::core::object::Record;
 use crate::core::object::{Gc, IntoObject, Object, UninternedSymbolMap, WithLifetime};
 use bumpalo::collections::Vec as GcVec;
 use std::cell::{Cell, RefCell};
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 
 /// A global store of all gc roots. This struct should be passed to the [Context]
 /// when it is created.
@@ -28,7 +37,13 @@ pub(in crate::core) enum DropStackElem {
 /// directly.
 #[derive(Default)]
 pub(crate) struct Block<const CONST: bool> {
+    // The nursery. All new objects are allocated here.
     pub(in crate::core) objects: bumpalo::Bump,
+    // The number of objects allocated since the last collection
+    pub(in crate::core) allocations: Cell<usize>,
+    // Objects that have survived a collection. This is only collected by a
+    // major collection.
+    pub(in crate::core) old: bumpalo::Bump,
     // Allocations that will be dropped when the objects are moved. At that time
     // the allocation will get copied into the GC heap. This let's us avoid an
     // extra copy of memory when a vector is first made an object. The
@@ -39,6 +54,17 @@ pub(crate) struct Block<const CONST: bool> {
     // track of the memory and free it only after the table is garbage
     // collected. Kind of a hack.
     pub(in crate::core) lisp_hashtables: RefCell<Vec<*const LispHashTable>>,
+    // Markers share their position with the buffer they point into. That
+    // shared allocation needs to be freed when the marker is collected so the
+    // buffer stops updating it.
+    pub(in crate::core) lisp_markers: RefCell<Vec<*const LispMarker>>,
+    // Text properties of strings live outside the GC heap, so strings that
+    // have them are tracked here to free the properties once the string is
+    // collected.
+    pub(in crate::core) lisp_strings: RefCell<Vec<*const LispString>>,
+    // Finalizers that have not run yet. Their function is queued once the
+    // finalizer itself is no longer reachable.
+    pub(in crate::core) finalizers: RefCell<Vec<*const Record>>,
     pub(in crate::core) uninterned_symbol_map: UninternedSymbolMap,
 }
 
@@ -51,12 +77,33 @@ pub(crate) struct Context<'rt> {
     pub(crate) block: Block<false>,
     root_set: &'rt RootSet,
     next_limit: usize,
+    // The minimum value of `next_limit`, set by `gc-cons-threshold`
+    cons_threshold: usize,
+    // How much the old generation can grow before the next major collection,
+    // as a fraction of the live data. Set by `gc-cons-percentage`.
+    cons_percentage: f64,
+    // Force a collection after this many allocations. See
+    // [`Context::set_gc_stress`].
+    gc_stress: Option<usize>,
+    // Whether the next collection of [`Context::alternate_collection`] is
+    // a major one.
+    next_major: bool,
+    gcs_done: usize,
+    gc_elapsed: Duration,
+    // The objects that were live after the last major collection
+    stats: GcStats,
+    // The functions of finalizers that became unreachable. They are roots
+    // until they are handed out by [`Context::take_finalizers`].
+    pending_finalizers: RefCell<Vec<Slot<Object<'static>>>>,
 }
 
 impl Drop for Context<'_> {
     fn drop(&mut self) {
+        // Nothing can run the finalizers anymore
+        self.block.finalizers.get_mut().clear();
+        self.pending_finalizers.get_mut().clear();
         self.garbage_collect(true);
-        if self.block.objects.allocated_bytes() == 0 {
+        if self.block.objects.allocated_bytes() == 0 && self.block.old.allocated_bytes() == 0 {
             return;
         }
         if std::thread::panicking() {
@@ -102,6 +149,12 @@ impl Block<false> {
 }
 
 impl<const CONST: bool> Block<CONST> {
+    /// Allocate a new object in the nursery.
+    pub(in crate::core) fn alloc<T>(&self, obj: T) -> &mut T {
+        self.allocations.set(self.allocations.get() + 1);
+        self.objects.alloc(obj)
+    }
+
     pub(crate) fn add<'ob, T, Tx>(&'ob self, obj: T) -> Object<'ob>
     where
         T: IntoObject<Out<'ob> = Tx>,
@@ -137,16 +190,98 @@ impl<const CONST: bool> Block<CONST> {
     }
 }
 
+impl Block<false> {
+    /// Track a finalizer record, whose second slot is the function to run
+    /// once the record is no longer reachable.
+    pub(crate) fn track_finalizer(&self, finalizer: &Record) {
+        self.finalizers.borrow_mut().push(finalizer);
+    }
+}
+
 impl<'ob, 'rt> Context<'rt> {
-    const MIN_GC_BYTES: usize = 2000;
-    const GC_GROWTH_FACTOR: usize = 12; // divide by 10
+    const NURSERY_BYTES: usize = 64 * 1024;
+    pub(crate) const DEFAULT_CONS_THRESHOLD: usize = 800_000;
+    pub(crate) const DEFAULT_CONS_PERCENTAGE: f64 = 0.1;
     pub(crate) fn new(roots: &'rt RootSet) -> Self {
-        Self { block: Block::new_local(), root_set: roots, next_limit: Self::MIN_GC_BYTES }
+        Self::with_block(Block::new_local(), roots)
     }
 
     pub(crate) fn from_block(block: Block<false>, roots: &'rt RootSet) -> Self {
         Block::assert_unique();
-        Context { block, root_set: roots, next_limit: Self::MIN_GC_BYTES }
+        Self::with_block(block, roots)
+    }
+
+    fn with_block(block: Block<false>, roots: &'rt RootSet) -> Self {
+        let gc_stress = cfg!(feature = "gc_stress").then_some(1);
+        Context {
+            block,
+            root_set: roots,
+            next_limit: Self::DEFAULT_CONS_THRESHOLD,
+            cons_threshold: Self::DEFAULT_CONS_THRESHOLD,
+            cons_percentage: Self::DEFAULT_CONS_PERCENTAGE,
+            gc_stress,
+            next_major: false,
+            gcs_done: 0,
+            gc_elapsed: Duration::ZERO,
+            stats: GcStats::default(),
+            pending_finalizers: RefCell::default(),
+        }
+    }
+
+    /// Set the number of bytes the old generation can hold before a major
+    /// collection, and how much it can grow past the live data after one.
+    pub(crate) fn set_gc_cons_threshold(&mut self, threshold: usize, percentage: f64) {
+        self.cons_threshold = threshold;
+        self.cons_percentage = percentage.max(0.0);
+        self.update_limit();
+    }
+
+    fn update_limit(&mut self) {
+        let live = self.block.old.allocated_bytes() as f64;
+        let growth = (live * (1.0 + self.cons_percentage)) as usize;
+        self.next_limit = std::cmp::max(self.cons_threshold, growth);
+    }
+
+    /// The number of collections that have run.
+    pub(crate) fn gcs_done(&self) -> usize {
+        self.gcs_done
+    }
+
+    /// The total time spent collecting garbage.
+    pub(crate) fn gc_elapsed(&self) -> Duration {
+        self.gc_elapsed
+    }
+
+    /// The number of objects of each type that were live after the last major
+    /// collection.
+    pub(crate) fn gc_stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// True if the next call to [`Context::garbage_collect`] will collect.
+    pub(crate) fn gc_pending(&self) -> bool {
+        match self.gc_stress {
+            Some(interval) => self.block.allocations.get() >= interval,
+            None => cfg!(test) || self.block.objects.allocated_bytes() >= Self::NURSERY_BYTES,
+        }
+    }
+
+    /// Force a collection at the next safe point after every `interval`
+    /// allocations, and verify the heap after each one. This is used to find
+    /// rooting bugs, and only has an effect when built with the `gc_stress`
+    /// feature.
+    pub(crate) fn set_gc_stress(&mut self, interval: Option<usize>) {
+        if cfg!(feature = "gc_stress") {
+            self.gc_stress = interval.map(|x| x.max(1));
+        }
+    }
+
+    /// Take the functions of the finalizers that became unreachable. They are
+    /// no longer rooted, so the caller needs to root them before running any
+    /// of them.
+    pub(crate) fn take_finalizers(&'ob self) -> Vec<Object<'ob>> {
+        let pending = self.pending_finalizers.take();
+        pending.iter().map(|x| unsafe { (**x).with_lifetime() }).collect()
     }
 
     pub(crate) fn bind<T>(&'ob self, obj: T) -> <T as WithLifetime<'ob>>::Out
@@ -160,13 +295,103 @@ impl<'ob, 'rt> Context<'rt> {
         self.root_set
     }
 
+    /// Collect garbage if enough has been allocated since the last collection.
+    /// A minor collection only evacuates the nursery, and a major collection
+    /// is run once the old generation has grown past its limit. If `force` is
+    /// true, a major collection is always run.
     pub(crate) fn garbage_collect(&mut self, force: bool) {
-        let bytes = self.block.objects.allocated_bytes();
-        if cfg!(not(test)) && !force && bytes < self.next_limit {
+        if force {
+            self.major_collection();
+            return;
+        }
+        if !self.gc_pending() {
+            return;
+        }
+        if self.gc_stress.is_some() {
+            self.stress_collection();
+            return;
+        }
+        // Tests collect at every safe point, so alternate the kinds of
+        // collection to cover the write barrier as well as the roots
+        if cfg!(test) {
+            self.alternate_collection();
             return;
         }
+        self.minor_collection();
+        if self.block.old.allocated_bytes() >= self.next_limit {
+            self.major_collection();
+        }
+    }
+
+    /// Promote everything that is live in the nursery to the old generation.
+    /// Old objects are not traced, except for the ones recorded by the write
+    /// barrier.
+    pub(crate) fn minor_collection(&mut self) {
+        self.collect(true);
+    }
+
+    /// Copy everything that is live in both generations into a new old
+    /// generation.
+    pub(crate) fn major_collection(&mut self) {
+        self.collect(false);
+    }
+
+    /// Alternate between minor and major collections, so that both missing
+    /// roots and missing write barriers are found, and then verify the heap.
+    fn stress_collection(&mut self) {
+        let mut from_space = self.alternate_collection();
+        self.verify_heap(&mut from_space);
+    }
+
+    /// Run a minor collection if the last one was major, and a major one
+    /// otherwise.
+    fn alternate_collection(&mut self) -> FromSpace {
+        let minor = !self.next_major;
+        self.next_major = minor;
+        self.collect(minor)
+    }
+
+    /// Check that nothing reachable from the roots points into `from_space`.
+    /// This walks the heap with another major collection while the evacuated
+    /// spaces are still allocated, so that a stale pointer can be detected
+    /// instead of reading freed memory.
+    fn verify_heap(&mut self, from_space: &mut FromSpace) {
+        verify::start(from_space);
+        self.collect(false);
+        let errors = verify::finish();
+        assert!(errors.is_empty(), "Heap verification failed:\n{}", errors.join("\n"));
+    }
 
-        let mut state = GcState::new();
+    /// Queue the functions of the finalizers that were not reached by the
+    /// trace. The functions are traced so that they survive until they run.
+    fn queue_finalizers(&mut self, state: &mut GcState) {
+        let pending = self.pending_finalizers.get_mut();
+        self.block.finalizers.borrow_mut().retain_mut(|ptr| {
+            let finalizer = unsafe { &**ptr };
+            if let Some(fwd) = finalizer.forwarding_ptr() {
+                *ptr = fwd.as_ptr().cast::<Record>();
+                return true;
+            }
+            let func = Slot::new(unsafe { finalizer[1].get().with_lifetime() });
+            func.trace(state);
+            pending.push(func);
+            false
+        });
+    }
+
+    /// Run a collection and return the spaces that were evacuated. They are
+    /// freed when the return value is dropped.
+    fn collect(&mut self, minor: bool) -> FromSpace {
+        let start = Instant::now();
+        let mut from_space = FromSpace::default();
+        from_space.spaces.push(std::mem::take(&mut self.block.objects));
+        let mut state = if minor {
+            GcState::with_to_space(std::mem::take(&mut self.block.old))
+        } else {
+            from_space.spaces.push(std::mem::take(&mut self.block.old));
+            GcState::new()
+        };
+        set_minor_collection(minor);
         for x in self.root_set.roots.borrow().iter() {
             // SAFETY: The contract of root structs will ensure that it removes
             // itself from this list before it drops.
@@ -174,10 +399,19 @@ impl<'ob, 'rt> Context<'rt> {
                 (**x).trace(&mut state);
             }
         }
+        self.pending_finalizers.trace(&mut state);
+        if minor {
+            barrier::trace_remembered_set(&mut state);
+        }
 
         state.trace_stack();
+        state.trace_ephemerons();
+        self.queue_finalizers(&mut state);
+        // The functions of the finalizers can reach weak tables that were not
+        // reachable before.
+        state.trace_ephemerons();
+        barrier::clear_remembered_set();
 
-        self.next_limit = (state.to_space.allocated_bytes() * Self::GC_GROWTH_FACTOR) / 10;
         self.block.drop_stack.borrow_mut().clear();
         // Find all hashtables that have not been moved (i.e. They are no longer
         // accessible) and drop them. Otherwise, update the object pointer.
@@ -187,12 +421,43 @@ impl<'ob, 'rt> Context<'rt> {
                 *ptr = fwd.as_ptr().cast::<LispHashTable>();
                 true
             } else {
+                from_space.freed_tables.push(*ptr as usize);
                 unsafe { std::ptr::drop_in_place(*ptr as *mut LispHashTable) };
                 false
             }
         });
-
-        self.block.objects = state.to_space;
+        self.block.lisp_markers.borrow_mut().retain_mut(|ptr| {
+            let marker = unsafe { &**ptr };
+            if let Some(fwd) = marker.forwarding_ptr() {
+                *ptr = fwd.as_ptr().cast::<LispMarker>();
+                true
+            } else {
+                unsafe { std::ptr::drop_in_place(*ptr as *mut LispMarker) };
+                false
+            }
+        });
+        self.block.lisp_strings.borrow_mut().retain_mut(|ptr| {
+            let string = unsafe { &**ptr };
+            if let Some(fwd) = string.forwarding_ptr() {
+                *ptr = fwd.as_ptr().cast::<LispString>();
+                true
+            } else {
+                string.drop_props();
+                false
+            }
+        });
+        set_minor_collection(false);
+
+        let stats = state.stats;
+        self.block.old = state.to_space;
+        self.block.allocations.set(0);
+        if !minor {
+            self.stats = stats;
+            self.update_limit();
+        }
+        self.gcs_done += 1;
+        self.gc_elapsed += start.elapsed();
+        from_space
     }
 }
 
@@ -227,7 +492,8 @@ mod test {
 
     use crate::core::{
         cons::Cons,
-        object::{HashTable, ObjectType, Symbol},
+        gc::{Rt, Slot},
+        object::{HashTable, LispVec, NIL, ObjectType, RecordBuilder, Symbol, Weakness},
     };
 
     use super::*;
@@ -288,4 +554,236 @@ mod test {
         assert_eq!(**float, 1.5);
         assert_eq!(int, 1);
     }
+
+    fn root_vec<'ob>(vec: &Rt<Slot<Object>>, cx: &'ob Context) -> &'ob LispVec {
+        let ObjectType::Vec(vec) = vec.bind(cx).untag() else { unreachable!() };
+        vec
+    }
+
+    // Store a new object into something that survived the last collection
+    // after every step, so that the young objects are only reachable through
+    // the write barrier when `collect` is a minor collection.
+    fn mutate_between_collections(collect: fn(&mut Context)) -> String {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let table = cx.add(HashTable::default());
+        let vec = vec![list![1, 2; cx], table, NIL];
+        let vec = cx.add(vec);
+        root!(vec, cx);
+        collect(cx);
+
+        let ObjectType::Cons(cons) = root_vec(vec, cx)[0].get().untag() else { unreachable!() };
+        cons.set_car(cx.add("car")).unwrap();
+        collect(cx);
+
+        let ObjectType::Cons(cons) = root_vec(vec, cx)[0].get().untag() else { unreachable!() };
+        cons.set_cdr(list!["cdr", 2.5; cx]).unwrap();
+        collect(cx);
+
+        let ObjectType::HashTable(table) = root_vec(vec, cx)[1].get().untag() else {
+            unreachable!()
+        };
+        let value = vec![cx.add(1.5), cx.add("value")];
+        table.insert(cx.add("key"), cx.add(value));
+        collect(cx);
+
+        root_vec(vec, cx).try_mut().unwrap()[2].set(list!["elem"; cx]);
+        collect(cx);
+        vec.bind(cx).to_string()
+    }
+
+    #[test]
+    fn test_minor_and_major_collection() {
+        let expect = mutate_between_collections(|_| {});
+        assert_eq!(mutate_between_collections(|cx| cx.minor_collection()), expect);
+        assert_eq!(mutate_between_collections(|cx| cx.major_collection()), expect);
+        assert_eq!(mutate_between_collections(|cx| cx.garbage_collect(false)), expect);
+    }
+
+    #[test]
+    fn test_alternate_collection() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let list = list![1, 2; cx];
+        root!(list, cx);
+        // Tests alternate between minor and major collections, and only a
+        // major collection updates the stats
+        cx.garbage_collect(false);
+        assert_eq!(cx.gc_stats().conses, 0);
+        cx.garbage_collect(false);
+        assert_eq!(cx.gc_stats().conses, 2);
+        assert_eq!(list.bind(cx).to_string(), "(1 2)");
+    }
+
+    #[test]
+    fn test_promotion() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let list = list![1, "two"; cx];
+        root!(list, cx);
+        // garbage
+        let _ = list![3, "four"; cx];
+        cx.minor_collection();
+        assert_eq!(cx.block.objects.allocated_bytes(), 0);
+        assert!(cx.block.old.allocated_bytes() > 0);
+
+        // Writing into a young object is not recorded
+        let young = Cons::new(1, 2, cx);
+        young.set_car(cx.add("young")).unwrap();
+        assert_eq!(barrier::remembered_set_len(), 0);
+
+        let ObjectType::Cons(old) = list.bind(cx).untag() else { unreachable!() };
+        old.set_car(cx.add("new")).unwrap();
+        old.set_car(cx.add("newer")).unwrap();
+        assert_eq!(barrier::remembered_set_len(), 1);
+        cx.minor_collection();
+        assert_eq!(barrier::remembered_set_len(), 0);
+        assert_eq!(list.bind(cx).to_string(), "(\"newer\" \"two\")");
+        cx.major_collection();
+        assert_eq!(list.bind(cx).to_string(), "(\"newer\" \"two\")");
+        assert_eq!(cx.block.objects.allocated_bytes(), 0);
+    }
+
+    #[test]
+    fn test_stress_collection() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let table = cx.add(HashTable::default());
+        let list = list![1, "two", table; cx];
+        root!(list, cx);
+        // one minor and one major collection
+        cx.stress_collection();
+        cx.stress_collection();
+        assert_eq!(cx.block.allocations.get(), 0);
+        let string = list.bind(cx).to_string();
+        assert!(string.starts_with("(1 \"two\" #s(hash-table"));
+    }
+
+    #[test]
+    #[should_panic(expected = "pointer into from-space")]
+    fn test_verify_stale_root() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        // The object is not rooted during the collection, so the pointer is
+        // left pointing to the old copy
+        let stale = unsafe { cx.add("stale").with_lifetime() };
+        let mut from_space = cx.collect(false);
+        root!(stale, cx);
+        cx.verify_heap(&mut from_space);
+    }
+
+    #[test]
+    fn test_gc_stats() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let vec = cx.add(vec![cx.add(1), cx.add(2)]);
+        let list = list![1.5, "abc", vec, HashTable::default(); cx];
+        root!(list, cx);
+        // garbage
+        let _ = list![2.5, "def"; cx];
+        cx.major_collection();
+        let stats = cx.gc_stats();
+        assert_eq!(stats.conses, 4);
+        assert_eq!(stats.floats, 1);
+        assert_eq!(stats.strings, 1);
+        assert_eq!(stats.string_bytes, 3);
+        assert_eq!(stats.vectors, 1);
+        assert_eq!(stats.vector_slots, 2);
+        assert_eq!(stats.hash_tables, 1);
+        assert_eq!(cx.gcs_done(), 1);
+        // minor collections don't update the statistics
+        cx.minor_collection();
+        assert_eq!(cx.gc_stats(), stats);
+        assert_eq!(cx.gcs_done(), 2);
+    }
+
+    // Collect a weak table whose entries are reachable in different ways and
+    // return the keys that are left.
+    fn weak_table_keys(weakness: Weakness, collect: fn(&mut Context)) -> String {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let table = cx.add(HashTable::default());
+        let ObjectType::HashTable(weak) = table.untag() else { unreachable!() };
+        weak.set_weakness(weakness);
+        let (k1, v2, k3, v3) = (cx.add("k1"), cx.add("v2"), cx.add("k3"), cx.add("v3"));
+        // Only reachable through the value of the first entry
+        let v1 = cx.add("v1");
+        weak.insert(k1, v1);
+        weak.insert(cx.add("k2"), v2);
+        weak.insert(k3, v3);
+        weak.insert(cx.add("k4"), cx.add("v4"));
+        weak.insert(v1, cx.add("v5"));
+        let live = list![table, k1, v2, k3, v3; cx];
+        root!(live, cx);
+        collect(cx);
+        let ObjectType::Cons(live) = live.bind(cx).untag() else { unreachable!() };
+        let ObjectType::HashTable(weak) = live.car().untag() else { unreachable!() };
+        let keys: Vec<_> =
+            (0..weak.len()).map(|i| weak.get_index(i).unwrap().0.to_string()).collect();
+        keys.join(" ")
+    }
+
+    #[test]
+    fn test_weak_hash_table() {
+        let collections: [fn(&mut Context); 2] =
+            [|cx| cx.minor_collection(), |cx| cx.major_collection()];
+        for collect in collections {
+            assert_eq!(weak_table_keys(Weakness::None, collect), r#""k1" "k2" "k3" "k4" "v1""#);
+            assert_eq!(weak_table_keys(Weakness::Key, collect), r#""k1" "k3" "v1""#);
+            assert_eq!(weak_table_keys(Weakness::Value, collect), r#""k2" "k3""#);
+            assert_eq!(weak_table_keys(Weakness::KeyOrValue, collect), r#""k1" "k2" "k3" "v1""#);
+            assert_eq!(weak_table_keys(Weakness::KeyAndValue, collect), r#""k3""#);
+        }
+    }
+
+    #[test]
+    fn test_old_weak_hash_table() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let table = cx.add(HashTable::default());
+        root!(table, cx);
+        cx.minor_collection();
+        // Entries added to an old table are only found through the barrier
+        let ObjectType::HashTable(weak) = table.bind(cx).untag() else { unreachable!() };
+        weak.set_weakness(Weakness::Key);
+        let key = cx.add("live");
+        weak.insert(key, cx.add("value"));
+        weak.insert(cx.add("dead"), cx.add("value"));
+        root!(key, cx);
+        cx.minor_collection();
+        let ObjectType::HashTable(weak) = table.bind(cx).untag() else { unreachable!() };
+        assert_eq!(weak.len(), 1);
+        assert_eq!(weak.get(key.bind(cx)).unwrap(), "value");
+        cx.major_collection();
+        let ObjectType::HashTable(weak) = table.bind(cx).untag() else { unreachable!() };
+        assert_eq!(weak.get(key.bind(cx)).unwrap(), "value");
+    }
+
+    fn make_finalizer<'ob>(func: &str, cx: &'ob Context) -> Gc<&'ob Record> {
+        let mut record = cx.vec_with_capacity(2);
+        record.push(NIL);
+        record.push(cx.add(func));
+        let finalizer = RecordBuilder(record).into_obj(cx);
+        cx.track_finalizer(finalizer.untag());
+        finalizer
+    }
+
+    #[test]
+    fn test_finalizers() {
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        let live = make_finalizer("live", cx);
+        root!(live, cx);
+        make_finalizer("dead", cx);
+        cx.minor_collection();
+        // Queued functions survive until they are taken
+        cx.major_collection();
+        let queued = cx.take_finalizers();
+        assert_eq!(queued, ["dead"]);
+        assert_eq!(cx.block.finalizers.borrow().len(), 1);
+        // A finalizer only runs once
+        cx.major_collection();
+        assert!(cx.take_finalizers().is_empty());
+        assert_eq!(live.bind(cx).untag()[1].get(), "live");
+    }
 }
@@ -52,15 +52,28 @@ impl fmt::Debug for GcHeader {
 struct HeaderData {
     is_present: u8,
     marked: Cell<bool>,
+    // Set once the object has survived a collection and lives in the old
+    // generation
+    old: Cell<bool>,
 }
 
 impl HeaderData {
     const PRESENT: u8 = 1;
     const fn new(marked: bool) -> Self {
-        Self { is_present: Self::PRESENT, marked: Cell::new(marked) }
+        Self { is_present: Self::PRESENT, marked: Cell::new(marked), old: Cell::new(false) }
     }
 }
 
+thread_local! {
+    /// Set while a minor collection is running. Old objects are left in place
+    /// during a minor collection, and only the nursery is evacuated.
+    static MINOR_COLLECTION: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(in crate::core) fn set_minor_collection(minor: bool) {
+    MINOR_COLLECTION.with(|x| x.set(minor));
+}
+
 /// A block of memory allocated on the heap that is managed by the garbage collector.
 #[repr(C)]
 #[derive(Debug)]
@@ -97,10 +110,15 @@ impl<T> GcHeap<T> {
     }
 
     pub(in crate::core) fn allocation_state(&self) -> AllocState {
+        if cfg!(any(test, feature = "gc_stress")) {
+            super::verify::check_pointer(std::ptr::from_ref(self).cast());
+        }
         match self.header().get_header() {
             Ok(header) => {
                 if header.marked.get() {
                     AllocState::Global
+                } else if header.old.get() && MINOR_COLLECTION.with(Cell::get) {
+                    AllocState::Old
                 } else {
                     AllocState::Unmoved
                 }
@@ -109,6 +127,18 @@ impl<T> GcHeap<T> {
         }
     }
 
+    /// Move the object into the old generation. This is called on the new copy
+    /// of an object after it survives a collection.
+    pub(in crate::core) fn promote(&self) {
+        if let Ok(header) = self.header().get_header() {
+            header.old.set(true);
+        }
+    }
+
+    pub(in crate::core) fn is_old(&self) -> bool {
+        matches!(self.header().get_header(), Ok(header) if header.old.get())
+    }
+
     fn is_marked(&self) -> bool {
         self.header().get_header().unwrap().marked.get()
     }
@@ -117,6 +147,9 @@ impl<T> GcHeap<T> {
 pub(in crate::core) enum AllocState {
     Forwarded(NonNull<u8>),
     Global,
+    /// The object is in the old generation and a minor collection is running,
+    /// so it stays where it is.
+    Old,
     Unmoved,
 }
 
@@ -128,6 +161,12 @@ pub(in crate::core) trait GcMoveable {
     fn move_value(&self, _to_space: &bumpalo::Bump) -> Option<(Self::Value, bool)> {
         None
     }
+
+    /// True if the running collection has already reached this object, or
+    /// will not move it at all.
+    fn is_live(&self) -> bool {
+        true
+    }
 }
 
 impl<'a, T: GcMoveable<Value = NonNull<T>>> GcMoveable for &'a T {
@@ -137,6 +176,10 @@ impl<'a, T: GcMoveable<Value = NonNull<T>>> GcMoveable for &'a T {
         let val = (*self).move_value(to_space);
         val.map(|(ptr, moved)| (unsafe { ptr.as_ref() }, moved))
     }
+
+    fn is_live(&self) -> bool {
+        (*self).is_live()
+    }
 }
 
 #[macro_export]
@@ -151,6 +194,10 @@ macro_rules! derive_GcMoveable {
                     None => None,
                 }
             }
+
+            fn is_live(&self) -> bool {
+                self.0.is_live()
+            }
         }
     };
 }
@@ -160,28 +207,32 @@ impl<T> GcMoveable for GcHeap<T> {
 
     fn move_value(&self, to_space: &bumpalo::Bump) -> Option<(Self::Value, bool)> {
         use std::ptr;
-        match self.header().get_header() {
-            Ok(header) => {
-                if header.marked.get() {
-                    // The object is global and should not be moved
-                    return None;
-                }
+        match self.allocation_state() {
+            // The object is global or old and should not be moved
+            AllocState::Global | AllocState::Old => None,
+            AllocState::Unmoved => {
                 // move to to_space
                 let layout = Layout::for_value(self);
                 let to_ptr = to_space.alloc_layout(layout);
-                unsafe {
+                let new = unsafe {
                     let src = ptr::from_ref(self);
                     let dst = to_ptr.cast::<Self>().as_ptr();
                     ptr::copy_nonoverlapping(src, dst, 1);
-                }
+                    &*dst
+                };
+                new.promote();
                 // write forwarding pointer
                 self.forward(to_ptr);
                 // return new address
                 Some((to_ptr.cast::<Self>(), true))
             }
-            Err(fwd) => Some((fwd.cast::<Self>(), false)),
+            AllocState::Forwarded(fwd) => Some((fwd.cast::<Self>(), false)),
         }
     }
+
+    fn is_live(&self) -> bool {
+        !matches!(self.allocation_state(), AllocState::Unmoved)
+    }
 }
 
 impl<T: Trace> Trace for GcHeap<T> {
@@ -441,6 +441,15 @@ impl TryFrom<&Rt<Slot<Object<'_>>>> for usize {
     }
 }
 
+impl TryFrom<&Rt<Slot<Object<'_>>>> for i64 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Rt<Slot<Object>>) -> Result<Self, Self::Error> {
+        let int: Gc<i64> = (*value.inner().get()).try_into()?;
+        Ok(int.untag())
+    }
+}
+
 impl<T> Rt<Slot<Gc<T>>> {
     /// Like `try_into().bind(cx)`, but needed to due no specialization
     pub(crate) fn bind_as<'ob, U, E>(&self, _cx: &'ob Context) -> Result<U, E>
@@ -708,6 +717,12 @@ where
         inner.get_mut(&root)
     }
 
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Rt<K>, &Rt<V>)> {
+        use std::ptr::from_ref;
+        let inner = unsafe { &*from_ref(self.as_ref()).cast::<IndexMap<Rt<K>, Rt<V>>>() };
+        inner.iter()
+    }
+
     pub(crate) fn remove<Q: IntoRoot<K>>(&mut self, k: Q) {
         let root = unsafe { k.into_root() };
         self.as_mut().swap_remove(&root);
@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 
 use super::super::object::RawObj;
-use crate::core::object::{Gc, Object};
+use crate::core::object::{Gc, Object, ObjectType};
 use rune_core::hashmap::{HashMap, HashSet};
 
 /// A trait for owned types that can be traced by the garbage collector. This should be implemented
@@ -19,14 +19,77 @@ pub(crate) trait TracePtr {
     fn trace_ptr(&self, state: &mut GcState);
 }
 
+/// A container that holds its entries weakly. Weak containers don't trace
+/// their entries when they are reached. Instead they are registered with
+/// [`GcState::push_ephemeron`] and processed once everything else has been
+/// traced, so that only the entries that are reachable from elsewhere are kept.
+pub(in crate::core) trait Ephemeron {
+    /// Trace the entries that should be kept given what is reachable so far.
+    /// Returns true if this reached any new objects.
+    fn trace_reachable(&self, state: &mut GcState) -> bool;
+
+    /// Remove the entries that were not kept and update the rest.
+    fn sweep(&self, state: &mut GcState);
+}
+
+/// The number of objects of each type that were copied by a collection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GcStats {
+    pub(crate) conses: usize,
+    pub(crate) symbols: usize,
+    pub(crate) strings: usize,
+    pub(crate) string_bytes: usize,
+    pub(crate) vectors: usize,
+    pub(crate) vector_slots: usize,
+    pub(crate) floats: usize,
+    pub(crate) hash_tables: usize,
+    pub(crate) bigints: usize,
+}
+
+impl GcStats {
+    pub(in crate::core) fn count(&mut self, obj: Object) {
+        match obj.untag() {
+            ObjectType::Cons(_) => self.conses += 1,
+            ObjectType::Symbol(_) => self.symbols += 1,
+            ObjectType::String(x) => {
+                self.strings += 1;
+                self.string_bytes += x.inner().len();
+            }
+            ObjectType::ByteString(x) => {
+                self.strings += 1;
+                self.string_bytes += x.len();
+            }
+            ObjectType::Vec(x) => {
+                self.vectors += 1;
+                self.vector_slots += x.len();
+            }
+            ObjectType::Record(x) => {
+                self.vectors += 1;
+                self.vector_slots += x.len();
+            }
+            ObjectType::Float(_) => self.floats += 1,
+            ObjectType::HashTable(_) => self.hash_tables += 1,
+            ObjectType::BigInt(_) => self.bigints += 1,
+            _ => {}
+        }
+    }
+}
+
 pub(crate) struct GcState {
     stack: Vec<RawObj>,
     pub(in crate::core) to_space: bumpalo::Bump,
+    pub(in crate::core) stats: GcStats,
+    ephemerons: Vec<*const dyn Ephemeron>,
 }
 
 impl GcState {
     pub fn new() -> Self {
-        GcState { stack: Vec::new(), to_space: bumpalo::Bump::new() }
+        Self::with_to_space(bumpalo::Bump::new())
+    }
+
+    /// Create a state that copies objects into an existing space.
+    pub(in crate::core) fn with_to_space(to_space: bumpalo::Bump) -> Self {
+        GcState { stack: Vec::new(), to_space, stats: GcStats::default(), ephemerons: Vec::new() }
     }
 
     pub fn push(&mut self, obj: Object) {
@@ -39,6 +102,38 @@ impl GcState {
             obj.trace_ptr(self);
         }
     }
+
+    /// Register a weak container to be processed by [`GcState::trace_ephemerons`].
+    /// The container must already be in its final location.
+    pub(in crate::core) fn push_ephemeron(&mut self, ephemeron: &(dyn Ephemeron + 'static)) {
+        self.ephemerons.push(ephemeron);
+    }
+
+    /// Trace the reachable entries of all weak containers until no new objects
+    /// are found, then remove the entries that are left. Keeping an entry can
+    /// make the entries of other containers reachable, so this has to iterate
+    /// to a fixpoint.
+    pub(in crate::core) fn trace_ephemerons(&mut self) {
+        loop {
+            let mut found = false;
+            // Tracing can reach more weak containers, so don't hold an
+            // iterator over the list.
+            let mut i = 0;
+            while let Some(&ephemeron) = self.ephemerons.get(i) {
+                // SAFETY: The containers live in to-space or the old
+                // generation, which are not freed during a collection.
+                found |= unsafe { (*ephemeron).trace_reachable(self) };
+                self.trace_stack();
+                i += 1;
+            }
+            if !found {
+                break;
+            }
+        }
+        for ephemeron in std::mem::take(&mut self.ephemerons) {
+            unsafe { (*ephemeron).sweep(self) };
+        }
+    }
 }
 
 impl<T: Trace> TracePtr for &T {
@@ -0,0 +1,63 @@
+//! Heap verification for the GC stress mode.
+//!
+//! Rooting bugs only show up when a collection happens at the wrong moment,
+//! and even then the stale pointer usually points to memory that still looks
+//! valid. To catch them, the spaces that were evacuated by a collection are
+//! kept alive while a second major collection walks everything reachable from
+//! the roots. Every object pointer that is followed is checked against the
+//! evacuated spaces and the hash tables that were freed.
+use std::cell::RefCell;
+use std::ops::Range;
+
+/// The spaces that were evacuated by a collection, along with the hash tables
+/// whose storage was freed because they were no longer reachable.
+#[derive(Default)]
+pub(in crate::core) struct FromSpace {
+    pub(super) spaces: Vec<bumpalo::Bump>,
+    pub(super) freed_tables: Vec<usize>,
+}
+
+struct Verifier {
+    ranges: Vec<Range<usize>>,
+    freed_tables: Vec<usize>,
+    errors: Vec<String>,
+}
+
+thread_local! {
+    static VERIFIER: RefCell<Option<Verifier>> = const { RefCell::new(None) };
+}
+
+/// Start checking all object pointers against `from_space`.
+pub(super) fn start(from_space: &mut FromSpace) {
+    let ranges = from_space
+        .spaces
+        .iter_mut()
+        .flat_map(|space| space.iter_allocated_chunks())
+        .map(|chunk| {
+            let start = chunk.as_ptr() as usize;
+            start..start + chunk.len()
+        })
+        .collect();
+    let freed_tables = from_space.freed_tables.clone();
+    VERIFIER.with(|x| {
+        *x.borrow_mut() = Some(Verifier { ranges, freed_tables, errors: Vec::new() });
+    });
+}
+
+/// Stop checking pointers and return the errors that were found.
+pub(super) fn finish() -> Vec<String> {
+    VERIFIER.with(|x| x.borrow_mut().take()).map(|x| x.errors).unwrap_or_default()
+}
+
+/// Check that a pointer followed by the collector refers to live memory.
+pub(super) fn check_pointer(ptr: *const u8) {
+    VERIFIER.with(|x| {
+        let Some(verifier) = &mut *x.borrow_mut() else { return };
+        let addr = ptr as usize;
+        if verifier.freed_tables.contains(&addr) {
+            verifier.errors.push(format!("pointer to freed hash table: {ptr:?}"));
+        } else if verifier.ranges.iter().any(|range| range.contains(&addr)) {
+            verifier.errors.push(format!("pointer into from-space: {ptr:?}"));
+        }
+    });
+}
@@ -17,6 +17,8 @@ mod float;
 mod func;
 mod hashtable;
 mod integer;
+mod marker;
+mod overlay;
 mod string;
 mod symbol;
 mod tagged;
@@ -30,6 +32,8 @@ pub(crate) use float::*;
 pub(crate) use func::*;
 pub(crate) use hashtable::*;
 pub(crate) use integer::*;
+pub(crate) use marker::*;
+pub(crate) use overlay::*;
 pub(crate) use string::*;
 pub(crate) use symbol::*;
 pub(crate) use tagged::*;
@@ -1,18 +1,26 @@
-use super::{Gc, Object, ObjectType, TagType, WithLifetime};
+use super::{
+    Gc, LispMarker, LispOverlay, MarkerPos, NIL, Object, ObjectType, Symbol, TRUE, TagType,
+    WithLifetime, int_to_char, raw_byte_to_char,
+};
 use crate::{
     core::{
+        env::sym,
         error::{Type, TypeError},
-        gc::{Block, Context, GcHeap, GcState, Trace},
+        gc::{Block, Context, GcHeap, GcState, Slot, Trace},
     },
     derive_GcMoveable,
     intervals::IntervalTree,
+    overlay::OverlayTree,
 };
 use anyhow::{Result, bail};
+use rune_core::hashmap::IndexMap;
 use rune_macros::Trace;
 use std::{
+    cell::RefCell,
     fmt::Display,
     ops::{Deref, DerefMut},
-    sync::{Mutex, MutexGuard},
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    time::SystemTime,
 };
 use text_buffer::Buffer as TextBuffer;
 
@@ -37,6 +45,12 @@ impl OpenBuffer<'_> {
     // TODO: we shouldn't leave it empty
     pub(crate) fn kill(&mut self) -> bool {
         let killed = self.data.is_some();
+        if let Some(data) = self.data.as_ref() {
+            for marker in data.markers.iter().filter_map(Weak::upgrade) {
+                marker.detach();
+            }
+            data.overlays.detach_all();
+        }
         *self.data = None;
         killed
     }
@@ -46,15 +60,28 @@ impl OpenBuffer<'_> {
     }
 
     pub(crate) fn insert(&mut self, arg: Object) -> Result<()> {
+        let pos = self.text.cursor().chars();
+        let len = self.text.len_chars();
         match arg.untag() {
             ObjectType::Int(i) => {
-                let Ok(u_32) = i.try_into() else { bail!("{i} is an invalid char") };
-                let Some(chr) = char::from_u32(u_32) else { bail!("{i} is an Invalid char") };
+                let Ok(chr) = int_to_char(i) else { bail!("{i} is an invalid char") };
                 self.get_mut().text.insert_char(chr);
             }
             ObjectType::String(s) => self.get_mut().text.insert(s),
+            ObjectType::ByteString(s) => {
+                let text: String = s.iter().map(|&x| raw_byte_to_char(x)).collect();
+                self.get_mut().text.insert(&text);
+            }
             x => bail!(TypeError::new(Type::String, x)),
         }
+        let inserted = self.text.len_chars() - len;
+        let back_ref = self.back_ref;
+        let data = self.get_mut();
+        if let Some((_, zv)) = &mut data.restriction {
+            *zv += inserted;
+        }
+        data.for_each_marker(|m| m.adjust_for_insert(pos, inserted));
+        data.overlays.adjust_for_insert(pos, inserted, back_ref);
         Ok(())
     }
 
@@ -68,11 +95,63 @@ impl OpenBuffer<'_> {
         let beg = self.in_range(beg)?;
         let end = self.in_range(end)?;
         self.get_mut().text.delete_range(beg, end);
+        let (beg, end) = if beg <= end { (beg, end) } else { (end, beg) };
+        let back_ref = self.back_ref;
+        let data = self.get_mut();
+        if let Some((_, zv)) = &mut data.restriction {
+            *zv -= end - beg;
+        }
+        data.for_each_marker(|m| m.adjust_for_delete(beg, end));
+        data.overlays.adjust_for_delete(beg, end, back_ref);
         Ok(())
     }
 
+    /// Restrict editing to the text between the 1-based positions `start` and
+    /// `end`. Unlike most position arguments, these are checked against the
+    /// whole buffer instead of the current restriction.
+    pub(crate) fn narrow_to_region(&mut self, start: usize, end: usize) -> Result<()> {
+        let (start, end) = if start <= end { (start, end) } else { (end, start) };
+        let len = self.text.len_chars();
+        if start == 0 || end > len + 1 {
+            bail!("Args out of range: {start}, {end}");
+        }
+        self.restrict(start - 1, end - 1);
+        Ok(())
+    }
+
+    /// Set the accessible portion of the buffer to the 0-based character range
+    /// `beg..end`, clamped to the size of the buffer. Point is moved inside
+    /// the new bounds if needed.
+    pub(crate) fn restrict(&mut self, beg: usize, end: usize) {
+        let len = self.text.len_chars();
+        let (beg, end) = (beg.min(len), end.min(len));
+        let data = self.get_mut();
+        data.restriction = if beg == 0 && end == len { None } else { Some((beg, end)) };
+        let point = data.text.cursor().chars();
+        data.text.set_cursor(point.clamp(beg, end));
+    }
+
+    /// Point `marker` at the 0-based character position `pos` in this buffer,
+    /// clamped to the size of the buffer.
+    pub(crate) fn set_marker(&mut self, marker: &LispMarker, pos: usize) {
+        let pos = pos.min(self.text.len_chars());
+        if let Some(shared) = marker.attach(self.back_ref, pos) {
+            self.get_mut().markers.push(Arc::downgrade(&shared));
+        }
+    }
+
+    /// Put `overlay` between the 1-based positions `beg` and `end` of this
+    /// buffer, clamped to the size of the buffer.
+    pub(crate) fn move_overlay(&mut self, overlay: &LispOverlay, beg: usize, end: usize) {
+        let len = self.text.len_chars();
+        let clamp = |pos: usize| pos.saturating_sub(1).min(len);
+        let (beg, end) = if beg <= end { (beg, end) } else { (end, beg) };
+        let back_ref = self.back_ref;
+        self.get_mut().overlays.move_overlay(overlay, clamp(beg), clamp(end), back_ref);
+    }
+
     fn in_range(&self, pos: usize) -> Result<usize> {
-        if pos == 0 || pos > self.get().text.len_chars() + 1 {
+        if pos < self.point_min() || pos > self.point_max() {
             bail!("Position {pos} out of range in {}", self.get().name);
         }
         Ok(pos - 1)
@@ -107,21 +186,156 @@ impl DerefMut for OpenBuffer<'_> {
     }
 }
 
-/// The actual data of the buffer. Buffer local variables will be stored here
-/// eventually.
+/// The actual data of the buffer, including the values of its buffer-local
+/// variables.
 #[derive(Debug)]
 pub(crate) struct BufferData {
     pub(crate) name: String,
     pub(crate) text: TextBuffer,
     pub(crate) textprops: IntervalTree<'static>,
+    /// Positions of the markers that point into this buffer. When a marker is
+    /// garbage collected or moved to another buffer its entry goes dead and is
+    /// pruned on the next edit.
+    markers: Vec<Weak<MarkerPos>>,
+    /// The overlays in this buffer. Like buffer-local values they live in
+    /// the heap of the thread that created them.
+    pub(crate) overlays: OverlayTree,
+    /// The accessible portion of the buffer as 0-based character positions, or
+    /// `None` if the buffer is not narrowed.
+    restriction: Option<(usize, usize)>,
+    /// Values of the buffer-local variables. These point into the heap of the
+    /// thread that set them and are traced through its
+    /// [`CurrentBuffer`](crate::core::env::CurrentBuffer).
+    locals: RefCell<IndexMap<Slot<Symbol<'static>>, Slot<Object<'static>>>>,
+    /// The 0-based position of point at the last undo boundary. It is
+    /// recorded in the undo list if the next change happens somewhere else.
+    pub(crate) undo_point: Option<usize>,
+    /// The syntax table of the buffer, or nil to use the standard one. Like
+    /// buffer-local values it lives in the heap of the thread that set it.
+    syntax_table: Slot<Object<'static>>,
+    /// The modification time of the visited file when it was last read or
+    /// written.
+    pub(crate) modtime: Modtime,
+}
+
+/// The recorded modification time of the file a buffer visits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Modtime {
+    /// The buffer isn't visiting a file, or the time was cleared.
+    #[default]
+    Unknown,
+    /// The visited file did not exist.
+    Missing,
+    Time(SystemTime),
 }
 
 impl BufferData {
+    /// Start of the accessible portion of the buffer as a 0-based character
+    /// position.
+    pub(crate) fn begv(&self) -> usize {
+        self.restriction.map_or(0, |(beg, _)| beg)
+    }
+
+    /// End of the accessible portion of the buffer as a 0-based character
+    /// position.
+    pub(crate) fn zv(&self) -> usize {
+        self.restriction.map_or(self.text.len_chars(), |(_, end)| end)
+    }
+
+    /// The minimum 1-based position in the accessible portion of the buffer.
+    pub(crate) fn point_min(&self) -> usize {
+        self.begv() + 1
+    }
+
+    /// The maximum 1-based position in the accessible portion of the buffer.
+    pub(crate) fn point_max(&self) -> usize {
+        self.zv() + 1
+    }
+
+    pub(crate) fn is_narrowed(&self) -> bool {
+        self.restriction.is_some()
+    }
+
+    pub(crate) fn widen(&mut self) {
+        self.restriction = None;
+    }
+
+    /// The buffer-local value of `var`, if it has one in this buffer.
+    pub(crate) fn local_var(&self, var: Symbol) -> Option<Object<'_>> {
+        let var = unsafe { var.with_lifetime() };
+        let locals = self.locals.borrow();
+        locals.get(&Slot::new(var)).map(|x| unsafe { (**x).with_lifetime() })
+    }
+
+    pub(crate) fn has_local_var(&self, var: Symbol) -> bool {
+        let var = unsafe { var.with_lifetime() };
+        self.locals.borrow().contains_key(&Slot::new(var))
+    }
+
+    /// Set the buffer-local value of `var`, creating it if needed. Only the
+    /// thread that owns `value` should call this, see
+    /// [`CurrentBuffer::set_local_var`](crate::core::env::CurrentBuffer::set_local_var).
+    pub(crate) fn set_local_var(&mut self, var: Symbol, value: Object) {
+        let (var, value) = unsafe { (var.with_lifetime(), value.with_lifetime()) };
+        self.locals.get_mut().insert(Slot::new(var), Slot::new(value));
+    }
+
+    pub(crate) fn kill_local_var(&mut self, var: Symbol) {
+        let var = unsafe { var.with_lifetime() };
+        self.locals.get_mut().shift_remove(&Slot::new(var));
+    }
+
+    /// All buffer-local variables and their values, in the order they were
+    /// created.
+    pub(crate) fn local_vars(&self) -> Vec<(Symbol<'_>, Object<'_>)> {
+        let locals = self.locals.borrow();
+        locals
+            .iter()
+            .map(|(var, value)| unsafe { ((**var).with_lifetime(), (**value).with_lifetime()) })
+            .collect()
+    }
+
+    /// The syntax table set with `set-syntax-table`, or nil if the buffer
+    /// uses the standard syntax table.
+    pub(crate) fn syntax_table(&self) -> Object<'_> {
+        unsafe { (*self.syntax_table).with_lifetime() }
+    }
+
+    /// Set the syntax table of the buffer. Like
+    /// [`Self::set_local_var`], only the thread that owns `table` should call
+    /// this.
+    pub(crate) fn set_syntax_table(&mut self, table: Object) {
+        self.syntax_table = Slot::new(unsafe { table.with_lifetime() });
+    }
+
+    fn for_each_marker(&mut self, f: impl Fn(&MarkerPos)) {
+        self.markers.retain(|marker| match marker.upgrade() {
+            Some(marker) => {
+                f(&marker);
+                true
+            }
+            None => false,
+        });
+    }
+
     pub fn textprops_with_lifetime<'new>(&mut self) -> &mut IntervalTree<'new> {
         unsafe { std::mem::transmute(&mut self.textprops) }
     }
 }
 
+impl Trace for BufferData {
+    fn trace(&self, state: &mut GcState) {
+        // Tracing can move uninterned symbols, so the keys need to be rehashed
+        self.locals.borrow_mut().rehash_keys(|var, value| {
+            var.trace(state);
+            value.trace(state);
+        });
+        self.overlays.trace(state);
+        self.syntax_table.trace(state);
+        self.textprops.trace(state);
+    }
+}
+
 #[derive(Debug)]
 struct LispBufferInner {
     text_buffer: Mutex<Option<BufferData>>,
@@ -142,19 +356,46 @@ impl LispBuffer {
 
     pub(crate) unsafe fn new(name: String, _: &Block<true>) -> LispBuffer {
         let textprops = IntervalTree::new();
+        // Every buffer has its own undo list. Undo is disabled in internal
+        // buffers, whose names start with a space.
+        let undo_list = if name.starts_with(' ') { TRUE } else { NIL };
+        let mut locals = IndexMap::default();
+        locals.insert(Slot::new(sym::BUFFER_UNDO_LIST), Slot::new(undo_list));
+        // The file visited by the buffer is always buffer-local
+        locals.insert(Slot::new(sym::BUFFER_FILE_NAME), Slot::new(NIL));
+        locals.insert(Slot::new(sym::BUFFER_FILE_TRUENAME), Slot::new(NIL));
         let new = LispBufferInner {
-            text_buffer: Mutex::new(Some(BufferData { name, text: TextBuffer::new(), textprops })),
+            text_buffer: Mutex::new(Some(BufferData {
+                name,
+                text: TextBuffer::new(),
+                textprops,
+                markers: Vec::new(),
+                overlays: OverlayTree::new(),
+                restriction: None,
+                locals: RefCell::new(locals),
+                undo_point: None,
+                syntax_table: Slot::new(NIL),
+                modtime: Modtime::Unknown,
+            })),
         };
         Self(GcHeap::new(new, true))
     }
 
     pub(crate) fn lock(&self) -> Result<OpenBuffer<'_>> {
-        let guard = self.0.text_buffer.lock().unwrap();
+        let Ok(guard) = self.0.text_buffer.lock() else {
+            bail!("buffer was poisoned by a panic");
+        };
         if guard.is_none() {
             bail!("selecting deleted buffer");
         }
         Ok(OpenBuffer { data: guard, back_ref: self })
     }
+
+    /// The name of the buffer, or `None` if it is killed or currently locked.
+    pub(crate) fn try_name(&self) -> Option<String> {
+        let data = self.0.text_buffer.try_lock().ok()?;
+        data.as_ref().map(|buf| buf.name.clone())
+    }
 }
 
 impl PartialEq for LispBufferInner {
@@ -163,6 +404,13 @@ impl PartialEq for LispBufferInner {
     }
 }
 
+// Buffers are compared by identity, so they are hashed by address as well.
+impl std::hash::Hash for LispBuffer {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self, state);
+    }
+}
+
 impl PartialEq<OpenBuffer<'_>> for LispBuffer {
     fn eq(&self, other: &OpenBuffer) -> bool {
         other.back_ref == self
@@ -179,7 +427,9 @@ impl Eq for LispBufferInner {}
 
 impl Display for LispBuffer {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let data = self.0.text_buffer.lock().unwrap();
+        // The buffer might be locked by this thread as the current buffer, so
+        // don't block on it
+        let Ok(data) = self.0.text_buffer.try_lock() else { return write!(f, "#<buffer>") };
         let name = match data.as_ref() {
             Some(buf) => &buf.name,
             None => "deleted buffer",
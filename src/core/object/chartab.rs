@@ -11,7 +11,7 @@ use std::{cell::RefCell, fmt};
 pub struct CharTableInner<'ob> {
     parent: RefCell<Option<Slot<&'ob CharTable>>>,
     data: RefCell<HashMap<usize, Slot<Object<'ob>>>>,
-    init: Slot<Object<'ob>>,
+    init: RefCell<Slot<Object<'ob>>>,
 }
 
 impl<'ob> CharTableInner<'ob> {
@@ -19,7 +19,7 @@ impl<'ob> CharTableInner<'ob> {
         CharTableInner {
             parent: RefCell::new(None),
             data: RefCell::new(HashMap::default()),
-            init: Slot::new(init.unwrap_or(NIL)),
+            init: RefCell::new(Slot::new(init.unwrap_or(NIL))),
         }
     }
 }
@@ -47,7 +47,7 @@ impl<'new> CloneIn<'new, &'new Self> for CharTable {
             data.insert(*key, new_value);
         }
         let data = RefCell::new(data);
-        let init = Slot::new(self.0.init.clone_in(bk));
+        let init = RefCell::new(Slot::new(self.0.init.borrow().clone_in(bk)));
         CharTableInner { parent, data, init }.into_obj(bk)
     }
 }
@@ -60,21 +60,50 @@ impl CharTable {
         Self(GcHeap::new(table, constant))
     }
 
+    /// The value for `idx`. If it is nil, the value is looked up in the
+    /// parent table instead.
     pub fn get(&self, idx: usize) -> Object<'_> {
-        match self.0.data.borrow().get(&idx) {
+        let value = match self.0.data.borrow().get(&idx) {
             Some(x) => **x,
-            None => *self.0.init,
+            None => **self.0.init.borrow(),
+        };
+        match self.0.parent.borrow().as_ref() {
+            Some(parent) if value.is_nil() => unsafe { parent.get(idx).with_lifetime() },
+            _ => value,
         }
     }
 
     pub fn set(&self, idx: usize, item: Object) {
+        self.0.write_barrier();
         unsafe { self.0.data.borrow_mut().insert(idx, Slot::new(item.with_lifetime())) };
     }
 
     pub fn set_parent(&self, new: Option<&Self>) {
+        self.0.write_barrier();
         let new_ptr = new.map(|n| unsafe { Slot::new(n.with_lifetime()) });
         *self.0.parent.borrow_mut() = new_ptr;
     }
+
+    pub fn parent(&self) -> Option<&Self> {
+        self.0.parent.borrow().as_ref().map(|p| **p)
+    }
+
+    /// The value of characters that were not set in this table.
+    pub fn default_value(&self) -> Object<'_> {
+        **self.0.init.borrow()
+    }
+
+    pub fn set_default_value(&self, item: Object) {
+        self.0.write_barrier();
+        unsafe { *self.0.init.borrow_mut() = Slot::new(item.with_lifetime()) };
+    }
+
+    /// The characters set in this table and their values, sorted by character.
+    pub fn entries(&self) -> Vec<(usize, Object<'_>)> {
+        let mut entries: Vec<_> = self.0.data.borrow().iter().map(|(k, v)| (*k, **v)).collect();
+        entries.sort_by_key(|(key, _)| *key);
+        entries
+    }
 }
 
 impl fmt::Display for CharTable {
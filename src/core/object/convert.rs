@@ -6,9 +6,10 @@ use crate::data::LispError;
 
 use super::{
     super::error::{Type, TypeError},
-    ByteString, CharTable, LispHashTable, LispString, LispVec, NIL, OptionalFlag, TRUE,
+    ByteString, CharTable, LispHashTable, LispMarker, LispOverlay, LispString, LispVec, NIL,
+    OptionalFlag, TRUE,
 };
-use super::{Gc, LispFloat, Object, ObjectType, Symbol};
+use super::{Gc, LispFloat, Object, ObjectType, Symbol, int_to_char};
 use anyhow::Context;
 
 impl<'ob> TryFrom<Object<'ob>> for &'ob str {
@@ -55,13 +56,37 @@ impl<'ob> TryFrom<Object<'ob>> for usize {
     }
 }
 
+/// A buffer position argument. Unlike a plain `usize`, a marker is also
+/// accepted and converts to the position it points at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Position(pub(crate) usize);
+
+impl<'ob> TryFrom<Object<'ob>> for Position {
+    type Error = anyhow::Error;
+    fn try_from(obj: Object<'ob>) -> Result<Self, Self::Error> {
+        match obj.untag() {
+            ObjectType::Marker(x) => x.lisp_position().map(Position),
+            ObjectType::Int(_) => obj.try_into().map(Position),
+            x => Err(TypeError::new(Type::IntegerOrMarker, x).into()),
+        }
+    }
+}
+
+impl<'ob> TryFrom<Object<'ob>> for Option<Position> {
+    type Error = anyhow::Error;
+    fn try_from(obj: Object<'ob>) -> Result<Self, Self::Error> {
+        match obj.untag() {
+            ObjectType::NIL => Ok(None),
+            _ => obj.try_into().map(Some),
+        }
+    }
+}
+
 impl<'ob> TryFrom<Object<'ob>> for char {
     type Error = TypeError;
     fn try_from(obj: Object<'ob>) -> Result<Self, Self::Error> {
-        let err = || TypeError::new(Type::Char, obj);
-        let ObjectType::Int(x) = obj.untag() else { Err(err())? };
-        let Ok(x) = u32::try_from(x) else { Err(err())? };
-        std::char::from_u32(x).ok_or_else(err)
+        let ObjectType::Int(x) = obj.untag() else { Err(TypeError::new(Type::Char, obj))? };
+        int_to_char(x)
     }
 }
 
@@ -126,6 +151,8 @@ define_unbox!(ByteString, String, &'ob ByteString);
 define_unbox!(Vec, &'ob LispVec);
 define_unbox!(Symbol, Symbol<'ob>);
 define_unbox!(CharTable, &'ob CharTable);
+define_unbox!(Marker, &'ob LispMarker);
+define_unbox!(Overlay, &'ob LispOverlay);
 
 impl<'ob, T> From<Option<T>> for Object<'ob>
 where
@@ -170,4 +197,20 @@ mod test {
         let res = wrapper(vec.as_slice());
         assert_eq!(6, res.unwrap());
     }
+
+    #[test]
+    fn test_position() {
+        let roots = &RootSet::default();
+        let cx = &Context::new(roots);
+        let marker: Object = crate::marker::make_marker(cx).into();
+        let err = usize::try_from(marker).unwrap_err();
+        assert_eq!(
+            err.downcast::<TypeError>().unwrap().to_string(),
+            "expected Int, found Marker: #<marker in no buffer>"
+        );
+        assert!(Option::<usize>::try_from(marker).is_err());
+        assert!(Position::try_from(marker).is_err());
+        assert_eq!(Position::try_from(cx.add(3)).unwrap(), Position(3));
+        assert_eq!(Option::<Position>::try_from(NIL).unwrap(), None);
+    }
 }
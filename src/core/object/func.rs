@@ -24,6 +24,8 @@ pub(crate) struct ByteFnPrototype {
     pub(super) op_codes: Box<[u8]>,
     // TODO: remove a level of pointer indirection here.
     pub(super) constants: Slot<&'static LispVec>,
+    pub(super) docstring: Option<Slot<Object<'static>>>,
+    pub(super) interactive: Option<Slot<Object<'static>>>,
 }
 
 /// A function implemented in lisp. Note that all functions are byte compiled,
@@ -47,15 +49,18 @@ impl ByteFn {
     pub(in crate::core) fn new(inner: ByteFnPrototype, constant: bool) -> ByteFn {
         ByteFn(GcHeap::new(inner, constant))
     }
-    // SAFETY: The caller must ensure that the constants are part of the same
-    // block as the bytecode function. Otherwise they will be collected and we
-    // will have a dangling pointer. Also this type must immediatly be put into
-    // the GC heap, because holding it past garbage collections is unsafe.
+    // SAFETY: The caller must ensure that the constants, docstring and
+    // interactive spec are part of the same block as the bytecode function.
+    // Otherwise they will be collected and we will have a dangling pointer.
+    // Also this type must immediatly be put into the GC heap, because holding
+    // it past garbage collections is unsafe.
     pub(crate) unsafe fn make(
         op_codes: &[u8],
         consts: &LispVec,
         args: FnArgs,
         depth: usize,
+        docstring: Option<Object>,
+        interactive: Option<Object>,
     ) -> ByteFnPrototype {
         let op_codes = op_codes.to_vec().into_boxed_slice();
         #[cfg(miri)]
@@ -71,6 +76,8 @@ impl ByteFn {
         }
         ByteFnPrototype {
             constants: unsafe { Slot::new(consts.with_lifetime()) },
+            docstring: docstring.map(|x| unsafe { Slot::new(x.with_lifetime()) }),
+            interactive: interactive.map(|x| unsafe { Slot::new(x.with_lifetime()) }),
             op_codes,
             args,
             depth,
@@ -87,26 +94,55 @@ impl ByteFnPrototype {
         unsafe { std::mem::transmute::<&'ob [ObjCell], &'ob [Object<'ob>]>(&self.constants) }
     }
 
-    pub(crate) fn index<'ob>(&self, index: usize, cx: &'ob Context) -> Option<Object<'ob>> {
+    /// The docstring slot of the function, if it has one.
+    pub(crate) fn docstring(&self) -> Option<Object<'_>> {
+        self.docstring.as_ref().map(|x| **x)
+    }
+
+    /// The interactive spec of the function, if it has one.
+    pub(crate) fn interactive(&self) -> Option<Object<'_>> {
+        self.interactive.as_ref().map(|x| **x)
+    }
+
+    pub(crate) fn index<'ob>(&'ob self, index: usize, cx: &'ob Context) -> Option<Object<'ob>> {
         match index {
             0 => Some((self.args.into_arg_spec() as i64).into()),
             1 => Some(cx.add(self.codes().to_vec())),
             2 => Some(cx.add(self.consts())),
             3 => Some(self.depth.into()),
+            // A function with an interactive spec always has a docstring slot
+            4 if self.len() > 4 => Some(self.docstring().unwrap_or_default()),
+            5 => self.interactive(),
             _ => None,
         }
     }
 
-    pub(crate) const fn len(&self) -> usize {
-        4
+    pub(crate) fn len(&self) -> usize {
+        if self.interactive.is_some() {
+            6
+        } else if self.docstring.is_some() {
+            5
+        } else {
+            4
+        }
     }
 }
 
 impl<'new> CloneIn<'new, &'new Self> for ByteFn {
     fn clone_in<const C: bool>(&self, bk: &'new Block<C>) -> super::Gc<&'new Self> {
         let constants = self.constants.clone_in(bk);
-        let byte_fn =
-            unsafe { ByteFn::make(&self.op_codes, constants.untag(), self.args, self.depth) };
+        let docstring = self.docstring().map(|x| x.clone_in(bk));
+        let interactive = self.interactive().map(|x| x.clone_in(bk));
+        let byte_fn = unsafe {
+            ByteFn::make(
+                &self.op_codes,
+                constants.untag(),
+                self.args,
+                self.depth,
+                docstring,
+                interactive,
+            )
+        };
         byte_fn.into_obj(bk)
     }
 }
@@ -117,7 +153,13 @@ impl Display for ByteFn {
         let code = display_slice(&self.op_codes);
         let consts = display_slice(&self.constants);
         let depth = self.depth;
-        write!(f, "#[{spec} {code} {consts} {depth}]")
+        write!(f, "#[{spec} {code} {consts} {depth}")?;
+        if let Some(interactive) = self.interactive() {
+            write!(f, " {} {interactive}", self.docstring().unwrap_or_default())?;
+        } else if let Some(docstring) = self.docstring() {
+            write!(f, " {docstring}")?;
+        }
+        write!(f, "]")
     }
 }
 
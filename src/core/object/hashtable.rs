@@ -4,7 +4,7 @@
 //! the heap allocation when it is garbage collected.
 use super::{CloneIn, Gc, IntoObject, ObjCell, Object, WithLifetime};
 use crate::core::env::INTERNED_SYMBOLS;
-use crate::core::gc::{Block, GcHeap, GcState, Trace};
+use crate::core::gc::{Block, Ephemeron, GcHeap, GcMoveable, GcState, Trace};
 use crate::derive_GcMoveable;
 use rune_core::hashmap::{HashSet, IndexMap};
 use rune_macros::Trace;
@@ -44,6 +44,7 @@ impl LispHashTable {
         match self.0.allocation_state() {
             A::Forwarded(f) => Some(f),
             A::Global => panic!("global hashtable allocation found in local heap"),
+            A::Old => Some(NonNull::from(self).cast()),
             A::Unmoved => None,
         }
     }
@@ -63,9 +64,35 @@ struct HashTableInner<'ob> {
     // The current index of a [`maphash`] iterator. This is needed because we
     // can't hold the hashtable across calls to elisp (it might mutate it).
     iter_idx: usize,
+    weakness: Weakness,
     inner: HashTable<'ob>,
 }
 
+/// Which parts of an entry need to be reachable from outside the table for the
+/// collector to keep it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Weakness {
+    #[default]
+    None,
+    Key,
+    Value,
+    KeyOrValue,
+    KeyAndValue,
+}
+
+impl Weakness {
+    /// Whether an entry is kept given if its key and value are reachable.
+    fn keeps(self, key: bool, value: bool) -> bool {
+        match self {
+            Weakness::None => true,
+            Weakness::Key => key,
+            Weakness::Value => value,
+            Weakness::KeyOrValue => key || value,
+            Weakness::KeyAndValue => key && value,
+        }
+    }
+}
+
 impl LispHashTable {
     pub(crate) fn len(&self) -> usize {
         self.0.with(|x| x.len())
@@ -86,6 +113,7 @@ impl LispHashTable {
     pub(crate) fn insert(&self, key: Object, value: Object) {
         match &self.0.0 {
             HashTableType::Local(table) => {
+                self.0.write_barrier();
                 let key = unsafe { key.with_lifetime() };
                 let value = unsafe { value.with_lifetime() };
                 table.borrow_mut().inner.insert(key, value)
@@ -120,12 +148,27 @@ impl LispHashTable {
             HashTableType::Global(table) => table.lock().unwrap().iter_idx = index,
         }
     }
+
+    pub(crate) fn weakness(&self) -> Weakness {
+        match &self.0.0 {
+            HashTableType::Local(table) => table.borrow().weakness,
+            HashTableType::Global(table) => table.lock().unwrap().weakness,
+        }
+    }
+
+    pub(crate) fn set_weakness(&self, weakness: Weakness) {
+        match &self.0.0 {
+            HashTableType::Local(table) => table.borrow_mut().weakness = weakness,
+            // Global tables are never collected, so weakness has no effect
+            HashTableType::Global(table) => table.lock().unwrap().weakness = weakness,
+        }
+    }
 }
 
 impl<'a> HashTableCore<'a> {
     unsafe fn new(table: HashTable, constant: bool) -> Self {
         let table = std::mem::transmute::<HashTable<'_>, HashTable<'a>>(table);
-        let inner = HashTableInner { iter_idx: 0, inner: table };
+        let inner = HashTableInner { iter_idx: 0, weakness: Weakness::None, inner: table };
         if constant {
             HashTableCore(HashTableType::Global(Mutex::new(inner)))
         } else {
@@ -144,23 +187,68 @@ impl<'a> HashTableCore<'a> {
     }
 }
 
-impl Trace for HashTableCore<'_> {
-    fn trace(&self, state: &mut GcState) {
+impl<'a> HashTableCore<'a> {
+    fn local(&self) -> &RefCell<HashTableInner<'a>> {
         let HashTableType::Local(table) = &self.0 else {
             panic!("Global hash table should not be traced")
         };
-        let table = &mut table.borrow_mut().inner;
-        // ObjCell are updated in place when traced, so casting to ObjCell will
-        // allow all the objects to be updated.
-        let table = unsafe {
-            std::mem::transmute::<&mut IndexMap<Object, Object>, &mut IndexMap<ObjCell, ObjCell>>(
-                table,
-            )
-        };
-        table.rehash_keys(|key, val| {
-            key.trace(state);
-            val.trace(state);
-        });
+        table
+    }
+}
+
+/// Trace all entries of `table` and rehash them, since the keys may have moved.
+fn trace_entries(table: &mut HashTable, state: &mut GcState) {
+    // ObjCell are updated in place when traced, so casting to ObjCell will
+    // allow all the objects to be updated.
+    let table = unsafe {
+        std::mem::transmute::<&mut IndexMap<Object, Object>, &mut IndexMap<ObjCell, ObjCell>>(table)
+    };
+    table.rehash_keys(|key, val| {
+        key.trace(state);
+        val.trace(state);
+    });
+}
+
+impl Trace for HashTableCore<'_> {
+    fn trace(&self, state: &mut GcState) {
+        let table = &mut self.local().borrow_mut();
+        if table.weakness == Weakness::None {
+            trace_entries(&mut table.inner, state);
+        } else {
+            // SAFETY: Hash tables are only ever allocated as
+            // `HashTableCore<'static>`, and this is called on the copy in
+            // to-space or the old generation.
+            let this = unsafe { &*std::ptr::from_ref(self).cast::<HashTableCore<'static>>() };
+            state.push_ephemeron(this);
+        }
+    }
+}
+
+impl Ephemeron for HashTableCore<'_> {
+    fn trace_reachable(&self, state: &mut GcState) -> bool {
+        let table = self.local().borrow();
+        let mut found = false;
+        for (key, val) in &table.inner {
+            let (key_live, val_live) = (key.is_live(), val.is_live());
+            if table.weakness.keeps(key_live, val_live) && !(key_live && val_live) {
+                found = true;
+                // The entries are only updated once the table is swept, since
+                // liveness is checked on the old location of the objects.
+                for obj in [key, val] {
+                    if let Some((new, true)) = obj.move_value(&state.to_space) {
+                        state.push(new);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn sweep(&self, state: &mut GcState) {
+        let table = &mut self.local().borrow_mut();
+        let weakness = table.weakness;
+        table.inner.retain(|key, val| weakness.keeps(key.is_live(), val.is_live()));
+        trace_entries(&mut table.inner, state);
     }
 }
 
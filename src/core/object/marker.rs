@@ -0,0 +1,218 @@
+//! Markers are positions in a buffer that move as text is inserted and deleted
+//! around them. The position itself is shared with the buffer the marker
+//! points into, so that the buffer can update it without having to find the
+//! marker object in the GC heap (which may move it at any time).
+use super::{CloneIn, Gc, IntoObject, LispBuffer, WithLifetime};
+use crate::core::gc::{Block, GcHeap, GcState, Trace};
+use crate::derive_GcMoveable;
+use anyhow::{Result, bail};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The part of a marker that is shared with its buffer. Positions are 0-based
+/// character offsets into the buffer text.
+#[derive(Debug)]
+pub(crate) struct MarkerPos {
+    position: AtomicUsize,
+    insertion_type: AtomicBool,
+}
+
+impl MarkerPos {
+    const NOWHERE: usize = usize::MAX;
+
+    fn new(position: usize, insertion_type: bool) -> Self {
+        Self {
+            position: AtomicUsize::new(position),
+            insertion_type: AtomicBool::new(insertion_type),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<usize> {
+        match self.position.load(Ordering::Relaxed) {
+            Self::NOWHERE => None,
+            pos => Some(pos),
+        }
+    }
+
+    pub(crate) fn set(&self, position: usize) {
+        self.position.store(position, Ordering::Relaxed);
+    }
+
+    /// Make the marker point nowhere. Called when the buffer is killed.
+    pub(crate) fn detach(&self) {
+        self.set(Self::NOWHERE);
+    }
+
+    /// If true the marker advances when text is inserted at its position.
+    pub(crate) fn insertion_type(&self) -> bool {
+        self.insertion_type.load(Ordering::Relaxed)
+    }
+
+    /// Update the position for an insertion of `len` characters at `pos`.
+    pub(crate) fn adjust_for_insert(&self, pos: usize, len: usize) {
+        if let Some(current) = self.get()
+            && (current > pos || (current == pos && self.insertion_type()))
+        {
+            self.set(current + len);
+        }
+    }
+
+    /// Update the position for a deletion of the characters between `beg` and
+    /// `end`.
+    pub(crate) fn adjust_for_delete(&self, beg: usize, end: usize) {
+        if let Some(current) = self.get() {
+            if current >= end {
+                self.set(current - (end - beg));
+            } else if current > beg {
+                self.set(beg);
+            }
+        }
+    }
+}
+
+/// A marker that has not been allocated yet.
+#[derive(Debug)]
+pub(crate) struct MarkerInner {
+    buffer: Cell<Option<&'static LispBuffer>>,
+    pos: RefCell<Arc<MarkerPos>>,
+}
+
+impl MarkerInner {
+    pub(crate) fn new() -> Self {
+        Self {
+            buffer: Cell::new(None),
+            pos: RefCell::new(Arc::new(MarkerPos::new(MarkerPos::NOWHERE, false))),
+        }
+    }
+}
+
+impl Default for MarkerInner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for MarkerInner {
+    fn eq(&self, other: &Self) -> bool {
+        let buffer_eq = match (self.buffer.get(), other.buffer.get()) {
+            (Some(a), Some(b)) => std::ptr::eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        buffer_eq && self.pos.borrow().get() == other.pos.borrow().get()
+    }
+}
+
+impl Eq for MarkerInner {}
+
+/// A lisp marker object.
+#[derive(PartialEq, Eq)]
+pub(crate) struct LispMarker(GcHeap<MarkerInner>);
+
+derive_GcMoveable!(LispMarker);
+
+impl LispMarker {
+    pub(in crate::core) fn new(marker: MarkerInner, constant: bool) -> Self {
+        Self(GcHeap::new(marker, constant))
+    }
+
+    pub(in crate::core) fn forwarding_ptr(&self) -> Option<NonNull<u8>> {
+        use crate::core::gc::AllocState as A;
+        match self.0.allocation_state() {
+            A::Forwarded(f) => Some(f),
+            A::Global => panic!("global marker allocation found in local heap"),
+            A::Old => Some(NonNull::from(self).cast()),
+            A::Unmoved => None,
+        }
+    }
+
+    /// The buffer this marker points into, or `None` if it points nowhere.
+    pub(crate) fn buffer(&self) -> Option<&LispBuffer> {
+        self.position()?;
+        self.0.buffer.get()
+    }
+
+    /// The 0-based character position of the marker, or `None` if it points
+    /// nowhere.
+    pub(crate) fn position(&self) -> Option<usize> {
+        self.0.buffer.get()?;
+        self.0.pos.borrow().get()
+    }
+
+    /// The 1-based buffer position of the marker, which is what lisp sees.
+    pub(crate) fn lisp_position(&self) -> Result<usize> {
+        match self.position() {
+            Some(pos) => Ok(pos + 1),
+            None => bail!("Marker does not point anywhere"),
+        }
+    }
+
+    pub(crate) fn insertion_type(&self) -> bool {
+        self.0.pos.borrow().insertion_type()
+    }
+
+    pub(crate) fn set_insertion_type(&self, advance: bool) {
+        self.0.pos.borrow().insertion_type.store(advance, Ordering::Relaxed);
+    }
+
+    /// Point the marker at `position` in `buffer`. If the marker was not
+    /// already in `buffer`, returns the new shared position that the buffer
+    /// needs to register so the marker gets updated on edits.
+    pub(crate) fn attach(&self, buffer: &LispBuffer, position: usize) -> Option<Arc<MarkerPos>> {
+        if self.buffer().is_some_and(|b| b == buffer) {
+            self.0.pos.borrow().set(position);
+            return None;
+        }
+        // Replacing the shared position drops it, which unregisters the marker
+        // from the old buffer.
+        let new = Arc::new(MarkerPos::new(position, self.insertion_type()));
+        *self.0.pos.borrow_mut() = new.clone();
+        self.0.buffer.set(Some(unsafe { buffer.with_lifetime() }));
+        Some(new)
+    }
+
+    /// Make the marker point nowhere.
+    pub(crate) fn detach(&self) {
+        let insertion_type = self.insertion_type();
+        *self.0.pos.borrow_mut() = Arc::new(MarkerPos::new(MarkerPos::NOWHERE, insertion_type));
+        self.0.buffer.set(None);
+    }
+}
+
+impl Trace for LispMarker {
+    // The buffer is allocated in the global block, so there is nothing to trace
+    fn trace(&self, _: &mut GcState) {}
+}
+
+impl<'new> CloneIn<'new, &'new Self> for LispMarker {
+    fn clone_in<const C: bool>(&self, bk: &'new Block<C>) -> Gc<&'new Self> {
+        // The clone shares its position with the original, so it continues to
+        // be updated by edits to the buffer.
+        let marker = MarkerInner {
+            buffer: Cell::new(self.0.buffer.get()),
+            pos: RefCell::new(self.0.pos.borrow().clone()),
+        };
+        marker.into_obj(bk)
+    }
+}
+
+impl fmt::Display for LispMarker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.buffer(), self.position()) {
+            (Some(buffer), Some(pos)) => match buffer.try_name() {
+                Some(name) => write!(f, "#<marker at {} in {name}>", pos + 1),
+                None => write!(f, "#<marker at {}>", pos + 1),
+            },
+            _ => write!(f, "#<marker in no buffer>"),
+        }
+    }
+}
+
+impl fmt::Debug for LispMarker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -0,0 +1,142 @@
+//! Overlays are ranges of a buffer with their own property list. The buffer
+//! keeps track of which text each overlay covers (see
+//! [`OverlayTree`](crate::overlay::OverlayTree)) and copies the resulting
+//! positions into the overlay after every edit, so that they can be read
+//! without locking the buffer.
+use super::{CloneIn, Gc, IntoObject, LispBuffer, NIL, Object, WithLifetime};
+use crate::core::gc::{Block, GcHeap, GcMoveable, GcState, Trace};
+use crate::derive_GcMoveable;
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An overlay that has not been allocated yet.
+#[derive(Debug)]
+pub(crate) struct OverlayInner {
+    buffer: Cell<Option<&'static LispBuffer>>,
+    start: AtomicUsize,
+    end: AtomicUsize,
+    plist: Cell<Object<'static>>,
+    front_advance: bool,
+    rear_advance: bool,
+}
+
+impl OverlayInner {
+    pub(crate) fn new(front_advance: bool, rear_advance: bool) -> Self {
+        Self {
+            buffer: Cell::new(None),
+            start: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+            plist: Cell::new(NIL),
+            front_advance,
+            rear_advance,
+        }
+    }
+}
+
+/// A lisp overlay object.
+pub(crate) struct LispOverlay(GcHeap<OverlayInner>);
+
+derive_GcMoveable!(LispOverlay);
+
+impl LispOverlay {
+    pub(in crate::core) fn new(overlay: OverlayInner, constant: bool) -> Self {
+        Self(GcHeap::new(overlay, constant))
+    }
+
+    /// The buffer this overlay belongs to, or `None` if it has been deleted.
+    pub(crate) fn buffer(&self) -> Option<&LispBuffer> {
+        self.0.buffer.get()
+    }
+
+    /// The 0-based start and end positions of the overlay, or `None` if it
+    /// has been deleted.
+    pub(crate) fn bounds(&self) -> Option<(usize, usize)> {
+        self.buffer()?;
+        Some((self.0.start.load(Ordering::Relaxed), self.0.end.load(Ordering::Relaxed)))
+    }
+
+    /// Record the buffer and positions of the overlay. This is only called by
+    /// the buffer that holds the overlay.
+    pub(crate) fn set_bounds(&self, buffer: &LispBuffer, start: usize, end: usize) {
+        self.0.buffer.set(Some(unsafe { buffer.with_lifetime() }));
+        self.0.start.store(start, Ordering::Relaxed);
+        self.0.end.store(end, Ordering::Relaxed);
+    }
+
+    pub(crate) fn detach(&self) {
+        self.0.buffer.set(None);
+    }
+
+    /// If true, text inserted at the start of the overlay is excluded from it.
+    pub(crate) fn front_advance(&self) -> bool {
+        self.0.front_advance
+    }
+
+    /// If true, text inserted at the end of the overlay is included in it.
+    pub(crate) fn rear_advance(&self) -> bool {
+        self.0.rear_advance
+    }
+
+    pub(crate) fn plist(&self) -> Object<'_> {
+        unsafe { self.0.plist.get().with_lifetime() }
+    }
+
+    pub(crate) fn set_plist(&self, plist: Object) {
+        self.0.write_barrier();
+        self.0.plist.set(unsafe { plist.with_lifetime() });
+    }
+}
+
+impl PartialEq for LispOverlay {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for LispOverlay {}
+
+impl Trace for OverlayInner {
+    fn trace(&self, state: &mut GcState) {
+        let plist = self.plist.get();
+        if let Some((new, moved)) = plist.move_value(&state.to_space) {
+            self.plist.set(new);
+            if moved {
+                state.push(new);
+            }
+        }
+    }
+}
+
+impl Trace for LispOverlay {
+    fn trace(&self, state: &mut GcState) {
+        self.0.trace(state);
+    }
+}
+
+impl<'new> CloneIn<'new, &'new Self> for LispOverlay {
+    fn clone_in<const C: bool>(&self, bk: &'new Block<C>) -> Gc<&'new Self> {
+        // The buffer only knows about the original, so the clone is detached.
+        let overlay = OverlayInner::new(self.front_advance(), self.rear_advance());
+        overlay.plist.set(unsafe { self.plist().clone_in(bk).with_lifetime() });
+        overlay.into_obj(bk)
+    }
+}
+
+impl fmt::Display for LispOverlay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.buffer(), self.bounds()) {
+            (Some(buffer), Some((start, end))) => match buffer.try_name() {
+                Some(name) => write!(f, "#<overlay from {} to {} in {name}>", start + 1, end + 1),
+                None => write!(f, "#<overlay from {} to {}>", start + 1, end + 1),
+            },
+            _ => write!(f, "#<overlay in no buffer>"),
+        }
+    }
+}
+
+impl fmt::Debug for LispOverlay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -1,6 +1,9 @@
-use super::{CloneIn, IntoObject};
+use super::{CloneIn, IntoObject, char_to_raw_byte};
 use crate::core::gc::{AllocState, Block, GcHeap, GcMoveable, GcState, Trace};
-use std::cell::Cell;
+use crate::intervals::IntervalTree;
+use anyhow::{Result, bail};
+use rune_core::hashmap::HashSet;
+use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::fmt::{Debug, Display};
 use std::ops::Deref;
 use std::ptr::NonNull;
@@ -15,7 +18,14 @@ pub(crate) struct LispString(GcHeap<LispStringInner>);
 //
 // Case 2: The new char is a different size:
 // Need to allocate a new string and update the cell to point to that.
-struct LispStringInner(Cell<*mut str>);
+//
+// The text properties are keyed by 0-based character index. They live outside
+// of the GC heap, so a string that has them is registered with its block and the
+// tree is freed when the string is collected.
+struct LispStringInner {
+    text: Cell<*mut str>,
+    props: RefCell<Option<Box<IntervalTree<'static>>>>,
+}
 
 impl GcMoveable for LispString {
     type Value = std::ptr::NonNull<LispString>;
@@ -23,13 +33,15 @@ impl GcMoveable for LispString {
     fn move_value(&self, to_space: &bumpalo::Bump) -> Option<(Self::Value, bool)> {
         match self.0.allocation_state() {
             AllocState::Forwarded(f) => Some((f.cast::<Self>(), false)),
-            AllocState::Global => None,
+            AllocState::Global | AllocState::Old => None,
             AllocState::Unmoved => {
                 let ptr = {
                     let mut new = GcString::from_str_in(self, to_space);
                     let lisp_str = unsafe { LispString::new(new.as_mut_str(), false) };
                     std::mem::forget(new);
+                    *lisp_str.0.props.borrow_mut() = self.0.props.take();
                     let alloc = to_space.alloc(lisp_str);
+                    alloc.0.promote();
                     NonNull::from(alloc)
                 };
                 self.0.forward(ptr.cast::<u8>());
@@ -37,10 +49,24 @@ impl GcMoveable for LispString {
             }
         }
     }
+
+    fn is_live(&self) -> bool {
+        self.0.is_live()
+    }
+}
+
+impl Trace for LispStringInner {
+    fn trace(&self, state: &mut GcState) {
+        if let Some(props) = &*self.props.borrow() {
+            props.trace(state);
+        }
+    }
 }
 
 impl Trace for LispString {
-    fn trace(&self, _state: &mut GcState) {}
+    fn trace(&self, state: &mut GcState) {
+        self.0.trace(state);
+    }
 }
 
 impl Debug for LispString {
@@ -56,13 +82,38 @@ impl Display for LispString {
             match c {
                 '\\' => output.push_str("\\\\"),
                 '"' => output.push_str("\\\""),
-                c => output.push(c),
+                c => match char_to_raw_byte(c) {
+                    Some(byte) => output.push_str(&format!("\\{byte:03o}")),
+                    None => output.push(c),
+                },
             }
         }
         Display::fmt(&output, f)
     }
 }
 
+impl LispString {
+    /// Print the string, using the `#("text" START END PLIST ...)` syntax if it
+    /// has text properties.
+    pub(crate) fn display_walk(
+        &self,
+        f: &mut std::fmt::Formatter,
+        seen: &mut HashSet<*const u8>,
+    ) -> std::fmt::Result {
+        let Some(props) = self.textprops() else { return write!(f, "\"{self}\"") };
+        let intervals: Vec<_> = props.iter(0, self.len()).filter(|(_, x)| !x.is_nil()).collect();
+        if intervals.is_empty() {
+            return write!(f, "\"{self}\"");
+        }
+        write!(f, "#(\"{self}\"")?;
+        for (range, plist) in intervals {
+            write!(f, " {} {} ", range.start, range.end)?;
+            plist.untag().display_walk(f, seen)?;
+        }
+        write!(f, ")")
+    }
+}
+
 impl PartialEq for LispString {
     fn eq(&self, other: &Self) -> bool {
         self.inner() == other.inner()
@@ -87,11 +138,54 @@ impl Deref for LispString {
 
 impl LispString {
     pub(in crate::core) unsafe fn new(string: *mut str, constant: bool) -> Self {
-        Self(GcHeap::new(LispStringInner(Cell::new(string)), constant))
+        let inner = LispStringInner { text: Cell::new(string), props: RefCell::new(None) };
+        Self(GcHeap::new(inner, constant))
     }
 
     pub(crate) fn inner(&self) -> &str {
-        unsafe { &*self.0.0.get() }
+        unsafe { &*self.0.text.get() }
+    }
+
+    pub(in crate::core) fn forwarding_ptr(&self) -> Option<NonNull<u8>> {
+        match self.0.allocation_state() {
+            AllocState::Forwarded(f) => Some(f),
+            AllocState::Global => panic!("global string allocation found in local heap"),
+            AllocState::Old => Some(NonNull::from(self).cast()),
+            AllocState::Unmoved => None,
+        }
+    }
+
+    /// Free the text properties of a string that is no longer reachable.
+    pub(in crate::core) fn drop_props(&self) {
+        self.0.props.take();
+    }
+
+    /// The text properties of the string, if it has any.
+    pub(crate) fn textprops(&self) -> Option<Ref<'_, IntervalTree<'static>>> {
+        Ref::filter_map(self.0.props.borrow(), |x| x.as_deref()).ok()
+    }
+
+    /// The text properties of the string, creating an empty tree if it has
+    /// none. The lifetime of the objects in the tree is tied to `block`.
+    pub(crate) fn textprops_mut<'ob, const C: bool>(
+        &self,
+        block: &'ob Block<C>,
+    ) -> Result<RefMut<'_, IntervalTree<'ob>>> {
+        if matches!(self.0.allocation_state(), AllocState::Global) {
+            bail!("Attempt to modify the text properties of a constant string");
+        }
+        self.0.write_barrier();
+        let mut props = self.0.props.borrow_mut();
+        if props.is_none() {
+            *props = Some(Box::new(IntervalTree::new()));
+            block.lisp_strings.borrow_mut().push(self);
+        }
+        let props = RefMut::map(props, |x| &mut **x.as_mut().unwrap());
+        Ok(unsafe {
+            std::mem::transmute::<RefMut<'_, IntervalTree<'static>>, RefMut<'_, IntervalTree<'ob>>>(
+                props,
+            )
+        })
     }
 }
 
@@ -101,7 +195,7 @@ impl LispString {
     }
 
     pub(crate) fn clear(&self) {
-        let inner_mut_str = unsafe { &mut *self.0.0.get() };
+        let inner_mut_str = unsafe { &mut *self.0.text.get() };
         for byte in unsafe { inner_mut_str.as_bytes_mut().iter_mut() } {
             *byte = b'\0';
         }
@@ -149,7 +243,7 @@ impl GcMoveable for ByteString {
     fn move_value(&self, to_space: &bumpalo::Bump) -> Option<(Self::Value, bool)> {
         match self.0.allocation_state() {
             AllocState::Forwarded(f) => Some((f.cast::<Self>(), false)),
-            AllocState::Global => None,
+            AllocState::Global | AllocState::Old => None,
             AllocState::Unmoved => {
                 let ptr = {
                     let mut new = ByteVec::new_in(to_space);
@@ -157,6 +251,7 @@ impl GcMoveable for ByteString {
                     let byte_string = ByteString::new(new.as_mut_slice(), false);
                     std::mem::forget(new);
                     let alloc = to_space.alloc(byte_string);
+                    alloc.0.promote();
                     NonNull::from(alloc)
                 };
                 self.0.forward(ptr.cast::<u8>());
@@ -164,6 +259,10 @@ impl GcMoveable for ByteString {
             }
         }
     }
+
+    fn is_live(&self) -> bool {
+        self.0.is_live()
+    }
 }
 
 impl PartialEq for ByteString {
@@ -23,6 +23,9 @@ struct SymbolCellData {
     // https://github.com/crossbeam-rs/crossbeam/issues/748
     func: Option<AtomicPtr<u8>>,
     special: AtomicBool,
+    // Set by `make-variable-buffer-local`. Setting the variable will create a
+    // buffer-local value in the current buffer.
+    local_if_set: AtomicBool,
 }
 
 #[derive(Debug)]
@@ -97,6 +100,14 @@ impl<'a> Symbol<'a> {
     pub(crate) fn is_special(self) -> bool {
         self.0.special.load(Ordering::Acquire)
     }
+
+    pub(crate) fn make_local_if_set(self) {
+        self.0.local_if_set.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn is_local_if_set(self) -> bool {
+        self.0.local_if_set.load(Ordering::Acquire)
+    }
 }
 
 unsafe impl Send for Symbol<'_> {}
@@ -112,6 +123,7 @@ impl<'new> WithLifetime<'new> for Symbol<'_> {
 
 impl TracePtr for Symbol<'_> {
     fn trace_ptr(&self, state: &mut GcState) {
+        state.stats.symbols += 1;
         self.get().trace(state);
     }
 }
@@ -130,6 +142,10 @@ impl<'a> GcMoveable for Symbol<'a> {
             (symbol, moved)
         })
     }
+
+    fn is_live(&self) -> bool {
+        self.get().0.is_live()
+    }
 }
 
 impl Trace for SymbolCell {
@@ -217,8 +233,7 @@ impl SymbolCell {
     }
 
     fn new_normal(name: &'static str, block: &Block<true>) -> Self {
-        // We have to do this workaround because starts_with is not const
-        if name.as_bytes()[0] == b':' {
+        if name.starts_with(':') {
             Self::new_const(name, block)
         } else {
             Self(GcHeap::new(
@@ -226,6 +241,7 @@ impl SymbolCell {
                     name: SymbolName::Interned(name),
                     func: Some(Self::EMTPTY),
                     special: AtomicBool::new(false),
+                    local_if_set: AtomicBool::new(false),
                 },
                 true,
             ))
@@ -241,6 +257,7 @@ impl SymbolCell {
                 name: SymbolName::Interned(name),
                 func: Some(Self::EMTPTY),
                 special: AtomicBool::new(false),
+                local_if_set: AtomicBool::new(false),
             }))
         }
     }
@@ -250,6 +267,7 @@ impl SymbolCell {
             name: SymbolName::Interned(name),
             func: Some(Self::EMTPTY),
             special: AtomicBool::new(true),
+            local_if_set: AtomicBool::new(false),
         }))
     }
 
@@ -259,6 +277,7 @@ impl SymbolCell {
                 name: SymbolName::Interned(name),
                 func: None,
                 special: AtomicBool::new(true),
+                local_if_set: AtomicBool::new(false),
             },
             true,
         ))
@@ -269,6 +288,7 @@ impl SymbolCell {
             name: SymbolName::Interned(name),
             func: None,
             special: AtomicBool::new(true),
+            local_if_set: AtomicBool::new(false),
         }))
     }
 
@@ -281,6 +301,7 @@ impl SymbolCell {
                 name: SymbolName::Uninterned(Cell::new(name)),
                 func: Some(Self::EMTPTY),
                 special: AtomicBool::new(false),
+                local_if_set: AtomicBool::new(false),
             },
             C,
         ))
@@ -4,7 +4,8 @@ use super::{
         error::{Type, TypeError},
         gc::Block,
     },
-    ByteFnPrototype, ByteString, CharTableInner, GcString, LispBigInt, LispBuffer,
+    ByteFnPrototype, ByteString, CharTableInner, GcString, LispBigInt, LispBuffer, LispMarker,
+    LispOverlay, MarkerInner, OverlayInner,
 };
 use super::{
     ByteFn, CharTable, HashTable, LispFloat, LispHashTable, LispString, LispVec, Record,
@@ -299,6 +300,8 @@ object_trait_impls!(LispHashTable);
 object_trait_impls!(LispBuffer);
 object_trait_impls!(CharTable);
 object_trait_impls!(LispBigInt);
+object_trait_impls!(LispMarker);
+object_trait_impls!(LispOverlay);
 
 /// Trait for types that can be managed by the GC. This trait is implemented for
 /// as many types as possible, even for types that are already Gc managed, Like
@@ -346,7 +349,7 @@ impl IntoObject for f64 {
     type Out<'ob> = &'ob LispFloat;
 
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
-        let ptr = block.objects.alloc(LispFloat::new(self, C));
+        let ptr = block.alloc(LispFloat::new(self, C));
         unsafe { Self::Out::tag_ptr(ptr) }
     }
 }
@@ -375,7 +378,7 @@ impl IntoObject for Cons {
     type Out<'ob> = &'ob Cons;
 
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
-        let ptr = block.objects.alloc(self);
+        let ptr = block.alloc(self);
         if C {
             ptr.mark_const();
         }
@@ -387,7 +390,7 @@ impl IntoObject for ByteFnPrototype {
     type Out<'ob> = &'ob ByteFn;
 
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
-        let ptr = block.objects.alloc(ByteFn::new(self, C));
+        let ptr = block.alloc(ByteFn::new(self, C));
         unsafe { Self::Out::tag_ptr(ptr) }
     }
 }
@@ -396,7 +399,7 @@ impl IntoObject for SymbolCell {
     type Out<'ob> = Symbol<'ob>;
 
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
-        let ptr = block.objects.alloc(self);
+        let ptr = block.alloc(self);
         let sym = unsafe { Symbol::from_ptr(ptr) };
         unsafe { Self::Out::tag_ptr(sym.get_ptr()) }
     }
@@ -409,7 +412,7 @@ impl IntoObject for String {
         unsafe {
             let mut this = self;
             let ptr = this.as_mut_str();
-            let ptr = block.objects.alloc(LispString::new(ptr, C));
+            let ptr = block.alloc(LispString::new(ptr, C));
             block.drop_stack.borrow_mut().push(DropStackElem::String(this));
             Self::Out::tag_ptr(ptr)
         }
@@ -422,7 +425,7 @@ impl IntoObject for GcString<'_> {
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
         unsafe {
             let mut this = self;
-            let ptr = block.objects.alloc(LispString::new(this.as_mut_str(), C));
+            let ptr = block.alloc(LispString::new(this.as_mut_str(), C));
             std::mem::forget(this);
             Self::Out::tag_ptr(ptr)
         }
@@ -443,7 +446,7 @@ impl IntoObject for Vec<u8> {
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
         let mut this = self;
         let slice = this.as_mut_slice();
-        let ptr = block.objects.alloc(ByteString::new(slice, C));
+        let ptr = block.alloc(ByteString::new(slice, C));
         block.drop_stack.borrow_mut().push(DropStackElem::ByteString(this));
         unsafe { <&ByteString>::tag_ptr(ptr) }
     }
@@ -456,7 +459,7 @@ impl IntoObject for Vec<Object<'_>> {
         unsafe {
             // having the reference implicity cast a ptr triggers UB
             let ptr = self.as_mut_slice() as *mut [Object];
-            let ptr = block.objects.alloc(LispVec::new(ptr, C));
+            let ptr = block.alloc(LispVec::new(ptr, C));
             block.drop_stack.borrow_mut().push(DropStackElem::Vec(self.with_lifetime()));
             <&LispVec>::tag_ptr(ptr)
         }
@@ -470,7 +473,7 @@ impl IntoObject for GcVec<'_, Object<'_>> {
         unsafe {
             // having the reference implicity cast a ptr triggers UB
             let ptr = self.into_bump_slice_mut() as *mut [Object];
-            let ptr = block.objects.alloc(LispVec::new(ptr, C));
+            let ptr = block.alloc(LispVec::new(ptr, C));
             <&LispVec>::tag_ptr(ptr)
         }
     }
@@ -493,7 +496,7 @@ impl IntoObject for RecordBuilder<'_> {
         unsafe {
             // record is the same layout as lispvec, just a different newtype wrapper
             let ptr = self.0.into_bump_slice_mut() as *mut [Object];
-            let ptr = block.objects.alloc(LispVec::new(ptr, C));
+            let ptr = block.alloc(LispVec::new(ptr, C));
             <&Record>::tag_ptr(ptr)
         }
     }
@@ -504,7 +507,7 @@ impl IntoObject for HashTable<'_> {
 
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
         unsafe {
-            let ptr = block.objects.alloc(LispHashTable::new(self, C));
+            let ptr = block.alloc(LispHashTable::new(self, C));
             block.lisp_hashtables.borrow_mut().push(ptr);
             <&LispHashTable>::tag_ptr(ptr)
         }
@@ -516,7 +519,7 @@ impl IntoObject for CharTableInner<'_> {
 
     fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
         unsafe {
-            let ptr = block.objects.alloc(CharTable::new(self, C));
+            let ptr = block.alloc(CharTable::new(self, C));
             <Self::Out<'_>>::tag_ptr(ptr)
         }
     }
@@ -527,13 +530,38 @@ impl IntoObject for BigInt {
 
     fn into_obj<const C: bool>(self, block: &Block<C>) -> super::Gc<Self::Out<'_>> {
         unsafe {
-            let ptr = block.objects.alloc(LispBigInt::new(self, C));
+            let ptr = block.alloc(LispBigInt::new(self, C));
             // block.lisp_integers.borrow_mut().push(ptr);
             <&LispBigInt>::tag_ptr(ptr)
         }
     }
 }
 
+impl IntoObject for MarkerInner {
+    type Out<'ob> = &'ob LispMarker;
+
+    fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
+        unsafe {
+            let ptr = block.alloc(LispMarker::new(self, C));
+            if !C {
+                block.lisp_markers.borrow_mut().push(ptr);
+            }
+            <&LispMarker>::tag_ptr(ptr)
+        }
+    }
+}
+
+impl IntoObject for OverlayInner {
+    type Out<'ob> = &'ob LispOverlay;
+
+    fn into_obj<const C: bool>(self, block: &Block<C>) -> Gc<Self::Out<'_>> {
+        unsafe {
+            let ptr = block.alloc(LispOverlay::new(self, C));
+            <&LispOverlay>::tag_ptr(ptr)
+        }
+    }
+}
+
 mod private {
     use super::{Gc, WithLifetime};
 
@@ -554,6 +582,8 @@ mod private {
         Buffer,
         CharTable,
         BigInt,
+        Marker,
+        Overlay,
     }
 
     /// Trait for tagged pointers. Anything that can be stored and passed around
@@ -662,6 +692,8 @@ impl<'a> TaggedPtr for ObjectType<'a> {
                 Tag::Buffer => ObjectType::Buffer(<&LispBuffer>::from_obj_ptr(ptr)),
                 Tag::CharTable => ObjectType::CharTable(<&CharTable>::from_obj_ptr(ptr)),
                 Tag::BigInt => ObjectType::BigInt(<&LispBigInt>::from_obj_ptr(ptr)),
+                Tag::Marker => ObjectType::Marker(<&LispMarker>::from_obj_ptr(ptr)),
+                Tag::Overlay => ObjectType::Overlay(<&LispOverlay>::from_obj_ptr(ptr)),
             }
         }
     }
@@ -682,6 +714,8 @@ impl<'a> TaggedPtr for ObjectType<'a> {
             ObjectType::Buffer(x) => TaggedPtr::tag(x).into(),
             ObjectType::CharTable(x) => TaggedPtr::tag(x).into(),
             ObjectType::BigInt(x) => TaggedPtr::tag(x).into(),
+            ObjectType::Marker(x) => TaggedPtr::tag(x).into(),
+            ObjectType::Overlay(x) => TaggedPtr::tag(x).into(),
         }
     }
 }
@@ -787,14 +821,63 @@ impl TaggedPtr for i64 {
     }
 }
 
+/// Emacs represents the raw bytes 0x80..=0xFF in multibyte text as the
+/// characters 0x3FFF80..=0x3FFFFF. Those are outside of Unicode, so a `char`
+/// stores them as the last 128 code points of plane 16 instead.
+const RAW_BYTE_CHAR: u32 = 0x10_FF00;
+/// The Emacs character code that raw bytes are offset from.
+const RAW_BYTE_CODE: i64 = 0x3F_FF00;
+
+/// Return the character that represents `byte` in multibyte text. ASCII
+/// bytes are their own characters.
+pub(crate) fn raw_byte_to_char(byte: u8) -> char {
+    if byte.is_ascii() {
+        byte as char
+    } else {
+        char::from_u32(RAW_BYTE_CHAR + u32::from(byte)).unwrap()
+    }
+}
+
+/// If `chr` represents a raw byte, return that byte.
+pub(crate) fn char_to_raw_byte(chr: char) -> Option<u8> {
+    let code = u32::from(chr).checked_sub(RAW_BYTE_CHAR)?;
+    u8::try_from(code).ok().filter(|x| !x.is_ascii())
+}
+
+/// Append `bytes` decoded as UTF-8 to `text`. Invalid bytes become raw byte
+/// characters. Valid sequences that encode one of the code points reserved
+/// for raw bytes are also kept as raw bytes, so that encoding the text again
+/// gives back the original bytes.
+pub(crate) fn push_utf8_raw(text: &mut String, bytes: &[u8]) {
+    for chunk in bytes.utf8_chunks() {
+        for chr in chunk.valid().chars() {
+            if char_to_raw_byte(chr).is_some() {
+                let mut buf = [0; 4];
+                text.extend(chr.encode_utf8(&mut buf).bytes().map(raw_byte_to_char));
+            } else {
+                text.push(chr);
+            }
+        }
+        text.extend(chunk.invalid().iter().map(|&x| raw_byte_to_char(x)));
+    }
+}
+
+/// Return the Emacs character code of `chr`.
+pub(crate) fn char_to_int(chr: char) -> i64 {
+    match char_to_raw_byte(chr) {
+        Some(byte) => RAW_BYTE_CODE + i64::from(byte),
+        None => i64::from(u32::from(chr)),
+    }
+}
+
 pub(crate) fn int_to_char(int: i64) -> Result<char, TypeError> {
-    let err = TypeError::new(Type::Char, TagType::tag(int));
-    match u32::try_from(int) {
-        Ok(x) => match char::from_u32(x) {
-            Some(c) => Ok(c),
-            None => Err(err),
-        },
-        Err(_) => Err(err),
+    if (RAW_BYTE_CODE + 0x80..=RAW_BYTE_CODE + 0xFF).contains(&int) {
+        return Ok(raw_byte_to_char((int - RAW_BYTE_CODE) as u8));
+    }
+    match u32::try_from(int).ok().and_then(char::from_u32) {
+        // The code points used for raw bytes can't be used as themselves
+        Some(chr) if char_to_raw_byte(chr).is_none() => Ok(chr),
+        _ => Err(TypeError::new(Type::Char, TagType::tag(int))),
     }
 }
 
@@ -961,8 +1044,35 @@ impl TaggedPtr for &LispBigInt {
     }
 }
 
+impl TaggedPtr for &LispMarker {
+    type Ptr = LispMarker;
+    const TAG: Tag = Tag::Marker;
+
+    unsafe fn from_obj_ptr(ptr: *const u8) -> Self {
+        &*ptr.cast::<Self::Ptr>()
+    }
+
+    fn get_ptr(self) -> *const Self::Ptr {
+        self as *const Self::Ptr
+    }
+}
+
+impl TaggedPtr for &LispOverlay {
+    type Ptr = LispOverlay;
+    const TAG: Tag = Tag::Overlay;
+
+    unsafe fn from_obj_ptr(ptr: *const u8) -> Self {
+        &*ptr.cast::<Self::Ptr>()
+    }
+
+    fn get_ptr(self) -> *const Self::Ptr {
+        self as *const Self::Ptr
+    }
+}
+
 impl<T> TracePtr for Gc<T> {
     fn trace_ptr(&self, state: &mut GcState) {
+        state.stats.count(self.as_obj());
         match self.as_obj().untag() {
             ObjectType::Int(_) | ObjectType::SubrFn(_) => {}
             ObjectType::Float(x) => x.trace(state),
@@ -977,6 +1087,8 @@ impl<T> TracePtr for Gc<T> {
             ObjectType::Buffer(x) => x.trace(state),
             ObjectType::CharTable(x) => x.trace(state),
             ObjectType::BigInt(x) => x.trace(state),
+            ObjectType::Marker(x) => x.trace(state),
+            ObjectType::Overlay(x) => x.trace(state),
         }
     }
 }
@@ -1100,6 +1212,8 @@ pub(crate) enum ObjectType<'ob> {
     Buffer(&'static LispBuffer) = Tag::Buffer as u8,
     CharTable(&'static CharTable) = Tag::CharTable as u8,
     BigInt(&'ob LispBigInt) = Tag::BigInt as u8,
+    Marker(&'ob LispMarker) = Tag::Marker as u8,
+    Overlay(&'ob LispOverlay) = Tag::Overlay as u8,
 }
 
 /// The Object defintion that contains all other possible lisp objects. This
@@ -1122,7 +1236,9 @@ cast_gc!(ObjectType<'ob> => NumberType<'ob>,
          &'ob SubrFn,
          &'ob LispBuffer,
          &'ob CharTable,
-         &'ob LispBigInt
+         &'ob LispBigInt,
+         &'ob LispMarker,
+         &'ob LispOverlay
 );
 
 impl ObjectType<'_> {
@@ -1144,6 +1260,8 @@ impl ObjectType<'_> {
             ObjectType::Buffer(_) => Type::Buffer,
             ObjectType::CharTable(_) => Type::CharTable,
             ObjectType::BigInt(_) => Type::BigInt,
+            ObjectType::Marker(_) => Type::Marker,
+            ObjectType::Overlay(_) => Type::Overlay,
         }
     }
 }
@@ -1197,7 +1315,7 @@ impl TagType for u32 {
 impl TagType for char {
     type Out = i64;
     fn tag(self) -> Gc<Self::Out> {
-        TagType::tag(i64::from(self as u32))
+        TagType::tag(char_to_int(self))
     }
 }
 
@@ -1407,6 +1525,28 @@ impl<'ob> TryFrom<Object<'ob>> for Gc<&'ob LispBigInt> {
     }
 }
 
+impl<'ob> TryFrom<Object<'ob>> for Gc<&'ob LispMarker> {
+    type Error = TypeError;
+
+    fn try_from(value: Object<'ob>) -> Result<Self, Self::Error> {
+        match value.get_tag() {
+            Tag::Marker => unsafe { Ok(cast_gc(value)) },
+            _ => Err(TypeError::new(Type::Marker, value)),
+        }
+    }
+}
+
+impl<'ob> TryFrom<Object<'ob>> for Gc<&'ob LispOverlay> {
+    type Error = TypeError;
+
+    fn try_from(value: Object<'ob>) -> Result<Self, Self::Error> {
+        match value.get_tag() {
+            Tag::Overlay => unsafe { Ok(cast_gc(value)) },
+            _ => Err(TypeError::new(Type::Overlay, value)),
+        }
+    }
+}
+
 impl<'ob> std::ops::Deref for Gc<&'ob Cons> {
     type Target = Cons;
 
@@ -1444,6 +1584,8 @@ where
             ObjectType::Buffer(x) => x.clone_in(bk).into(),
             ObjectType::CharTable(x) => x.clone_in(bk).into(),
             ObjectType::BigInt(x) => x.clone_in(bk).into(),
+            ObjectType::Marker(x) => x.clone_in(bk).into(),
+            ObjectType::Overlay(x) => x.clone_in(bk).into(),
         };
         let Ok(x) = Gc::<U>::try_from(obj) else { unreachable!() };
         x
@@ -1460,6 +1602,10 @@ where
     fn move_value(&self, to_space: &bumpalo::Bump) -> Option<(Self::Value, bool)> {
         self.untag().move_value(to_space).map(|(x, moved)| (x.tag(), moved))
     }
+
+    fn is_live(&self) -> bool {
+        self.untag().is_live()
+    }
 }
 
 impl GcMoveable for Object<'_> {
@@ -1485,11 +1631,33 @@ impl GcMoveable for Object<'_> {
             }
             ObjectType::CharTable(x) => cast_pair(x.move_value(to_space)?),
             ObjectType::BigInt(x) => cast_pair(x.move_value(to_space)?),
+            ObjectType::Marker(x) => cast_pair(x.move_value(to_space)?),
+            ObjectType::Overlay(x) => cast_pair(x.move_value(to_space)?),
         };
 
         let tag = self.get_tag();
         unsafe { Some((Object::from_ptr(data.0, tag), data.1)) }
     }
+
+    fn is_live(&self) -> bool {
+        match self.untag() {
+            ObjectType::Int(_) | ObjectType::SubrFn(_) | ObjectType::NIL => true,
+            ObjectType::Float(x) => x.is_live(),
+            ObjectType::Cons(x) => x.is_live(),
+            ObjectType::Vec(x) => x.is_live(),
+            ObjectType::Record(x) => x.is_live(),
+            ObjectType::HashTable(x) => x.is_live(),
+            ObjectType::String(x) => x.is_live(),
+            ObjectType::ByteString(x) => x.is_live(),
+            ObjectType::ByteFn(x) => x.is_live(),
+            ObjectType::Buffer(x) => x.is_live(),
+            ObjectType::Symbol(x) => x.is_live(),
+            ObjectType::CharTable(x) => x.is_live(),
+            ObjectType::BigInt(x) => x.is_live(),
+            ObjectType::Marker(x) => x.is_live(),
+            ObjectType::Overlay(x) => x.is_live(),
+        }
+    }
 }
 
 impl GcMoveable for Function<'_> {
@@ -1673,7 +1841,7 @@ impl ObjectType<'_> {
             ObjectType::Vec(x) => x.display_walk(f, seen),
             ObjectType::Record(x) => x.display_walk(f, seen),
             ObjectType::HashTable(x) => x.display_walk(f, seen),
-            ObjectType::String(x) => write!(f, "\"{x}\""),
+            ObjectType::String(x) => x.display_walk(f, seen),
             ObjectType::ByteString(x) => write!(f, "\"{x}\""),
             ObjectType::Symbol(x) => D::fmt(x, f),
             ObjectType::ByteFn(x) => D::fmt(x, f),
@@ -1682,13 +1850,15 @@ impl ObjectType<'_> {
             ObjectType::Buffer(x) => D::fmt(x, f),
             ObjectType::CharTable(x) => D::fmt(x, f),
             ObjectType::BigInt(x) => D::fmt(x, f),
+            ObjectType::Marker(x) => D::fmt(x, f),
+            ObjectType::Overlay(x) => D::fmt(x, f),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{MAX_FIXNUM, MIN_FIXNUM, TagType};
+    use super::{MAX_FIXNUM, MIN_FIXNUM, TagType, char_to_int, int_to_char, raw_byte_to_char};
     use crate::core::gc::{Context, RootSet};
     use rune_core::macros::list;
 
@@ -1702,6 +1872,17 @@ mod test {
         assert_eq!(MIN_FIXNUM.tag().untag(), MIN_FIXNUM);
     }
 
+    #[test]
+    fn test_raw_byte_chars() {
+        let raw = raw_byte_to_char(0xAB);
+        assert_eq!(raw, '\u{10FFAB}');
+        assert_eq!(char_to_int(raw), 0x3F_FFAB);
+        assert_eq!(int_to_char(0x3F_FFAB), Ok(raw));
+        // The code points used for raw bytes are not characters themselves
+        assert!(int_to_char(0x10_FFAB).is_err());
+        assert_eq!(int_to_char(0x10_FF05), Ok('\u{10FF05}'));
+    }
+
     #[test]
     fn test_print_circle() {
         let roots = &RootSet::default();
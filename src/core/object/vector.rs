@@ -11,7 +11,7 @@ use std::{
     cell::Cell,
     fmt::{self, Write},
     ops::Deref,
-    ptr::addr_of,
+    ptr::{NonNull, addr_of},
 };
 
 struct LispVecInner {
@@ -68,6 +68,7 @@ impl LispVec {
         if self.0.is_const {
             Err(anyhow!("Attempt to mutate constant Vector"))
         } else {
+            self.0.write_barrier();
             // SAFETY: ObjCell and MutObjCell have the same representation.
             unsafe { Ok(&*(self.0.inner.get() as *const [MutObjCell])) }
         }
@@ -173,10 +174,21 @@ impl fmt::Display for Record {
 }
 
 impl Record {
+    pub(in crate::core) fn forwarding_ptr(&self) -> Option<NonNull<u8>> {
+        use crate::core::gc::AllocState as A;
+        match self.0.allocation_state() {
+            A::Forwarded(f) => Some(f),
+            A::Global => panic!("global record allocation found in local heap"),
+            A::Old => Some(NonNull::from(self).cast()),
+            A::Unmoved => None,
+        }
+    }
+
     pub(crate) fn try_mut(&self) -> Result<&[MutObjCell]> {
         if self.0.is_const {
             Err(anyhow!("Attempt to mutate constant Vector"))
         } else {
+            self.0.write_barrier();
             // SAFETY: ObjCell and MutObjCell have the same representation.
             unsafe { Ok(&*(self.0.inner.get() as *const [MutObjCell])) }
         }
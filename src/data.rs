@@ -5,10 +5,11 @@ use crate::core::{
     error::{Type, TypeError},
     gc::{Context, Rt},
     object::{
-        IntoObject, List, ListType, NIL, Number, Object, ObjectType, SubrFn, Symbol, WithLifetime,
+        Gc, IntoObject, LispBuffer, List, ListType, NIL, Number, Object, ObjectType, SubrFn,
+        Symbol, WithLifetime, char_to_int,
     },
 };
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use rune_core::{hashmap::HashSet, macros::list};
 use rune_macros::defun;
 use std::sync::LazyLock;
@@ -36,9 +37,16 @@ pub(crate) fn fset<'ob>(symbol: Symbol<'ob>, definition: Object) -> Result<Symbo
 pub(crate) fn defalias<'ob>(
     symbol: Symbol<'ob>,
     definition: Object,
-    _docstring: Option<&str>,
+    docstring: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &Context,
 ) -> Result<Symbol<'ob>> {
-    fset(symbol, definition)
+    fset(symbol, definition)?;
+    if let Some(docstring) = docstring.filter(|x| !x.is_nil()) {
+        env.set_prop(symbol, sym::FUNCTION_DOCUMENTATION, docstring);
+    }
+    crate::lread::loadhist_attach(Cons::new(sym::DEFUN, symbol, cx).into(), env, cx)?;
+    Ok(symbol)
 }
 
 #[defun]
@@ -78,20 +86,85 @@ pub(crate) fn get<'ob>(
     }
 }
 
+/// Make VARIABLE have a separate value in the current buffer.
+/// Other buffers will continue to share a common default value. The
+/// buffer-local value starts out as the same value VARIABLE previously had.
 #[defun]
-pub(crate) fn local_variable_if_set_p(_sym: Symbol) -> bool {
-    // TODO: Implement buffer locals
-    false
+pub(crate) fn make_local_variable<'ob>(
+    variable: Symbol<'ob>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<Symbol<'ob>> {
+    env.make_local_var(variable, cx)?;
+    Ok(variable)
+}
+
+/// Make VARIABLE no longer have a separate value in the current buffer.
+/// From now on the default value will apply in this buffer.
+#[defun]
+pub(crate) fn kill_local_variable<'ob>(variable: Symbol<'ob>, env: &mut Rt<Env>) -> Symbol<'ob> {
+    env.kill_local_var(variable);
+    variable
+}
+
+/// Make VARIABLE become buffer-local whenever it is set.
+/// At any time, the value for the current buffer is in effect, unless the
+/// variable has never been set in this buffer, in which case the default
+/// value is in effect. If VARIABLE is void, its default value is set to nil.
+#[defun]
+pub(crate) fn make_variable_buffer_local<'ob>(
+    variable: Symbol<'ob>,
+    env: &mut Rt<Env>,
+) -> Result<Symbol<'ob>> {
+    if variable.is_const() {
+        bail!("Symbol {variable} may not be buffer-local");
+    }
+    if env.vars.get(variable).is_none() {
+        env.set_default(variable, NIL)?;
+    }
+    variable.make_local_if_set();
+    Ok(variable)
+}
+
+/// Non-nil if VARIABLE has a local binding in buffer BUFFER.
+/// BUFFER defaults to the current buffer.
+#[defun]
+pub(crate) fn local_variable_p(
+    variable: Symbol,
+    buffer: Option<Gc<&LispBuffer>>,
+    env: &Rt<Env>,
+) -> Result<bool> {
+    match buffer {
+        Some(buffer) => env.with_buffer(buffer.untag(), |b| b.has_local_var(variable)),
+        None => Ok(env.current_buffer.has_local_var(variable)),
+    }
+}
+
+/// Non-nil if VARIABLE is local in buffer BUFFER when set there.
+/// BUFFER defaults to the current buffer. This is true if the variable is
+/// already local or was made buffer-local whenever set.
+#[defun]
+pub(crate) fn local_variable_if_set_p(
+    variable: Symbol,
+    buffer: Option<Gc<&LispBuffer>>,
+    env: &Rt<Env>,
+) -> Result<bool> {
+    Ok(variable.is_local_if_set() || local_variable_p(variable, buffer, env)?)
 }
 
+/// Return SYMBOL's default value.
+/// This is the value that is seen in buffers that do not have their own
+/// values for this variable.
 #[defun]
 pub(crate) fn default_value<'ob>(
     symbol: Symbol,
     env: &Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    // TODO: Implement buffer locals
-    symbol_value(symbol, env, cx).ok_or_else(|| anyhow!("Void variable: {symbol}"))
+    match env.vars.get(symbol) {
+        Some(value) => Ok(value.bind(cx)),
+        None => Err(anyhow!("Void variable: {symbol}")),
+    }
 }
 
 #[defun]
@@ -108,7 +181,7 @@ pub(crate) fn symbol_value<'ob>(
     env: &Rt<Env>,
     cx: &'ob Context,
 ) -> Option<Object<'ob>> {
-    env.vars.get(symbol).map(|x| x.bind(cx))
+    env.var(symbol, cx)
 }
 
 #[defun]
@@ -134,7 +207,7 @@ pub(crate) fn fmakunbound(symbol: Symbol) -> Symbol {
 
 #[defun]
 pub(crate) fn boundp(symbol: Symbol, env: &Rt<Env>) -> bool {
-    env.vars.get(symbol).is_some()
+    env.current_buffer.has_local_var(symbol) || env.vars.get(symbol).is_some()
 }
 
 #[defun]
@@ -189,9 +262,8 @@ pub(crate) fn numberp(object: Object) -> bool {
 }
 
 #[defun]
-pub(crate) fn markerp(_: Object) -> bool {
-    // TODO: implement
-    false
+pub(crate) fn markerp(object: Object) -> bool {
+    object.is_marker()
 }
 
 #[defun]
@@ -272,17 +344,17 @@ fn string_to_number<'ob>(string: &str, base: Option<i64>, cx: &'ob Context) -> N
 pub(crate) fn defvar<'ob>(
     symbol: Symbol,
     initvalue: Option<Object<'ob>>,
-    _docstring: Option<&str>,
+    docstring: Option<Object>,
     env: &mut Rt<Env>,
+    cx: &Context,
 ) -> Result<Object<'ob>> {
     let value = initvalue.unwrap_or_default();
-    set(symbol, value, env)
-}
-
-#[defun]
-pub(crate) fn make_variable_buffer_local(variable: Symbol) -> Symbol {
-    // TODO: Implement
-    variable
+    set(symbol, value, env)?;
+    if let Some(docstring) = docstring.filter(|x| !x.is_nil()) {
+        env.set_prop(symbol, sym::VARIABLE_DOCUMENTATION, docstring);
+    }
+    crate::lread::loadhist_attach(symbol.into(), env, cx)?;
+    Ok(value)
 }
 
 #[defun]
@@ -358,7 +430,7 @@ pub(crate) fn aref<'ob>(array: Object<'ob>, idx: usize, cx: &'ob Context) -> Res
             }
         },
         ObjectType::String(string) => match string.chars().nth(idx) {
-            Some(x) => Ok((i64::from(x as u32)).into()),
+            Some(x) => Ok(char_to_int(x).into()),
             None => {
                 let len = string.len();
                 Err(anyhow!("index {idx} is out of bounds. Length was {len}"))
@@ -395,6 +467,8 @@ fn type_of(object: Object) -> Object {
         ObjectType::SubrFn(_) => sym::SUBR.into(),
         ObjectType::Buffer(_) => sym::BUFFER.into(),
         ObjectType::CharTable(_) => sym::CHAR_TABLE.into(),
+        ObjectType::Marker(_) => sym::MARKER.into(),
+        ObjectType::Overlay(_) => sym::OVERLAY.into(),
         ObjectType::BigInt(_) => sym::BIG_INT.into(),
     }
 }
@@ -411,12 +485,17 @@ pub(crate) fn indirect_function<'ob>(object: Object<'ob>, cx: &'ob Context) -> O
 }
 
 #[defun]
-pub(crate) fn provide<'ob>(feature: Symbol<'ob>, _subfeatures: Option<&Cons>) -> Symbol<'ob> {
-    let mut features = FEATURES.lock().unwrap();
+pub(crate) fn provide<'ob>(
+    feature: Symbol<'ob>,
+    _subfeatures: Option<&Cons>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<Symbol<'ob>> {
     // TODO: SYMBOL - need to trace this
     let feat = unsafe { feature.with_lifetime() };
-    features.insert(feat);
-    feature
+    FEATURES.lock().unwrap().insert(feat);
+    crate::lread::loadhist_attach(Cons::new(sym::PROVIDE, feature, cx).into(), env, cx)?;
+    Ok(feature)
 }
 
 #[defun]
@@ -538,6 +617,34 @@ mod test {
     fn test_functionp() {
         assert_lisp("(functionp '(lambda nil))", "t");
     }
+
+    #[test]
+    fn test_buffer_local() {
+        assert_lisp(
+            "(progn (defvar local-test-a 1) (make-local-variable 'local-test-a) (setq local-test-a 2) (list local-test-a (default-value 'local-test-a) (local-variable-p 'local-test-a)))",
+            "(2 1 t)",
+        );
+        assert_lisp(
+            "(progn (defvar local-test-b 1) (let ((buf (get-buffer-create \"local-test-b1\"))) (set-buffer buf) (set (make-local-variable 'local-test-b) 2) (set-buffer (get-buffer-create \"local-test-b2\")) (list local-test-b (buffer-local-value 'local-test-b buf))))",
+            "(1 2)",
+        );
+        assert_lisp(
+            "(progn (defvar local-test-c 1) (set (make-local-variable 'local-test-c) 2) (kill-local-variable 'local-test-c) (list local-test-c (local-variable-p 'local-test-c)))",
+            "(1 nil)",
+        );
+        assert_lisp(
+            "(progn (make-variable-buffer-local 'local-test-d) (setq local-test-d 3) (list local-test-d (default-value 'local-test-d) (local-variable-if-set-p 'local-test-d)))",
+            "(3 nil t)",
+        );
+        assert_lisp(
+            "(progn (defvar local-test-e 1) (set (make-local-variable 'local-test-e) 2) (list (let ((local-test-e 3)) (list local-test-e (default-value 'local-test-e))) local-test-e))",
+            "((3 1) 2)",
+        );
+        assert_lisp(
+            "(progn (defvar local-test-f 1) (set (make-local-variable 'local-test-f) 2) (assq 'local-test-f (buffer-local-variables)))",
+            "(local-test-f . 2)",
+        );
+    }
 }
 
 defsym!(MANY);
@@ -548,4 +655,6 @@ defsym!(HASH_TABLE);
 defsym!(BUFFER);
 defsym!(SUBR);
 defsym!(CHAR_TABLE);
+defsym!(MARKER);
+defsym!(OVERLAY);
 defsym!(BIG_INT);
@@ -0,0 +1,82 @@
+//! Fetching documentation strings.
+use crate::core::{
+    env::Env,
+    gc::{Context, Rt},
+    object::{NIL, Object, ObjectType, OptionalFlag, Symbol},
+};
+use anyhow::{Context as _, Result, bail};
+use rune_macros::defun;
+use std::fs;
+
+/// Read the docstring that starts at byte `pos` in `file`. Byte-compiled files
+/// store their docstrings out of line, terminated by `^_`, and refer to them
+/// with `(FILE . POS)` so that they only need to be loaded when used.
+pub(crate) fn get_doc_string(file: &str, pos: i64) -> Result<String> {
+    let contents =
+        fs::read(file).with_context(|| format!("Cannot open doc string file `{file}'"))?;
+    let Some(tail) = usize::try_from(pos).ok().and_then(|pos| contents.get(pos..)) else {
+        bail!("Invalid doc string position {pos} in `{file}'");
+    };
+    let end = tail.iter().position(|x| *x == 0x1F).unwrap_or(tail.len());
+    // ^A is used as an escape character for the bytes that can't appear
+    // unquoted in a docstring
+    let mut bytes = Vec::with_capacity(end);
+    let mut iter = tail[..end].iter();
+    while let Some(&byte) = iter.next() {
+        if byte != 0x01 {
+            bytes.push(byte);
+            continue;
+        }
+        match iter.next() {
+            Some(b'1') => bytes.push(0x01),
+            Some(b'0') => bytes.push(0x00),
+            Some(b'_') => bytes.push(0x1F),
+            _ => bail!("Invalid data in documentation file -- ^A followed by invalid code"),
+        }
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Return the documentation string that is SYMBOL's PROP property.
+/// Third argument RAW omitted or nil means pass the result through
+/// `substitute-command-keys' if it is a string.
+///
+/// This differs from `get' in that it can refer to strings stored in the
+/// byte-compiled file that the symbol was defined in.
+#[defun]
+fn documentation_property<'ob>(
+    symbol: Symbol,
+    prop: Symbol,
+    _raw: OptionalFlag,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let value = crate::data::get(symbol, prop, env, cx);
+    match value.untag() {
+        ObjectType::Cons(cons) => match (cons.car().untag(), cons.cdr().untag()) {
+            (ObjectType::String(file), ObjectType::Int(pos)) => {
+                Ok(cx.add(get_doc_string(file, pos)?))
+            }
+            _ => Ok(NIL),
+        },
+        ObjectType::String(_) => Ok(value),
+        _ => Ok(NIL),
+    }
+}
+
+defsym!(VARIABLE_DOCUMENTATION);
+defsym!(FUNCTION_DOCUMENTATION);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_doc_string() {
+        let file = std::env::temp_dir().join("rune-doc-string-test.elc");
+        fs::write(&file, b"#@11 a\x01_b\x011c\n\x1F(x)").unwrap();
+        let file = file.to_str().unwrap();
+        assert_eq!(get_doc_string(file, 5).unwrap(), "a\x1Fb\x01c\n");
+        assert!(get_doc_string(file, 100).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 //! Buffer editing utilities.
 use crate::core::{
-    env::{ArgSlice, Env},
-    gc::{Context, Rt},
-    object::{Object, ObjectType},
+    env::{ArgSlice, CurrentBuffer, Env},
+    gc::{Context, Rt, Slot},
+    object::{IntoObject, LispMarker, LispString, Object, ObjectType, Position},
 };
+use crate::{arith, fns, intervals::copy_string_properties, marker, undo};
 use anyhow::{Result, bail, ensure};
 use rune_macros::defun;
 use std::{fmt::Write as _, io::Write};
@@ -85,57 +86,235 @@ fn char_to_string(chr: char) -> String {
 #[defun]
 pub(crate) fn insert(args: ArgSlice, env: &mut Rt<Env>, cx: &Context) -> Result<()> {
     let env = &mut **env; // Deref into rooted type so we can split the borrow
-    let buffer = env.current_buffer.get_mut();
     let args = Rt::bind_slice(env.stack.arg_slice(args), cx);
     for arg in args {
-        buffer.insert(*arg)?;
+        insert_object(&mut env.current_buffer, *arg, cx)?;
     }
     Ok(())
 }
 
+/// Insert a string or character at point and record it for undo.
+pub(crate) fn insert_object(buffer: &mut CurrentBuffer, arg: Object, cx: &Context) -> Result<()> {
+    let data = buffer.get_mut();
+    let beg = data.text.cursor().chars();
+    let len = data.text.len_chars();
+    data.insert(arg)?;
+    let inserted = data.text.len_chars() - len;
+    undo::record_insert(buffer, beg, inserted, cx);
+    Ok(())
+}
+
+/// Delete the text between the positions `start` and `end` and record it for
+/// undo.
+pub(crate) fn delete_text(
+    buffer: &mut CurrentBuffer,
+    start: usize,
+    end: usize,
+    cx: &Context,
+) -> Result<()> {
+    let (start, end) = if start <= end { (start, end) } else { (end, start) };
+    let (a, b) = buffer.get().slice_with_gap(start, end)?;
+    let text = format!("{a}{b}");
+    undo::record_delete(buffer, start - 1, &text, cx);
+    buffer.get_mut().delete(start, end)
+}
+
+/// Set point to POSITION, a number or marker.
+/// Beginning of buffer is position (point-min), end is (point-max).
+/// Positions are 1-based like in Emacs, while the cursor of the buffer text is
+/// 0-based.
 // TODO: this should not throw and error. Buffer will always be present.
 #[defun]
-pub(crate) fn goto_char(position: usize, env: &mut Rt<Env>) -> Result<()> {
+pub(crate) fn goto_char(position: Object, env: &mut Rt<Env>) -> Result<()> {
+    let position = arith::int_or_marker(position)?;
     let buffer = env.current_buffer.get_mut();
-    buffer.text.set_cursor(position);
+    let (min, max) = (buffer.point_min(), buffer.point_max());
+    let position = position.clamp(min as i64, max as i64) as usize;
+    buffer.text.set_cursor(position - 1);
     Ok(())
 }
 
 // TODO: this should not throw and error. Buffer will always be present.
 #[defun]
 pub(crate) fn point_max(env: &mut Rt<Env>) -> Result<usize> {
+    Ok(env.current_buffer.get().point_max())
+}
+
+#[defun]
+pub(crate) fn point_min(env: &Rt<Env>) -> usize {
+    env.current_buffer.get().point_min()
+}
+
+/// Restrict editing in this buffer to the current region.
+/// The rest of the text becomes temporarily invisible and untouchable but
+/// is not deleted; if you save the buffer in a file, the invisible text is
+/// included in the file. \[widen] makes all visible again.
+#[defun]
+pub(crate) fn narrow_to_region(
+    Position(start): Position,
+    Position(end): Position,
+    env: &mut Rt<Env>,
+) -> Result<()> {
+    env.current_buffer.get_mut().narrow_to_region(start, end)
+}
+
+/// Remove restrictions (narrowing) from current buffer.
+/// This allows the buffer's full text to be seen and edited.
+#[defun]
+pub(crate) fn widen(env: &mut Rt<Env>) {
+    env.current_buffer.get_mut().widen();
+}
+
+/// Return non-nil if the current buffer is narrowed.
+#[defun]
+fn buffer_narrowed_p(env: &Rt<Env>) -> bool {
+    env.current_buffer.get().is_narrowed()
+}
+
+#[defun]
+pub(crate) fn point_marker<'ob>(env: &mut Rt<Env>, cx: &'ob Context) -> &'ob LispMarker {
+    let marker = marker::make_marker(cx);
     let buffer = env.current_buffer.get_mut();
-    // TODO: Handle narrowing
-    Ok(buffer.text.len_chars() + 1)
+    let point = buffer.text.cursor().chars();
+    buffer.set_marker(marker, point);
+    marker
 }
 
 #[defun]
-pub(crate) fn point_min() -> usize {
-    // TODO: Handle narrowing
-    1
+pub(crate) fn delete_region(
+    Position(start): Position,
+    Position(end): Position,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<()> {
+    delete_text(&mut env.current_buffer, start, end, cx)
 }
 
+/// Return the contents of part of the current buffer as a string.
+/// The two arguments START and END are character positions;
+/// they can be in either order.
+/// The string returned is multibyte if the buffer is multibyte.
+///
+/// This function copies the text properties of that part of the buffer
+/// into the result string; if you don't want the text properties,
+/// use `buffer-substring-no-properties' instead.
 #[defun]
-pub(crate) fn point_marker(env: &mut Rt<Env>) -> usize {
-    // TODO: Implement marker objects
-    env.current_buffer.get_mut().text.cursor().chars()
+pub(crate) fn buffer_substring<'ob>(
+    Position(start): Position,
+    Position(end): Position,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let (start, end) = if start <= end { (start, end) } else { (end, start) };
+    let buffer = env.current_buffer.get();
+    let (a, b) = buffer.slice_with_gap(start, end)?;
+    let string = format!("{a}{b}").into_obj(cx).untag();
+    buffer.textprops.copy_into(start, end, &mut *string.textprops_mut(cx)?, 0, cx)?;
+    Ok(string.into())
 }
 
+/// Return the characters of part of the buffer, without the text properties.
+/// The two arguments START and END are character positions;
+/// they can be in either order.
 #[defun]
-fn delete_region(start: usize, end: usize, env: &mut Rt<Env>) -> Result<()> {
-    env.current_buffer.get_mut().delete(start, end)
+fn buffer_substring_no_properties(
+    Position(start): Position,
+    Position(end): Position,
+    env: &Rt<Env>,
+) -> Result<String> {
+    let (start, end) = if start <= end { (start, end) } else { (end, start) };
+    let (a, b) = env.current_buffer.get().slice_with_gap(start, end)?;
+    Ok(format!("{a}{b}"))
 }
 
+/// Return a copy of STRING with text properties added.
+/// First argument is the string to copy.
+/// Remaining arguments form a sequence of PROPERTY VALUE pairs for text
+/// properties to add to the result.
 #[defun]
-fn bolp(env: &Rt<Env>) -> bool {
+fn propertize<'ob>(
+    string: &LispString,
+    properties: &[Object<'ob>],
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    ensure!(properties.len().is_multiple_of(2), "Wrong number of arguments to propertize");
+    let new = string.inner().to_owned().into_obj(cx).untag();
+    copy_string_properties(string, 0, string.len(), new, 0, cx)?;
+    if !properties.is_empty() {
+        let plist = fns::slice_into_list(properties, None, cx);
+        new.textprops_mut(cx)?.insert(0, new.len(), Slot::new(plist), cx);
+    }
+    Ok(new.into())
+}
+
+#[defun]
+pub(crate) fn bolp(env: &Rt<Env>) -> bool {
     let buf = env.current_buffer.get();
     let chars = buf.text.cursor().chars();
     chars == 0 || buf.text.char_at(chars - 1).unwrap() == '\n'
 }
 
+/// Return t if point is at the end of a line.
+/// `End of a line' includes point being at the end of the buffer.
 #[defun]
-fn point(env: &Rt<Env>) -> usize {
-    env.current_buffer.get().text.cursor().chars()
+pub(crate) fn eolp(env: &Rt<Env>) -> bool {
+    char_after(None, env).is_none_or(|c| c == '\n')
+}
+
+/// Return t if point is at the beginning of the buffer.
+/// If the buffer is narrowed, this means the beginning of the narrowed part.
+#[defun]
+pub(crate) fn bobp(env: &Rt<Env>) -> bool {
+    let buf = env.current_buffer.get();
+    buf.text.cursor().chars() == buf.begv()
+}
+
+/// Return t if point is at the end of the buffer.
+/// If the buffer is narrowed, this means the end of the narrowed part.
+#[defun]
+pub(crate) fn eobp(env: &Rt<Env>) -> bool {
+    let buf = env.current_buffer.get();
+    buf.text.cursor().chars() == buf.zv()
+}
+
+/// Return value of point, as an integer.
+/// Beginning of buffer is position (point-min). Positions are 1-based, one
+/// more than the 0-based cursor of the buffer text.
+#[defun]
+pub(crate) fn point(env: &Rt<Env>) -> usize {
+    env.current_buffer.get().text.cursor().chars() + 1
+}
+
+/// Return character in current buffer at position POS.
+/// POS is an integer or a marker and defaults to point.
+/// If POS is out of range, the value is nil.
+#[defun]
+pub(crate) fn char_after(pos: Option<Position>, env: &Rt<Env>) -> Option<char> {
+    let buf = env.current_buffer.get();
+    let pos = match pos {
+        Some(Position(pos)) => pos.checked_sub(1)?,
+        None => buf.text.cursor().chars(),
+    };
+    if pos < buf.begv() || pos >= buf.zv() {
+        return None;
+    }
+    buf.text.char_at(pos)
+}
+
+/// Return the character following point, as a number.
+/// At the end of the buffer or accessible region, return 0.
+#[defun]
+pub(crate) fn following_char(env: &Rt<Env>) -> char {
+    char_after(None, env).unwrap_or('\0')
+}
+
+/// Return the character preceding point, as a number.
+/// At the beginning of the buffer or accessible region, return 0.
+#[defun]
+pub(crate) fn preceding_char(env: &Rt<Env>) -> char {
+    let buf = env.current_buffer.get();
+    let point = buf.text.cursor().chars();
+    if point > buf.begv() { buf.text.char_at(point - 1).unwrap() } else { '\0' }
 }
 
 #[defun]
@@ -203,7 +382,48 @@ mod test {
         insert(ArgSlice::new(2), env, cx).unwrap();
 
         assert_eq!(env.current_buffer.get(), "hello world");
-        delete_region(2, 4, env).unwrap();
+        delete_region(Position(2), Position(4), env, cx).unwrap();
         assert_eq!(env.current_buffer.get(), "hlo world");
     }
+
+    #[test]
+    fn test_point() {
+        use crate::interpreter::assert_lisp;
+        assert_lisp("(point)", "1");
+        assert_lisp("(progn (insert \"abc\") (list (point) (point-min) (point-max)))", "(4 1 4)");
+        assert_lisp("(progn (insert \"abc\") (goto-char 2) (list (point) (char-after)))", "(2 98)");
+        assert_lisp("(progn (insert \"abc\") (goto-char 0) (point))", "1");
+        assert_lisp("(progn (insert \"abc\") (goto-char 10) (point))", "4");
+        assert_lisp("(progn (insert \"abc\") (goto-char -5) (point))", "1");
+        assert_lisp(
+            "(progn (insert \"abc\") (let ((m (point-marker))) (goto-char 1) (goto-char m) (list (point) (buffer-substring 2 m))))",
+            "(4 \"bc\")",
+        );
+    }
+
+    #[test]
+    fn test_narrowing() {
+        use crate::interpreter::assert_lisp;
+        assert_lisp(
+            "(progn (insert \"hello world\") (narrow-to-region 3 6) (list (point-min) (point-max) (buffer-narrowed-p)))",
+            "(3 6 t)",
+        );
+        assert_lisp(
+            "(progn (insert \"hello world\") (narrow-to-region 3 6) (widen) (list (point-min) (point-max) (buffer-narrowed-p)))",
+            "(1 12 nil)",
+        );
+        assert_lisp(
+            "(progn (insert \"hello world\") (narrow-to-region 3 6) (goto-char 1) (point))",
+            "3",
+        );
+        assert_lisp(
+            "(progn (insert \"hello world\") (save-restriction (narrow-to-region 3 6)) (buffer-narrowed-p))",
+            "nil",
+        );
+        // the saved bounds move with edits made while widened
+        assert_lisp(
+            "(progn (insert \"hello world\") (narrow-to-region 3 6) (save-restriction (widen) (goto-char 1) (insert \"ab\")) (list (point-min) (point-max)))",
+            "(5 8)",
+        );
+    }
 }
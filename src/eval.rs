@@ -1,10 +1,10 @@
 //! Lisp evaluation primitives.
 use crate::core::cons::{Cons, ConsError};
-use crate::core::env::{ArgSlice, CallFrame, Env, sym};
+use crate::core::env::{ArgSlice, BacktraceFrame, CallFrame, Env, Handler, HandlerType, sym};
 use crate::core::error::{Type, TypeError};
 use crate::core::gc::{Rt, Rto};
 use crate::core::object::{
-    FnArgs, Function, LispString, NIL, ObjectType, Symbol, TagType, display_slice,
+    FnArgs, Function, LispString, NIL, ObjectType, Symbol, TRUE, TagType, display_slice,
 };
 use crate::core::{
     gc::Context,
@@ -12,18 +12,27 @@ use crate::core::{
 };
 use crate::data::LispError;
 use crate::fns::{assq, eq};
+use crate::regex_emacs::{Input, Regexp};
 use crate::rooted_iter;
 use anyhow::{Result, anyhow, bail, ensure};
 use fallible_iterator::FallibleIterator;
 use fallible_streaming_iterator::FallibleStreamingIterator;
-use rune_core::macros::{bail_err, call, list, root};
+use rune_core::macros::{bail_err, call, list, rebind, root};
 use rune_macros::defun;
+use std::cell::Cell;
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub(crate) struct EvalError {
     backtrace: Vec<Box<str>>,
     pub(crate) error: ErrorType,
+    /// Set once the error was signaled. At that point the handler that will
+    /// receive it was found and the debugger was considered, so neither
+    /// happens again as the error unwinds.
+    signaled: bool,
+    /// The index of the handler in [`Env::handlers`] that the error unwinds to,
+    /// or `None` if it unwinds to the top level.
+    handler: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -47,19 +56,20 @@ impl Display for EvalError {
 }
 
 impl EvalError {
+    fn from_type(error: ErrorType) -> Self {
+        Self { backtrace: Vec::new(), error, signaled: false, handler: None }
+    }
+
     pub(crate) fn new_error(error: anyhow::Error) -> Self {
-        Self { backtrace: Vec::new(), error: ErrorType::Err(error) }
+        Self::from_type(ErrorType::Err(error))
     }
 
     pub(crate) fn signal(error_symbol: Object, data: Object, env: &mut Rt<Env>) -> Self {
-        Self {
-            backtrace: Vec::new(),
-            error: ErrorType::Signal(env.set_exception(error_symbol, data)),
-        }
+        Self::from_type(ErrorType::Signal(env.set_exception(error_symbol, data)))
     }
 
     pub(crate) fn throw(tag: Object, data: Object, env: &mut Rt<Env>) -> Self {
-        Self { backtrace: Vec::new(), error: ErrorType::Throw(env.set_exception(tag, data)) }
+        Self::from_type(ErrorType::Throw(env.set_exception(tag, data)))
     }
 
     pub(crate) fn new(error: impl Into<Self>) -> Self {
@@ -69,7 +79,7 @@ impl EvalError {
     pub(crate) fn with_trace(error: anyhow::Error, name: &str, args: &[Rto<Object>]) -> Self {
         let display = display_slice(args);
         let trace = format!("{name} {display}").into_boxed_str();
-        Self { backtrace: vec![trace], error: ErrorType::Err(error) }
+        Self { backtrace: vec![trace], ..Self::new_error(error) }
     }
 
     pub(crate) fn add_trace(mut self, name: &str, args: &[Rto<Object>]) -> Self {
@@ -78,6 +88,50 @@ impl EvalError {
         self
     }
 
+    /// Return the error as the `(ERROR-SYMBOL . DATA)` cons seen by handlers,
+    /// or `None` if this is a throw.
+    pub(crate) fn error_object<'ob>(&self, env: &Rt<Env>, cx: &'ob Context) -> Option<&'ob Cons> {
+        match &self.error {
+            ErrorType::Signal(id) => {
+                let (sym, data) = env.get_exception(*id).expect("Exception not found");
+                Some(Cons::new(sym, data, cx))
+            }
+            ErrorType::Err(err) => match err.downcast_ref::<LispError>() {
+                Some(lisp_error) => Some(lisp_error.bind(cx)),
+                // TODO: Need to remove the anyhow branch once full errors are
+                // implemented
+                None => Some(Cons::new(sym::ERROR, format!("{err}"), cx)),
+            },
+            ErrorType::Throw(_) => None,
+        }
+    }
+
+    /// True if this error unwinds to the handler at `index` in
+    /// [`Env::handlers`].
+    pub(crate) fn is_handled_by(&self, index: usize) -> bool {
+        self.handler == Some(index)
+    }
+
+    /// The value received by the handler: the value thrown to a `catch`, or
+    /// the error for a `condition-case`.
+    pub(crate) fn handler_value<'ob>(&self, env: &Rt<Env>, cx: &'ob Context) -> Object<'ob> {
+        match &self.error {
+            ErrorType::Throw(id) => env.get_exception(*id).map_or(NIL, |(_, data)| data.bind(cx)),
+            _ => self.error_object(env, cx).map_or(NIL, Object::from),
+        }
+    }
+
+    /// Running lisp while the error is being signaled can overwrite the
+    /// exception it refers to, so set it again from the `error` object.
+    fn restore_exception(&mut self, error: &Rto<Object>, env: &mut Rt<Env>, cx: &Context) {
+        if let ErrorType::Signal(id) = self.error
+            && env.get_exception(id).is_none()
+            && let ObjectType::Cons(error) = error.untag(cx)
+        {
+            self.error = ErrorType::Signal(env.set_exception(error.car(), error.cdr()));
+        }
+    }
+
     pub(crate) fn print_backtrace(&self) {
         println!("BEGIN_BACKTRACE");
         for (i, x) in self.backtrace.iter().enumerate() {
@@ -177,8 +231,7 @@ fn run_hooks<'ob>(hooks: ArgSlice, env: &mut Rt<Env>, cx: &'ob mut Context) -> R
         let hook = env.stack[hook_count - i - 1].bind(cx);
         match hook.untag() {
             ObjectType::Symbol(sym) => {
-                if let Some(val) = env.vars.get(sym) {
-                    let val = val.bind(cx);
+                if let Some(val) = env.var(sym, cx) {
                     match val.untag() {
                         ObjectType::Cons(hook_list) => {
                             rooted_iter!(hooks, hook_list, cx);
@@ -211,8 +264,7 @@ fn run_hook_with_args<'ob>(
 ) -> Result<Object<'ob>> {
     match hook.untag(cx) {
         ObjectType::Symbol(sym) => {
-            if let Some(val) = env.vars.get(sym) {
-                let val = val.bind(cx);
+            if let Some(val) = env.var(sym, cx) {
                 match val.untag() {
                     ObjectType::Cons(hook_list) => {
                         rooted_iter!(hooks, hook_list, cx);
@@ -261,7 +313,7 @@ pub(crate) fn autoload_do_load<'ob>(
         "autoload arguments are not yet implemented"
     );
     root!(file, cx);
-    crate::lread::load(file, None, None, cx, env)?;
+    crate::lread::load(file, None, None, None, None, cx, env)?;
     match funname {
         Some(func) => match func.untag(cx).func(cx) {
             Some(x) => Ok(x.into()),
@@ -390,18 +442,108 @@ fn signal(mut error_symbol: Object, data: Object, env: &mut Rt<Env>) -> Result<b
     Err(EvalError::signal(error_symbol, data, env).into())
 }
 
+/// Throw to the catch for TAG and return VALUE from it.
+/// Both TAG and VALUE are evalled.
+#[defun]
+fn throw<'ob>(
+    tag: Object<'ob>,
+    value: Object<'ob>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<bool> {
+    let catch = env
+        .handlers
+        .iter()
+        .any(|x| x.kind == HandlerType::Catch && eq(x.tag.bind(cx), tag));
+    if catch {
+        Err(EvalError::throw(tag, value, env).into())
+    } else {
+        Err(EvalError::signal(sym::NO_CATCH.into(), list![tag, value; cx], env).into())
+    }
+}
+
+/// Set up error handlers around execution of BODYFUN.
+/// BODYFUN should be a function and it is called with no arguments.
+/// CONDITIONS should be a list of condition names (symbols).
+/// When an error is signaled during execution of BODYFUN, if that
+/// error matches one of CONDITIONS, then the associated HANDLER is
+/// called with the error as argument.
+/// HANDLER should either transfer the control via a non-local exit,
+/// or return normally.
+/// If it returns normally, the search for an error handler continues
+/// from where it left off.
+#[defun]
+fn handler_bind_1<'ob>(
+    bodyfun: &Rto<Function>,
+    handlers: ArgSlice,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let args = Rt::bind_slice(env.stack.arg_slice(handlers), cx);
+    ensure!(args.len() % 2 == 0, "handler-bind-1 takes pairs of CONDITIONS and HANDLER");
+    // The first handler is pushed last so that it is the innermost
+    let pairs: Vec<(Object, Object)> = args.chunks(2).rev().map(|x| (x[0], x[1])).collect();
+    let base = env.handlers.len();
+    for (conditions, handler) in pairs {
+        env.handlers.push(Handler::handler_bind(conditions, handler));
+    }
+    let result = call!(bodyfun; env, cx);
+    env.handlers.truncate(base);
+    Ok(result?)
+}
+
+/// Call FUNCTION with ARGS and return its value, or nil if it signals an
+/// error.
+#[defun]
+fn ignore_error_wrapper<'ob>(
+    function: &Rto<Function>,
+    arguments: ArgSlice,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let index = env.handlers.len();
+    env.handlers.push(Handler::new(HandlerType::ConditionCase, sym::ERROR.into()));
+    let beg = env.stack.len() - arguments.len();
+    env.stack.extend_as_vec_from_within(beg..);
+    let frame = &mut CallFrame::new_with_args(env, arguments.len());
+    let err = match function.call(frame, None, cx) {
+        Ok(x) => {
+            frame.handlers.truncate(index);
+            return Ok(rebind!(x, cx));
+        }
+        Err(e) => e,
+    };
+    let err = signal_error(err, frame, cx);
+    frame.handlers.truncate(index);
+    if err.is_handled_by(index) { Ok(NIL) } else { Err(err.into()) }
+}
+
 #[defun]
 fn special_variable_p(symbol: Symbol) -> bool {
     symbol.is_special()
 }
 
+/// Return the value of SYMBOL outside of any `let` bindings. Signal
+/// `void-variable` if it has no value.
+#[defun]
+fn default_toplevel_value<'ob>(
+    symbol: Symbol,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    match env.vars.get(symbol) {
+        Some(value) => Ok(value.bind(cx)),
+        None => Err(LispError::new(Cons::new(sym::VOID_VARIABLE, list![symbol; cx], cx)).into()),
+    }
+}
+
 #[defun]
 fn set_default_toplevel_value<'ob>(
     symbol: Symbol,
     value: Object,
     env: &'ob mut Rt<Env>,
 ) -> Result<Object<'ob>> {
-    env.set_var(symbol, value)?;
+    env.set_default(symbol, value)?;
     Ok(NIL)
 }
 
@@ -411,8 +553,7 @@ fn set_default<'ob>(
     value: Object<'ob>,
     env: &'ob mut Rt<Env>,
 ) -> Result<Object<'ob>> {
-    // TODO: implement buffer local variables
-    env.set_var(symbol, value)?;
+    env.set_default(symbol, value)?;
     Ok(value)
 }
 
@@ -422,12 +563,27 @@ impl Rto<Function<'_>> {
         frame: &mut CallFrame<'_, '_>,
         name: Option<&str>,
         cx: &'ob mut Context,
+    ) -> EvalResult<'ob> {
+        let value = match self.call_function(frame, name, cx) {
+            Ok(x) if !frame.stack.debug_on_exit() => return Ok(rebind!(x, cx)),
+            result => result?,
+        };
+        root!(value, cx);
+        debug_on_exit(value, frame, cx)
+    }
+
+    fn call_function<'ob>(
+        &self,
+        frame: &mut CallFrame<'_, '_>,
+        name: Option<&str>,
+        cx: &'ob mut Context,
     ) -> EvalResult<'ob> {
         debug!("calling: {self}");
         let name = name.unwrap_or("lambda");
         frame.finalize_arguments();
+        frame.stack.set_frame_function(self.bind(cx).into());
         let arg_cnt = frame.arg_count();
-        cx.garbage_collect(false);
+        crate::alloc::maybe_garbage_collect(frame, cx)?;
         match self.untag(cx) {
             FunctionType::ByteFn(f) => {
                 root!(f, cx);
@@ -435,7 +591,13 @@ impl Rto<Function<'_>> {
                     .map_err(|e| e.add_trace(name, frame.arg_slice()))
             }
             FunctionType::SubrFn(f) => {
-                (*f).call(arg_cnt, frame, cx).map_err(|e| add_trace(e, name, frame.arg_slice()))
+                let err = match (*f).call(arg_cnt, frame, cx) {
+                    Ok(x) => return Ok(rebind!(x, cx)),
+                    Err(e) => add_trace(e, name, frame.arg_slice()),
+                };
+                // Builtins are where errors are signaled, so this is the
+                // innermost frame that can see them
+                Err(signal_error(err, frame, cx))
             }
             FunctionType::Cons(_) => {
                 crate::interpreter::call_closure(self.try_as().unwrap(), arg_cnt, name, frame, cx)
@@ -453,11 +615,11 @@ impl Rto<Function<'_>> {
                     };
                     root!(func, cx);
                     let name = sym.bind(cx).name().to_owned();
-                    func.call(frame, Some(&name), cx)
+                    func.call_function(frame, Some(&name), cx)
                 } else {
                     root!(func, cx);
                     let name = sym.name().to_owned();
-                    func.call(frame, Some(&name), cx)
+                    func.call_function(frame, Some(&name), cx)
                 }
             }
         }
@@ -471,6 +633,452 @@ pub(crate) fn add_trace(err: anyhow::Error, name: &str, args: &[Rto<Object>]) ->
     }
 }
 
+/// An interactive debugger that is entered in place of the `debugger`
+/// function. It is called with the error object and returns the error to
+/// unwind with instead, or `None` to let the original error continue.
+pub(crate) type BreakLoop = fn(&Rto<Object>, &mut Rt<Env>, &mut Context) -> Option<EvalError>;
+
+thread_local! {
+    /// Set while the debugger is running, so that errors inside of it don't
+    /// call it again.
+    static IN_DEBUGGER: Cell<bool> = const { Cell::new(false) };
+    static BREAK_LOOP: Cell<Option<BreakLoop>> = const { Cell::new(None) };
+}
+
+/// Install a break loop that is entered instead of calling `debugger`. This is
+/// used by the REPL.
+pub(crate) fn set_break_loop(break_loop: Option<BreakLoop>) {
+    BREAK_LOOP.with(|x| x.set(break_loop));
+}
+
+/// Signal `err`: find the handler that will receive it, calling the matching
+/// `handler-bind` handlers on the way, and call the debugger if
+/// `debug-on-error` and friends ask for it. This happens where the error is
+/// signaled, so both see the frames before they are unwound. Returns the error
+/// that should continue to unwind the stack.
+pub(crate) fn signal_error(mut err: EvalError, env: &mut Rt<Env>, cx: &mut Context) -> EvalError {
+    if err.signaled {
+        return err;
+    }
+    err.signaled = true;
+    let Some(error) = err.error_object(env, cx) else {
+        err.handler = find_catch(&err, env, cx);
+        return err;
+    };
+    let error = Object::from(error);
+    root!(error, cx);
+    err.handler = match find_condition_handler(error, env, cx) {
+        Ok(handler) => handler,
+        // A `handler-bind` handler exited non-locally
+        Err(new_err) => return new_err,
+    };
+    err.restore_exception(error, env, cx);
+    if IN_DEBUGGER.with(Cell::get) {
+        return err;
+    }
+    // Avoid looking at the handler in the common case
+    let is_set = |var| env.var(var, cx).is_some_and(|x| !x.is_nil());
+    if !is_set(sym::DEBUG_ON_ERROR) && !is_set(sym::DEBUG_ON_QUIT) {
+        return err;
+    }
+    let handler = err.handler.map(|i| env.handlers[i].tag.bind(cx));
+    let Ok(cons) = error.bind_as(cx) else { return err };
+    if !wants_debugger(cons, handler, env, cx) {
+        return err;
+    }
+    IN_DEBUGGER.with(|x| x.set(true));
+    let result = match BREAK_LOOP.with(Cell::get) {
+        Some(break_loop) => break_loop(error, env, cx),
+        None => run_debugger(error, env, cx).err(),
+    };
+    IN_DEBUGGER.with(|x| x.set(false));
+    match result {
+        Some(new_err) => signal_error(new_err, env, cx),
+        None => {
+            err.restore_exception(error, env, cx);
+            err
+        }
+    }
+}
+
+/// Return the index of the innermost `catch` for the tag thrown by `err`.
+fn find_catch(err: &EvalError, env: &Rt<Env>, cx: &Context) -> Option<usize> {
+    let ErrorType::Throw(id) = err.error else { return None };
+    let (tag, _) = env.get_exception(id)?;
+    let tag = tag.bind(cx);
+    env.handlers
+        .iter()
+        .rposition(|x| x.kind == HandlerType::Catch && eq(x.tag.bind(cx), tag))
+}
+
+/// Return the index of the innermost `condition-case` clause that handles
+/// `error`. The matching `handler-bind` handlers above it are called with the
+/// error first. If one of them exits non-locally, that error is returned
+/// instead.
+fn find_condition_handler(
+    error: &Rto<Object>,
+    env: &mut Rt<Env>,
+    cx: &mut Context,
+) -> Result<Option<usize>, EvalError> {
+    let mut idx = env.handlers.len();
+    while idx > 0 {
+        idx -= 1;
+        let kind = env.handlers[idx].kind;
+        let conditions = match kind {
+            HandlerType::Catch => continue,
+            HandlerType::SkipConditions(start) => {
+                idx = start;
+                continue;
+            }
+            HandlerType::ConditionCase | HandlerType::HandlerBind => env.handlers[idx].tag.bind(cx),
+        };
+        if !handler_matches(conditions, &signal_conditions(error.bind(cx), env, cx)) {
+            continue;
+        }
+        if kind == HandlerType::ConditionCase {
+            return Ok(Some(idx));
+        }
+        let func: Function = env.handlers[idx].func.bind(cx).try_into()?;
+        root!(func, cx);
+        env.handlers.push(Handler::new(HandlerType::SkipConditions(idx), NIL));
+        let result = call!(func, error; env, cx).map(|_| ());
+        env.handlers.pop();
+        result?;
+    }
+    Ok(None)
+}
+
+/// Call the function in `debugger` as `(debugger 'error ERROR)`.
+fn run_debugger(error: &Rto<Object>, env: &mut Rt<Env>, cx: &mut Context) -> Result<(), EvalError> {
+    let debugger = env.var(sym::DEBUGGER, cx).unwrap_or_default();
+    let debugger: Function = debugger.try_into()?;
+    root!(debugger, cx);
+    call!(debugger, Object::from(sym::ERROR), error; env, cx)?;
+    Ok(())
+}
+
+/// Call the debugger as `(debugger 'exit VALUE)` when a frame marked by
+/// `backtrace-debug` returns. The value returned by the debugger becomes the
+/// value of the frame.
+pub(crate) fn debug_on_exit<'ob>(
+    value: &Rto<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> EvalResult<'ob> {
+    let debugger = env.var(sym::DEBUGGER, cx).unwrap_or_default();
+    let debugger: Function = debugger.try_into()?;
+    root!(debugger, cx);
+    call!(debugger, Object::from(sym::EXIT), value; env, cx)
+}
+
+/// Decide if signaling `error` should enter the debugger. `handler` is the
+/// conditions of the `condition-case` clause that will handle it, if any.
+fn wants_debugger(error: &Cons, handler: Option<Object>, env: &Rt<Env>, cx: &Context) -> bool {
+    let Ok(symbol) = Symbol::try_from(error.car()) else { return false };
+    let conditions = error_conditions(symbol, env, cx);
+    let var = if symbol == sym::QUIT { sym::DEBUG_ON_QUIT } else { sym::DEBUG_ON_ERROR };
+    let debug_on = env.var(var, cx).unwrap_or_default();
+    // The variable is either t or a list of conditions to debug
+    let debug = match debug_on.untag() {
+        ObjectType::NIL => false,
+        ObjectType::Cons(_) => handler_matches(debug_on, &conditions),
+        _ => true,
+    };
+    if !debug || is_ignored_error(symbol, error.cdr(), &conditions, env, cx) {
+        return false;
+    }
+    // A handler that catches the error will run instead, unless its
+    // conditions are a list that includes `debug'
+    let debug_on_signal = env.var(sym::DEBUG_ON_SIGNAL, cx).is_some_and(|x| !x.is_nil());
+    match handler.map(|x| x.untag()) {
+        Some(ObjectType::Cons(list)) if !debug_on_signal => {
+            list.elements().any(|x| x.is_ok_and(|x| x == sym::DEBUG))
+        }
+        Some(_) => debug_on_signal,
+        None => true,
+    }
+}
+
+/// Return the `error-conditions` of an error symbol. Symbols that were not
+/// defined with `define-error` only have themselves and `error`.
+fn error_conditions<'ob>(symbol: Symbol<'ob>, env: &Rt<Env>, cx: &'ob Context) -> Vec<Symbol<'ob>> {
+    let conditions = crate::data::get(symbol, sym::ERROR_CONDITIONS, env, cx);
+    let conditions: Vec<Symbol> = match conditions.as_list() {
+        Ok(list) => list.filter_map(|x| x.ok()?.try_into().ok()).collect(),
+        Err(_) => Vec::new(),
+    };
+    if conditions.is_empty() { vec![symbol, sym::ERROR] } else { conditions }
+}
+
+/// The conditions of a signaled `error`, or none if it is not an
+/// `(ERROR-SYMBOL . DATA)` cons.
+fn signal_conditions<'ob>(error: Object<'ob>, env: &Rt<Env>, cx: &'ob Context) -> Vec<Symbol<'ob>> {
+    match error.as_cons_pair() {
+        Ok((symbol, _)) => error_conditions(symbol, env, cx),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// True if `handler`, the conditions of a `condition-case` clause or of
+/// `debug-on-error`, matches any of the error `conditions`.
+fn handler_matches(handler: Object, conditions: &[Symbol]) -> bool {
+    match handler.untag() {
+        ObjectType::TRUE => true,
+        ObjectType::Symbol(sym) => conditions.contains(&sym),
+        ObjectType::Cons(list) => list.elements().any(|x| {
+            x.ok()
+                .and_then(|x| Symbol::try_from(x).ok())
+                .is_some_and(|x| conditions.contains(&x))
+        }),
+        _ => false,
+    }
+}
+
+/// True if the error is listed in `debug-ignored-errors`, either by one of its
+/// conditions or by a regexp that matches its message.
+fn is_ignored_error(
+    symbol: Symbol,
+    data: Object,
+    conditions: &[Symbol],
+    env: &Rt<Env>,
+    cx: &Context,
+) -> bool {
+    let ignored = env.var(sym::DEBUG_IGNORED_ERRORS, cx).unwrap_or_default();
+    let Ok(ignored) = ignored.as_list() else { return false };
+    let message = error_message(symbol, data, env, cx);
+    ignored.filter_map(Result::ok).any(|x| match x.untag() {
+        ObjectType::String(regexp) => Regexp::new(regexp)
+            .is_ok_and(|re| matches!(re.search(&Input::new(&message), 0), Ok(Some(_)))),
+        ObjectType::Symbol(sym) => conditions.contains(&sym),
+        _ => false,
+    })
+}
+
+/// The message of an error. `error' and `user-error' pass their message as
+/// the first datum, otherwise it is the `error-message' of the symbol.
+fn error_message(symbol: Symbol, data: Object, env: &Rt<Env>, cx: &Context) -> String {
+    if let ObjectType::Cons(data) = data.untag()
+        && let ObjectType::String(message) = data.car().untag()
+    {
+        return message.to_string();
+    }
+    match crate::data::get(symbol, sym::ERROR_MESSAGE, env, cx).untag() {
+        ObjectType::String(message) => message.to_string(),
+        _ => symbol.name().to_owned(),
+    }
+}
+
+/// Return the local variables of a live call frame as an alist of `(NAME .
+/// VALUE)`. For an interpreted closure these are its captured environment and
+/// its arguments. The names of other locals are not known, so they are
+/// numbered by their position in the frame.
+pub(crate) fn frame_locals<'ob>(frame: &BacktraceFrame, cx: &'ob Context) -> Object<'ob> {
+    let args = Rt::bind_slice(frame.args, cx);
+    let locals: Vec<Object> = match indirect_function(frame.func.bind(cx), cx).untag() {
+        ObjectType::Cons(closure) if closure.car() == sym::CLOSURE => {
+            let vars = crate::interpreter::closure_locals(closure, args, cx).unwrap_or_default();
+            // The highest priority bindings are at the end of the vector, but
+            // the start of the alist
+            vars.into_iter().rev().map(Object::from).collect()
+        }
+        _ => {
+            let slots = args.iter().chain(Rt::bind_slice(frame.locals, cx));
+            slots
+                .enumerate()
+                .map(|(i, x)| Cons::new(Object::from(i), *x, cx).into())
+                .collect()
+        }
+    };
+    crate::fns::slice_into_list(&locals, None, cx)
+}
+
+/// Resolve a function name through its chain of aliases.
+fn indirect_function<'ob>(func: Object<'ob>, cx: &'ob Context) -> Object<'ob> {
+    match func.untag() {
+        ObjectType::Symbol(sym) => sym.follow_indirect(cx).map_or(func, Into::into),
+        _ => func,
+    }
+}
+
+/// Return the index of the nearest activation of `base` in `backtrace`. The
+/// backtrace primitives count their frames from there. When `base` is nil they
+/// count from the frame of the primitive itself, `this`.
+fn backtrace_base(
+    backtrace: &[BacktraceFrame],
+    base: Option<Object>,
+    this: Symbol,
+    cx: &Context,
+) -> usize {
+    let base = base.filter(|x| !x.is_nil()).unwrap_or(this.into());
+    let base = indirect_function(base, cx);
+    backtrace
+        .iter()
+        .position(|frame| eq(indirect_function(frame.func.bind(cx), cx), base))
+        .unwrap_or(backtrace.len())
+}
+
+/// Call `f` with the frame that is `nframes` out from the nearest activation of
+/// `base`. Return `None` if there is no such frame.
+fn nth_frame<T>(
+    nframes: usize,
+    base: Option<Object>,
+    this: Symbol,
+    env: &Rt<Env>,
+    cx: &Context,
+    f: impl FnOnce(&BacktraceFrame) -> T,
+) -> Option<T> {
+    let backtrace = env.stack.backtrace();
+    let start = backtrace_base(&backtrace, base, this, cx);
+    backtrace.get(start + nframes).map(f)
+}
+
+/// The arguments of a frame as a list. A `&rest` argument of a bytecode
+/// function is already a list, so it becomes the tail.
+fn frame_args<'ob>(frame: &BacktraceFrame, cx: &'ob Context) -> Object<'ob> {
+    let args = Rt::bind_slice(frame.args, cx);
+    match args.split_last() {
+        Some((rest, args)) if frame.rest => crate::fns::slice_into_list(args, Some(*rest), cx),
+        _ => crate::fns::slice_into_list(args, None, cx),
+    }
+}
+
+/// The function, arguments and flags of a frame, in the order they are passed
+/// to the function given to `mapbacktrace`.
+fn frame_data<'ob>(frame: &BacktraceFrame, cx: &'ob Context) -> Vec<Object<'ob>> {
+    let flags = if frame.debug_on_exit { list![sym::KW_DEBUG_ON_EXIT, TRUE; cx] } else { NIL };
+    vec![frame.func.bind(cx), frame_args(frame, cx), flags]
+}
+
+/// Return the function and arguments NFRAMES up from current execution point.
+/// If that frame has not evaluated the arguments yet (or is a special form),
+/// the value is (nil FUNCTION ARG-FORMS...).
+/// If that frame has evaluated its arguments and called its function already,
+/// the value is (t FUNCTION ARG-VALUES...).
+/// A &rest arg is represented as the tail of the list ARG-VALUES.
+/// FUNCTION is whatever was supplied as car of evaluated list,
+/// or a lambda expression for macro calls.
+/// If NFRAMES is more than the number of frames, the value is nil.
+/// If BASE is non-nil, it should be a function and NFRAMES counts from its
+/// nearest activation frame.
+#[defun]
+fn backtrace_frame<'ob>(
+    nframes: usize,
+    base: Option<Object>,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Object<'ob> {
+    let frame = nth_frame(nframes, base, sym::BACKTRACE_FRAME, env, cx, |frame| {
+        Cons::new(frame.func.bind(cx), frame_args(frame, cx), cx)
+    });
+    match frame {
+        Some(frame) => Cons::new(TRUE, frame, cx).into(),
+        None => NIL,
+    }
+}
+
+/// Call FUNCTION on stack frame NFRAMES away from BASE.
+/// Return the result of FUNCTION, or nil if no matching frame could be found.
+#[defun]
+#[expect(non_snake_case)]
+fn backtrace_frame__internal<'ob>(
+    function: &Rto<Function>,
+    nframes: usize,
+    base: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let base = base.map(|x| x.bind(cx));
+    let this = sym::BACKTRACE_FRAME__INTERNAL;
+    let Some(frame) = nth_frame(nframes, base, this, env, cx, |frame| frame_data(frame, cx)) else {
+        return Ok(NIL);
+    };
+    root!(frame, cx);
+    Ok(call!(function, TRUE, &frame[0], &frame[1], &frame[2]; env, cx)?)
+}
+
+/// Call FUNCTION for each frame in backtrace.
+/// If BASE is non-nil, it should be a function and iteration will start
+/// from its nearest activation frame.
+/// FUNCTION is called with 4 arguments: EVALD, FUNC, ARGS, and FLAGS.  If
+/// a frame has not evaluated its arguments yet or is a special form,
+/// EVALD is nil and ARGS is a list of forms.  If a frame has evaluated
+/// its arguments and called its function already, EVALD is t and ARGS is
+/// a list of values.
+/// FLAGS is a plist of properties of the current frame: currently, the
+/// only supported property is :debug-on-exit.  `mapbacktrace' always
+/// returns nil.
+#[defun]
+fn mapbacktrace(
+    function: &Rto<Function>,
+    base: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &mut Context,
+) -> Result<bool> {
+    let frames: Vec<Object> = {
+        let backtrace = env.stack.backtrace();
+        let base = base.map(|x| x.bind(cx));
+        let start = backtrace_base(&backtrace, base, sym::MAPBACKTRACE, cx);
+        backtrace[start..].iter().flat_map(|frame| frame_data(frame, cx)).collect()
+    };
+    root!(frames, cx);
+    for frame in frames.chunks(3) {
+        call!(function, TRUE, &frame[0], &frame[1], &frame[2]; env, cx)?;
+    }
+    Ok(false)
+}
+
+/// Return names and values of local variables of a stack frame.
+/// NFRAMES and BASE specify the activation frame to use, as in `backtrace-frame'.
+#[defun]
+#[expect(non_snake_case)]
+fn backtrace__locals<'ob>(
+    nframes: usize,
+    base: Option<Object>,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Object<'ob> {
+    let this = sym::BACKTRACE__LOCALS;
+    nth_frame(nframes, base, this, env, cx, |frame| frame_locals(frame, cx)).unwrap_or_default()
+}
+
+/// Evaluate EXP in the context of some activation frame.
+/// NFRAMES and BASE specify the activation frame to use, as in `backtrace-frame'.
+#[defun]
+fn backtrace_eval<'ob>(
+    exp: &Rto<Object>,
+    nframes: usize,
+    base: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let base = base.map(|x| x.bind(cx));
+    let this = sym::BACKTRACE_EVAL;
+    let Some(locals) = nth_frame(nframes, base, this, env, cx, |frame| frame_locals(frame, cx))
+    else {
+        bail!("Activation frame not found!");
+    };
+    root!(locals, cx);
+    crate::interpreter::eval(exp, Some(locals), env, cx)
+}
+
+/// Set the debug-on-exit flag of eval frame LEVEL levels down to FLAG.
+/// LEVEL and BASE specify the activation frame to use, as in `backtrace-frame'.
+/// The debugger is entered when that frame exits, if the flag is non-nil.
+#[defun]
+fn backtrace_debug<'ob>(
+    level: usize,
+    flag: Object<'ob>,
+    base: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Object<'ob> {
+    let this = sym::BACKTRACE_DEBUG;
+    if let Some(depth) = nth_frame(level, base, this, env, cx, |frame| frame.depth) {
+        env.stack.set_debug_on_exit(depth, !flag.is_nil());
+    }
+    flag
+}
+
 defsym!(FUNCTION);
 defsym!(QUOTE);
 defsym!(MACRO);
@@ -484,6 +1092,7 @@ defsym!(CLOSURE);
 defsym!(CONDITION_CASE);
 defsym!(UNWIND_PROTECT);
 defsym!(SAVE_EXCURSION);
+defsym!(SAVE_RESTRICTION);
 defsym!(SAVE_CURRENT_BUFFER);
 defsym!(WHILE);
 defsym!(INLINE);
@@ -500,10 +1109,185 @@ defsym!(AND);
 defsym!(OR);
 defsym!(INTERACTIVE);
 defsym!(CATCH);
-defsym!(THROW);
 defsym!(ERROR);
 defsym!(DEBUG);
 defsym!(VOID_VARIABLE);
 
+defsym!(QUIT);
+defsym!(ERROR_CONDITIONS);
+defsym!(ERROR_MESSAGE);
+defsym!(DEBUG_EARLY);
+defsym!(TOP_LEVEL);
+defsym!(EXIT);
+defsym!(KW_DEBUG_ON_EXIT);
+defsym!(NO_CATCH);
+defsym!(KW_SUCCESS);
+
 defvar!(DEBUG_ON_ERROR, false);
+defvar!(DEBUG_ON_SIGNAL, false);
+defvar!(DEBUG_ON_QUIT, false);
+defvar!(DEBUG_IGNORED_ERRORS);
+defvar!(DEBUGGER, sym::DEBUG_EARLY);
 defvar!(INTERNAL_MAKE_INTERPRETED_CLOSURE_FUNCTION);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::assert_lisp;
+    use std::cell::RefCell;
+
+    const SET_DEBUGGER: &str =
+        "(setq debug-on-error t dbg nil debugger #'(lambda (&rest args) (setq dbg args)))";
+
+    fn assert_debugger(setup: &str, form: &str, expect: &str) {
+        let test = format!(
+            "(progn {SET_DEBUGGER} {setup} (condition-case nil {form} ((debug error) nil)) dbg)"
+        );
+        assert_lisp(&test, expect);
+    }
+
+    #[test]
+    fn test_debug_on_error() {
+        assert_debugger("", "(signal 'foo '(1 2))", "(error (foo 1 2))");
+        assert_debugger("(setq debug-on-error nil)", "(signal 'foo nil)", "nil");
+        assert_debugger("(setq debug-on-error '(bar))", "(signal 'foo nil)", "nil");
+        assert_debugger("(setq debug-on-error '(foo))", "(signal 'foo nil)", "(error (foo))");
+        assert_debugger("", "(signal 'quit nil)", "nil");
+        assert_debugger("(setq debug-on-quit t)", "(signal 'quit nil)", "(error (quit))");
+        // Errors caught by a handler without `debug'
+        let handled = "(condition-case nil (signal 'foo nil) (error nil))";
+        assert_debugger("", handled, "nil");
+        assert_debugger("(setq debug-on-signal t)", handled, "(error (foo))");
+        // A `t' handler catches every error, but doesn't include `debug'
+        assert_debugger("", "(condition-case nil (signal 'foo nil) (t nil))", "nil");
+    }
+
+    #[test]
+    fn test_debug_ignored_errors() {
+        let form = "(signal 'foo '(\"Bad thing\"))";
+        assert_debugger("(setq debug-ignored-errors '(foo))", form, "nil");
+        assert_debugger("(setq debug-ignored-errors '(\"^Bad\"))", form, "nil");
+        assert_debugger(
+            "(setq debug-ignored-errors '(bar \"^Good\"))",
+            form,
+            "(error (foo \"Bad thing\"))",
+        );
+    }
+
+    thread_local! {
+        static BREAK_FRAMES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_frames(_: &Rto<Object>, env: &mut Rt<Env>, cx: &mut Context) -> Option<EvalError> {
+        let backtrace = env.stack.backtrace();
+        let mut frames: Vec<String> =
+            backtrace.iter().map(|x| x.func.bind(cx).to_string()).collect();
+        frames.push(frame_locals(&backtrace[1], cx).to_string());
+        BREAK_FRAMES.with(|x| *x.borrow_mut() = frames);
+        None
+    }
+
+    #[test]
+    fn test_break_loop() {
+        set_break_loop(Some(record_frames));
+        let test = "(progn (setq debug-on-error t)
+                      (defalias 'my-fn #'(lambda (x) (signal 'foo (list x))))
+                      (condition-case nil (my-fn 1) ((debug error) 'caught)))";
+        assert_lisp(test, "caught");
+        set_break_loop(None);
+        let frames = BREAK_FRAMES.with(RefCell::take);
+        assert_eq!(frames, ["signal", "my-fn", "((x . 1))"]);
+    }
+
+    #[test]
+    fn test_handler_bind() {
+        // The handler sees the error before the `condition-case' does
+        let test = "(condition-case err
+                        (handler-bind-1 #'(lambda () (signal 'my-error (list 1)))
+                                        '(my-error) #'(lambda (e) (setcar (cdr e) 2)))
+                      (my-error (cdr err)))";
+        assert_lisp(test, "(2)");
+        // Errors in the handler are not sent back to it
+        let test = "(condition-case err
+                        (handler-bind-1 #'(lambda () (signal 'my-error (list 1)))
+                                        '(my-error) #'(lambda (e) (signal 'my-error (list 2))))
+                      (my-error (cdr err)))";
+        assert_lisp(test, "(2)");
+        let test = "(catch 'done
+                      (handler-bind-1 #'(lambda () (car 1))
+                                      '(arith-error) #'(lambda (e) (throw 'done 'arith))
+                                      '(error) #'(lambda (e) (throw 'done 'error))))";
+        assert_lisp(test, "error");
+        assert_lisp("(handler-bind-1 #'(lambda () 5) '(error) #'(lambda (e) nil))", "5");
+    }
+
+    #[test]
+    fn test_ignore_error_wrapper() {
+        assert_lisp("(ignore-error-wrapper #'+ 1 2)", "3");
+        assert_lisp("(ignore-error-wrapper #'car 1)", "nil");
+        assert_lisp("(catch 'done (ignore-error-wrapper #'throw 'done 4))", "4");
+    }
+
+    #[test]
+    fn test_backtrace_frame() {
+        let test = "(progn
+                      (defalias 'bt-inner
+                        #'(lambda (x &rest rest)
+                            (list (backtrace-frame 0 'bt-inner)
+                                  (backtrace-frame 1 'bt-inner)
+                                  (backtrace--locals 0 'bt-inner)
+                                  (backtrace-eval '(+ x 1) 0 'bt-inner))))
+                      (defalias 'bt-outer #'(lambda (y) (bt-inner y 2 3)))
+                      (bt-outer 1))";
+        assert_lisp(test, "((t bt-inner 1 2 3) (t bt-outer 1) ((rest 2 3) (x . 1)) 2)");
+        assert_lisp("(backtrace-frame 0 'not-called)", "nil");
+    }
+
+    #[test]
+    fn test_mapbacktrace() {
+        let test = "(progn
+                      (setq frames nil)
+                      (defalias 'bt-map
+                        #'(lambda (a base)
+                            (mapbacktrace #'(lambda (evald func args flags)
+                                              (setq frames (cons (list evald func args flags) frames)))
+                                          base)))
+                      (bt-map 5 'bt-map)
+                      frames)";
+        assert_lisp(test, "((t bt-map (5 bt-map) nil))");
+        let test = "(progn
+                      (setq frames nil)
+                      (defalias 'bt-map
+                        #'(lambda ()
+                            (mapbacktrace #'(lambda (_evald func _args _flags)
+                                              (setq frames (cons func frames))))))
+                      (bt-map)
+                      frames)";
+        assert_lisp(test, "(bt-map mapbacktrace)");
+    }
+
+    #[test]
+    fn test_backtrace_debug() {
+        let test = "(progn
+                      (setq dbg nil flags nil debugger #'(lambda (&rest args) (setq dbg args) 10))
+                      (defalias 'bt-debug
+                        #'(lambda ()
+                            (backtrace-debug 0 t 'bt-debug)
+                            (backtrace-frame--internal
+                             #'(lambda (_evald _func _args frame-flags) (setq flags frame-flags)) 0 'bt-debug)
+                            1))
+                      (list (bt-debug) dbg flags))";
+        assert_lisp(test, "(10 (exit 1) (:debug-on-exit t))");
+    }
+
+    #[test]
+    fn test_default_toplevel_value() {
+        assert_lisp(
+            "(progn (set-default-toplevel-value 'tl-var 3) (default-toplevel-value 'tl-var))",
+            "3",
+        );
+        let test =
+            "(condition-case nil (default-toplevel-value 'tl-unbound) (void-variable 'void))";
+        assert_lisp(test, "void");
+    }
+}
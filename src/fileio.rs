@@ -1,20 +1,27 @@
 //! File I/O.
 use crate::{
+    coding::CodingSystem,
     core::{
         cons::Cons,
-        env::{Env, sym},
+        env::{CurrentBuffer, Env, sym},
         error::{Type, TypeError},
         gc::{Context, Rt},
-        object::{Number, Object, ObjectType, OptionalFlag, Symbol},
+        object::{
+            Gc, LispBuffer, Modtime, Number, Object, ObjectType, OpenBuffer, OptionalFlag, Symbol,
+        },
     },
     data::symbol_value,
+    editfns,
+    eval::EvalError,
     lisp::maybe_quit,
-    search::lisp_regex_to_rust,
+    regex_emacs::{Input, Regexp},
     sym::{FILE_NAME_HANDLER_ALIST, INHIBIT_FILE_NAME_HANDLERS, NIL},
+    timefns,
 };
 use anyhow::{Result, bail, ensure};
-use fancy_regex::Regex;
+use rune_core::macros::list;
 use rune_macros::defun;
+use std::io;
 use std::path::{Component, MAIN_SEPARATOR, Path};
 
 #[defun]
@@ -139,6 +146,9 @@ fn directory_name_p(name: &str) -> bool {
 defvar!(FILE_NAME_HANDLER_ALIST);
 defvar!(INHIBIT_FILE_NAME_HANDLERS);
 defsym!(INHIBIT_FILE_NAME_OPERATION);
+defsym!(FILE_ERROR);
+defsym!(FILE_MISSING);
+defsym!(FILE_ALREADY_EXISTS);
 
 /// Return FILENAME's handler function for OPERATION, if it has one.
 /// Otherwise, return nil.
@@ -173,9 +183,9 @@ fn find_file_name_handler_internal<'ob>(
         .filter_map(|elem| {
             let cons = elem.ok()?.cons()?;
             let regexp = cons.car().string()?;
-            let re = Regex::new(&lisp_regex_to_rust(regexp))
+            let re = Regexp::new(regexp)
                 .unwrap_or_else(|err| panic!("Invalid regexp '{regexp}': {err}"));
-            if re.is_match(filename).ok()? {
+            if re.search(&Input::new(filename), 0).ok()?.is_some() {
                 let handler = cons.cdr().symbol()?;
                 if operation != sym::INHIBIT_FILE_NAME_OPERATION
                     || !inhibit_file_name_handlers
@@ -245,34 +255,321 @@ fn test_case_sensative_call() {
     let _ = file_name_case_insensitive_p("/");
 }
 
+/// Return the error to signal for an I/O failure on `filename`. `action`
+/// describes what was being done, such as "Opening input file".
+fn file_error(
+    action: &str,
+    err: &io::Error,
+    filename: &str,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> anyhow::Error {
+    let error = match err.kind() {
+        io::ErrorKind::NotFound => sym::FILE_MISSING,
+        io::ErrorKind::AlreadyExists => sym::FILE_ALREADY_EXISTS,
+        _ => sym::FILE_ERROR,
+    };
+    // Drop the "(os error N)" suffix to match the messages of Emacs
+    let message = err.to_string();
+    let message = message.split(" (os error").next().unwrap_or_default();
+    let data = list![action, message, filename; cx];
+    EvalError::signal(error.into(), data, env).into()
+}
+
+/// The modification time of `filename`.
+fn file_modtime(filename: &str) -> Modtime {
+    match std::fs::metadata(filename).and_then(|x| x.modified()) {
+        Ok(time) => Modtime::Time(time),
+        Err(_) => Modtime::Missing,
+    }
+}
+
+/// Make the current buffer visit `filename` and record its modification time.
+fn visit_file(filename: &str, env: &mut Rt<Env>, cx: &Context) {
+    env.current_buffer.set_local_var(sym::BUFFER_FILE_NAME, cx.add(filename));
+    env.current_buffer.get_mut().modtime = file_modtime(filename);
+}
+
+/// Insert contents of file FILENAME after point.
+/// Returns list of absolute file name and number of characters inserted.
+/// If second argument VISIT is non-nil, the buffer's visited filename and
+/// last save file modtime are set.  If visiting and the file does not
+/// exist, visiting is completed before the error is signaled.
+///
+/// The optional third and fourth arguments BEG and END specify what
+/// portion of the file to insert.  These arguments count bytes in the
+/// file, not characters in the buffer.  If VISIT is non-nil, BEG and END
+/// must be nil.
+///
+/// If optional fifth argument REPLACE is non-nil, replace the current
+/// buffer contents (in the accessible portion) with the file contents.
+/// This is better than simply deleting and inserting the whole thing
+/// because (1) it preserves some marker positions (in unchanged portions
+/// at the start and end of the buffer) and (2) it puts less data in the
+/// undo list.
+#[defun]
+fn insert_file_contents<'ob>(
+    filename: &str,
+    visit: Option<Object>,
+    beg: Option<usize>,
+    end: Option<usize>,
+    replace: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let filename = expand_file_name(filename, None, env, cx)?;
+    let visit = visit.is_some_and(|x| !x.is_nil());
+    if visit && (beg.is_some() || end.is_some()) {
+        bail!("Attempt to visit less than an entire file");
+    }
+    if visit {
+        visit_file(&filename, env, cx);
+    }
+    let bytes = match std::fs::read(&filename) {
+        Ok(bytes) => bytes,
+        Err(err) => return Err(file_error("Opening input file", &err, &filename, env, cx)),
+    };
+    let end = end.unwrap_or(bytes.len()).min(bytes.len());
+    let beg = beg.unwrap_or(0).min(end);
+    let coding = env.var(sym::CODING_SYSTEM_FOR_READ, cx).unwrap_or(NIL.into());
+    let coding = CodingSystem::from_object(coding, env)?.unwrap_or(CodingSystem::UNDECIDED);
+    let (contents, coding) = coding.decode(&bytes[beg..end]);
+    env.set_var(sym::LAST_CODING_SYSTEM_USED, coding.to_object(cx))?;
+    if visit {
+        let coding = coding.to_object(cx);
+        env.current_buffer.set_local_var(sym::BUFFER_FILE_CODING_SYSTEM, coding);
+    }
+
+    // Visiting a file doesn't make the insertion undoable
+    let keep_undo =
+        !visit || env.current_buffer.local_var(sym::BUFFER_UNDO_LIST).is_none_or(|x| !x.is_nil());
+    let buffer = &mut env.current_buffer;
+    let inserted = if replace.is_some_and(|x| !x.is_nil()) {
+        replace_accessible(&contents, buffer, cx)?
+    } else {
+        let point = buffer.get().text.cursor().chars();
+        let len = contents.chars().count();
+        editfns::insert_object(buffer, cx.add(contents), cx)?;
+        // Point is left before the inserted text
+        buffer.get_mut().text.set_cursor(point);
+        len
+    };
+    if !keep_undo {
+        env.current_buffer.set_local_var(sym::BUFFER_UNDO_LIST, NIL.into());
+    }
+    Ok(list![filename, inserted; cx])
+}
+
+/// Replace the accessible portion of `buffer` with `contents`. The text at
+/// the start and end that is already the same is left alone, so markers
+/// there keep their positions. Returns the number of characters inserted.
+fn replace_accessible(contents: &str, buffer: &mut CurrentBuffer, cx: &Context) -> Result<usize> {
+    let data = buffer.get();
+    let (begv, zv) = (data.begv(), data.zv());
+    let (a, b) = data.text.slice(begv..zv);
+    let old = format!("{a}{b}");
+    let same = |(x, y): &(char, char)| x == y;
+    let prefix = old.chars().zip(contents.chars()).take_while(same).count();
+    let len = contents.chars().count();
+    let max_suffix = (zv - begv).min(len) - prefix;
+    let suffix = old
+        .chars()
+        .rev()
+        .zip(contents.chars().rev())
+        .take(max_suffix)
+        .take_while(same)
+        .count();
+
+    let start = begv + prefix;
+    if start < zv - suffix {
+        editfns::delete_text(buffer, start + 1, zv - suffix + 1, cx)?;
+    }
+    let inserted = len - prefix - suffix;
+    if inserted > 0 {
+        let middle: String = contents.chars().skip(prefix).take(inserted).collect();
+        buffer.get_mut().text.set_cursor(start);
+        editfns::insert_object(buffer, cx.add(middle), cx)?;
+    }
+    buffer.get_mut().text.set_cursor(start);
+    Ok(inserted)
+}
+
+/// Update buffer's recorded modification time from the visited file's time.
+/// Useful if the buffer was not read from the file normally
+/// or if the file itself has been changed for some known benign reason.
+/// An argument specifies the modification time value to use
+/// (instead of that of the visited file), in the form of a time value as
+/// returned by `current-time'.  The value 0 means the modification time
+/// is unknown and -1 means the file does not exist.
+#[defun]
+fn set_visited_file_modtime(
+    time_flag: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<()> {
+    let time_flag = time_flag.unwrap_or(NIL.into());
+    let modtime = match time_flag.untag() {
+        ObjectType::NIL => {
+            let filename = env.current_buffer.local_var(sym::BUFFER_FILE_NAME);
+            match filename.map(|x| x.untag()) {
+                Some(ObjectType::String(name)) => {
+                    let name = expand_file_name(name, None, env, cx)?;
+                    file_modtime(&name)
+                }
+                _ => Modtime::Unknown,
+            }
+        }
+        ObjectType::Int(0) => Modtime::Unknown,
+        ObjectType::Int(-1) => Modtime::Missing,
+        _ => Modtime::Time(timefns::lisp_to_time(time_flag)?),
+    };
+    env.current_buffer.get_mut().modtime = modtime;
+    Ok(())
+}
+
+/// Return the current buffer's recorded visited file modification time.
+/// Return 0 if the buffer is not visiting a file or if the visited file's
+/// modtime has not been determined by this function or
+/// `set-visited-file-modtime'.  Return -1 if the visited file did not exist.
+#[defun]
+fn visited_file_modtime<'ob>(env: &Rt<Env>, cx: &'ob Context) -> Object<'ob> {
+    match env.current_buffer.get().modtime {
+        Modtime::Unknown => cx.add(0),
+        Modtime::Missing => cx.add(-1),
+        Modtime::Time(time) => timefns::time_to_lisp(time, cx),
+    }
+}
+
+/// Return t if last mod time of BUF's visited file matches what BUF records.
+/// This means that the file has not been changed since it was visited or saved.
+/// If BUF is omitted or nil, it defaults to the current buffer.
+#[defun]
+fn verify_visited_file_modtime(
+    buf: Option<Gc<&LispBuffer>>,
+    env: &Rt<Env>,
+    cx: &Context,
+) -> Result<bool> {
+    let visited = |b: &OpenBuffer| {
+        let filename = b.local_var(sym::BUFFER_FILE_NAME).and_then(|x| match x.untag() {
+            ObjectType::String(name) => Some(name.to_string()),
+            _ => None,
+        });
+        (filename, b.modtime)
+    };
+    let (filename, modtime) = match buf {
+        Some(buf) => env.with_buffer(buf.untag(), visited)?,
+        None => visited(env.current_buffer.get()),
+    };
+    let Some(filename) = filename else { return Ok(true) };
+    if modtime == Modtime::Unknown {
+        return Ok(true);
+    }
+    let filename = expand_file_name(&filename, None, env, cx)?;
+    Ok(file_modtime(&filename) == modtime)
+}
+
+/// Write current region into specified file.
+/// When called from a program, requires three arguments:
+/// START, END and FILENAME.  START and END are normally buffer positions
+/// specifying the part of the buffer to write.
+/// If START is nil, that means to use the entire buffer contents; END is
+/// ignored.
+/// If START is a string, then output that string to the file
+/// instead of any buffer contents; END is ignored.
+///
+/// Optional fourth argument APPEND if non-nil means
+///   append to existing file contents (if any).  If it is a number,
+///   seek to that offset in the file before writing.
+/// Optional fifth argument VISIT, if t or a string, means
+///   set the last-save-file-modtime of buffer to this file's modtime
+///   and mark buffer not modified.
+/// If VISIT is t, the buffer is marked as visiting FILENAME.
+/// If VISIT is a string, it is a second file name;
+///   the output goes to FILENAME, but the buffer is marked as visiting VISIT.
+///
+/// The optional seventh arg MUSTBENEW, if non-nil, insists on a check
+///   for an existing file with the same name.  If MUSTBENEW is `excl',
+///   that means to get an error if the file already exists; never overwrite.
+///   Since there is no way to ask for confirmation, any other non-nil value
+///   is treated the same way.
 #[defun]
 #[expect(clippy::too_many_arguments)]
 fn write_region(
-    start: i64,
-    end: i64,
+    start: Object,
+    end: Object,
     filename: &str,
-    append: OptionalFlag,
-    visit: OptionalFlag,
+    append: Option<Object>,
+    visit: Option<Object>,
     lockname: OptionalFlag,
-    mustbenew: OptionalFlag,
-    env: &Rt<Env>,
+    mustbenew: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &Context,
 ) -> Result<()> {
-    use std::io::Write;
-    ensure!(append.is_none(), "append not implemented");
-    ensure!(visit.is_none(), "visit not implemented");
+    use std::io::{Seek, SeekFrom, Write};
     ensure!(lockname.is_none(), "lockname not implemented");
-    ensure!(mustbenew.is_none(), "mustbenew not implemented");
-    // Open filename for writing
-    let mut file = std::fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(filename)
-        .unwrap();
-    let b = env.current_buffer.get();
-    let (s1, s2) = b.slice_with_gap(start as usize, end as usize)?;
-    write!(file, "{s1}")?;
-    write!(file, "{s2}")?;
+    let filename = expand_file_name(filename, None, env, cx)?;
+    let buffer = env.current_buffer.get();
+    let contents = match start.untag() {
+        ObjectType::NIL => {
+            let (s1, s2) = buffer.text.slice(0..buffer.text.len_chars());
+            format!("{s1}{s2}")
+        }
+        ObjectType::String(string) => string.to_string(),
+        _ => {
+            let start: usize = start.try_into()?;
+            let end: usize = if end.is_nil() { start } else { end.try_into()? };
+            let (start, end) = if start <= end { (start, end) } else { (end, start) };
+            let (s1, s2) = buffer.slice_with_gap(start, end)?;
+            format!("{s1}{s2}")
+        }
+    };
+    let coding = match env.var(sym::CODING_SYSTEM_FOR_WRITE, cx).filter(|x| !x.is_nil()) {
+        Some(coding) => Some(coding),
+        None => env.var(sym::BUFFER_FILE_CODING_SYSTEM, cx),
+    };
+    let coding = coding.unwrap_or(NIL.into());
+    let coding = CodingSystem::from_object(coding, env)?.unwrap_or(CodingSystem::UTF_8_UNIX);
+    let contents = coding.encode(&contents);
+
+    let append = append.filter(|x| !x.is_nil());
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    match append.map(|x| x.untag()) {
+        Some(ObjectType::Int(_)) => options.create(true),
+        Some(_) => options.create(true).append(true),
+        None => options.create(true).truncate(true),
+    };
+    if mustbenew.is_some_and(|x| !x.is_nil()) {
+        options.create_new(true);
+    }
+    let mut file = match options.open(&filename) {
+        Ok(file) => file,
+        Err(err) => return Err(file_error("Opening output file", &err, &filename, env, cx)),
+    };
+    let written = match append.map(|x| x.untag()) {
+        Some(ObjectType::Int(offset)) => {
+            let offset = u64::try_from(offset)?;
+            file.seek(SeekFrom::Start(offset)).and_then(|_| file.write_all(&contents))
+        }
+        _ => file.write_all(&contents),
+    };
+    if let Err(err) = written {
+        return Err(file_error("Write error", &err, &filename, env, cx));
+    }
+    env.set_var(sym::LAST_CODING_SYSTEM_USED, coding.to_object(cx))?;
+
+    let visit_name = match visit.map(|x| x.untag()) {
+        Some(ObjectType::String(name)) => Some(expand_file_name(name, None, env, cx)?),
+        Some(ObjectType::TRUE) => Some(filename.clone()),
+        _ => None,
+    };
+    if let Some(name) = visit_name {
+        env.current_buffer.set_local_var(sym::BUFFER_FILE_NAME, cx.add(name));
+        env.current_buffer
+            .set_local_var(sym::BUFFER_FILE_CODING_SYSTEM, coding.to_object(cx));
+        // The modification time is the one of the file that was written
+        env.current_buffer.get_mut().modtime = file_modtime(&filename);
+    }
     Ok(())
 }
 
@@ -374,7 +671,7 @@ mod tests {
 
     #[test]
     #[should_panic(
-        expected = "Invalid regexp '\\\\(incorrect-regexp': Parsing error at position 17: Opening parenthesis without closing parenthesis"
+        expected = "Invalid regexp '\\\\(incorrect-regexp': Unmatched ( or \\("
     )]
     fn test_find_file_name_handler_panic_on_invalid_regex() {
         let roots = &RootSet::default();
@@ -391,6 +688,88 @@ mod tests {
         find_file_name_handler("example", NIL, env, cx);
     }
 
+    #[test]
+    fn test_insert_file_contents() {
+        use crate::interpreter::assert_lisp;
+        let dir = std::env::temp_dir().join(format!("rune-fileio-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("input.txt");
+        let file = file.to_str().unwrap();
+        let out = dir.join("output.txt");
+        let out = out.to_str().unwrap();
+        let buffer_contents = || std::fs::read_to_string(out).unwrap();
+
+        assert_lisp(
+            &format!(
+                r#"(progn (insert "hello world") (write-region nil nil "{file}")
+                          (delete-region (point-min) (point-max)) (insert "<>") (goto-char 2)
+                          (prog1 (list (insert-file-contents "{file}" nil 6) (point))
+                                 (write-region nil nil "{out}")))"#
+            ),
+            &format!(r#"(("{file}" 5) 2)"#),
+        );
+        assert_eq!(buffer_contents(), "<world>");
+
+        assert_lisp(
+            &format!(
+                r#"(progn (write-region "abc" nil "{file}") (write-region "de" nil "{file}" t)
+                          (write-region "X" nil "{file}" 1)
+                          (insert-file-contents "{file}") (write-region nil nil "{out}"))"#
+            ),
+            "nil",
+        );
+        assert_eq!(buffer_contents(), "aXcde");
+
+        assert_lisp(
+            &format!(
+                r#"(progn (insert "a-old-z") (write-region "a-new-z" nil "{file}")
+                          (prog1 (insert-file-contents "{file}" nil nil nil t)
+                                 (write-region nil nil "{out}")))"#
+            ),
+            &format!(r#"("{file}" 3)"#),
+        );
+        assert_eq!(buffer_contents(), "a-new-z");
+
+        assert_lisp(
+            &format!(
+                r#"(progn (insert-file-contents "{file}" t)
+                          (list (equal (buffer-file-name) "{file}")
+                                (eq (get-file-buffer "{file}") (get-buffer (buffer-name)))
+                                (verify-visited-file-modtime)))"#
+            ),
+            "(t t t)",
+        );
+        assert_lisp(
+            &format!(
+                r#"(condition-case nil (write-region "" nil "{file}" nil nil nil 'excl)
+                     (error 'exists))"#
+            ),
+            "exists",
+        );
+
+        // Line endings and invalid UTF-8 round-trip through a visited buffer
+        std::fs::write(file, b"a\xE9\r\nb\xC3\xA9\xFF").unwrap();
+        assert_lisp(
+            &format!(
+                r#"(progn (insert-file-contents "{file}" t)
+                          (prog1 (list buffer-file-coding-system (point-max))
+                                 (write-region nil nil "{out}" nil t)))"#
+            ),
+            "(iso-latin-1-dos 8)",
+        );
+        assert_eq!(std::fs::read(out).unwrap(), b"a\xE9\r\nb\xC3\xA9\xFF");
+        std::fs::write(file, b"\xC3\xA9\xFF\n").unwrap();
+        assert_lisp(
+            &format!(
+                r#"(let ((coding-system-for-read 'utf-8))
+                     (insert-file-contents "{file}")
+                     (list last-coding-system-used (point-max)))"#
+            ),
+            "(utf-8-unix 4)",
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     #[ignore = "TODO: Handle ~ in expand-file-name"]
     fn test_expand_file_name() {
@@ -7,10 +7,12 @@ use crate::{
         gc::{Context, Rt, Rto},
         object::{
             Function, Gc, HashTable, IntoObject, LispHashTable, LispString, LispVec, List,
-            ListType, NIL, Object, ObjectType, OptionalFlag, Symbol, WithLifetime,
+            ListType, NIL, Object, ObjectType, OptionalFlag, Symbol, Weakness, WithLifetime,
+            char_to_int, char_to_raw_byte, push_utf8_raw, raw_byte_to_char,
         },
     },
     data::aref,
+    intervals::{IntervalTree, copy_string_properties},
     library::filevercmp::filevercmp,
     rooted_iter,
 };
@@ -70,8 +72,67 @@ pub(crate) fn eql<'ob>(obj1: Object<'ob>, obj2: Object<'ob>) -> bool {
 
 #[defun]
 fn equal_including_properties<'ob>(o1: Object<'ob>, o2: Object<'ob>) -> bool {
-    // TODO: implement text properties
-    equal(o1, o2)
+    equal(o1, o2) && equal_properties(o1, o2)
+}
+
+/// Compare the text properties of all the strings in two objects that are
+/// already known to be `equal`.
+fn equal_properties(o1: Object, o2: Object) -> bool {
+    match (o1.untag(), o2.untag()) {
+        (ObjectType::String(s1), ObjectType::String(s2)) => {
+            let (p1, p2) = (s1.textprops(), s2.textprops());
+            let len = s1.len();
+            let mut i1 = props_over(p1.as_deref(), len);
+            let mut i2 = props_over(p2.as_deref(), len);
+            let (mut a, mut b) = (i1.next(), i2.next());
+            while let (Some((r1, v1)), Some((r2, v2))) = (&a, &b) {
+                if !plists_equal(*v1, *v2) {
+                    return false;
+                }
+                match r1.end.cmp(&r2.end) {
+                    std::cmp::Ordering::Less => a = i1.next(),
+                    std::cmp::Ordering::Greater => b = i2.next(),
+                    std::cmp::Ordering::Equal => (a, b) = (i1.next(), i2.next()),
+                }
+            }
+            true
+        }
+        (ObjectType::Cons(c1), ObjectType::Cons(c2)) => {
+            equal_properties(c1.car(), c2.car()) && equal_properties(c1.cdr(), c2.cdr())
+        }
+        (ObjectType::Vec(v1), ObjectType::Vec(v2)) => {
+            v1.iter().zip(v2.iter()).all(|(x, y)| equal_properties(x.get(), y.get()))
+        }
+        _ => true,
+    }
+}
+
+/// The property lists of a string of length `len`, covering every character.
+fn props_over<'a>(
+    tree: Option<&'a IntervalTree<'static>>,
+    len: usize,
+) -> Box<dyn Iterator<Item = (std::ops::Range<usize>, Object<'static>)> + 'a> {
+    match tree {
+        Some(tree) => Box::new(tree.iter(0, len)),
+        None => Box::new(std::iter::once((0..len, NIL))),
+    }
+}
+
+/// Whether two property lists have the same properties with `equal` values.
+fn plists_equal(p1: Object, p2: Object) -> bool {
+    fn pairs(plist: Object) -> Vec<(Object, Object)> {
+        let mut pairs = Vec::new();
+        let mut iter = plist.as_list().into_iter().flatten().flatten();
+        while let (Some(key), Some(val)) = (iter.next(), iter.next()) {
+            pairs.push((key, val));
+        }
+        pairs
+    }
+    let (p1, p2) = (pairs(p1), pairs(p2));
+    p1.len() == p2.len()
+        && p1
+            .iter()
+            .all(|(k1, v1)| p2.iter().any(|(k2, v2)| eq(*k1, *k2) && equal(*v1, *v2)))
 }
 
 #[defun]
@@ -109,14 +170,70 @@ fn plist_member<'ob>(
 }
 
 #[defun]
-pub(crate) fn prin1_to_string(object: Object, _noescape: Option<Object>) -> String {
-    format!("{object}")
+fn string_to_multibyte<'ob>(string: Object<'ob>, cx: &'ob Context) -> Result<Object<'ob>> {
+    match string.untag() {
+        ObjectType::String(_) => Ok(string),
+        ObjectType::ByteString(s) => {
+            let multibyte: String = s.iter().map(|&x| raw_byte_to_char(x)).collect();
+            Ok(cx.add(multibyte))
+        }
+        _ => Err(TypeError::new(Type::String, string).into()),
+    }
+}
+
+#[defun]
+fn string_to_unibyte<'ob>(string: Object<'ob>, cx: &'ob Context) -> Result<Object<'ob>> {
+    match string.untag() {
+        ObjectType::String(s) => {
+            let mut unibyte = Vec::with_capacity(s.len());
+            for (idx, chr) in s.chars().enumerate() {
+                match char_to_raw_byte(chr) {
+                    Some(byte) => unibyte.push(byte),
+                    None if chr.is_ascii() => unibyte.push(chr as u8),
+                    None => bail!("Can't convert the {idx}th character to unibyte"),
+                }
+            }
+            Ok(cx.add(unibyte))
+        }
+        ObjectType::ByteString(_) => Ok(string),
+        _ => Err(TypeError::new(Type::String, string).into()),
+    }
+}
+
+/// Return the bytes of the multibyte `string`, where raw byte characters are
+/// the bytes themselves.
+pub(crate) fn str_as_unibyte(string: &str) -> Vec<u8> {
+    let mut unibyte = Vec::with_capacity(string.len());
+    let mut buf = [0; 4];
+    for chr in string.chars() {
+        match char_to_raw_byte(chr) {
+            Some(byte) => unibyte.push(byte),
+            None => unibyte.extend_from_slice(chr.encode_utf8(&mut buf).as_bytes()),
+        }
+    }
+    unibyte
+}
+
+#[defun]
+fn string_as_unibyte<'ob>(string: Object<'ob>, cx: &'ob Context) -> Result<Object<'ob>> {
+    match string.untag() {
+        ObjectType::String(s) => Ok(cx.add(str_as_unibyte(s))),
+        ObjectType::ByteString(_) => Ok(string),
+        _ => Err(TypeError::new(Type::String, string).into()),
+    }
 }
 
 #[defun]
-fn string_to_multibyte(string: &LispString) -> &LispString {
-    // TODO: Handle the unibyte case
-    string
+fn string_as_multibyte<'ob>(string: Object<'ob>, cx: &'ob Context) -> Result<Object<'ob>> {
+    match string.untag() {
+        ObjectType::String(_) => Ok(string),
+        ObjectType::ByteString(s) => {
+            let mut multibyte = String::with_capacity(s.len());
+            push_utf8_raw(&mut multibyte, s);
+            Ok(cx.add(multibyte))
+        }
+        _ => Err(TypeError::new(Type::String, string).into()),
+    }
 }
 
 #[defun]
@@ -505,6 +622,9 @@ pub(crate) fn require<'ob>(
     env: &mut Rt<Env>,
     cx: &'ob mut Context,
 ) -> Result<Symbol<'ob>> {
+    // Record the require even if the feature is already loaded
+    let entry = Cons::new(sym::REQUIRE, feature.untag(cx), cx);
+    crate::lread::loadhist_attach(entry.into(), env, cx)?;
     // TODO: Fix this unsafe into_root
     let feat = unsafe { feature.untag(cx).with_lifetime() };
     if crate::data::FEATURES.lock().unwrap().contains(&feat) {
@@ -516,23 +636,53 @@ pub(crate) fn require<'ob>(
     };
     let file = file.into_obj(cx);
     root!(file, cx);
-    match crate::lread::load(file, noerror, None, cx, env) {
+    match crate::lread::load(file, noerror, None, None, None, cx, env) {
         Ok(_) => Ok(feature.untag(cx)),
         Err(e) => Err(e),
     }
 }
 
 #[defun]
-pub(crate) fn concat(sequences: &[Object]) -> Result<String> {
-    let mut concat = String::new();
+pub(crate) fn concat<'ob>(sequences: &[Object], cx: &'ob Context) -> Result<Object<'ob>> {
+    let mut chars = Vec::new();
+    // The result is multibyte if any character didn't come from a unibyte
+    // string or a raw byte. Strings with only ASCII are treated as unibyte.
+    let mut multibyte = false;
+    // strings with text properties and where they start in the result
+    let mut propertized = Vec::new();
     for elt in sequences {
         match elt.untag() {
-            ObjectType::String(string) => concat += string,
-            ObjectType::NIL => continue,
-            _ => bail!("Currently only concatenating strings are supported"),
+            ObjectType::String(string) => {
+                multibyte |= !string.is_ascii();
+                if string.textprops().is_some() {
+                    propertized.push((string, chars.len()));
+                }
+                chars.extend(string.chars());
+            }
+            ObjectType::ByteString(string) => {
+                chars.extend(string.iter().map(|&x| raw_byte_to_char(x)));
+            }
+            ObjectType::Cons(_) | ObjectType::Vec(_) => {
+                for chr in vconcat(&[*elt], cx)?.untag().iter() {
+                    let chr = char::try_from(chr.get())?;
+                    multibyte |= !chr.is_ascii() && char_to_raw_byte(chr).is_none();
+                    chars.push(chr);
+                }
+            }
+            ObjectType::NIL => {}
+            obj => bail!(TypeError::new(Type::Sequence, obj)),
+        }
+    }
+    if multibyte || chars.iter().all(char::is_ascii) {
+        let result = chars.into_iter().collect::<String>().into_obj(cx).untag();
+        for (string, offset) in propertized {
+            copy_string_properties(string, 0, string.len(), result, offset, cx)?;
         }
+        Ok(result.into())
+    } else {
+        let bytes = chars.into_iter().map(|x| char_to_raw_byte(x).unwrap_or(x as u8));
+        Ok(cx.add(bytes.collect::<Vec<u8>>()))
     }
-    Ok(concat)
 }
 
 #[defun]
@@ -540,10 +690,14 @@ pub(crate) fn vconcat<'ob>(sequences: &[Object], cx: &'ob Context) -> Result<Gc<
     let mut concated: Vec<Object> = Vec::new();
     for elt in sequences {
         match elt.untag() {
-            // TODO: need to correctly handle unibyte strings (no unicode codepoints)
             ObjectType::String(string) => {
                 for chr in string.chars() {
-                    concated.push((chr as i64).into());
+                    concated.push(char_to_int(chr).into());
+                }
+            }
+            ObjectType::ByteString(string) => {
+                for byte in string.iter() {
+                    concated.push(i64::from(*byte).into());
                 }
             }
             ObjectType::Cons(cons) => {
@@ -753,8 +907,16 @@ pub(crate) fn levenshtein_distance<T: PartialEq, I: Iterator<Item = T>>(s1: I, s
 }
 
 #[defun]
-pub(crate) fn string_bytes(string: &str) -> usize {
-    string.len()
+pub(crate) fn string_bytes(string: Object) -> Result<usize> {
+    match string.untag() {
+        // Raw bytes take two bytes in the Emacs multibyte representation
+        ObjectType::String(s) => Ok(s
+            .chars()
+            .map(|x| if char_to_raw_byte(x).is_some() { 2 } else { x.len_utf8() })
+            .sum()),
+        ObjectType::ByteString(s) => Ok(s.len()),
+        _ => Err(TypeError::new(Type::String, string).into()),
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -835,25 +997,65 @@ pub(crate) fn clear_string(string: &LispString) -> Result<Object<'_>> {
 
 defsym!(KW_TEST);
 defsym!(KW_DOCUMENTATION);
+defsym!(KW_WEAKNESS);
+defsym!(KEY);
+defsym!(VALUE);
+defsym!(KEY_OR_VALUE);
+defsym!(KEY_AND_VALUE);
+
+/// Find the value of `keyword` in a plist of keyword arguments.
+fn keyword_arg<'ob>(keyword_args: &[Object<'ob>], keyword: Symbol) -> Result<Option<Object<'ob>>> {
+    let Some(i) = keyword_args.iter().step_by(2).position(|&x| x == keyword) else {
+        return Ok(None);
+    };
+    match keyword_args.get((i * 2) + 1) {
+        Some(val) => Ok(Some(*val)),
+        None => bail!("Missing keyword value for {keyword}"),
+    }
+}
 
 #[defun]
 pub(crate) fn make_hash_table<'ob>(
     keyword_args: &[Object<'ob>],
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    let kw_test_pos = keyword_args.iter().step_by(2).position(|&x| x == sym::KW_TEST);
-    if let Some(i) = kw_test_pos {
-        let Some(val) = keyword_args.get((i * 2) + 1) else {
-            bail!("Missing keyword value for :test")
-        };
-        if *val != sym::EQ && *val != sym::EQUAL && *val != sym::EQL {
-            // TODO: we are currently only using `equal', but eq should be okay
-            bail!("only `eq' and `equal' keywords support for make-hash-table :test. Found {val}");
-        }
-    }
+    if let Some(val) = keyword_arg(keyword_args, sym::KW_TEST)?
+        && val != sym::EQ
+        && val != sym::EQUAL
+        && val != sym::EQL
+    {
+        // TODO: we are currently only using `equal', but eq should be okay
+        bail!("only `eq' and `equal' keywords support for make-hash-table :test. Found {val}");
+    }
+    let weakness = match keyword_arg(keyword_args, sym::KW_WEAKNESS)? {
+        None => Weakness::None,
+        Some(val) => match val.untag() {
+            ObjectType::NIL => Weakness::None,
+            ObjectType::TRUE => Weakness::KeyAndValue,
+            ObjectType::Symbol(s) if s == sym::KEY => Weakness::Key,
+            ObjectType::Symbol(s) if s == sym::VALUE => Weakness::Value,
+            ObjectType::Symbol(s) if s == sym::KEY_OR_VALUE => Weakness::KeyOrValue,
+            ObjectType::Symbol(s) if s == sym::KEY_AND_VALUE => Weakness::KeyAndValue,
+            _ => bail!("Invalid hash table weakness: {val}"),
+        },
+    };
     // TODO, the rest of the keywords need to be supported here
     let map = HashTable::with_hasher(std::hash::BuildHasherDefault::default());
-    Ok(cx.add(map))
+    let table = map.into_obj(cx);
+    table.untag().set_weakness(weakness);
+    Ok(table.into())
+}
+
+/// Return the weakness of TABLE.
+#[defun]
+fn hash_table_weakness(table: &LispHashTable) -> Object<'_> {
+    match table.weakness() {
+        Weakness::None => NIL,
+        Weakness::Key => sym::KEY.into(),
+        Weakness::Value => sym::VALUE.into(),
+        Weakness::KeyOrValue => sym::KEY_OR_VALUE.into(),
+        Weakness::KeyAndValue => sym::KEY_AND_VALUE.into(),
+    }
 }
 
 #[defun]
@@ -929,7 +1131,7 @@ fn maphash(
 }
 
 #[defun]
-fn copy_sequence<'ob>(arg: Object<'ob>, cx: &'ob Context) -> Result<Object<'ob>> {
+pub(crate) fn copy_sequence<'ob>(arg: Object<'ob>, cx: &'ob Context) -> Result<Object<'ob>> {
     match arg.untag() {
         ObjectType::Vec(x) => Ok(cx.add(x.to_vec())),
         ObjectType::Cons(x) => {
@@ -945,27 +1147,37 @@ fn copy_sequence<'ob>(arg: Object<'ob>, cx: &'ob Context) -> Result<Object<'ob>>
             }
             Ok(slice_into_list(&elements, tail, cx))
         }
-        ObjectType::String(x) => Ok(cx.add(x.to_owned())),
+        ObjectType::String(x) => {
+            let copy = x.inner().to_owned().into_obj(cx).untag();
+            copy_string_properties(x, 0, x.len(), copy, 0, cx)?;
+            Ok(copy.into())
+        }
         ObjectType::NIL => Ok(NIL),
         _ => Err(TypeError::new(Type::Sequence, arg).into()),
     }
 }
 
 #[defun]
-fn substring(string: &str, from: Option<usize>, to: Option<usize>) -> Result<String> {
-    if from.unwrap_or_default() > string.len() || to.unwrap_or_default() > string.len() {
+pub(crate) fn substring<'ob>(
+    string: &LispString,
+    from: Option<i64>,
+    to: Option<i64>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let len = string.len();
+    let index = |idx: i64| usize::try_from(if idx < 0 { idx + len as i64 } else { idx }).ok();
+    let start = index(from.unwrap_or(0));
+    let end = index(to.unwrap_or(len as i64));
+    let (Some(start), Some(end)) = (start, end) else {
         bail!("substring args out of range for {string} : {from:?} {to:?}");
-    }
-    let new_string = match (from, to) {
-        (None, None) => string,
-        (None, Some(t)) => &string[..t],
-        (Some(f), None) => &string[f..],
-        (Some(f), Some(t)) => {
-            let range = if f > t { t..f } else { f..t };
-            &string[range]
-        }
     };
-    Ok(new_string.to_owned())
+    if start > end || end > len {
+        bail!("substring args out of range for {string} : {from:?} {to:?}");
+    }
+    let new_string: String = string.chars().skip(start).take(end - start).collect();
+    let new_string = new_string.into_obj(cx).untag();
+    copy_string_properties(string, start, end, new_string, 0, cx)?;
+    Ok(new_string.into())
 }
 
 defsym!(MD5);
@@ -1061,6 +1273,13 @@ mod test {
         }};
     }
 
+    #[test]
+    fn test_hash_table_weakness() {
+        assert_lisp("(hash-table-weakness (make-hash-table))", "nil");
+        assert_lisp("(hash-table-weakness (make-hash-table :weakness 'key))", "key");
+        assert_lisp("(hash-table-weakness (make-hash-table :weakness t))", "key-and-value");
+    }
+
     #[test]
     fn test_take() {
         assert_lisp("(take 2 '(1 2 3 4))", "(1 2)");
@@ -1324,4 +1543,77 @@ mod test {
         );
         assert_lisp("(let ((str \"\")) (clear-string str) str)", "\"\"");
     }
+
+    #[test]
+    fn test_multibyte_strings() {
+        assert_lisp(r#"(string-bytes (concat "é" "\377"))"#, "4");
+        assert_lisp(r#"(list (length "a\377") (string-bytes "a\377"))"#, "(2 2)");
+        assert_lisp(r#"(aref (string-to-multibyte "\377") 0)"#, "4194303");
+        assert_lisp(r#"(string-to-unibyte (string-to-multibyte "a\377"))"#, r#""a\377""#);
+        assert_lisp(r#"(string-as-unibyte (concat "é" "\377"))"#, r#""\303\251\377""#);
+        assert_lisp(r#"(string-as-multibyte "\303\251\377")"#, r#""é\377""#);
+        assert_lisp("(vconcat (concat '(97 4194303)))", "[97 255]");
+        assert_lisp(
+            "(list (unibyte-char-to-multibyte 200) (multibyte-char-to-unibyte 4194248))",
+            "(4194248 200)",
+        );
+    }
+
+    #[test]
+    fn test_string_properties() {
+        assert_lisp(r#"(equal-including-properties #("ab" 0 1 (face bold)) "ab")"#, "nil");
+        assert_lisp(
+            r#"(equal-including-properties #("ab" 0 1 (a 1 b 2)) #("ab" 0 1 (b 2 a 1)))"#,
+            "t",
+        );
+        assert_lisp(
+            r#"(equal-including-properties (propertize "ab" 'face 'bold) #("ab" 0 2 (face bold)))"#,
+            "t",
+        );
+        assert_lisp(
+            r#"(equal-including-properties (substring #("abcd" 1 3 (x 1)) 2) #("cd" 0 1 (x 1)))"#,
+            "t",
+        );
+        assert_lisp(
+            r#"(equal-including-properties (concat "a" #("bc" 0 1 (x 1)) #("d" 0 1 (y 2)))
+                                           #("abcd" 1 2 (x 1) 3 4 (y 2)))"#,
+            "t",
+        );
+        assert_lisp(
+            r#"(let ((s (copy-sequence "abc"))) (put-text-property 1 2 'x 1 s)
+                 (list (get-text-property 1 'x s) (get-text-property 2 'x s) s))"#,
+            r#"(1 nil "abc")"#,
+        );
+        assert_lisp(r#"(prin1-to-string (propertize "ab" 'x 1))"#, r##""#(\"ab\" 0 2 (x 1))""##);
+        assert_lisp(r#"(substring "héllo" -3 -1)"#, r#""ll""#);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_multibyte_strings_elprop() {
+        proptest!(|(bytes in prop::collection::vec(any::<u8>(), 0..6), text in "[a-zé€]{0,3}")| {
+            let bytes: Vec<_> = bytes.iter().map(ToString::to_string).collect();
+            let bytes = bytes.join(" ");
+            assert_elprop![
+                r#"(let* ((u (unibyte-string {})) (m (string-to-multibyte u)) (s (concat {:?} u)))
+                     (list (length s) (string-bytes s) (vconcat s) (string-bytes m) (vconcat m)
+                           (string-to-unibyte m) (string-as-unibyte s) (concat u u)))"#,
+                bytes,
+                text
+            ];
+        });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_raw_byte_chars_elprop() {
+        proptest!(|(byte in any::<u8>())| {
+            assert_elprop![
+                r#"(let ((c (unibyte-char-to-multibyte {})))
+                     (list c (multibyte-char-to-unibyte c) (aref (string-to-multibyte (unibyte-string {})) 0)))"#,
+                byte,
+                byte
+            ];
+        });
+    }
 }
@@ -0,0 +1,103 @@
+//! Indentation functions.
+use crate::core::{
+    env::{Env, sym},
+    gc::{Context, Rt},
+    object::ObjectType,
+};
+use crate::editfns::insert_object;
+use anyhow::Result;
+use rune_macros::defun;
+
+/// The value of `tab-width`, or 8 if it is not a sensible width.
+fn tab_width(env: &Rt<Env>, cx: &Context) -> usize {
+    match env.var(sym::TAB_WIDTH, cx).map(|x| x.untag()) {
+        Some(ObjectType::Int(width @ 1..=1000)) => width as usize,
+        _ => 8,
+    }
+}
+
+/// Return the horizontal position of point. Beginning of line is column 0.
+/// This is calculated by adding together the widths of all the displayed
+/// representations of the character between the start of the previous line
+/// and point (e.g., control characters will have a width of 2, tabs will
+/// have a variable width).
+#[defun]
+pub(crate) fn current_column(env: &Rt<Env>, cx: &Context) -> usize {
+    let tab_width = tab_width(env, cx);
+    let buf = env.current_buffer.get();
+    let point = buf.text.cursor().chars();
+    let mut start = point;
+    while start > buf.begv() && buf.text.char_at(start - 1) != Some('\n') {
+        start -= 1;
+    }
+    // TODO: account for the display width of wide characters
+    (start..point)
+        .filter_map(|pos| buf.text.char_at(pos))
+        .fold(0, |column, c| match c {
+            '\t' => (column / tab_width + 1) * tab_width,
+            c if c < ' ' || c == '\x7f' => column + 2,
+            _ => column + 1,
+        })
+}
+
+/// Indent from point with tabs and spaces until COLUMN is reached.
+/// Optional second argument MINIMUM says always do at least MINIMUM spaces
+/// even if that goes past COLUMN; by default, MINIMUM is zero.
+///
+/// Whether this uses tabs or spaces depends on `indent-tabs-mode'.
+///
+/// The return value is the column where the insertion ends.
+#[defun]
+pub(crate) fn indent_to(
+    column: usize,
+    minimum: Option<usize>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<usize> {
+    let from = current_column(env, cx);
+    let to = column.max(from + minimum.unwrap_or(0));
+    if to == from {
+        return Ok(to);
+    }
+    let mut indent = String::new();
+    let mut start = from;
+    if env.var(sym::INDENT_TABS_MODE, cx).is_some_and(|x| !x.is_nil()) {
+        let tab_width = tab_width(env, cx);
+        let tabs = to / tab_width - from / tab_width;
+        if tabs > 0 {
+            indent.extend(std::iter::repeat_n('\t', tabs));
+            start = to / tab_width * tab_width;
+        }
+    }
+    indent.extend(std::iter::repeat_n(' ', to - start));
+    insert_object(&mut env.current_buffer, cx.add(indent), cx)?;
+    Ok(to)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_current_column() {
+        assert_lisp("(progn (insert \"ab\\ncd\") (current-column))", "2");
+        assert_lisp("(progn (insert \"a\\tb\") (current-column))", "9");
+        assert_lisp("(progn (insert \"a\\tb\") (let ((tab-width 4)) (current-column)))", "5");
+    }
+
+    #[test]
+    fn test_indent_to() {
+        assert_lisp(
+            "(progn (insert \"ab\") (list (indent-to 5) (buffer-substring 1 (point-max))))",
+            "(5 \"ab   \")",
+        );
+        assert_lisp(
+            "(progn (insert \"abcd\") (list (indent-to 2 1) (buffer-substring 1 (point-max))))",
+            "(5 \"abcd \")",
+        );
+        assert_lisp(
+            "(progn (insert \"ab\") (let ((indent-tabs-mode t)) (list (indent-to 10) (buffer-substring 1 (point-max)))))",
+            "(10 \"ab\t  \")",
+        );
+    }
+}
@@ -2,13 +2,13 @@
 use crate::{
     core::{
         cons::{Cons, ElemStreamIter, IntoArray},
-        env::{CallFrame, Env, sym},
+        env::{CallFrame, Env, Handler, HandlerType, sym},
         error::{Type, TypeError},
         gc::{Context, Rt, Rto, Slot},
         object::{Function, Gc, List, ListType, NIL, Object, ObjectType, Symbol, TRUE, TagType},
     },
     data::LispError,
-    eval::{ErrorType, EvalError, EvalResult, add_trace},
+    eval::{EvalError, EvalResult, add_trace},
     rooted_iter,
 };
 use anyhow::Context as _;
@@ -30,7 +30,7 @@ pub(crate) fn eval<'ob>(
     env: &mut Rt<Env>,
     cx: &'ob mut Context,
 ) -> Result<Object<'ob>, anyhow::Error> {
-    cx.garbage_collect(false);
+    crate::alloc::maybe_garbage_collect(env, cx)?;
     root!(vars, new(Vec<Slot<&Cons>>), cx);
     if let Some(ObjectType::Cons(cons)) = lexical.map(|x| x.untag(cx)) {
         for var in cons.elements() {
@@ -45,14 +45,20 @@ pub(crate) fn eval<'ob>(
 
 impl Interpreter<'_, '_> {
     fn eval_form<'ob>(&mut self, rt: &Rto<Object>, cx: &'ob mut Context) -> EvalResult<'ob> {
-        match rt.untag(cx) {
+        let result = match rt.untag(cx) {
             ObjectType::Symbol(sym) => self.var_ref(sym, cx),
             ObjectType::Cons(_) => {
                 let x = rt.try_as().unwrap();
                 self.eval_sexp(x, cx)
             }
             _ => Ok(rt.bind(cx)),
-        }
+        };
+        let err = match result {
+            Ok(x) => return Ok(rebind!(x, cx)),
+            Err(e) => e,
+        };
+        // Special forms signal errors outside of any function call
+        Err(crate::eval::signal_error(err, self.env, cx))
     }
 
     pub(crate) fn eval_sexp<'ob>(
@@ -81,10 +87,10 @@ impl Interpreter<'_, '_> {
                 sym::FUNCTION => self.eval_function(forms, cx),
                 sym::INTERACTIVE => Ok(NIL), // TODO: implement
                 sym::CATCH => self.catch(forms, cx),
-                sym::THROW => self.throw(forms.bind(cx), cx),
                 sym::CONDITION_CASE => self.condition_case(forms, cx),
                 sym::SAVE_CURRENT_BUFFER => self.save_current_buffer(forms, cx),
                 sym::SAVE_EXCURSION => self.save_excursion(forms, cx),
+                sym::SAVE_RESTRICTION => self.save_restriction(forms, cx),
                 sym::UNWIND_PROTECT => self.unwind_protect(forms, cx),
                 _ => {
                     root!(sym, cx);
@@ -100,40 +106,22 @@ impl Interpreter<'_, '_> {
         let Some(tag) = forms.next()? else {
             bail_err!(LispError::arg_cnt(sym::CATCH, 1, 0, cx))
         };
-        // push this tag on the catch stack
-        self.env.catch_stack.push(tag);
-        let result = match self.implicit_progn(forms, cx) {
-            Ok(x) => Ok(rebind!(x, cx)),
-            Err(e) => {
-                if let ErrorType::Throw(id) = e.error
-                    && let Some((throw_tag, data)) = self.env.get_exception(id)
-                {
-                    let catch_tag = self.env.catch_stack.last().unwrap();
-                    // TODO: Remove binds
-                    if catch_tag == throw_tag {
-                        return Ok(data.bind(cx));
-                    }
-                }
-                Err(e)
+        let tag = rebind!(self.eval_form(tag, cx)?);
+        let index = self.env.handlers.len();
+        self.env.handlers.push(Handler::new(HandlerType::Catch, tag));
+        let err = match self.implicit_progn(forms, cx) {
+            Ok(x) => {
+                self.env.handlers.truncate(index);
+                return Ok(rebind!(x, cx));
             }
+            Err(e) => crate::eval::signal_error(e, self.env, cx),
         };
-        // pop this tag from the catch stack
-        self.env.catch_stack.pop();
-        result
-    }
-
-    fn throw<'ob>(&mut self, obj: Object, cx: &'ob Context) -> EvalResult<'ob> {
-        let [tag, value] = match obj.into_array()? {
-            Ok(x) => x,
-            Err(e) => bail_err!(LispError::arg_cnt(sym::THROW, 2, e, cx)),
-        };
-
-        // Need to check now that there is a catch, because we may have a
-        // condition-case along the unwind path
-        if self.env.catch_stack.iter().any(|x| x.bind(cx) == tag) {
-            Err(EvalError::throw(tag, value, self.env))
+        self.env.handlers.truncate(index);
+        // TODO: Remove binds
+        if err.is_handled_by(index) {
+            Ok(err.handler_value(self.env, cx))
         } else {
-            Err(error!("No catch for {tag}"))
+            Err(err)
         }
     }
 
@@ -151,8 +139,17 @@ impl Interpreter<'_, '_> {
             // (defvar x)
             None => NIL,
         };
-        self.env.defvar(name.bind(cx), value)?;
-        Ok(value)
+        root!(value, cx);
+        // (defvar x y doc)
+        if let Some(doc) = forms.next()? {
+            let doc = doc.bind(cx);
+            if !doc.is_nil() {
+                self.env.set_prop(name.bind(cx), sym::VARIABLE_DOCUMENTATION, doc);
+            }
+        }
+        self.env.defvar(name.bind(cx), value.bind(cx))?;
+        crate::lread::loadhist_attach(name.bind(cx).into(), self.env, cx)?;
+        Ok(value.bind(cx))
     }
 
     fn eval_call<'ob>(
@@ -196,6 +193,7 @@ impl Interpreter<'_, '_> {
         }
         let frame = &mut CallFrame::new(self.env);
         frame.push_arg_slice(Rt::bind_slice(args, cx));
+        frame.stack.set_frame_function(sym.bind(cx).into());
         let name = sym.bind(cx).name().to_owned();
         func.call(frame, Some(&name), cx)
     }
@@ -430,8 +428,8 @@ impl Interpreter<'_, '_> {
             let mut iter = self.vars.iter().rev();
             match iter.find_map(|cons| (cons.car(cx) == sym).then(|| cons.cdr(cx))) {
                 Some(value) => Ok(value),
-                None => match self.env.vars.get(sym) {
-                    Some(v) => Ok(v.bind(cx)),
+                None => match self.env.var(sym, cx) {
+                    Some(v) => Ok(v),
                     None => Err(error!("Void variable: {sym}")),
                 },
             }
@@ -596,14 +594,40 @@ impl Interpreter<'_, '_> {
     }
 
     fn save_excursion<'ob>(&mut self, form: &Rto<Object>, cx: &'ob mut Context) -> EvalResult<'ob> {
-        let point = self.env.current_buffer.get().text.cursor();
-        let buffer = self.env.current_buffer.get().lisp_buffer(cx);
-        root!(buffer, cx);
-        let result = rebind!(self.eval_progn(form, cx)?);
-        self.env.set_buffer(buffer.bind(cx));
-        let buf = self.env.current_buffer.get_mut();
-        buf.text.set_cursor(point.chars());
-        Ok(result)
+        let depth = self.env.binding_depth();
+        self.env.save_excursion(cx);
+        self.eval_restoring(depth, form, cx)
+    }
+
+    fn save_restriction<'ob>(
+        &mut self,
+        form: &Rto<Object>,
+        cx: &'ob mut Context,
+    ) -> EvalResult<'ob> {
+        let depth = self.env.binding_depth();
+        self.env.save_restriction(cx);
+        self.eval_restoring(depth, form, cx)
+    }
+
+    /// Evaluate the body `form` and then undo the bindings made since the
+    /// binding stack was `depth` deep, even if the body exits non-locally.
+    fn eval_restoring<'ob>(
+        &mut self,
+        depth: usize,
+        form: &Rto<Object>,
+        cx: &'ob mut Context,
+    ) -> EvalResult<'ob> {
+        match self.eval_progn(form, cx) {
+            Ok(x) => {
+                root!(x, cx);
+                self.env.unbind_to(depth, cx);
+                Ok(x.bind(cx))
+            }
+            Err(e) => {
+                self.env.unbind_to(depth, cx);
+                Err(e)
+            }
+        }
     }
 
     fn save_current_buffer<'ob>(
@@ -627,72 +651,71 @@ impl Interpreter<'_, '_> {
         let Some(bodyform) = forms.next()? else {
             bail_err!(LispError::arg_cnt(sym::CONDITION_CASE, 2, 1, cx))
         };
+        // Push a handler for each clause. The first clause is pushed last so
+        // that it is the innermost.
+        let (clauses, _) = condition_case_clauses(form.bind(cx))?;
+        let base = self.env.handlers.len();
+        for clause in clauses.iter().rev() {
+            self.env.handlers.push(Handler::new(HandlerType::ConditionCase, clause.car()));
+        }
+        let top = self.env.handlers.len();
         let err = match self.eval_form(bodyform, cx) {
-            Ok(x) => return Ok(rebind!(x, cx)),
-            Err(e) => e,
+            Ok(x) => {
+                root!(x, cx);
+                self.env.handlers.truncate(base);
+                let (_, success) = condition_case_clauses(form.bind(cx))?;
+                let Some(success) = success else { return Ok(x.bind(cx)) };
+                let body = success.cdr();
+                root!(body, cx);
+                return self.eval_clause(var, x, body, cx);
+            }
+            Err(e) => crate::eval::signal_error(e, self.env, cx),
         };
-        if matches!(err.error, ErrorType::Throw(_)) {
-            return Err(err);
-        }
-        while let Some(handler) = forms.next()? {
-            match handler.untag(cx) {
-                ObjectType::Cons(cons) => {
-                    // Check that conditions match
-                    let condition = cons.car();
-                    match condition.untag() {
-                        ObjectType::Symbol(sym::ERROR | sym::VOID_VARIABLE) => {}
-                        // TODO: Remove this once error handling is correctly implemented
-                        ObjectType::Symbol(s) if s.name() == "cl--generic-cyclic-definition" => {}
-                        ObjectType::Cons(conditions) => {
-                            for condition in conditions {
-                                let condition = condition?;
-                                // TODO: Handle different error symbols
-                                if condition != sym::DEBUG && condition != sym::ERROR {
-                                    bail_err!("non-error conditions {condition} not yet supported")
-                                }
-                            }
-                        }
-                        _ => bail_err!("Invalid condition handler: {condition}"),
-                    }
-
-                    // Call handlers with error
-                    let error = match err.error {
-                        ErrorType::Signal(id) => {
-                            let Some((sym, data)) = self.env.get_exception(id) else {
-                                unreachable!("Exception not found")
-                            };
-                            Cons::new(sym, data, cx)
-                        }
-                        ErrorType::Err(err) => {
-                            let err_str = format!("{err}");
-                            if let Ok(lisp_error) = err.downcast::<LispError>() {
-                                lisp_error.bind(cx)
-                            } else {
-                                // TODO: Need to remove the anyhow branch once
-                                // full errors are implemented
-                                Cons::new(sym::ERROR, err_str, cx)
-                            }
-                        }
-                        _ => unreachable!("Error type throw was not handled"),
-                    };
+        self.env.handlers.truncate(base);
+        let Some(index) = (base..top).find(|x| err.is_handled_by(*x)) else { return Err(err) };
+        let error = err.handler_value(self.env, cx);
+        root!(error, cx);
+        let (clauses, _) = condition_case_clauses(form.bind(cx))?;
+        let body = clauses[top - 1 - index].cdr();
+        root!(body, cx);
+        self.eval_clause(var, error, body, cx)
+    }
 
-                    let binding = Cons::new(var, error, cx);
-                    self.vars.push(binding);
-                    let list: List = match cons.cdr().try_into() {
-                        Ok(x) => x,
-                        Err(_) => return Ok(NIL),
-                    };
-                    rooted_iter!(handlers, list, cx);
-                    let result = self.implicit_progn(handlers, cx)?;
-                    self.vars.pop();
-                    return Ok(result);
-                }
-                ObjectType::NIL => {}
-                invalid => bail_err!("Invalid condition handler: {invalid}"),
-            }
+    /// Evaluate the `body` of a `condition-case` clause with `var` bound to
+    /// `value`.
+    fn eval_clause<'ob>(
+        &mut self,
+        var: &Rto<Object>,
+        value: &Rto<Object>,
+        body: &Rto<Object>,
+        cx: &'ob mut Context,
+    ) -> EvalResult<'ob> {
+        // An improper clause like `(error . 7)` has no body
+        let ObjectType::Cons(_) = body.untag(cx) else { return Ok(NIL) };
+        let binding = Cons::new(var.bind(cx), value.bind(cx), cx);
+        self.vars.push(binding);
+        let result = self.eval_progn(body, cx);
+        self.vars.pop();
+        result
+    }
+}
+
+/// Return the handler clauses of a `condition-case` form, which starts with its
+/// variable, and its `:success` clause.
+fn condition_case_clauses<'ob>(
+    form: Object<'ob>,
+) -> Result<(Vec<&'ob Cons>, Option<&'ob Cons>), EvalError> {
+    let mut clauses = Vec::new();
+    let mut success = None;
+    for clause in form.as_list()?.skip(2) {
+        match clause?.untag() {
+            ObjectType::Cons(clause) if clause.car() == sym::KW_SUCCESS => success = Some(clause),
+            ObjectType::Cons(clause) => clauses.push(clause),
+            ObjectType::NIL => {}
+            invalid => bail_err!("Invalid condition handler: {invalid}"),
         }
-        Err(err)
     }
+    Ok((clauses, success))
 }
 
 pub(crate) fn call_closure<'ob>(
@@ -702,7 +725,7 @@ pub(crate) fn call_closure<'ob>(
     env: &mut Rt<Env>,
     cx: &'ob mut Context,
 ) -> EvalResult<'ob> {
-    cx.garbage_collect(false);
+    crate::alloc::maybe_garbage_collect(env, cx)?;
     let closure: &Cons = closure.untag(cx);
     match closure.car().untag() {
         ObjectType::Symbol(sym::CLOSURE) => {
@@ -717,6 +740,21 @@ pub(crate) fn call_closure<'ob>(
     }
 }
 
+/// Return the lexical variables seen by the body of the interpreted `closure`
+/// when it is called with `args`. The highest priority bindings are last.
+pub(crate) fn closure_locals<'a>(
+    closure: &'a Cons,
+    args: &[Object<'a>],
+    cx: &'a Context,
+) -> AnyResult<Vec<&'a Cons>> {
+    let mut forms = closure.cdr().as_list()?;
+    let env = forms.next().transpose()?.unwrap_or_default();
+    let mut vars = parse_closure_env(env)?;
+    let arg_list = forms.next().transpose()?.unwrap_or_default();
+    bind_args(arg_list, args, &mut vars, "lambda", cx)?;
+    Ok(vars)
+}
+
 fn bind_variables<'a>(
     forms: &mut ElemStreamIter<'_>,
     args: &[Object<'a>],
@@ -1105,6 +1143,20 @@ mod test {
         check_error("(condition-case nil (if))", cx);
         check_error("(condition-case nil (if) nil)", cx);
         check_error("(condition-case nil (if) 5 (error 7))", cx);
+        // Only clauses with matching conditions handle the error
+        check_interpreter(
+            "(condition-case nil (signal 'arith-error nil) (void-variable 1) (arith-error 2))",
+            2,
+            cx,
+        );
+        check_interpreter(
+            "(condition-case nil (condition-case nil (signal 'arith-error nil) (void-variable 1)) (error 2))",
+            2,
+            cx,
+        );
+        check_error("(condition-case nil (signal 'arith-error nil) (void-variable 1))", cx);
+        check_interpreter("(condition-case v (+ 1 2) (:success (* v 2)) (error 0))", 6, cx);
+        check_interpreter("(condition-case v (if) (:success (* v 2)) (error 0))", 0, cx);
     }
 
     #[test]
@@ -1120,7 +1172,26 @@ mod test {
         check_interpreter("(condition-case nil (throw 1 2) (error 3))", 3, cx);
         check_interpreter("(catch 1 (condition-case nil (throw 1 2) (error 3)))", 2, cx);
         check_interpreter("(catch 1 (catch 2 (throw 1 3)))", 3, cx);
+        check_interpreter("(catch 'a (catch 'b (throw 'a 3)) 4)", 3, cx);
+        let data = list!(1, 2; cx);
+        root!(data, cx);
+        check_interpreter("(condition-case err (throw 1 2) (no-catch (cdr err)))", data, cx);
         check_error("(throw 1 2)", cx);
         check_error("(catch 2 (throw 3 4))", cx);
     }
+
+    #[test]
+    fn test_save_excursion() {
+        assert_lisp(
+            r#"(progn (insert "hello") (list (save-excursion (goto-char 1) (insert "<") (point)) (point)))"#,
+            "(2 7)",
+        );
+        assert_lisp(
+            r#"(progn (insert "hello")
+                      (condition-case nil
+                          (save-restriction (save-excursion (narrow-to-region 2 4) (car 1)))
+                        (error (list (point) (point-min) (point-max)))))"#,
+            "(6 1 6)",
+        );
+    }
 }
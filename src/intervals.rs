@@ -7,9 +7,9 @@ use crate::{
         cons::Cons,
         error::{Type, TypeError},
         gc::{IntoRoot, Slot, Trace},
-        object::{NIL, Object, ObjectType, TagType, WithLifetime},
+        object::{LispString, NIL, Object, ObjectType, TagType, WithLifetime},
     },
-    fns::eq,
+    fns::{build_list, eq},
     textprops::add_properties,
 };
 
@@ -99,6 +99,26 @@ impl<'ob> IntervalTree<'ob> {
         self.tree.clean(|a, b| eq(**a, **b), |n| n.is_nil());
     }
 
+    /// Copies the properties in [start..end) into `dest`, shifted so that `start` lands on
+    /// `offset`. The property lists are copied so the two trees don't share structure.
+    pub(crate) fn copy_into<'new>(
+        &self,
+        start: usize,
+        end: usize,
+        dest: &mut IntervalTree<'new>,
+        offset: usize,
+        cx: &'new Context,
+    ) -> Result<()> {
+        for (range, plist) in self.iter(start, end) {
+            if plist.is_nil() {
+                continue;
+            }
+            let plist = build_list(cx.bind(plist).as_list()?, cx)?;
+            dest.set_properties(range.start - start + offset, range.end - start + offset, plist);
+        }
+        Ok(())
+    }
+
     pub(crate) fn iter<'a>(&'a self, start: usize, end: usize) -> IntervalIntersections<'ob, 'a> {
         IntervalIntersections::new(self, start, end)
     }
@@ -112,6 +132,20 @@ impl<'ob> IntervalTree<'ob> {
     }
 }
 
+/// Copies the properties of `src` in [start..end) onto the string `dest`, shifted so that
+/// `start` lands on `offset`.
+pub(crate) fn copy_string_properties(
+    src: &LispString,
+    start: usize,
+    end: usize,
+    dest: &LispString,
+    offset: usize,
+    cx: &Context,
+) -> Result<()> {
+    let Some(props) = src.textprops() else { return Ok(()) };
+    props.copy_into(start, end, &mut *dest.textprops_mut(cx)?, offset, cx)
+}
+
 /// Removes all occurrences of `sym` from the property list `props`.
 ///
 /// This function scans through a property list (a cons cell chain) and removes any
@@ -1,5 +1,5 @@
 #[repr(u64)]
-enum CharBits {
+pub(crate) enum CharBits {
     Alt = 0x0400000,
     Super = 0x0800000,
     Hyper = 0x1000000,
@@ -1,21 +1,24 @@
 //! Loading elisp from files and strings.
+use crate::coding::CodingSystem;
 use crate::core::cons::Cons;
 use crate::core::env::{Env, sym};
 use crate::core::error::{Type, TypeError};
 use crate::core::gc::{Context, Rt, Rto};
 use crate::core::object::{
-    Function, Gc, LispString, NIL, Object, ObjectType, OptionalFlag, Symbol, TRUE, TagType,
-    WithLifetime,
+    Function, Gc, LispString, NIL, Object, ObjectType, OpenBuffer, OptionalFlag, Symbol, TRUE,
+    TagType, WithLifetime, char_to_int, int_to_char,
 };
 use crate::reader;
+use crate::search::BufferText;
 use crate::{interpreter, rooted_iter};
-use anyhow::{Context as _, anyhow};
+use anyhow::Context as _;
 use anyhow::{Result, bail, ensure};
 use fallible_streaming_iterator::FallibleStreamingIterator;
 use rune_core::macros::{call, rebind, root};
 use rune_macros::defun;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 fn check_lower_bounds(idx: Option<i64>, len: usize) -> Result<usize> {
     let len = len as i64;
@@ -39,6 +42,18 @@ fn check_upper_bounds(idx: Option<i64>, len: usize) -> Result<usize> {
     Ok(idx as usize)
 }
 
+/// Convert the character index `pos` in `string` to a byte index.
+fn char_to_byte(string: &str, pos: usize) -> usize {
+    string.char_indices().nth(pos).map_or(string.len(), |(idx, _)| idx)
+}
+
+/// Read one Lisp expression which is represented as text by STRING.
+/// Returns a cons: (OBJECT-READ . FINAL-STRING-INDEX).
+/// FINAL-STRING-INDEX is an integer giving the position of the next
+/// remaining character in STRING. START and END optionally delimit
+/// a substring of STRING from which to read; they default to 0 and
+/// (length STRING) respectively. Negative values are counted from
+/// the end of STRING.
 #[defun]
 pub(crate) fn read_from_string<'ob>(
     string: &str,
@@ -46,29 +61,168 @@ pub(crate) fn read_from_string<'ob>(
     end: Option<i64>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    let len = string.len();
+    let len = string.chars().count();
     let start = check_lower_bounds(start, len)?;
     let end = check_upper_bounds(end, len)?;
+    let beg_byte = char_to_byte(string, start);
+    let end_byte = char_to_byte(string, end);
+    let slice = &string[beg_byte..end_byte];
 
-    let (obj, new_pos) = match reader::read(&string[start..end], cx) {
+    let (obj, new_pos) = match reader::read(slice, cx) {
         Ok((obj, pos)) => (obj, pos),
         Err(mut e) => {
-            e.update_pos(start);
+            e.update_pos(beg_byte);
             bail!(e);
         }
     };
+    let new_pos = start + slice[..new_pos].chars().count();
     Ok(Cons::new(obj, new_pos as i64, cx).into())
 }
 
-pub(crate) fn load_internal(contents: &str, cx: &mut Context, env: &mut Rt<Env>) -> Result<bool> {
+/// Read one Lisp expression as text from STREAM, return as Lisp object.
+/// If STREAM is nil, use the value of `standard-input' (which see).
+/// STREAM or the value of `standard-input' may be:
+///  a buffer (read from point and advance it)
+///  a marker (read from where it points and advance it)
+///  a function (call it with no arguments for each character,
+///      call it with a char as argument to push a char back)
+///  a string (takes text from string, starting at the beginning)
+///  t (read text line using minibuffer and use it, or read from
+///     standard input in batch mode).
+#[defun]
+pub(crate) fn read<'ob>(
+    stream: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let stream = match stream.map(|x| x.bind(cx)) {
+        Some(stream) if !stream.is_nil() => stream,
+        _ => env.var(sym::STANDARD_INPUT, cx).unwrap_or(TRUE),
+    };
+    match stream.untag() {
+        ObjectType::String(string) => Ok(read_str(string, cx)?.0),
+        ObjectType::Buffer(buffer) => {
+            let cx: &'ob Context = cx;
+            env.with_buffer_mut(buffer, |buf| {
+                let point = buf.text.cursor().chars();
+                let (obj, end) = read_buffer(buf, point, cx)?;
+                buf.text.set_cursor(end);
+                Ok(obj)
+            })?
+        }
+        ObjectType::Marker(marker) => {
+            let (Some(buffer), Some(pos)) = (marker.buffer(), marker.position()) else {
+                bail!("Marker does not point anywhere");
+            };
+            let cx: &'ob Context = cx;
+            env.with_buffer_mut(buffer, |buf| {
+                let (obj, end) = read_buffer(buf, pos, cx)?;
+                buf.set_marker(marker, end);
+                Ok(obj)
+            })?
+        }
+        ObjectType::TRUE => {
+            let mut text = String::new();
+            let end = read_incrementally(&mut text, env, cx, |text, _, _| {
+                Ok(std::io::stdin().read_line(text)? != 0)
+            })?;
+            Ok(read_str(&text[..end], cx)?.0)
+        }
+        _ => {
+            let func: Function = stream.try_into()?;
+            root!(func, cx);
+            let mut text = String::new();
+            let end = read_incrementally(&mut text, env, cx, |text, env, cx| {
+                let chr = call!(func; env, cx)?;
+                match chr.untag() {
+                    ObjectType::Int(chr) => text.push(int_to_char(chr)?),
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            })?;
+            // Push back the characters that were read past the end of the sexp
+            for chr in text[end..].chars().rev() {
+                call!(func, char_to_int(chr); env, cx)?;
+            }
+            Ok(read_str(&text[..end], cx)?.0)
+        }
+    }
+}
+
+/// Read one Lisp expression as text from STREAM, return as Lisp object.
+/// This is the same as `read', except that symbols with positions are not
+/// supported yet, so the symbols that are read are bare symbols.
+#[defun]
+fn read_positioning_symbols<'ob>(
+    stream: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    read(stream, env, cx)
+}
+
+/// Read a sexp from `string`, returning the object and the byte position after
+/// it.
+fn read_str<'ob>(string: &str, cx: &'ob Context) -> Result<(Object<'ob>, usize)> {
+    reader::read(string, cx).map_err(Into::into)
+}
+
+/// Read a sexp from the 0-based position `pos` in `buf`, returning the object
+/// and the position after it.
+fn read_buffer<'ob>(
+    buf: &mut OpenBuffer,
+    pos: usize,
+    cx: &'ob Context,
+) -> Result<(Object<'ob>, usize)> {
+    let end = buf.zv();
+    ensure!(buf.begv() <= pos && pos <= end, "Args out of range: {}", pos + 1);
+    let text = BufferText::new(&mut buf.text, pos, end);
+    let (obj, bytes) = read_str(text.hay, cx)?;
+    Ok((obj, pos + text.hay[..bytes].chars().count()))
+}
+
+/// Collect text for a stream that can only be read one piece at a time, and
+/// return the byte position after the first sexp in it. `fill` adds more text
+/// and returns false at the end of input. The text is collected before it is
+/// read, because the reader can't call into lisp while it is allocating.
+fn read_incrementally(
+    text: &mut String,
+    env: &mut Rt<Env>,
+    cx: &mut Context,
+    mut fill: impl FnMut(&mut String, &mut Rt<Env>, &mut Context) -> Result<bool>,
+) -> Result<usize> {
+    loop {
+        let eof = !fill(text, env, cx)?;
+        match reader::read(text, cx) {
+            // A sexp that ends with the text could still be continued, unless
+            // it ends with a delimiter
+            Ok((_, end)) if end < text.len() || eof || text.ends_with([')', ']', '"']) => {
+                return Ok(end);
+            }
+            Ok(_) => {}
+            Err(e) if !eof && e.is_incomplete() => {}
+            Err(e) => bail!(e),
+        }
+    }
+}
+
+/// Evaluate each form in `contents`. Byte-compiled files are already
+/// macroexpanded, so `macroexpand` is false for them.
+fn eval_contents(
+    contents: &str,
+    macroexpand: bool,
+    cx: &mut Context,
+    env: &mut Rt<Env>,
+) -> Result<bool> {
     let mut pos = 0;
-    let macroexpand: Option<Function> = None;
-    root!(macroexpand, cx);
-    if let Some(fun) = sym::INTERNAL_MACROEXPAND_FOR_LOAD.func(cx) {
-        macroexpand.set(Some(fun));
+    let expander: Option<Function> = None;
+    root!(expander, cx);
+    if macroexpand && let Some(fun) = sym::INTERNAL_MACROEXPAND_FOR_LOAD.func(cx) {
+        expander.set(Some(fun));
     }
     loop {
-        let (obj, new_pos) = match reader::read(&contents[pos..], cx) {
+        let load_file_name = env.vars.get(sym::LOAD_FILE_NAME).map_or(NIL, |x| x.bind(cx));
+        let (obj, new_pos) = match reader::read_for_load(&contents[pos..], load_file_name, cx) {
             Ok((obj, pos)) => (obj, pos),
             Err(reader::Error::EmptyStream) => return Ok(true),
             Err(mut e) => {
@@ -82,7 +236,7 @@ pub(crate) fn load_internal(contents: &str, cx: &mut Context, env: &mut Rt<Env>)
             println!("-----READ END-----");
         }
         root!(obj, cx);
-        let result = if let Some(fun) = macroexpand.as_ref() {
+        let result = if let Some(fun) = expander.as_ref() {
             eager_expand(obj, fun, env, cx)
         } else {
             interpreter::eval(obj, None, env, cx)
@@ -121,88 +275,239 @@ fn eager_expand<'ob>(
     interpreter::eval(result, None, env, cx)
 }
 
-fn file_in_path(file: &str, path: &str) -> Option<PathBuf> {
-    let path = Path::new(path).join(file);
-    if path.exists() {
-        Some(path)
-    } else {
-        let with_ext = path.with_extension("el");
-        with_ext.exists().then_some(with_ext)
+/// Collect the strings in the list stored in `var`.
+fn string_list(var: Symbol, env: &Rt<Env>, cx: &Context) -> Result<Vec<String>> {
+    let list = env.var(var, cx).unwrap_or_default();
+    let mut strings = Vec::new();
+    for elt in list.as_list().with_context(|| format!("`{var}' was not a list"))? {
+        match elt?.untag() {
+            ObjectType::String(string) => strings.push(string.to_string()),
+            x => return Err(TypeError::new(Type::String, x).into()),
+        }
     }
+    Ok(strings)
+}
+
+/// Every combination of `load-suffixes` and `load-file-rep-suffixes`.
+fn load_suffixes(env: &Rt<Env>, cx: &Context) -> Result<Vec<String>> {
+    let rep_suffixes = string_list(sym::LOAD_FILE_REP_SUFFIXES, env, cx)?;
+    let suffixes = string_list(sym::LOAD_SUFFIXES, env, cx)?;
+    Ok(suffixes
+        .iter()
+        .flat_map(|suffix| rep_suffixes.iter().map(move |rep| format!("{suffix}{rep}")))
+        .collect())
+}
+
+/// Return the list of all suffixes that `load' should try.
+/// Uses the variables `load-suffixes' and `load-file-rep-suffixes'.
+#[defun]
+fn get_load_suffixes<'ob>(env: &Rt<Env>, cx: &'ob Context) -> Result<Object<'ob>> {
+    let suffixes: Vec<Object> = load_suffixes(env, cx)?.into_iter().map(|x| cx.add(x)).collect();
+    Ok(crate::fns::slice_into_list(&suffixes, None, cx))
 }
 
-fn find_file_in_load_path(file: &str, cx: &Context, env: &Rt<Env>) -> Result<PathBuf> {
-    let load_path = env.vars.get(sym::LOAD_PATH).unwrap();
-    let paths = load_path.bind(cx).as_list().context("`load-path' was not a list")?;
-    let mut final_file = None;
-    for path in paths {
-        match path?.untag() {
-            ObjectType::String(path) => {
-                if let Some(x) = file_in_path(file, path) {
-                    final_file = Some(x);
-                    break;
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|x| x.modified()).ok()
+}
+
+/// Find `file` with one of `suffixes`. Relative names are looked up in the
+/// current directory and then each directory of `load-path`, and the first
+/// directory with a match wins. Within a directory the earliest suffix is used,
+/// unless `load-prefer-newer` is set, in which case the newest file is.
+fn locate_file(
+    file: &str,
+    suffixes: &[String],
+    env: &Rt<Env>,
+    cx: &Context,
+) -> Result<Option<PathBuf>> {
+    let mut dirs = vec![PathBuf::new()];
+    if Path::new(file).is_relative() {
+        let load_path = env.var(sym::LOAD_PATH, cx).unwrap_or_default();
+        for dir in load_path.as_list().context("`load-path' was not a list")? {
+            match dir?.untag() {
+                ObjectType::String(dir) => dirs.push(PathBuf::from(dir.to_string())),
+                ObjectType::NIL => dirs.push(PathBuf::from(".")),
+                x => {
+                    return Err(TypeError::new(Type::String, x))
+                        .context("Found non-string in `load-path'");
                 }
             }
-            x => {
-                return Err(TypeError::new(Type::String, x))
-                    .context("Found non-string in `load-path'");
+        }
+    }
+    let prefer_newer = env.var(sym::LOAD_PREFER_NEWER, cx).is_some_and(|x| !x.is_nil());
+    for dir in dirs {
+        let mut found = suffixes
+            .iter()
+            .map(|suffix| dir.join(format!("{file}{suffix}")))
+            .filter(|path| path.is_file());
+        let Some(first) = found.next() else { continue };
+        if !prefer_newer {
+            return Ok(Some(first));
+        }
+        // Keep the earliest suffix when the times are the same
+        let newest =
+            found.fold(
+                first,
+                |newest, path| {
+                    if modified(&path) > modified(&newest) { path } else { newest }
+                },
+            );
+        return Ok(Some(newest));
+    }
+    Ok(None)
+}
+
+/// Check the `;ELC` header of a byte-compiled file and decode its contents.
+fn decode_elc(bytes: &[u8], file: &Path) -> Result<String> {
+    match bytes {
+        [b';', b'E', b'L', b'C', version, ..] if *version >= MIN_ELC_VERSION => {}
+        _ => bail!("File `{}' was not compiled in Emacs", file.display()),
+    }
+    Ok(CodingSystem::UTF_8_UNIX.decode(bytes).0)
+}
+
+/// The oldest version of the byte-code format that can be loaded.
+const MIN_ELC_VERSION: u8 = 20;
+
+/// Record `entry` as a definition made by the file currently being loaded. It
+/// is added to `load-history` when the load finishes.
+pub(crate) fn loadhist_attach(entry: Object, env: &mut Rt<Env>, cx: &Context) -> Result<()> {
+    if env.var(sym::LOAD_IN_PROGRESS, cx).is_none_or(|x| x.is_nil()) {
+        return Ok(());
+    }
+    let list = env.var(sym::CURRENT_LOAD_LIST, cx).unwrap_or_default();
+    env.set_var(sym::CURRENT_LOAD_LIST, Cons::new(entry, list, cx).into())
+}
+
+/// Add an entry for `file` to the front of `load-history` with the definitions
+/// from `current-load-list`, replacing any earlier entry for the same file.
+fn build_load_history(file: &str, env: &mut Rt<Env>, cx: &Context) -> Result<()> {
+    let mut entries = Vec::new();
+    let current = env.var(sym::CURRENT_LOAD_LIST, cx).unwrap_or_default();
+    for entry in current.as_list()? {
+        entries.push(entry?);
+    }
+    entries.reverse();
+    let entries = crate::fns::slice_into_list(&entries, None, cx);
+    let mut history = vec![Cons::new(cx.add(file), entries, cx).into()];
+    let old = env.var(sym::LOAD_HISTORY, cx).unwrap_or_default();
+    for entry in old.as_list()? {
+        let entry = entry?;
+        let same_file = match entry.untag() {
+            ObjectType::Cons(cons) => {
+                matches!(cons.car().untag(), ObjectType::String(name) if name == file)
             }
+            _ => false,
+        };
+        if !same_file {
+            history.push(entry);
         }
     }
-    final_file.ok_or_else(|| anyhow!("Unable to find file `{file}' in load-path"))
+    env.set_var(sym::LOAD_HISTORY, crate::fns::slice_into_list(&history, None, cx))
 }
 
+/// Execute a file of Lisp code named FILE.
+/// First try FILE with `.elc' appended, then try with `.el', then try
+/// FILE unmodified (the exact suffixes in the exact order are determined
+/// by `load-suffixes').  Environment variable references in FILE are
+/// replaced with their values by calling `substitute-in-file-name'.
+/// This function searches the directories in `load-path'.
+///
+/// If optional second arg NOERROR is non-nil,
+/// report no error if FILE doesn't exist.
+/// Print messages at start and end of loading unless
+/// optional third arg NOMESSAGE is non-nil.
+/// If optional fourth arg NOSUFFIX is non-nil, don't try adding
+/// suffixes to the specified name FILE.
+/// If optional fifth arg MUST-SUFFIX is non-nil, insist on
+/// the suffix `.elc' or `.el'; don't accept just FILE unless
+/// it ends in one of those suffixes or includes a directory name.
+///
+/// If this function fails to find a file, it may look for different
+/// representations of that file before trying another file.
+/// It does so by adding the non-empty suffixes in `load-file-rep-suffixes'
+/// to the file name.
+///
+/// If `load-prefer-newer' is non-nil, the newest of the matching files is
+/// loaded, rather than the first one.
+///
+/// Return t if the file exists and loads successfully.
 #[defun]
 pub(crate) fn load(
     file: &Rto<Gc<&LispString>>,
     noerror: OptionalFlag,
     nomessage: OptionalFlag,
+    nosuffix: OptionalFlag,
+    must_suffix: OptionalFlag,
     cx: &mut Context,
     env: &mut Rt<Env>,
 ) -> Result<bool> {
     let noerror = noerror.is_some();
     let nomessage = nomessage.is_some();
-    let file: &str = file.untag(cx);
-    let final_file = if Path::new(file).exists() {
-        PathBuf::from(file)
+    let name: &str = file.untag(cx);
+    let suffixes = if nosuffix.is_some() {
+        vec![String::new()]
     } else {
-        match find_file_in_load_path(file, cx, env) {
-            Ok(x) => x,
-            Err(e) => {
-                return if noerror { Ok(false) } else { Err(e) };
-            }
+        let mut suffixes = load_suffixes(env, cx)?;
+        if must_suffix.is_none() || name.ends_with(".el") || name.ends_with(".elc") {
+            suffixes.extend(string_list(sym::LOAD_FILE_REP_SUFFIXES, env, cx)?);
         }
+        suffixes
+    };
+    let final_file = match locate_file(name, &suffixes, env, cx)? {
+        Some(x) => std::path::absolute(x)?,
+        None if noerror => return Ok(false),
+        None => bail!("Unable to find file `{name}' in load-path"),
     };
 
-    let filename = String::from(file);
+    let compiled = final_file.extension().is_some_and(|x| x == "elc");
+    let source = final_file.to_str().and_then(|x| x.strip_suffix('c')).map(Path::new);
+    let newer_source = compiled && source.is_some_and(|x| modified(x) > modified(&final_file));
+
+    let filename = String::from(name);
+    // Like Emacs, a newer source file is noted in the loading message, or in
+    // a message of its own when those are suppressed
+    let note = if newer_source { " (compiled; note, source file is newer)" } else { "" };
     if !nomessage {
-        println!("Loading {filename}...");
+        println!("Loading {filename}{note}...");
+    } else if let Some(source) = source.filter(|_| newer_source) {
+        println!(
+            "Source file `{}' newer than byte-compiled file; using older file",
+            source.display()
+        );
     }
-    let new_load_file = cx.add(final_file.to_string_lossy().to_string());
-    let prev_load_file = match env.vars.get_mut(sym::LOAD_FILE_NAME) {
-        Some(val) => {
-            let prev = val.bind(cx);
-            val.set(new_load_file);
-            prev
-        }
-        None => NIL,
-    };
-    root!(prev_load_file, cx);
-    let result = match fs::read_to_string(&final_file)
+    let contents = fs::read(&final_file)
         .with_context(|| format!("Couldn't open file {:?}", final_file.as_os_str()))
-    {
-        Ok(content) => load_internal(&content, cx, env),
-        Err(e) => match noerror {
-            true => Ok(false),
-            false => Err(e),
-        },
+        .and_then(|bytes| match compiled {
+            true => decode_elc(&bytes, &final_file),
+            false => String::from_utf8(bytes).map_err(Into::into),
+        });
+    let contents = match contents {
+        Ok(contents) => contents,
+        Err(_) if noerror => return Ok(false),
+        Err(e) => return Err(e),
     };
 
-    if !nomessage && result.is_ok() {
-        println!("Loading {filename} Done");
+    let load_file = final_file.to_string_lossy().into_owned();
+    let load_file_name = cx.add(load_file.as_str());
+    root!(load_file_name, cx);
+    env.varbind(sym::LOAD_FILE_NAME, load_file_name.bind(cx), cx);
+    env.varbind(sym::LOAD_TRUE_FILE_NAME, load_file_name.bind(cx), cx);
+    env.varbind(sym::LOAD_IN_PROGRESS, TRUE, cx);
+    env.varbind(sym::CURRENT_LOAD_LIST, NIL, cx);
+    let result = eval_contents(&contents, !compiled, cx, env)
+        .and_then(|_| build_load_history(&load_file, env, cx));
+    env.unbind(4, cx);
+    result?;
+
+    if !nomessage {
+        println!("Loading {filename}{note} Done");
+    }
+    if let Some(func) = sym::DO_AFTER_LOAD_EVALUATION.func(cx) {
+        root!(func, cx);
+        call!(func, load_file_name.bind(cx); env, cx)?;
     }
-    env.vars.insert(sym::LOAD_FILE_NAME, &*prev_load_file);
-    result
+    Ok(true)
 }
 
 #[defun]
@@ -233,11 +538,20 @@ pub(crate) fn intern_soft(string: Object, obarray: OptionalFlag) -> Result<Symbo
 }
 
 defsym!(INTERNAL_MACROEXPAND_FOR_LOAD);
+defsym!(DO_AFTER_LOAD_EVALUATION);
+defsym!(DEFUN);
 defvar!(LEXICAL_BINDING, true);
 defvar!(CURRENT_LOAD_LIST);
 defvar!(LOAD_HISTORY);
 defvar!(LOAD_PATH, list![format!("{}/lisp", env!("CARGO_MANIFEST_DIR"))]);
 defvar!(LOAD_FILE_NAME);
+defvar!(LOAD_TRUE_FILE_NAME);
+defvar!(LOAD_IN_PROGRESS);
+defvar!(LOAD_SUFFIXES, list![".elc", ".el"]);
+defvar!(LOAD_FILE_REP_SUFFIXES, list![""]);
+defvar!(LOAD_PREFER_NEWER);
+defvar!(AFTER_LOAD_FUNCTIONS);
+defvar!(STANDARD_INPUT, true);
 defvar!(BYTE_BOOLEAN_VARS);
 defvar!(MACROEXP__DYNVARS);
 defvar!(AFTER_LOAD_ALIST);
@@ -249,13 +563,85 @@ mod test {
     use crate::core::gc::RootSet;
     use rune_core::macros::root;
 
+    #[test]
+    fn test_read_streams() {
+        use crate::interpreter::assert_lisp;
+        assert_lisp(r#"(read-from-string "(a) b" 3)"#, "(b . 5)");
+        assert_lisp(r#"(cdr (read-from-string "\u00e9 b"))"#, "1");
+        assert_lisp(r#"(read-from-string "ab" -1)"#, "(b . 2)");
+        assert_lisp(r#"(read "(1 2) 3")"#, "(1 2)");
+        assert_lisp(
+            r#"(let ((buf (get-buffer-create "read-test")))
+                 (set-buffer buf) (insert "foo (bar)") (goto-char 1)
+                 (list (read buf) (point) (read buf) (point)))"#,
+            "(foo 4 (bar) 10)",
+        );
+        assert_lisp(
+            r#"(let ((m (make-marker)))
+                 (set-buffer (get-buffer-create "read-marker-test"))
+                 (insert "[1 2] 3") (set-marker m 1)
+                 (list (read m) (marker-position m) (point)))"#,
+            "([1 2] 6 8)",
+        );
+        assert_lisp(
+            r#"(let* ((text "foo bar") (i 0)
+                      (obj (read #'(lambda (&optional c)
+                                     (if c (setq i (1- i))
+                                       (if (< i (length text))
+                                           (prog1 (aref text i) (setq i (1+ i)))))))))
+                 (list obj i))"#,
+            "(foo 3)",
+        );
+        assert_lisp(r#"(let ((standard-input "(x . y)")) (read))"#, "(x . y)");
+    }
+
+    #[test]
+    fn test_load_files() {
+        use crate::interpreter::assert_lisp;
+        let dir = std::env::temp_dir().join(format!("rune-load-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("elc-test.el"), "(setq elc-test 'source)").unwrap();
+        fs::write(dir.join("elc-test.elc"), ";ELC\x1C\0\0\0\n(setq elc-test 'compiled)").unwrap();
+        fs::write(dir.join("bad-test.elc"), "(setq elc-test 'bad)").unwrap();
+        fs::write(
+            dir.join("hist-test.el"),
+            "(defvar hist-a 1) (defalias 'hist-f 'car) (provide 'hist-test)",
+        )
+        .unwrap();
+        let path = dir.to_str().unwrap();
+        let bindings = format!(
+            r#"((load-path '("{path}")) (load-suffixes '(".elc" ".el"))
+                 (load-file-rep-suffixes '("")))"#
+        );
+        assert_lisp(&format!("(let {bindings} (load \"elc-test\" nil t) elc-test)"), "compiled");
+        assert_lisp(&format!("(let {bindings} (load \"elc-test.el\" nil t) elc-test)"), "source");
+        assert_lisp(&format!("(progn (load \"{path}/elc-test.el\" nil t t) elc-test)"), "source");
+        assert_lisp(
+            &format!(
+                "(let {bindings} (condition-case nil (load \"bad-test\" nil t) (error 'failed)))"
+            ),
+            "failed",
+        );
+        assert_lisp(&format!("(let {bindings} (load \"missing\" t t))"), "nil");
+        assert_lisp(
+            &format!("(let {bindings} (load \"hist-test\" nil t) (cdr (car load-history)))"),
+            "(hist-a (defun . hist-f) (provide . hist-test))",
+        );
+        assert_lisp(
+            "(let ((load-suffixes '(\".elc\" \".el\")) (load-file-rep-suffixes '(\"\" \".gz\")))
+               (get-load-suffixes))",
+            "(\".elc\" \".elc.gz\" \".el\" \".el.gz\")",
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_load() {
         let roots = &RootSet::default();
         let cx = &mut Context::new(roots);
         sym::init_symbols();
         root!(env, new(Env), cx);
-        load_internal("(setq foo 1) (setq bar 2) (setq baz 1.5)", cx, env).unwrap();
+        eval_contents("(setq foo 1) (setq bar 2) (setq baz 1.5)", true, cx, env).unwrap();
 
         let obj = reader::read("(+ foo bar baz)", cx).unwrap().0;
         root!(obj, cx);
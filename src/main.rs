@@ -12,8 +12,11 @@ mod bytecode;
 mod casefiddle;
 mod character;
 mod chartab;
+mod cmds;
+mod coding;
 mod data;
 mod dired;
+mod doc;
 mod editfns;
 mod emacs;
 mod eval;
@@ -21,28 +24,36 @@ mod fileio;
 mod filelock;
 mod floatfns;
 mod fns;
+mod indent;
 mod interpreter;
 mod intervals;
 mod keymap;
 mod library;
 mod lisp;
 mod lread;
+mod marker;
+mod overlay;
+mod pdumper;
 mod print;
 mod reader;
+mod regex_emacs;
 mod search;
+mod syntax;
 mod textprops;
 mod threads;
 mod timefns;
+mod undo;
 
 use crate::core::{
     env::{Env, intern, sym},
-    gc::{Context, RootSet, Rt},
-    object::{Gc, LispString, NIL},
+    gc::{Context, RootSet, Rt, Rto},
+    object::{Gc, LispString, NIL, Object, display_slice},
 };
-use crate::eval::EvalError;
+use crate::eval::{ErrorType, EvalError};
 use clap::Parser;
 use rune_core::macros::root;
 use std::io::{self, Write};
+use std::path::Path;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -55,6 +66,12 @@ struct Args {
     no_bootstrap: bool,
     #[arg(long)]
     eval_stdin: bool,
+    /// Bootstrap and then write the state to an image FILE
+    #[arg(long, value_name = "FILE")]
+    dump: Option<String>,
+    /// Start from an image FILE created with --dump instead of bootstrapping
+    #[arg(long, value_name = "FILE", conflicts_with = "dump")]
+    image: Option<String>,
 }
 
 fn main() -> Result<(), ()> {
@@ -66,14 +83,26 @@ fn main() -> Result<(), ()> {
 
     sym::init_symbols();
     crate::core::env::init_variables(cx, env);
-    crate::data::defalias(intern("not", cx), (sym::NULL).into(), None)
+    crate::data::defalias(intern("not", cx), (sym::NULL).into(), None, env, cx)
         .expect("null should be defined");
 
     if args.eval_stdin {
         return eval_stdin(cx, env);
     }
 
-    if !args.no_bootstrap {
+    if let Some(image) = &args.image {
+        if let Err(e) = crate::pdumper::load_image(Path::new(image), env, cx) {
+            eprintln!("Error: {e}");
+            return Err(());
+        }
+    } else if let Some(file) = &args.dump {
+        env.vars.insert(sym::DUMP_MODE, cx.add("pdump"));
+        bootstrap(env, cx)?;
+        env.vars.insert(sym::DUMP_MODE, NIL);
+        return crate::pdumper::dump(Path::new(file), env, cx).map_err(|e| {
+            eprintln!("Error: {e}");
+        });
+    } else if !args.no_bootstrap {
         bootstrap(env, cx)?;
     }
 
@@ -94,6 +123,7 @@ fn parens_closed(buffer: &str) -> bool {
 }
 
 fn repl(env: &mut Rt<Env>, cx: &mut Context) {
+    crate::eval::set_break_loop(Some(break_loop));
     let mut buffer = String::new();
     let stdin = io::stdin();
     loop {
@@ -121,21 +151,121 @@ fn repl(env: &mut Rt<Env>, cx: &mut Context) {
         root!(obj, cx);
         match interpreter::eval(obj, None, env, cx) {
             Ok(val) => println!("{val}"),
-            Err(e) => {
-                eprintln!("Error: {e}");
-                if let Ok(e) = e.downcast::<EvalError>() {
+            Err(e) => match e.downcast::<EvalError>() {
+                Ok(e) if is_abort(&e, env) => println!("Aborted"),
+                Ok(e) => {
+                    eprintln!("Error: {e}");
                     e.print_backtrace();
                 }
+                Err(e) => eprintln!("Error: {e}"),
+            },
+        }
+        buffer.clear();
+    }
+}
+
+const BREAK_HELP: &str = "\
+Commands:
+  bt     show the backtrace
+  f N    select frame N
+  l      show the locals of the selected frame
+  c      continue unwinding the error
+  q      abort to the top level
+Anything else is evaluated in the selected frame.";
+
+/// The break loop entered by the REPL when an error calls the debugger. The
+/// frames of the error are still live, so they can be inspected before the
+/// error continues to unwind.
+fn break_loop(error: &Rto<Object>, env: &mut Rt<Env>, cx: &mut Context) -> Option<EvalError> {
+    println!("Debugger entered--Lisp error: {}", error.bind(cx));
+    print_frames(env, cx);
+    println!("Type `h' for help");
+    let mut selected = 0;
+    let mut buffer = String::new();
+    let stdin = io::stdin();
+    loop {
+        print!("debug[{selected}]> ");
+        io::stdout().flush().unwrap();
+        if stdin.read_line(&mut buffer).unwrap() == 0 {
+            return None;
+        }
+        if !parens_closed(&buffer) {
+            continue;
+        }
+        let input = buffer.trim();
+        if input == "c" {
+            return None;
+        } else if input == "q" {
+            return Some(EvalError::throw(sym::TOP_LEVEL.into(), NIL, env));
+        } else if input == "h" {
+            println!("{BREAK_HELP}");
+        } else if input == "bt" {
+            print_frames(env, cx);
+        } else if input == "l" {
+            print_locals(selected, env, cx);
+        } else if let Some(frame) = input.strip_prefix("f ") {
+            match frame.trim().parse() {
+                Ok(frame) if frame < env.stack.backtrace().len() => {
+                    selected = frame;
+                    print_locals(selected, env, cx);
+                }
+                _ => eprintln!("No frame {frame}"),
             }
+        } else if !input.is_empty() {
+            eval_in_frame(input, selected, env, cx);
         }
         buffer.clear();
     }
 }
 
+fn print_frames(env: &Rt<Env>, cx: &Context) {
+    for (i, frame) in env.stack.backtrace().iter().enumerate() {
+        let args = Rt::bind_slice(frame.args, cx);
+        println!("{i:>3}: {} {}", frame.func.bind(cx), display_slice(args));
+    }
+}
+
+fn print_locals(selected: usize, env: &Rt<Env>, cx: &Context) {
+    let backtrace = env.stack.backtrace();
+    let Some(frame) = backtrace.get(selected) else { return };
+    let locals = crate::eval::frame_locals(frame, cx);
+    for local in locals.as_list().into_iter().flatten().flatten() {
+        if let Some(local) = local.cons() {
+            println!("  {} = {}", local.car(), local.cdr());
+        }
+    }
+}
+
+/// Evaluate `input` with the locals of the selected frame bound lexically.
+fn eval_in_frame(input: &str, selected: usize, env: &mut Rt<Env>, cx: &mut Context) {
+    let obj = match reader::read(input, cx) {
+        Ok((obj, _)) => obj,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return;
+        }
+    };
+    root!(obj, cx);
+    let backtrace = env.stack.backtrace();
+    let locals = backtrace.get(selected).map_or(NIL, |x| crate::eval::frame_locals(x, cx));
+    root!(locals, cx);
+    match interpreter::eval(obj, Some(locals), env, cx) {
+        Ok(val) => println!("{val}"),
+        Err(e) => eprintln!("Error: {e}"),
+    }
+}
+
+/// True if the error is the throw to `top-level` made by aborting from the
+/// break loop.
+fn is_abort(err: &EvalError, env: &Rt<Env>) -> bool {
+    let ErrorType::Throw(id) = err.error else { return false };
+    env.get_exception(id).is_some_and(|(tag, _)| *tag == sym::TOP_LEVEL)
+}
+
 fn load(file: &str, cx: &mut Context, env: &mut Rt<Env>) -> Result<(), ()> {
     let file: Gc<&LispString> = cx.add_as(file);
     root!(file, cx);
-    match crate::lread::load(file, None, None, cx, env) {
+    match crate::lread::load(file, None, None, None, None, cx, env) {
         Ok(val) => {
             println!("{val}");
             Ok(())
@@ -0,0 +1,172 @@
+//! Marker functions.
+use crate::core::{
+    env::Env,
+    error::{Type, TypeError},
+    gc::{Context, Rt},
+    object::{Gc, LispBuffer, LispMarker, MarkerInner, Object, ObjectType, TagType},
+};
+use anyhow::Result;
+use rune_macros::defun;
+
+/// Return a newly allocated marker which does not point at any place.
+#[defun]
+pub(crate) fn make_marker<'ob>(cx: &'ob Context) -> &'ob LispMarker {
+    let marker: Gc<&LispMarker> = cx.add_as(MarkerInner::new());
+    marker.untag()
+}
+
+/// Position MARKER before character number POSITION in BUFFER.
+/// If BUFFER is omitted or nil, it defaults to the current buffer. If
+/// POSITION is nil, makes marker point nowhere so it no longer slows down
+/// editing in any buffer. Returns MARKER.
+#[defun]
+pub(crate) fn set_marker<'ob>(
+    marker: &'ob LispMarker,
+    position: Option<Object>,
+    buffer: Option<Gc<&LispBuffer>>,
+    env: &mut Rt<Env>,
+) -> Result<&'ob LispMarker> {
+    let position = match position.map(|x| x.untag()) {
+        None | Some(ObjectType::NIL) => {
+            marker.detach();
+            return Ok(marker);
+        }
+        Some(ObjectType::Int(x)) => x,
+        Some(ObjectType::Marker(m)) => match m.position() {
+            Some(pos) => pos as i64 + 1,
+            None => {
+                marker.detach();
+                return Ok(marker);
+            }
+        },
+        Some(x) => return Err(TypeError::new(Type::IntegerOrMarker, x).into()),
+    };
+    // positions are 1-based in lisp
+    let pos = usize::try_from(position - 1).unwrap_or(0);
+    match buffer {
+        Some(buffer) => env.with_buffer_mut(buffer.untag(), |b| b.set_marker(marker, pos))?,
+        None => env.current_buffer.get_mut().set_marker(marker, pos),
+    }
+    Ok(marker)
+}
+
+/// Return a new marker pointing at the same place as MARKER.
+/// If argument is a number, makes a new marker pointing at that position in
+/// the current buffer. If MARKER is nil, the new marker points nowhere.
+/// The optional argument INSERTION-TYPE specifies the insertion type of the
+/// new marker.
+#[defun]
+fn copy_marker<'ob>(
+    marker: Option<Object>,
+    insertion_type: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<&'ob LispMarker> {
+    let new = make_marker(cx);
+    new.set_insertion_type(insertion_type.is_some_and(|x| !x.is_nil()));
+    let buffer = match marker.map(|x| x.untag()) {
+        Some(ObjectType::Marker(m)) => m.buffer().map(|b| cx.bind(b).tag()),
+        Some(ObjectType::Int(_) | ObjectType::NIL) | None => None,
+        Some(x) => return Err(TypeError::new(Type::IntegerOrMarker, x).into()),
+    };
+    set_marker(new, marker, buffer, env)
+}
+
+/// Return the position of MARKER, or nil if it points nowhere.
+#[defun]
+fn marker_position(marker: &LispMarker) -> Option<usize> {
+    marker.position().map(|x| x + 1)
+}
+
+/// Return the buffer that MARKER points into, or nil if none.
+/// Returns nil if MARKER points into a dead buffer.
+#[defun]
+fn marker_buffer<'ob>(marker: &LispMarker, cx: &'ob Context) -> Option<&'ob LispBuffer> {
+    marker.buffer().map(|b| cx.bind(b))
+}
+
+/// Return insertion type of MARKER: t if it stays after inserted text.
+/// The value nil means the marker stays before text inserted there.
+#[defun]
+fn marker_insertion_type(marker: &LispMarker) -> bool {
+    marker.insertion_type()
+}
+
+/// Set the insertion-type of MARKER to INSERTION-TYPE.
+/// If INSERTION-TYPE is t, it means the marker advances when you insert text
+/// at it. If INSERTION-TYPE is nil, it means the marker stays behind when you
+/// insert text at it.
+#[defun]
+fn set_marker_insertion_type<'ob>(marker: &LispMarker, insertion_type: Object<'ob>) -> Object<'ob> {
+    marker.set_insertion_type(!insertion_type.is_nil());
+    insertion_type
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_marker_position() {
+        assert_lisp("(marker-position (make-marker))", "nil");
+        assert_lisp("(markerp (make-marker))", "t");
+        assert_lisp("(markerp 1)", "nil");
+        assert_lisp(
+            "(let ((m (make-marker))) (insert \"abc\") (marker-position (set-marker m 2)))",
+            "2",
+        );
+        assert_lisp(
+            "(let ((m (make-marker))) (set-marker m 2) (set-marker m nil) (marker-position m))",
+            "nil",
+        );
+        assert_lisp("(let ((m (copy-marker 1))) (marker-position m))", "1");
+    }
+
+    #[test]
+    fn test_marker_insertion() {
+        // inserting before a marker moves it
+        assert_lisp(
+            "(let ((m (make-marker))) (insert \"abc\") (set-marker m 3) (goto-char 0) (insert \"xy\") (marker-position m))",
+            "5",
+        );
+        // inserting at a marker only moves it if the insertion type is t
+        assert_lisp(
+            "(let ((m (make-marker))) (insert \"abc\") (set-marker m 4) (insert \"xy\") (marker-position m))",
+            "4",
+        );
+        assert_lisp(
+            "(let ((m (copy-marker 1 t))) (insert \"xy\") (list (marker-insertion-type m) (marker-position m)))",
+            "(t 3)",
+        );
+    }
+
+    #[test]
+    fn test_marker_deletion() {
+        assert_lisp(
+            "(let ((m (make-marker))) (insert \"abcdef\") (set-marker m 5) (delete-region 2 4) (marker-position m))",
+            "3",
+        );
+        assert_lisp(
+            "(let ((m (make-marker))) (insert \"abcdef\") (set-marker m 3) (delete-region 2 5) (marker-position m))",
+            "2",
+        );
+    }
+
+    #[test]
+    fn test_marker_arith() {
+        assert_lisp("(let ((m (make-marker))) (insert \"abc\") (logior (set-marker m 2) 4))", "6");
+        assert_lisp("(let ((m (make-marker))) (insert \"abcdef\") (% (set-marker m 5) 3))", "2");
+        assert_lisp(
+            r#"(progn (insert "abcd")
+                      (let ((m (copy-marker 3)))
+                        (list (+ m 1) (- m) (- 10 m) (* m 2) (1+ m) (max 1 m) (min 5 m))))"#,
+            "(4 -3 7 6 4 3 3)",
+        );
+        assert_lisp(
+            r#"(progn (insert "abcd")
+                      (let ((m (copy-marker 3)))
+                        (list (< 1 m 4) (< m 2) (= m 3) (= 3 m 3.0) (>= m (point-marker)))))"#,
+            "(t nil t t nil)",
+        );
+    }
+}
@@ -0,0 +1,621 @@
+//! Overlays.
+use crate::{
+    core::{
+        cons::Cons,
+        env::{Env, sym},
+        gc::{Context, GcState, Rt, Slot, Trace},
+        object::{
+            Gc, LispBuffer, LispOverlay, ListType, Object, OpenBuffer, OverlayInner, Position,
+            WithLifetime,
+        },
+    },
+    fns::{copy_sequence, eq, plist_get, slice_into_list},
+};
+use anyhow::Result;
+use interval_tree::{IntervalTree, TextRange};
+use rune_core::hashmap::HashMap;
+use rune_macros::defun;
+use std::ptr;
+
+/// The overlays that cover a stretch of text, in the order they were added.
+type OverlaySet = Vec<Slot<&'static LispOverlay>>;
+
+fn contains(set: &OverlaySet, overlay: &LispOverlay) -> bool {
+    set.iter().any(|x| ptr::eq(**x, overlay))
+}
+
+fn same_overlays(a: &OverlaySet, b: &OverlaySet) -> bool {
+    a.len() == b.len() && a.iter().all(|x| contains(b, x))
+}
+
+/// The overlays of a buffer. The text is split into intervals that each hold
+/// the set of overlays covering them, so the tree can be
+/// [advanced](IntervalTree::advance) on insertion like the text properties.
+/// Empty overlays cover no text and are kept on the side. All positions are
+/// 0-based.
+///
+/// After every change the bounds of the overlays are copied into the overlay
+/// objects, so that they can be read without locking the buffer.
+#[derive(Debug)]
+pub(crate) struct OverlayTree {
+    tree: IntervalTree<OverlaySet>,
+    empty: Vec<(usize, Slot<&'static LispOverlay>)>,
+}
+
+// SAFETY: The overlays are only accessed while the buffer is locked, and the
+// buffer is traced from the thread that owns them.
+unsafe impl Send for OverlayTree {}
+
+impl OverlayTree {
+    pub(crate) fn new() -> Self {
+        Self { tree: IntervalTree::new(), empty: Vec::new() }
+    }
+
+    /// Put `overlay` between `start` and `end`, removing it from wherever it
+    /// was before in this buffer.
+    pub(crate) fn move_overlay(
+        &mut self,
+        overlay: &LispOverlay,
+        start: usize,
+        end: usize,
+        buffer: &LispBuffer,
+    ) {
+        if overlay.buffer().is_some_and(|b| b == buffer) {
+            self.remove(overlay);
+        }
+        let overlay = unsafe { overlay.with_lifetime() };
+        if start == end {
+            self.empty.push((start, Slot::new(overlay)));
+        } else {
+            self.modify(start, end, |set| set.push(Slot::new(overlay)));
+        }
+        self.sync(buffer);
+    }
+
+    pub(crate) fn delete_overlay(&mut self, overlay: &LispOverlay) {
+        self.remove(overlay);
+        overlay.detach();
+    }
+
+    /// Detach all overlays. Called when the buffer is killed.
+    pub(crate) fn detach_all(&self) {
+        self.tree.apply(&mut |set| set.iter().for_each(|x| x.detach()));
+        self.empty.iter().for_each(|(_, x)| x.detach());
+    }
+
+    fn remove(&mut self, overlay: &LispOverlay) {
+        if let Some((start, end)) = overlay.bounds()
+            && start < end
+        {
+            self.modify(start, end, |set| set.retain(|x| !ptr::eq(**x, overlay)));
+        }
+        self.empty.retain(|(_, x)| !ptr::eq(**x, overlay));
+    }
+
+    /// Apply `f` to the overlay sets between `beg` and `end`, splitting the
+    /// intervals at the edges of the range as needed.
+    fn modify(&mut self, beg: usize, end: usize, f: impl Fn(&mut OverlaySet)) {
+        let range = TextRange::new(beg, end);
+        let nodes: Vec<_> =
+            self.tree.find_intersects(range).map(|n| (n.key, n.val.clone())).collect();
+        let mut segments = Vec::new();
+        let mut pos = beg;
+        for (key, set) in nodes {
+            self.tree.delete_exact(key);
+            if key.start < beg {
+                segments.push((key.start, beg, set.clone()));
+            }
+            if pos < key.start {
+                let mut gap = Vec::new();
+                f(&mut gap);
+                segments.push((pos, key.start, gap));
+            }
+            let mut inside = set.clone();
+            f(&mut inside);
+            segments.push((key.start.max(beg), key.end.min(end), inside));
+            if key.end > end {
+                segments.push((end, key.end, set));
+            }
+            pos = key.end;
+        }
+        if pos < end {
+            let mut gap = Vec::new();
+            f(&mut gap);
+            segments.push((pos, end, gap));
+        }
+        for (start, end, set) in segments {
+            if start < end && !set.is_empty() {
+                self.tree.insert(TextRange::new(start, end), set, |new, _| new);
+            }
+        }
+        self.tree.merge(same_overlays);
+    }
+
+    /// Update the overlays for an insertion of `len` characters at `pos`.
+    pub(crate) fn adjust_for_insert(&mut self, pos: usize, len: usize, buffer: &LispBuffer) {
+        if len == 0 {
+            return;
+        }
+        let bounds_of = |x: &Slot<&'static LispOverlay>| x.bounds().map(|b| (**x, b));
+        // Advancing the tree includes the new text in every overlay that
+        // covers `pos`, so front-advance overlays starting there have to give
+        // it back, and rear-advance overlays ending there have to take it.
+        let starting: Vec<_> = self
+            .tree
+            .find(pos)
+            .into_iter()
+            .flat_map(|n| n.val.iter().filter_map(bounds_of))
+            .filter(|(x, (start, _))| *start == pos && x.front_advance())
+            .map(|(x, _)| x)
+            .collect();
+        let ending: Vec<_> = pos
+            .checked_sub(1)
+            .and_then(|prev| self.tree.find(prev))
+            .into_iter()
+            .flat_map(|n| n.val.iter().filter_map(bounds_of))
+            .filter(|(x, (_, end))| *end == pos && x.rear_advance())
+            .map(|(x, _)| x)
+            .collect();
+        self.tree.advance(pos, len);
+        for overlay in starting {
+            self.modify(pos, pos + len, |set| set.retain(|x| !ptr::eq(**x, overlay)));
+        }
+        let mut grown = Vec::new();
+        for (start, overlay) in &mut self.empty {
+            if *start > pos {
+                *start += len;
+            } else if *start == pos && overlay.rear_advance() {
+                if overlay.front_advance() {
+                    *start += len;
+                } else {
+                    grown.push(**overlay);
+                }
+            }
+        }
+        self.empty.retain(|(_, x)| !grown.iter().any(|g| ptr::eq(*g, **x)));
+        for overlay in ending.into_iter().chain(grown) {
+            self.modify(pos, pos + len, |set| set.push(Slot::new(overlay)));
+        }
+        self.sync(buffer);
+    }
+
+    /// Update the overlays for a deletion of the characters between `beg` and
+    /// `end`. Overlays that only covered deleted text become empty.
+    pub(crate) fn adjust_for_delete(&mut self, beg: usize, end: usize, buffer: &LispBuffer) {
+        if beg == end {
+            return;
+        }
+        let len = end - beg;
+        let nodes: Vec<_> = self
+            .tree
+            .find_intersects(TextRange::new(beg, end))
+            .map(|n| (n.key, n.val.clone()))
+            .collect();
+        for (key, _) in &nodes {
+            self.tree.delete_exact(*key);
+        }
+        self.tree.apply_mut(&mut |node| {
+            if node.key.start >= end {
+                node.key.start -= len;
+                node.key.end -= len;
+            }
+        });
+        for (start, _) in &mut self.empty {
+            if *start >= end {
+                *start -= len;
+            } else if *start > beg {
+                *start = beg;
+            }
+        }
+        for (key, set) in nodes {
+            for overlay in &set {
+                if overlay.bounds().is_some_and(|(s, e)| s >= beg && e <= end)
+                    && !self.empty.iter().any(|(_, x)| ptr::eq(**x, **overlay))
+                {
+                    self.empty.push((beg, overlay.clone()));
+                }
+            }
+            // The parts of the interval on either side of the deleted text
+            // are now next to each other
+            let new_start = key.start.min(beg);
+            let new_end = if key.end > end { key.end - len } else { beg };
+            if new_start < new_end {
+                self.tree.insert(TextRange::new(new_start, new_end), set, |new, _| new);
+            }
+        }
+        self.tree.merge(same_overlays);
+        self.sync(buffer);
+    }
+
+    /// Copy the bounds of every overlay into the overlay object.
+    fn sync(&self, buffer: &LispBuffer) {
+        let mut bounds = HashMap::default();
+        for node in self.tree.find_intersects(TextRange::new(0, usize::MAX)) {
+            for overlay in &node.val {
+                bounds
+                    .entry(ptr::from_ref::<LispOverlay>(**overlay))
+                    .and_modify(|(_, _, end)| *end = node.key.end)
+                    .or_insert((**overlay, node.key.start, node.key.end));
+            }
+        }
+        for (overlay, start, end) in bounds.into_values() {
+            overlay.set_bounds(buffer, start, end);
+        }
+        for (pos, overlay) in &self.empty {
+            overlay.set_bounds(buffer, *pos, *pos);
+        }
+    }
+
+    /// The overlays that contain the character at `pos`.
+    pub(crate) fn overlays_at(&self, pos: usize) -> Vec<&LispOverlay> {
+        self.tree
+            .find(pos)
+            .map(|n| n.val.iter().map(|x| **x).collect())
+            .unwrap_or_default()
+    }
+
+    /// The overlays that contain any character between `beg` and `end`, plus
+    /// the empty overlays at `beg` or strictly inside the range. If
+    /// `include_end` is true empty overlays at `end` are included as well.
+    pub(crate) fn overlays_in(
+        &self,
+        beg: usize,
+        end: usize,
+        include_end: bool,
+    ) -> Vec<&LispOverlay> {
+        let mut overlays: Vec<&LispOverlay> = Vec::new();
+        if beg < end {
+            for node in self.tree.find_intersects(TextRange::new(beg, end)) {
+                for overlay in &node.val {
+                    if !overlays.iter().any(|x| ptr::eq(*x, **overlay)) {
+                        overlays.push(**overlay);
+                    }
+                }
+            }
+        }
+        let empty = self.empty.iter().filter(|(pos, _)| {
+            (beg..end).contains(pos) || (*pos == end && (include_end || beg == end))
+        });
+        overlays.extend(empty.map(|(_, x)| **x));
+        overlays
+    }
+
+    /// The first position after `pos` where an overlay starts or ends.
+    pub(crate) fn next_change(&self, pos: usize) -> Option<usize> {
+        let mut current: Option<&OverlaySet> = None;
+        let mut current_end = pos;
+        let mut next = None;
+        for node in self.tree.find_intersects(TextRange::new(pos, usize::MAX)) {
+            if node.key.start > current_end {
+                next = Some(if current.is_some() { current_end } else { node.key.start });
+                break;
+            }
+            if node.key.start > pos && !current.is_some_and(|x| same_overlays(x, &node.val)) {
+                next = Some(node.key.start);
+                break;
+            }
+            current = Some(&node.val);
+            current_end = node.key.end;
+        }
+        if next.is_none() && current.is_some() {
+            next = Some(current_end);
+        }
+        let empty = self.empty.iter().map(|(x, _)| *x).filter(|x| *x > pos).min();
+        match (next, empty) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl Trace for OverlayTree {
+    fn trace(&self, state: &mut GcState) {
+        self.tree.apply(&mut |set| set.trace(state));
+        self.empty.trace(state);
+    }
+}
+
+/// The priority of `overlay`. Overlays without an integer `priority` property
+/// have priority 0.
+fn priority(overlay: &LispOverlay) -> i64 {
+    plist_get(overlay.plist(), sym::PRIORITY.into())
+        .ok()
+        .and_then(|x| x.try_into().ok())
+        .unwrap_or(0)
+}
+
+/// Sort `overlays` in increasing order of precedence. Higher priorities take
+/// precedence, and for equal priorities the overlay that starts later, ends
+/// earlier, or was added later wins.
+fn sort_overlays(overlays: &mut [&LispOverlay]) {
+    overlays.sort_by_key(|x| {
+        let (start, end) = x.bounds().unwrap_or_default();
+        (priority(x), start, std::cmp::Reverse(end))
+    });
+}
+
+/// The value of `prop` in the overlay with the highest precedence at the
+/// 0-based position `pos`, together with that overlay.
+pub(crate) fn overlay_property<'ob>(
+    buffer: &OpenBuffer,
+    pos: usize,
+    prop: Object,
+    cx: &'ob Context,
+) -> Option<(Object<'ob>, &'ob LispOverlay)> {
+    let mut overlays = buffer.overlays.overlays_at(pos);
+    sort_overlays(&mut overlays);
+    overlays.into_iter().rev().find_map(|overlay| {
+        let value = plist_get(overlay.plist(), prop).ok()?;
+        (!value.is_nil()).then(|| (cx.bind(value), cx.bind(overlay)))
+    })
+}
+
+/// Return t if OBJECT is an overlay.
+#[defun]
+fn overlayp(object: Object) -> bool {
+    object.is_overlay()
+}
+
+/// Create a new overlay with range BEG to END in BUFFER and return it.
+/// If omitted, BUFFER defaults to the current buffer.
+/// BEG and END may be integers or markers.
+/// The fourth arg FRONT-ADVANCE, if non-nil, makes the marker
+/// for the front of the overlay advance when text is inserted there
+/// \(which means the text *is not* included in the overlay).
+/// The fifth arg REAR-ADVANCE, if non-nil, makes the marker
+/// for the rear of the overlay advance when text is inserted there
+/// \(which means the text *is* included in the overlay).
+#[defun]
+fn make_overlay<'ob>(
+    Position(beg): Position,
+    Position(end): Position,
+    buffer: Option<Gc<&LispBuffer>>,
+    front_advance: Option<Object>,
+    rear_advance: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<&'ob LispOverlay> {
+    let front_advance = front_advance.is_some_and(|x| !x.is_nil());
+    let rear_advance = rear_advance.is_some_and(|x| !x.is_nil());
+    let overlay: Gc<&LispOverlay> = cx.add_as(OverlayInner::new(front_advance, rear_advance));
+    let overlay = overlay.untag();
+    move_overlay(overlay, Position(beg), Position(end), buffer, env)
+}
+
+/// Set the endpoints of OVERLAY to BEG and END in BUFFER.
+/// If BUFFER is omitted, leave OVERLAY in the same buffer it inhabits now.
+/// If BUFFER is omitted, and OVERLAY is in no buffer, put it in the current
+/// buffer.
+#[defun]
+fn move_overlay<'ob>(
+    overlay: &'ob LispOverlay,
+    Position(beg): Position,
+    Position(end): Position,
+    buffer: Option<Gc<&LispBuffer>>,
+    env: &mut Rt<Env>,
+) -> Result<&'ob LispOverlay> {
+    let buffer = match buffer {
+        Some(buffer) => buffer.untag(),
+        None => overlay.buffer().unwrap_or(env.current_buffer.buf_ref),
+    };
+    if let Some(old) = overlay.buffer()
+        && old != buffer
+    {
+        env.with_buffer_mut(old, |b| b.overlays.delete_overlay(overlay))?;
+    }
+    env.with_buffer_mut(buffer, |b| b.move_overlay(overlay, beg, end))?;
+    // The overlay lives in our heap, so the buffer needs to be traced with it
+    env.current_buffer.track(buffer);
+    Ok(overlay)
+}
+
+/// Delete the overlay OVERLAY from its buffer.
+#[defun]
+fn delete_overlay(overlay: &LispOverlay, env: &mut Rt<Env>) -> Result<bool> {
+    if let Some(buffer) = overlay.buffer() {
+        env.with_buffer_mut(buffer, |b| b.overlays.delete_overlay(overlay))?;
+    }
+    Ok(false)
+}
+
+/// Return the position at which OVERLAY starts.
+#[defun]
+fn overlay_start(overlay: &LispOverlay) -> Option<usize> {
+    overlay.bounds().map(|(start, _)| start + 1)
+}
+
+/// Return the position at which OVERLAY ends.
+#[defun]
+fn overlay_end(overlay: &LispOverlay) -> Option<usize> {
+    overlay.bounds().map(|(_, end)| end + 1)
+}
+
+/// Return the buffer OVERLAY belongs to.
+/// Return nil if OVERLAY has been deleted.
+#[defun]
+fn overlay_buffer<'ob>(overlay: &LispOverlay, cx: &'ob Context) -> Option<&'ob LispBuffer> {
+    overlay.buffer().map(|b| cx.bind(b))
+}
+
+/// Return a list of the properties on OVERLAY.
+/// This is a copy of OVERLAY's plist; modifying its conses has no
+/// effect on OVERLAY.
+#[defun]
+fn overlay_properties<'ob>(overlay: &LispOverlay, cx: &'ob Context) -> Result<Object<'ob>> {
+    copy_sequence(cx.bind(overlay.plist()), cx)
+}
+
+/// Get the property of overlay OVERLAY with property name PROP.
+#[defun]
+fn overlay_get<'ob>(overlay: &'ob LispOverlay, prop: Object<'ob>) -> Result<Object<'ob>> {
+    plist_get(overlay.plist(), prop)
+}
+
+/// Set one property of overlay OVERLAY: give property PROP value VALUE.
+/// VALUE will be returned.
+#[defun]
+fn overlay_put<'ob>(
+    overlay: &LispOverlay,
+    prop: Object<'ob>,
+    value: Object<'ob>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let plist = overlay.plist();
+    if let Ok(list) = Gc::<ListType>::try_from(plist) {
+        let mut iter = list.conses();
+        while let Some(key) = iter.next() {
+            let Some(value_cons) = iter.next() else { break };
+            if eq(key?.car(), prop) {
+                value_cons?.set_car(value)?;
+                return Ok(value);
+            }
+        }
+    }
+    overlay.set_plist(Cons::new(prop, Cons::new(value, plist, cx), cx).into());
+    Ok(value)
+}
+
+/// Return a list of the overlays that contain the character at POS.
+/// If SORTED is non-nil, then sort them in decreasing order of priority.
+#[defun]
+fn overlays_at<'ob>(
+    Position(pos): Position,
+    sorted: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Object<'ob> {
+    let buffer = env.current_buffer.get();
+    let mut overlays = buffer.overlays.overlays_at(pos.saturating_sub(1));
+    if sorted.is_some_and(|x| !x.is_nil()) {
+        sort_overlays(&mut overlays);
+        overlays.reverse();
+    }
+    let overlays: Vec<Object> = overlays.into_iter().map(|x| cx.bind(x).into()).collect();
+    slice_into_list(&overlays, None, cx)
+}
+
+/// Return a list of the overlays that overlap the region BEG ... END.
+/// Overlap means that at least one character is contained within the overlay
+/// and also contained within the specified region; however, empty overlays
+/// are included in the result if they are located at BEG, strictly between
+/// BEG and END, or at END provided END denotes the position at the end of
+/// the accessible part of the buffer.
+#[defun]
+fn overlays_in<'ob>(
+    Position(beg): Position,
+    Position(end): Position,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Object<'ob> {
+    let (beg, end) = if beg <= end { (beg, end) } else { (end, beg) };
+    let buffer = env.current_buffer.get();
+    let at_end = end >= buffer.point_max();
+    let (beg, end) = (beg.saturating_sub(1), end.saturating_sub(1));
+    let overlays = buffer.overlays.overlays_in(beg, end, at_end);
+    let overlays: Vec<Object> = overlays.into_iter().map(|x| cx.bind(x).into()).collect();
+    slice_into_list(&overlays, None, cx)
+}
+
+/// Return the next position after POS where an overlay starts or ends.
+/// If there are no overlay boundaries from POS to (point-max),
+/// the value is (point-max).
+#[defun]
+fn next_overlay_change(Position(pos): Position, env: &mut Rt<Env>) -> usize {
+    let buffer = env.current_buffer.get();
+    let point_max = buffer.point_max();
+    match buffer.overlays.next_change(pos.saturating_sub(1)) {
+        Some(next) => (next + 1).min(point_max),
+        None => point_max,
+    }
+}
+
+defsym!(PRIORITY);
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_overlay_basics() {
+        assert_lisp("(overlayp (make-overlay 1 1))", "t");
+        assert_lisp("(overlayp 1)", "nil");
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 5 2))) (list (overlay-start o) (overlay-end o))))",
+            "(2 5)",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 2 5))) (overlay-put o 'face 'bold) (overlay-put o 'face 'italic) (list (overlay-get o 'face) (overlay-properties o))))",
+            "(italic (face italic))",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 2 5))) (delete-overlay o) (list (overlay-start o) (overlay-buffer o))))",
+            "(nil nil)",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 2 5))) (move-overlay o 1 3) (list (overlay-start o) (overlay-end o))))",
+            "(1 3)",
+        );
+    }
+
+    #[test]
+    fn test_overlay_insertion() {
+        // text inserted inside or before the overlay moves it
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 2 5))) (goto-char 3) (insert \"xy\") (goto-char 1) (insert \"z\") (list (overlay-start o) (overlay-end o))))",
+            "(3 8)",
+        );
+        // text inserted at the edges is only included if the overlay advances
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 2 5))) (goto-char 2) (insert \"x\") (goto-char 6) (insert \"y\") (list (overlay-start o) (overlay-end o))))",
+            "(2 6)",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 2 5 nil t t))) (goto-char 2) (insert \"x\") (goto-char 6) (insert \"y\") (list (overlay-start o) (overlay-end o))))",
+            "(3 7)",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 3 3 nil nil t))) (goto-char 3) (insert \"xy\") (list (overlay-start o) (overlay-end o))))",
+            "(3 5)",
+        );
+    }
+
+    #[test]
+    fn test_overlay_deletion() {
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 3 6))) (delete-region 2 4) (list (overlay-start o) (overlay-end o))))",
+            "(2 4)",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((o (make-overlay 3 5))) (delete-region 2 6) (list (overlay-start o) (overlay-end o))))",
+            "(2 2)",
+        );
+    }
+
+    #[test]
+    fn test_overlays_at() {
+        assert_lisp(
+            "(progn (insert \"abcdef\") (make-overlay 1 3) (make-overlay 2 5) (make-overlay 4 4) (list (length (overlays-at 2)) (length (overlays-at 4)) (length (overlays-in 1 7)) (length (overlays-in 4 4))))",
+            "(2 1 3 1)",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (make-overlay 2 3) (make-overlay 2 5) (list (next-overlay-change 1) (next-overlay-change 2) (next-overlay-change 3) (next-overlay-change 5)))",
+            "(2 3 5 7)",
+        );
+    }
+
+    #[test]
+    fn test_get_char_property() {
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((a (make-overlay 1 4)) (b (make-overlay 2 3))) (overlay-put a 'face 'bold) (overlay-put b 'face 'italic) (list (get-char-property 1 'face nil) (get-char-property 2 'face nil) (get-char-property 5 'face nil))))",
+            "(bold italic nil)",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((a (make-overlay 1 4)) (b (make-overlay 2 3))) (overlay-put a 'face 'bold) (overlay-put a 'priority 5) (overlay-put b 'face 'italic) (get-char-property 2 'face nil)))",
+            "bold",
+        );
+        assert_lisp(
+            "(progn (insert \"abcdef\") (let ((a (make-overlay 1 4))) (overlay-put a 'face 'bold) (eq (cdr (get-char-property-and-overlay 2 'face nil)) a)))",
+            "t",
+        );
+    }
+}
@@ -0,0 +1,781 @@
+//! Dumping the lisp heap to an image file and loading it back.
+//!
+//! Running `bootstrap.el` loads a lot of lisp on every startup. Instead the
+//! state after bootstrapping can be written to an image with `--dump`, and
+//! later runs can start from it with `--image`. The image contains every
+//! object reachable from the interned symbols (their function cells and
+//! flags), the default values of variables, symbol properties and the list
+//! of features. Objects are stored in a table and refer to each other by
+//! index, so shared structure and cycles survive the round trip.
+//!
+//! Buffers, markers and overlays can't be part of the image, and dumping an
+//! object that refers to one is an error. Text properties and buffer-local
+//! values are not saved either.
+use crate::core::{
+    cons::Cons,
+    env::{Env, INTERNED_SYMBOLS, intern, sym},
+    gc::{Context, Rt},
+    object::{
+        CharTableInner, Function, FunctionType, HashTable, IntoObject, LispVec, NIL, Object,
+        ObjectType, RecordBuilder, Symbol, Weakness, WithLifetime,
+    },
+};
+use anyhow::{Context as _, Result, anyhow, bail, ensure};
+use num_bigint::BigInt;
+use rune_core::hashmap::HashMap;
+use rune_core::macros::list;
+use rune_macros::defun;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 8] = b"RUNEDUMP";
+const VERSION: u32 = 3;
+
+/// The image that this process was started from and how long it took to load.
+static LOADED_IMAGE: Mutex<Option<(String, Duration)>> = Mutex::new(None);
+
+/// The kinds of objects stored in the image table.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+enum Kind {
+    Float,
+    BigInt,
+    Symbol,
+    String,
+    ByteString,
+    Cons,
+    Vec,
+    Record,
+    HashTable,
+    ByteFn,
+    SubrFn,
+    CharTable,
+}
+
+impl TryFrom<u8> for Kind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Ok(match value {
+            0 => Kind::Float,
+            1 => Kind::BigInt,
+            2 => Kind::Symbol,
+            3 => Kind::String,
+            4 => Kind::ByteString,
+            5 => Kind::Cons,
+            6 => Kind::Vec,
+            7 => Kind::Record,
+            8 => Kind::HashTable,
+            9 => Kind::ByteFn,
+            10 => Kind::SubrFn,
+            11 => Kind::CharTable,
+            _ => bail!("Invalid object kind {value} in dump image"),
+        })
+    }
+}
+
+const SYMBOL_INTERNED: u8 = 1 << 0;
+const SYMBOL_SPECIAL: u8 = 1 << 1;
+const SYMBOL_LOCAL_IF_SET: u8 = 1 << 2;
+
+fn weakness_from_byte(value: u8) -> Result<Weakness> {
+    Ok(match value {
+        0 => Weakness::None,
+        1 => Weakness::Key,
+        2 => Weakness::Value,
+        3 => Weakness::KeyOrValue,
+        4 => Weakness::KeyAndValue,
+        _ => bail!("Invalid hash table weakness {value} in dump image"),
+    })
+}
+
+/// A reference to an object in the image. Integers are stored inline and
+/// everything else is an index into the object table.
+#[derive(Debug, Clone, Copy)]
+enum Ref {
+    Int(i64),
+    Index(u32),
+}
+
+struct Encoder<'ob> {
+    entries: Vec<u8>,
+    ids: HashMap<*const u8, u32>,
+    queue: VecDeque<Object<'ob>>,
+    cx: &'ob Context<'ob>,
+}
+
+impl<'ob> Encoder<'ob> {
+    fn new(cx: &'ob Context<'ob>) -> Self {
+        Self { entries: Vec::new(), ids: HashMap::default(), queue: VecDeque::new(), cx }
+    }
+
+    /// Write a reference to `obj`, adding it to the table if it is not there
+    /// yet.
+    fn write_ref(&mut self, obj: Object<'ob>, out: &mut Vec<u8>) -> Result<()> {
+        let id = match obj.untag() {
+            ObjectType::Int(int) => {
+                out.push(0);
+                out.extend_from_slice(&int.to_le_bytes());
+                return Ok(());
+            }
+            ObjectType::Symbol(sym) => ptr::from_ref(sym.get()).cast(),
+            ObjectType::Float(x) => ptr::from_ref(x).cast(),
+            ObjectType::BigInt(x) => ptr::from_ref(x).cast(),
+            ObjectType::String(x) => ptr::from_ref(x).cast(),
+            ObjectType::ByteString(x) => ptr::from_ref(x).cast(),
+            ObjectType::Cons(x) => ptr::from_ref(x).cast(),
+            ObjectType::Vec(x) => ptr::from_ref(x).cast(),
+            ObjectType::Record(x) => ptr::from_ref(x).cast(),
+            ObjectType::HashTable(x) => ptr::from_ref(x).cast(),
+            ObjectType::ByteFn(x) => ptr::from_ref(x).cast(),
+            ObjectType::SubrFn(x) => ptr::from_ref(x).cast(),
+            ObjectType::CharTable(x) => ptr::from_ref(x).cast(),
+            ObjectType::Buffer(_) | ObjectType::Marker(_) | ObjectType::Overlay(_) => {
+                bail!("Can't dump object of type {:?}: {obj}", obj.untag().get_type())
+            }
+        };
+        let next = self.ids.len() as u32;
+        let index = *self.ids.entry(id).or_insert_with(|| {
+            self.queue.push_back(obj);
+            next
+        });
+        out.push(1);
+        out.extend_from_slice(&index.to_le_bytes());
+        Ok(())
+    }
+
+    fn write_optional_ref(&mut self, obj: Option<Object<'ob>>, out: &mut Vec<u8>) -> Result<()> {
+        match obj {
+            Some(obj) => {
+                out.push(1);
+                self.write_ref(obj, out)
+            }
+            None => {
+                out.push(0);
+                Ok(())
+            }
+        }
+    }
+
+    fn write_refs(
+        &mut self,
+        objects: impl IntoIterator<Item = Object<'ob>>,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        let objects: Vec<_> = objects.into_iter().collect();
+        write_len(objects.len(), out);
+        for obj in objects {
+            self.write_ref(obj, out)?;
+        }
+        Ok(())
+    }
+
+    /// Write the table entries for all referenced objects. Writing an entry
+    /// can reference new objects, which are queued after it, so the entries
+    /// end up in the same order as their indices.
+    fn finish(&mut self) -> Result<()> {
+        while let Some(obj) = self.queue.pop_front() {
+            let mut out = Vec::new();
+            self.write_entry(obj, &mut out)?;
+            self.entries.extend_from_slice(&out);
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, obj: Object<'ob>, out: &mut Vec<u8>) -> Result<()> {
+        match obj.untag() {
+            ObjectType::Float(x) => {
+                out.push(Kind::Float as u8);
+                out.extend_from_slice(&x.to_le_bytes());
+            }
+            ObjectType::BigInt(x) => {
+                out.push(Kind::BigInt as u8);
+                write_bytes(x.to_string().as_bytes(), out);
+            }
+            ObjectType::Symbol(sym) => {
+                out.push(Kind::Symbol as u8);
+                write_bytes(sym.name().as_bytes(), out);
+                let mut flags = 0;
+                if sym.interned() {
+                    flags |= SYMBOL_INTERNED;
+                }
+                if sym.is_special() {
+                    flags |= SYMBOL_SPECIAL;
+                }
+                if sym.is_local_if_set() {
+                    flags |= SYMBOL_LOCAL_IF_SET;
+                }
+                out.push(flags);
+                self.write_optional_ref(sym.func(self.cx).map(Into::into), out)?;
+            }
+            ObjectType::String(x) => {
+                out.push(Kind::String as u8);
+                write_bytes(x.as_bytes(), out);
+            }
+            ObjectType::ByteString(x) => {
+                out.push(Kind::ByteString as u8);
+                write_bytes(x.inner(), out);
+            }
+            ObjectType::Cons(cons) => {
+                out.push(Kind::Cons as u8);
+                self.write_ref(cons.car(), out)?;
+                self.write_ref(cons.cdr(), out)?;
+            }
+            ObjectType::Vec(vec) => {
+                out.push(Kind::Vec as u8);
+                self.write_refs(vec.iter().map(|x| x.get()), out)?;
+            }
+            ObjectType::Record(record) => {
+                out.push(Kind::Record as u8);
+                self.write_refs(record.iter().map(|x| x.get()), out)?;
+            }
+            ObjectType::HashTable(table) => {
+                out.push(Kind::HashTable as u8);
+                out.push(table.weakness() as u8);
+                write_len(table.len(), out);
+                for (key, value) in (0..table.len()).filter_map(|i| table.get_index(i)) {
+                    self.write_ref(key, out)?;
+                    self.write_ref(value, out)?;
+                }
+            }
+            ObjectType::ByteFn(func) => {
+                out.push(Kind::ByteFn as u8);
+                out.extend_from_slice(&func.args.into_arg_spec().to_le_bytes());
+                out.extend_from_slice(&(func.depth as u64).to_le_bytes());
+                write_bytes(func.codes(), out);
+                self.write_refs(func.consts().iter().copied(), out)?;
+                self.write_optional_ref(func.docstring(), out)?;
+                self.write_optional_ref(func.interactive(), out)?;
+            }
+            ObjectType::SubrFn(func) => {
+                out.push(Kind::SubrFn as u8);
+                write_bytes(func.name.as_bytes(), out);
+            }
+            ObjectType::CharTable(table) => {
+                out.push(Kind::CharTable as u8);
+                self.write_ref(table.default_value(), out)?;
+                let parent = table.parent().map_or(NIL, |x| x.into());
+                self.write_ref(parent, out)?;
+                let entries = table.entries();
+                write_len(entries.len(), out);
+                for (idx, value) in entries {
+                    out.extend_from_slice(&(idx as u32).to_le_bytes());
+                    self.write_ref(value, out)?;
+                }
+            }
+            _ => unreachable!("object should not be in the dump table"),
+        }
+        Ok(())
+    }
+}
+
+fn write_len(len: usize, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_len(bytes.len(), out);
+    out.extend_from_slice(bytes);
+}
+
+/// Write the state of the lisp heap reachable from `env` and the symbol table
+/// to `file`.
+pub(crate) fn dump(file: &Path, env: &Rt<Env>, cx: &Context) -> Result<()> {
+    let mut encoder = Encoder::new(cx);
+    let mut roots = Vec::new();
+
+    // Symbols with a definition or flags. Their table entries hold the
+    // function cell and flags.
+    let symbols: Vec<Symbol> = {
+        let map = INTERNED_SYMBOLS.lock().unwrap();
+        map.symbols()
+            .filter(|x| x.has_func() || x.is_special() || x.is_local_if_set())
+            .map(|x| unsafe { x.with_lifetime() })
+            .collect()
+    };
+    encoder.write_refs(symbols.into_iter().map(Into::into), &mut roots)?;
+
+    let vars: Vec<_> = env.vars.iter().map(|(k, v)| (k.bind(cx), v.bind(cx))).collect();
+    write_len(vars.len(), &mut roots);
+    for (var, value) in vars {
+        encoder.write_ref(var.into(), &mut roots)?;
+        encoder.write_ref(value, &mut roots)?;
+    }
+
+    let props: Vec<_> = env.props.iter().collect();
+    write_len(props.len(), &mut roots);
+    for (symbol, plist) in props {
+        encoder.write_ref(symbol.bind(cx).into(), &mut roots)?;
+        write_len(plist.len(), &mut roots);
+        for element in plist.iter() {
+            encoder.write_ref(element.0.bind(cx).into(), &mut roots)?;
+            encoder.write_ref(element.1.bind(cx), &mut roots)?;
+        }
+    }
+
+    let features: Vec<Symbol> = crate::data::FEATURES.lock().unwrap().iter().copied().collect();
+    encoder.write_refs(features.into_iter().map(Into::into), &mut roots)?;
+    encoder.write_ref(env.standard_syntax_table.bind(cx), &mut roots)?;
+    encoder.finish()?;
+
+    let mut image = Vec::with_capacity(MAGIC.len() + encoder.entries.len() + roots.len() + 8);
+    image.extend_from_slice(MAGIC);
+    image.extend_from_slice(&VERSION.to_le_bytes());
+    write_len(encoder.ids.len(), &mut image);
+    image.extend_from_slice(&encoder.entries);
+    image.extend_from_slice(&roots);
+    std::fs::write(file, image)
+        .with_context(|| format!("Failed to write dump image {}", file.display()))
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or_else(|| anyhow!("Dump image is truncated"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.bytes()?).context("Invalid string in dump image")
+    }
+
+    fn reference(&mut self) -> Result<Ref> {
+        match self.byte()? {
+            0 => Ok(Ref::Int(i64::from_le_bytes(self.array()?))),
+            1 => Ok(Ref::Index(u32::from_le_bytes(self.array()?))),
+            x => bail!("Invalid reference tag {x} in dump image"),
+        }
+    }
+
+    fn optional_reference(&mut self) -> Result<Option<Ref>> {
+        match self.byte()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.reference()?)),
+        }
+    }
+
+    fn references(&mut self) -> Result<Vec<Ref>> {
+        let len = self.len()?;
+        (0..len).map(|_| self.reference()).collect()
+    }
+}
+
+/// An entry of the object table, before the references are resolved.
+enum Entry<'a> {
+    Float(f64),
+    BigInt(&'a str),
+    Symbol {
+        name: &'a str,
+        flags: u8,
+        func: Option<Ref>,
+    },
+    String(&'a str),
+    ByteString(&'a [u8]),
+    Cons(Ref, Ref),
+    Vec(Vec<Ref>),
+    Record(Vec<Ref>),
+    HashTable {
+        weakness: Weakness,
+        pairs: Vec<(Ref, Ref)>,
+    },
+    ByteFn {
+        args: u64,
+        depth: u64,
+        codes: &'a [u8],
+        consts: Vec<Ref>,
+        docstring: Option<Ref>,
+        interactive: Option<Ref>,
+    },
+    SubrFn(&'a str),
+    CharTable {
+        default: Ref,
+        parent: Ref,
+        entries: Vec<(usize, Ref)>,
+    },
+}
+
+fn read_entry<'a>(decoder: &mut Decoder<'a>) -> Result<Entry<'a>> {
+    Ok(match Kind::try_from(decoder.byte()?)? {
+        Kind::Float => Entry::Float(f64::from_le_bytes(decoder.array()?)),
+        Kind::BigInt => Entry::BigInt(decoder.string()?),
+        Kind::Symbol => {
+            let name = decoder.string()?;
+            let flags = decoder.byte()?;
+            let func = decoder.optional_reference()?;
+            Entry::Symbol { name, flags, func }
+        }
+        Kind::String => Entry::String(decoder.string()?),
+        Kind::ByteString => Entry::ByteString(decoder.bytes()?),
+        Kind::Cons => Entry::Cons(decoder.reference()?, decoder.reference()?),
+        Kind::Vec => Entry::Vec(decoder.references()?),
+        Kind::Record => Entry::Record(decoder.references()?),
+        Kind::HashTable => {
+            let weakness = weakness_from_byte(decoder.byte()?)?;
+            let len = decoder.len()?;
+            let pairs = (0..len).map(|_| Ok((decoder.reference()?, decoder.reference()?)));
+            Entry::HashTable { weakness, pairs: pairs.collect::<Result<_>>()? }
+        }
+        Kind::ByteFn => {
+            let args = decoder.u64()?;
+            let depth = decoder.u64()?;
+            let codes = decoder.bytes()?;
+            let consts = decoder.references()?;
+            let docstring = decoder.optional_reference()?;
+            let interactive = decoder.optional_reference()?;
+            Entry::ByteFn { args, depth, codes, consts, docstring, interactive }
+        }
+        Kind::SubrFn => Entry::SubrFn(decoder.string()?),
+        Kind::CharTable => {
+            let default = decoder.reference()?;
+            let parent = decoder.reference()?;
+            let len = decoder.len()?;
+            let entries = (0..len).map(|_| Ok((decoder.len()?, decoder.reference()?)));
+            Entry::CharTable { default, parent, entries: entries.collect::<Result<_>>()? }
+        }
+    })
+}
+
+fn resolve<'ob>(objects: &[Object<'ob>], reference: Ref) -> Result<Object<'ob>> {
+    match reference {
+        Ref::Int(int) => Ok(int.into()),
+        Ref::Index(index) => objects
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("Invalid object index {index} in dump image")),
+    }
+}
+
+/// Load an image written by [`dump`] into `env` and the symbol table. This
+/// needs to happen before any lisp is run, because builtin functions are found
+/// by looking them up in their symbol's function cell.
+pub(crate) fn load_image(file: &Path, env: &mut Rt<Env>, cx: &Context) -> Result<()> {
+    let start = Instant::now();
+    let bytes = std::fs::read(file)
+        .with_context(|| format!("Failed to read dump image {}", file.display()))?;
+    let decoder = &mut Decoder { bytes: &bytes, pos: 0 };
+    ensure!(decoder.take(MAGIC.len())? == MAGIC, "{} is not a dump image", file.display());
+    let version = u32::from_le_bytes(decoder.array()?);
+    ensure!(version == VERSION, "Dump image version {version} is not supported");
+
+    let count = decoder.len()?;
+    let entries = (0..count).map(|_| read_entry(decoder)).collect::<Result<Vec<_>>>()?;
+
+    // Allocate every object first, so that references to objects later in
+    // the table (or cycles) can be resolved when filling them in.
+    let mut objects = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let obj = match entry {
+            Entry::Float(x) => cx.add(*x),
+            Entry::BigInt(x) => cx.add(x.parse::<BigInt>()?),
+            Entry::Symbol { name, flags, .. } if flags & SYMBOL_INTERNED != 0 => {
+                intern(name, cx).into()
+            }
+            Entry::Symbol { name, .. } => Symbol::new_uninterned(name, cx).into(),
+            Entry::String(x) => cx.add(*x),
+            Entry::ByteString(x) => cx.add(x.to_vec()),
+            Entry::Cons(..) => Cons::new(NIL, NIL, cx).into(),
+            Entry::Vec(refs) => cx.add(vec![NIL; refs.len()]),
+            Entry::Record(refs) => {
+                let mut record = cx.vec_with_capacity(refs.len());
+                record.resize(refs.len(), NIL);
+                cx.add(RecordBuilder(record))
+            }
+            Entry::HashTable { .. } => cx.add(HashTable::default()),
+            // Byte-code functions can't be changed once they are made, so they
+            // are allocated below when everything they refer to exists
+            Entry::ByteFn { .. } => NIL,
+            Entry::SubrFn(name) => match intern(name, cx).func(cx).map(Function::untag) {
+                Some(FunctionType::SubrFn(subr)) => subr.into(),
+                _ => bail!("Dump image refers to unknown builtin function {name}"),
+            },
+            Entry::CharTable { .. } => CharTableInner::new(None).into_obj(cx).into(),
+        };
+        objects.push(obj);
+    }
+
+    // The constants of byte-code functions, which are filled in with the rest
+    let mut byte_fn_consts: Vec<(&LispVec, &[Ref])> = Vec::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        let Entry::ByteFn { args, depth, codes, consts: refs, docstring, interactive } = entry
+        else {
+            continue;
+        };
+        let ObjectType::ByteString(codes) = cx.add(codes.to_vec()).untag() else {
+            unreachable!("byte vector was not a byte string")
+        };
+        let ObjectType::Vec(consts_vec) = cx.add(vec![NIL; refs.len()]).untag() else {
+            unreachable!("vector was not a vector")
+        };
+        byte_fn_consts.push((consts_vec, refs.as_slice()));
+        let resolve_slot = |reference: &Ref| {
+            if let Ref::Index(i) = *reference
+                && i as usize >= idx
+                && matches!(entries.get(i as usize), Some(Entry::ByteFn { .. }))
+            {
+                bail!("Byte-code function {idx} in dump image refers to a later one");
+            }
+            resolve(&objects, *reference)
+        };
+        let docstring = docstring.as_ref().map(resolve_slot).transpose()?;
+        let interactive = interactive.as_ref().map(resolve_slot).transpose()?;
+        let func = crate::alloc::make_byte_code(
+            *args as i64,
+            codes,
+            consts_vec,
+            *depth as usize,
+            docstring,
+            interactive,
+            &[],
+            cx,
+        )?;
+        objects[idx] = func.into();
+    }
+
+    for (entry, obj) in entries.iter().zip(&objects) {
+        match (entry, obj.untag()) {
+            (Entry::Cons(car, cdr), ObjectType::Cons(cons)) => {
+                cons.set_car(resolve(&objects, *car)?)?;
+                cons.set_cdr(resolve(&objects, *cdr)?)?;
+            }
+            (Entry::Vec(refs), ObjectType::Vec(vec)) => {
+                for (cell, reference) in vec.try_mut()?.iter().zip(refs) {
+                    cell.set(resolve(&objects, *reference)?);
+                }
+            }
+            (Entry::Record(refs), ObjectType::Record(record)) => {
+                for (cell, reference) in record.try_mut()?.iter().zip(refs) {
+                    cell.set(resolve(&objects, *reference)?);
+                }
+            }
+            (Entry::HashTable { weakness, pairs }, ObjectType::HashTable(table)) => {
+                table.set_weakness(*weakness);
+                for (key, value) in pairs {
+                    table.insert(resolve(&objects, *key)?, resolve(&objects, *value)?);
+                }
+            }
+            (Entry::CharTable { default, parent, entries }, ObjectType::CharTable(table)) => {
+                table.set_default_value(resolve(&objects, *default)?);
+                match resolve(&objects, *parent)?.untag() {
+                    ObjectType::CharTable(parent) => table.set_parent(Some(parent)),
+                    ObjectType::NIL => {}
+                    _ => bail!("Invalid char-table parent in dump image"),
+                }
+                for (idx, value) in entries {
+                    table.set(*idx, resolve(&objects, *value)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    for (consts, refs) in byte_fn_consts {
+        for (cell, reference) in consts.try_mut()?.iter().zip(refs) {
+            cell.set(resolve(&objects, *reference)?);
+        }
+    }
+
+    // Now that all objects are complete, install them
+    for (entry, obj) in entries.iter().zip(&objects) {
+        let (Entry::Symbol { flags, func, .. }, ObjectType::Symbol(sym)) = (entry, obj.untag())
+        else {
+            continue;
+        };
+        if flags & SYMBOL_SPECIAL != 0 {
+            sym.make_special();
+        }
+        if flags & SYMBOL_LOCAL_IF_SET != 0 {
+            sym.make_local_if_set();
+        }
+        if let Some(func) = func
+            && !sym.is_const()
+        {
+            crate::data::fset(sym, resolve(&objects, *func)?)?;
+        }
+    }
+
+    // The symbols with definitions were already installed above
+    decoder.references()?;
+    for _ in 0..decoder.len()? {
+        let var: Symbol = resolve(&objects, decoder.reference()?)?.try_into()?;
+        let value = resolve(&objects, decoder.reference()?)?;
+        env.vars.insert(var, value);
+    }
+    for _ in 0..decoder.len()? {
+        let symbol: Symbol = resolve(&objects, decoder.reference()?)?.try_into()?;
+        for _ in 0..decoder.len()? {
+            let prop: Symbol = resolve(&objects, decoder.reference()?)?.try_into()?;
+            let value = resolve(&objects, decoder.reference()?)?;
+            env.set_prop(symbol, prop, value);
+        }
+    }
+    let mut features = crate::data::FEATURES.lock().unwrap();
+    for feature in decoder.references()? {
+        let feature: Symbol = resolve(&objects, feature)?.try_into()?;
+        features.insert(unsafe { feature.with_lifetime() });
+    }
+    env.standard_syntax_table.set(resolve(&objects, decoder.reference()?)?);
+    ensure!(decoder.pos == bytes.len(), "Trailing data in dump image");
+
+    let name = file.to_string_lossy().into_owned();
+    *LOADED_IMAGE.lock().unwrap() = Some((name, start.elapsed()));
+    Ok(())
+}
+
+/// Dump current state of Emacs into dump file FILENAME.
+/// The image contains the definitions, variables, symbol properties and
+/// features of the current session. Start from it with `--image FILENAME`.
+#[defun]
+fn dump_emacs_portable(
+    filename: &str,
+    _track_referrers: Option<Object>,
+    env: &Rt<Env>,
+    cx: &Context,
+) -> Result<bool> {
+    dump(Path::new(filename), env, cx)?;
+    Ok(false)
+}
+
+/// Return statistics about portable dumping used by this session.
+/// If this Emacs session was started from a dump image, the return value is
+/// an alist of the form:
+///
+///   ((dumped-with-pdumper . t) (load-time . TIME) (dump-file-name . FILE))
+///
+/// where TIME is the time in seconds it took to restore Emacs state, and FILE
+/// is the name of the dump file. Otherwise return nil.
+#[defun]
+fn pdumper_stats<'ob>(cx: &'ob Context) -> Object<'ob> {
+    match &*LOADED_IMAGE.lock().unwrap() {
+        Some((file, time)) => {
+            let dumped = Cons::new(sym::DUMPED_WITH_PDUMPER, sym::TRUE, cx);
+            let load_time = Cons::new(sym::LOAD_TIME, time.as_secs_f64(), cx);
+            let file_name = Cons::new(sym::DUMP_FILE_NAME, file.as_str(), cx);
+            list![dumped, load_time, file_name; cx]
+        }
+        None => NIL,
+    }
+}
+
+defsym!(DUMPED_WITH_PDUMPER);
+defsym!(LOAD_TIME);
+defsym!(DUMP_FILE_NAME);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::gc::RootSet;
+    use rune_core::macros::root;
+
+    fn eval_forms(forms: &[&str], env: &mut Rt<Env>, cx: &mut Context) -> Vec<String> {
+        let mut results = Vec::new();
+        for form in forms {
+            let obj = crate::reader::read(form, cx).unwrap().0;
+            root!(obj, cx);
+            let value = crate::interpreter::eval(obj, None, env, cx).unwrap();
+            results.push(value.to_string());
+        }
+        results
+    }
+
+    #[test]
+    fn test_dump_round_trip() {
+        let name = format!("rune-pdumper-test-{}.pdmp", std::process::id());
+        let image = std::env::temp_dir().join(name);
+        let setup = [
+            "(defvar dump-test-var (list 1 2.5 \"three\" [4 five] (make-symbol \"six\")))",
+            "(setq dump-test-cycle (list 'a 'b))",
+            "(setcdr (cdr dump-test-cycle) dump-test-cycle)",
+            "(setq dump-test-table (make-hash-table :test 'equal))",
+            "(puthash \"key\" '(value) dump-test-table)",
+            "(setq dump-test-weak (make-hash-table :weakness 'key))",
+            "(setq dump-test-bytefn #[0 \"\" [] 0 \"Doc.\" (interactive \"p\")])",
+            "(setq dump-test-big 123456789012345678901234567890)",
+            "(defalias 'dump-test-fn #'(lambda (x) (cons x dump-test-var)))",
+            "(put 'dump-test-var 'dump-test-prop \"prop\")",
+            "(setq dump-test-syntax (make-syntax-table))",
+            "(modify-syntax-entry ?' \"w\" dump-test-syntax)",
+            "(modify-syntax-entry ?$ \"w\" (standard-syntax-table))",
+        ];
+        let forms = [
+            "dump-test-var",
+            "(eq (cdr (cdr dump-test-cycle)) dump-test-cycle)",
+            "(gethash \"key\" dump-test-table)",
+            "(hash-table-weakness dump-test-weak)",
+            "(list (aref dump-test-bytefn 4) (aref dump-test-bytefn 5))",
+            "dump-test-big",
+            "(dump-test-fn 0)",
+            "(get 'dump-test-var 'dump-test-prop)",
+            "(special-variable-p 'dump-test-var)",
+            "(car dump-test-cycle)",
+            "(aref dump-test-syntax ?')",
+            "(aref dump-test-syntax ?\\()",
+            "(aref (standard-syntax-table) ?$)",
+            "(progn (aset (standard-syntax-table) ?% '(2)) (aref dump-test-syntax ?%))",
+        ];
+
+        let expected = {
+            let roots = &RootSet::default();
+            let cx = &mut Context::new(roots);
+            sym::init_symbols();
+            root!(env, new(Env), cx);
+            eval_forms(&setup, env, cx);
+            dump(&image, env, cx).unwrap();
+            let expected = eval_forms(&forms, env, cx);
+            // Make sure that the definition comes from the image
+            intern("dump-test-fn", cx).unbind_func();
+            expected
+        };
+
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        root!(env, new(Env), cx);
+        load_image(&image, env, cx).unwrap();
+        assert_eq!(eval_forms(&forms, env, cx), expected);
+        assert!(matches!(pdumper_stats(cx).untag(), ObjectType::Cons(_)));
+        std::fs::remove_file(&image).unwrap();
+    }
+
+    #[test]
+    fn test_dump_buffer_error() {
+        let name = format!("rune-pdumper-buffer-test-{}.pdmp", std::process::id());
+        let image = std::env::temp_dir().join(name);
+        let roots = &RootSet::default();
+        let cx = &mut Context::new(roots);
+        sym::init_symbols();
+        root!(env, new(Env), cx);
+        eval_forms(&["(setq dump-test-buffer (get-buffer-create \"dump-test\"))"], env, cx);
+        let err = dump(&image, env, cx).unwrap_err();
+        assert!(err.to_string().starts_with("Can't dump object of type Buffer"));
+        assert!(!image.exists());
+    }
+}
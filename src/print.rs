@@ -1,6 +1,22 @@
 //! Printing utilities.
-use crate::core::object::Object;
+use crate::core::{
+    cons::Cons,
+    env::{Env, sym},
+    error::{Type, TypeError},
+    gc::{Context, Rt, Rto},
+    object::{
+        ByteString, Function, LispString, Object, ObjectType, OptionalFlag, Symbol, TRUE, TagType,
+        WithLifetime, char_to_int, char_to_raw_byte, int_to_char, raw_byte_to_char,
+    },
+};
+use crate::editfns;
+use anyhow::{Result, bail};
+use rune_core::hashmap::HashMap;
+use rune_core::macros::{call, root};
 use rune_macros::defun;
+use std::fmt::Write as _;
+use std::hash::{Hash, Hasher};
+use std::io::Write as _;
 
 #[defun]
 fn error_message_string(obj: Object) -> String {
@@ -8,6 +24,843 @@ fn error_message_string(obj: Object) -> String {
     format!("Error: {obj}")
 }
 
+/// The settings that control how an object is printed. Most of them come from
+/// the `print-*` variables.
+#[derive(Clone, Debug)]
+pub(crate) struct PrintOptions {
+    /// Print so the output can be read back (`prin1`) instead of printing
+    /// strings and symbols bare (`princ`).
+    escape: bool,
+    length: Option<usize>,
+    level: Option<usize>,
+    circle: bool,
+    gensym: bool,
+    quoted: bool,
+    escape_newlines: bool,
+    escape_nonascii: bool,
+    escape_multibyte: bool,
+    float_format: Option<FloatFormat>,
+}
+
+impl PrintOptions {
+    /// The options used when all of the print variables have their default
+    /// values.
+    pub(crate) fn new(escape: bool) -> Self {
+        Self {
+            escape,
+            length: None,
+            level: None,
+            circle: false,
+            gensym: false,
+            quoted: true,
+            escape_newlines: false,
+            escape_nonascii: false,
+            escape_multibyte: false,
+            float_format: None,
+        }
+    }
+
+    /// The options given by the current values of the print variables.
+    pub(crate) fn from_env(escape: bool, env: &Rt<Env>, cx: &Context) -> Self {
+        let flag = |var| env.var(var, cx).is_some_and(|x| !x.is_nil());
+        let limit = |var| env.var(var, cx).and_then(|x| usize::try_from(x).ok());
+        Self {
+            escape,
+            length: limit(sym::PRINT_LENGTH),
+            level: limit(sym::PRINT_LEVEL),
+            circle: flag(sym::PRINT_CIRCLE),
+            gensym: flag(sym::PRINT_GENSYM),
+            // defaults to t
+            quoted: env.var(sym::PRINT_QUOTED, cx).is_none_or(|x| !x.is_nil()),
+            escape_newlines: flag(sym::PRINT_ESCAPE_NEWLINES),
+            escape_nonascii: flag(sym::PRINT_ESCAPE_NONASCII),
+            escape_multibyte: flag(sym::PRINT_ESCAPE_MULTIBYTE),
+            float_format: env
+                .var(sym::FLOAT_OUTPUT_FORMAT, cx)
+                .and_then(|x| FloatFormat::parse(x.try_into().ok()?)),
+        }
+    }
+
+    /// Apply the OVERRIDES argument of `prin1` and friends. It is either `t`,
+    /// which resets everything to the defaults, or an alist of `(SETTING . VALUE)`.
+    fn apply_overrides(&mut self, overrides: Object) -> Result<()> {
+        if overrides == TRUE {
+            *self = Self::new(self.escape);
+            return Ok(());
+        }
+        for elem in overrides.as_list()? {
+            let elem = elem?;
+            if elem == TRUE {
+                *self = Self::new(self.escape);
+                continue;
+            }
+            let ObjectType::Cons(cons) = elem.untag() else {
+                bail!(TypeError::new(Type::Cons, elem))
+            };
+            let value = cons.cdr();
+            let limit = usize::try_from(value).ok();
+            let ObjectType::Symbol(setting) = cons.car().untag() else {
+                bail!(TypeError::new(Type::Symbol, cons.car()))
+            };
+            match setting.name() {
+                "length" => self.length = limit,
+                "level" => self.level = limit,
+                "circle" => self.circle = !value.is_nil(),
+                "gensym" => self.gensym = !value.is_nil(),
+                "quoted" => self.quoted = !value.is_nil(),
+                "escape-newlines" => self.escape_newlines = !value.is_nil(),
+                "escape-nonascii" => self.escape_nonascii = !value.is_nil(),
+                "escape-multibyte" => self.escape_multibyte = !value.is_nil(),
+                "float-format" => {
+                    self.float_format = value.try_into().ok().and_then(FloatFormat::parse);
+                }
+                name => bail!("Unknown print override: {name}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A parsed `float-output-format`, such as `%.3f`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct FloatFormat {
+    kind: char,
+    precision: Option<usize>,
+}
+
+impl FloatFormat {
+    fn parse(format: &str) -> Option<Self> {
+        let spec = format.strip_prefix('%')?;
+        let kind = spec.chars().last()?;
+        if !matches!(kind, 'e' | 'f' | 'g') {
+            return None;
+        }
+        let spec = &spec[..spec.len() - 1];
+        let precision = match spec.strip_prefix('.') {
+            Some(digits) => Some(digits.parse().ok()?),
+            None if spec.is_empty() => None,
+            None => return None,
+        };
+        // 0 is not allowed with `e' or `g'
+        if kind != 'f' && precision == Some(0) {
+            return None;
+        }
+        Some(Self { kind, precision })
+    }
+}
+
+/// Print a float. Without a format, this uses the shortest representation
+/// that reads back as the same number.
+pub(crate) fn float_to_string(float: f64, format: Option<FloatFormat>) -> String {
+    if float.is_infinite() {
+        return if float < 0.0 { "-1.0e+INF" } else { "1.0e+INF" }.to_owned();
+    }
+    if float.is_nan() {
+        return if float.is_sign_negative() { "-0.0e+NaN" } else { "0.0e+NaN" }.to_owned();
+    }
+    let string = match format {
+        None => {
+            let (digits, exp) = decimal_digits(&format!("{float:e}"));
+            // Emacs tries precisions starting with DBL_DIG until the number
+            // round trips, so short numbers are still printed with %.15g.
+            general_notation(float.is_sign_negative(), &digits, exp, digits.len().max(15))
+        }
+        // "%.0f" is allowed to not have a decimal point
+        Some(FloatFormat { kind: 'f', precision: Some(0) }) => return format!("{float:.0}"),
+        Some(FloatFormat { kind: 'f', precision }) => {
+            let precision = precision.unwrap_or(6);
+            format!("{float:.precision$}")
+        }
+        Some(FloatFormat { kind: 'e', precision }) => {
+            let precision = precision.unwrap_or(6);
+            let (digits, exp) = decimal_digits(&format!("{float:.precision$e}"));
+            let (int, frac) = digits.split_at(1);
+            let sign = if float.is_sign_negative() { "-" } else { "" };
+            let point = if frac.is_empty() { "" } else { "." };
+            format!("{sign}{int}{point}{frac}e{}", exponent(exp))
+        }
+        Some(FloatFormat { precision, .. }) => {
+            let precision = precision.unwrap_or(6).max(1);
+            let decimals = precision - 1;
+            let (digits, exp) = decimal_digits(&format!("{float:.decimals$e}"));
+            let digits = digits.trim_end_matches('0');
+            let digits = if digits.is_empty() { "0" } else { digits };
+            general_notation(float.is_sign_negative(), digits, exp, precision)
+        }
+    };
+    // make sure the result reads back as a float
+    if string.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        string + ".0"
+    } else {
+        string
+    }
+}
+
+/// Split Rust's scientific notation into the significant digits and the
+/// decimal exponent.
+fn decimal_digits(scientific: &str) -> (String, i32) {
+    let (mantissa, exp) = scientific.split_once('e').unwrap();
+    let digits = mantissa.chars().filter(char::is_ascii_digit).collect();
+    (digits, exp.parse().unwrap())
+}
+
+fn exponent(exp: i32) -> String {
+    let sign = if exp < 0 { '-' } else { '+' };
+    format!("{sign}{:02}", exp.abs())
+}
+
+/// Lay out significant digits like C's `%g` with the given precision.
+fn general_notation(negative: bool, digits: &str, exp: i32, precision: usize) -> String {
+    let mut out = String::from(if negative { "-" } else { "" });
+    if exp < -4 || exp >= precision as i32 {
+        let (int, frac) = digits.split_at(1);
+        out.push_str(int);
+        if !frac.is_empty() {
+            out.push('.');
+            out.push_str(frac);
+        }
+        out.push('e');
+        out.push_str(&exponent(exp));
+    } else if exp < 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', exp.unsigned_abs() as usize - 1));
+        out.push_str(digits);
+    } else {
+        let int_len = exp as usize + 1;
+        if digits.len() > int_len {
+            out.push_str(&digits[..int_len]);
+            out.push('.');
+            out.push_str(&digits[int_len..]);
+        } else {
+            out.push_str(digits);
+            out.extend(std::iter::repeat_n('0', int_len - digits.len()));
+        }
+    }
+    out
+}
+
+/// An object compared by identity, so it can be used as a key to find shared
+/// structure.
+#[derive(Copy, Clone)]
+struct Identity<'ob>(Object<'ob>);
+
+impl PartialEq for Identity<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(other.0)
+    }
+}
+
+impl Eq for Identity<'_> {}
+
+impl Hash for Identity<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// Print `obj` to a string using `options`.
+pub(crate) fn print_to_string(obj: Object, options: &PrintOptions) -> String {
+    let mut printer = Printer {
+        options,
+        out: String::new(),
+        labels: HashMap::default(),
+        next_label: 0,
+        being_printed: Vec::new(),
+    };
+    if options.circle {
+        printer.find_shared(obj);
+    }
+    printer.print(obj);
+    printer.out
+}
+
+struct Printer<'a, 'ob> {
+    options: &'a PrintOptions,
+    out: String,
+    /// Objects that appear more than once when `print-circle` is non-nil, along
+    /// with their `#N=` label. The label is 0 until the object is printed.
+    labels: HashMap<Identity<'ob>, usize>,
+    next_label: usize,
+    /// The lists and vectors that we are inside of.
+    being_printed: Vec<Object<'ob>>,
+}
+
+impl<'ob> Printer<'_, 'ob> {
+    fn is_circle_candidate(&self, obj: Object) -> bool {
+        match obj.untag() {
+            ObjectType::Cons(_)
+            | ObjectType::Vec(_)
+            | ObjectType::Record(_)
+            | ObjectType::String(_)
+            | ObjectType::ByteString(_) => true,
+            ObjectType::Symbol(sym) => self.options.gensym && !sym.interned(),
+            _ => false,
+        }
+    }
+
+    /// Find all objects that are reachable more than once from `obj`.
+    fn find_shared(&mut self, obj: Object<'ob>) {
+        let mut seen: HashMap<Identity, bool> = HashMap::default();
+        let mut stack = vec![obj];
+        while let Some(obj) = stack.pop() {
+            if !self.is_circle_candidate(obj) {
+                continue;
+            }
+            if let Some(shared) = seen.get_mut(&Identity(obj)) {
+                *shared = true;
+                continue;
+            }
+            seen.insert(Identity(obj), false);
+            match obj.untag() {
+                ObjectType::Cons(cons) => stack.extend([cons.cdr(), cons.car()]),
+                ObjectType::Vec(vec) => stack.extend(vec.iter().rev().map(|x| x.get())),
+                ObjectType::Record(rec) => stack.extend(rec.iter().rev().map(|x| x.get())),
+                _ => {}
+            }
+        }
+        self.labels = seen.into_iter().filter(|x| x.1).map(|(obj, _)| (obj, 0)).collect();
+    }
+
+    /// Print the `#N=` or `#N#` label of `obj`. Returns true if the object
+    /// was already printed and only needed a reference.
+    fn print_label(&mut self, obj: Object<'ob>) -> bool {
+        let Some(label) = self.labels.get_mut(&Identity(obj)) else { return false };
+        if *label != 0 {
+            write!(self.out, "#{label}#").unwrap();
+            return true;
+        }
+        self.next_label += 1;
+        *label = self.next_label;
+        write!(self.out, "#{label}=").unwrap();
+        false
+    }
+
+    /// Start printing a list or vector. Returns false if it was printed as a
+    /// reference or elided by `print-level` instead.
+    fn enter(&mut self, obj: Object<'ob>) -> bool {
+        if !self.options.circle
+            && let Some(i) = self.being_printed.iter().position(|x| x.ptr_eq(obj))
+        {
+            write!(self.out, "#{i}").unwrap();
+            return false;
+        }
+        if self.options.level.is_some_and(|level| self.being_printed.len() >= level) {
+            self.out.push_str("...");
+            return false;
+        }
+        self.being_printed.push(obj);
+        true
+    }
+
+    fn print(&mut self, obj: Object<'ob>) {
+        if self.options.circle && self.print_label(obj) {
+            return;
+        }
+        match obj.untag() {
+            ObjectType::Int(x) => write!(self.out, "{x}").unwrap(),
+            ObjectType::Float(x) => {
+                self.out.push_str(&float_to_string(**x, self.options.float_format))
+            }
+            ObjectType::Symbol(x) => self.print_symbol(x),
+            ObjectType::String(x) => self.print_string(x),
+            ObjectType::ByteString(x) => self.print_byte_string(x),
+            ObjectType::Cons(x) => self.print_list(x),
+            ObjectType::Vec(x) => self.print_vector(obj, "[", x.iter().map(|x| x.get()), "]"),
+            ObjectType::Record(x) => self.print_vector(obj, "#s(", x.iter().map(|x| x.get()), ")"),
+            x => write!(self.out, "{x}").unwrap(),
+        }
+    }
+
+    fn print_symbol(&mut self, sym: Symbol) {
+        let name = sym.name();
+        if !self.options.escape {
+            self.out.push_str(name);
+            return;
+        }
+        if self.options.gensym && !sym.interned() {
+            self.out.push_str("#:");
+        } else if name.is_empty() {
+            self.out.push_str("##");
+        }
+        // a name that would read back as a number or a character needs a
+        // leading escape
+        let confusing =
+            name.parse::<f64>().is_ok_and(|_| name.contains(|c: char| c.is_ascii_digit()))
+                || name.starts_with('?')
+                || name == ".";
+        if confusing {
+            self.out.push('\\');
+        }
+        for c in name.chars() {
+            if matches!(c, '"' | '\\' | '\'' | ';' | '#' | '(' | ')' | ',' | '`' | '[' | ']')
+                || c <= ' '
+                || c == '\u{a0}'
+            {
+                self.out.push('\\');
+            }
+            self.out.push(c);
+        }
+    }
+
+    fn print_string(&mut self, string: &LispString) {
+        if !self.options.escape {
+            self.out.push_str(string);
+            return;
+        }
+        let props = string.textprops();
+        let intervals: Vec<_> = match &props {
+            Some(props) => props.iter(0, string.len()).filter(|(_, x)| !x.is_nil()).collect(),
+            None => Vec::new(),
+        };
+        drop(props);
+        if !intervals.is_empty() {
+            self.out.push_str("#(");
+        }
+        self.out.push('"');
+        let mut hex_escape = false;
+        for c in string.chars() {
+            if let Some(byte) = char_to_raw_byte(c) {
+                write!(self.out, "\\{byte:03o}").unwrap();
+                hex_escape = false;
+            } else if !c.is_ascii() && self.options.escape_multibyte {
+                write!(self.out, "\\x{:x}", char_to_int(c)).unwrap();
+                hex_escape = true;
+            } else {
+                // a hex digit after a hex escape would be read as part of it
+                if hex_escape && c.is_ascii_hexdigit() {
+                    self.out.push_str("\\ ");
+                }
+                self.print_string_char(c);
+                hex_escape = false;
+            }
+        }
+        self.out.push('"');
+        if !intervals.is_empty() {
+            for (range, plist) in intervals {
+                write!(self.out, " {} {} ", range.start, range.end).unwrap();
+                // SAFETY: the properties are owned by the string being printed
+                self.print(unsafe { plist.with_lifetime() });
+            }
+            self.out.push(')');
+        }
+    }
+
+    fn print_byte_string(&mut self, string: &ByteString) {
+        if !self.options.escape {
+            self.out.extend(string.iter().map(|&x| raw_byte_to_char(x)));
+            return;
+        }
+        self.out.push('"');
+        for &byte in string.iter() {
+            if byte.is_ascii() {
+                self.print_string_char(byte as char);
+            } else if self.options.escape_nonascii {
+                write!(self.out, "\\{byte:03o}").unwrap();
+            } else {
+                self.out.push(raw_byte_to_char(byte));
+            }
+        }
+        self.out.push('"');
+    }
+
+    fn print_string_char(&mut self, c: char) {
+        match c {
+            '"' | '\\' => {
+                self.out.push('\\');
+                self.out.push(c);
+            }
+            '\n' if self.options.escape_newlines => self.out.push_str("\\n"),
+            '\x0c' if self.options.escape_newlines => self.out.push_str("\\f"),
+            c => self.out.push(c),
+        }
+    }
+
+    fn print_list(&mut self, cons: &'ob Cons) {
+        if self.options.quoted && self.print_quoted(cons) {
+            return;
+        }
+        let obj: Object = cons.into();
+        if !self.enter(obj) {
+            return;
+        }
+        self.out.push('(');
+        let mut cons = cons;
+        let mut count = 0;
+        // Brent's cycle detection for the tail of the list
+        let (mut tortoise, mut tortoise_idx, mut power, mut steps) = (cons, 0, 2, 2);
+        loop {
+            if self.options.length == Some(count) {
+                self.out.push_str("...");
+                break;
+            }
+            self.print(cons.car());
+            count += 1;
+            match cons.cdr().untag() {
+                ObjectType::NIL => break,
+                ObjectType::Cons(next) => {
+                    let next_obj: Object = next.into();
+                    if self.options.circle && self.labels.contains_key(&Identity(next_obj)) {
+                        self.out.push_str(" . ");
+                        self.print(next_obj);
+                        break;
+                    }
+                    self.out.push(' ');
+                    if !self.options.circle {
+                        if std::ptr::eq(next, tortoise) {
+                            write!(self.out, ". #{tortoise_idx}").unwrap();
+                            break;
+                        }
+                        steps -= 1;
+                        if steps == 0 {
+                            tortoise = next;
+                            tortoise_idx = count;
+                            power *= 2;
+                            steps = power;
+                        }
+                    }
+                    cons = next;
+                }
+                tail => {
+                    self.out.push_str(" . ");
+                    self.print(tail.tag());
+                    break;
+                }
+            }
+        }
+        self.out.push(')');
+        self.being_printed.pop();
+    }
+
+    /// Print `(quote x)` and friends using the reader shorthand.
+    fn print_quoted(&mut self, cons: &'ob Cons) -> bool {
+        let ObjectType::Cons(rest) = cons.cdr().untag() else { return false };
+        if !rest.cdr().is_nil() {
+            return false;
+        }
+        let prefix = match cons.car().untag() {
+            ObjectType::Symbol(sym::QUOTE) => "'",
+            ObjectType::Symbol(sym::FUNCTION) => "#'",
+            ObjectType::Symbol(sym::BACKQUOTE) => "`",
+            ObjectType::Symbol(sym::UNQUOTE) => ",",
+            ObjectType::Symbol(sym::SPLICE) => ",@",
+            _ => return false,
+        };
+        self.out.push_str(prefix);
+        self.print(rest.car());
+        true
+    }
+
+    fn print_vector(
+        &mut self,
+        obj: Object<'ob>,
+        open: &str,
+        elements: impl Iterator<Item = Object<'ob>>,
+        close: &str,
+    ) {
+        if !self.enter(obj) {
+            return;
+        }
+        self.out.push_str(open);
+        for (i, elem) in elements.enumerate() {
+            if i != 0 {
+                self.out.push(' ');
+            }
+            if self.options.length == Some(i) {
+                self.out.push_str("...");
+                break;
+            }
+            self.print(elem);
+        }
+        self.out.push_str(close);
+        self.being_printed.pop();
+    }
+}
+
+/// Send `text` to PRINTCHARFUN, which can be a buffer, a marker, a function, or
+/// `t` for the echo area. If it is nil, the value of `standard-output` is used.
+fn output(
+    text: &str,
+    printcharfun: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &mut Context,
+) -> Result<()> {
+    let dest = match printcharfun.map(|x| x.bind(cx)) {
+        Some(dest) if !dest.is_nil() => dest,
+        _ => env.var(sym::STANDARD_OUTPUT, cx).unwrap_or(TRUE),
+    };
+    match dest.untag() {
+        ObjectType::NIL | ObjectType::TRUE => {
+            print!("{text}");
+            std::io::stdout().flush()?;
+        }
+        ObjectType::Buffer(buffer) if buffer == env.current_buffer.buf_ref => {
+            editfns::insert_object(&mut env.current_buffer, cx.add(text), cx)?;
+        }
+        ObjectType::Buffer(buffer) => {
+            let text = cx.add(text);
+            env.with_buffer_mut(buffer, |buf| buf.insert(text))??;
+        }
+        ObjectType::Marker(marker) => {
+            let (Some(buffer), Some(pos)) = (marker.buffer(), marker.position()) else {
+                bail!("Marker does not point anywhere");
+            };
+            let text = cx.add(text);
+            env.with_buffer_mut(buffer, |buf| {
+                let point = buf.text.cursor().chars();
+                buf.text.set_cursor(pos);
+                buf.insert(text)?;
+                let end = buf.text.cursor().chars();
+                let point = if point >= pos { point + end - pos } else { point };
+                buf.text.set_cursor(point);
+                buf.set_marker(marker, end);
+                Ok::<_, anyhow::Error>(())
+            })??;
+        }
+        _ => {
+            let func: Function = dest.try_into()?;
+            root!(func, cx);
+            for c in text.chars() {
+                call!(func, char_to_int(c); env, cx)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Output the printed representation of OBJECT, any Lisp object.
+/// Quoting characters are printed when needed to make output that `read'
+/// can handle, whenever this is possible.
+///
+/// Optional argument PRINTCHARFUN is the output stream, which can be one
+/// of these: a buffer, a marker, a function called with each character,
+/// or t for the echo area. If PRINTCHARFUN is omitted, the value of
+/// `standard-output' is used.
+///
+/// Optional argument OVERRIDES should be a list of settings for print-related
+/// variables. An element in this list can be the symbol t, which means "reset
+/// all the values to their defaults". Otherwise, an element should be a pair,
+/// where the `car' or the pair is the setting symbol, and the `cdr' is the
+/// value of the setting, for instance (length . 5).
+#[defun]
+pub(crate) fn prin1<'ob>(
+    object: &Rto<Object>,
+    printcharfun: Option<&Rto<Object>>,
+    overrides: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let mut options = PrintOptions::from_env(true, env, cx);
+    if let Some(overrides) = overrides {
+        options.apply_overrides(overrides.bind(cx))?;
+    }
+    let text = print_to_string(object.bind(cx), &options);
+    output(&text, printcharfun, env, cx)?;
+    Ok(object.bind(cx))
+}
+
+/// Output the printed representation of OBJECT, any Lisp object.
+/// No quoting characters are used; no delimiters are printed around
+/// the contents of strings.
+///
+/// PRINTCHARFUN is the output stream; see `prin1' for the possible values.
+#[defun]
+pub(crate) fn princ<'ob>(
+    object: &Rto<Object>,
+    printcharfun: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let text = print_to_string(object.bind(cx), &PrintOptions::from_env(false, env, cx));
+    output(&text, printcharfun, env, cx)?;
+    Ok(object.bind(cx))
+}
+
+/// Output the printed representation of OBJECT, with newlines around it.
+/// Quoting characters are printed when needed to make output that `read'
+/// can handle, whenever this is possible.
+///
+/// PRINTCHARFUN is the output stream; see `prin1' for the possible values.
+#[defun]
+pub(crate) fn print<'ob>(
+    object: &Rto<Object>,
+    printcharfun: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &'ob mut Context,
+) -> Result<Object<'ob>> {
+    let text = print_to_string(object.bind(cx), &PrintOptions::from_env(true, env, cx));
+    output(&format!("\n{text}\n"), printcharfun, env, cx)?;
+    Ok(object.bind(cx))
+}
+
+/// Output a newline to stream PRINTCHARFUN.
+/// If ENSURE is non-nil only output a newline if not already at the
+/// beginning of a line. Value is non-nil if a newline is printed.
+///
+/// PRINTCHARFUN is the output stream; see `prin1' for the possible values.
+#[defun]
+fn terpri(
+    printcharfun: Option<&Rto<Object>>,
+    ensure: OptionalFlag,
+    env: &mut Rt<Env>,
+    cx: &mut Context,
+) -> Result<bool> {
+    if ensure.is_some()
+        && let Some(ObjectType::Buffer(buffer)) = printcharfun.map(|x| x.untag(cx))
+    {
+        let bolp = env.with_buffer(buffer, |buf| {
+            let point = buf.text.cursor().chars();
+            point == 0 || buf.text.char_at(point - 1) == Some('\n')
+        })?;
+        if bolp {
+            return Ok(false);
+        }
+    }
+    output("\n", printcharfun, env, cx)?;
+    Ok(true)
+}
+
+/// Output character CHARACTER to stream PRINTCHARFUN.
+/// PRINTCHARFUN is the output stream; see `prin1' for the possible values.
+#[defun]
+fn write_char(
+    character: i64,
+    printcharfun: Option<&Rto<Object>>,
+    env: &mut Rt<Env>,
+    cx: &mut Context,
+) -> Result<i64> {
+    let Ok(chr) = int_to_char(character) else {
+        bail!(TypeError::new(Type::Char, cx.add(character)))
+    };
+    output(&chr.to_string(), printcharfun, env, cx)?;
+    Ok(character)
+}
+
+/// Return a string containing the printed representation of OBJECT.
+/// OBJECT can be any Lisp object.  This function outputs quoting characters
+/// when necessary to make output that `read' can handle, whenever possible,
+/// unless the optional second argument NOESCAPE is non-nil.
+///
+/// OVERRIDES is as for `prin1'.
+#[defun]
+pub(crate) fn prin1_to_string(
+    object: Object,
+    noescape: Option<Object>,
+    overrides: Option<Object>,
+    env: &Rt<Env>,
+    cx: &Context,
+) -> Result<String> {
+    let escape = noescape.is_none_or(|x| x.is_nil());
+    let mut options = PrintOptions::from_env(escape, env, cx);
+    if let Some(overrides) = overrides {
+        options.apply_overrides(overrides)?;
+    }
+    Ok(print_to_string(object, &options))
+}
+
 defvar!(PRINT_LENGTH);
 defvar!(PRINT_LEVEL);
 defvar_bool!(PRINT_ESCAPE_NEWLINES, false);
+defvar_bool!(PRINT_ESCAPE_NONASCII, false);
+defvar_bool!(PRINT_ESCAPE_MULTIBYTE, false);
+defvar_bool!(PRINT_CIRCLE, false);
+defvar_bool!(PRINT_GENSYM, false);
+defvar_bool!(PRINT_QUOTED, true);
+defvar!(FLOAT_OUTPUT_FORMAT);
+defvar!(STANDARD_OUTPUT, true);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_float_to_string() {
+        let print = |x| float_to_string(x, None);
+        assert_eq!(print(1.0), "1.0");
+        assert_eq!(print(-0.0), "-0.0");
+        assert_eq!(print(0.1), "0.1");
+        assert_eq!(print(100.0), "100.0");
+        assert_eq!(print(1e15), "1e+15");
+        assert_eq!(print(123456789012345.0), "123456789012345.0");
+        assert_eq!(print(1e-5), "1e-05");
+        assert_eq!(print(0.0001), "0.0001");
+        assert_eq!(print(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(print(f64::INFINITY), "1.0e+INF");
+        assert_eq!(print(f64::NEG_INFINITY), "-1.0e+INF");
+        assert_eq!(print(f64::NAN), "0.0e+NaN");
+        let format = |x, fmt| float_to_string(x, FloatFormat::parse(fmt));
+        assert_eq!(format(1.5, "%.3f"), "1.500");
+        assert_eq!(format(1.5, "%.0f"), "2");
+        assert_eq!(format(1234.5, "%.2e"), "1.23e+03");
+        assert_eq!(format(1234.5, "%.3g"), "1.23e+03");
+        assert_eq!(format(2.0, "%g"), "2.0");
+        assert_eq!(format(2.0, "%.0g"), "2.0");
+    }
+
+    #[test]
+    fn test_prin1_to_string() {
+        assert_lisp(r#"(prin1-to-string "a\"b")"#, r#""\"a\\\"b\"""#);
+        assert_lisp(r#"(prin1-to-string "a\"b" t)"#, r#""a\"b""#);
+        assert_lisp(r#"(prin1-to-string '(quote x))"#, r#""'x""#);
+        assert_lisp(r#"(prin1-to-string (intern "a b"))"#, r#""a\\ b""#);
+        assert_lisp(r#"(prin1-to-string (intern "1"))"#, r#""\\1""#);
+        assert_lisp(r#"(prin1-to-string (intern ""))"#, r###""##""###);
+        assert_lisp(r#"(let ((print-length 2)) (prin1-to-string '(1 2 3)))"#, r#""(1 2 ...)""#);
+        assert_lisp(r#"(let ((print-length 1)) (prin1-to-string [1 2]))"#, r#""[1 ...]""#);
+        assert_lisp(r#"(let ((print-level 1)) (prin1-to-string '(1 (2))))"#, r#""(1 ...)""#);
+        assert_lisp(r#"(prin1-to-string '(1 2) nil '((length . 1)))"#, r#""(1 ...)""#);
+        assert_lisp(
+            r#"(let ((print-escape-newlines t)) (prin1-to-string "a\nb"))"#,
+            r#""\"a\\nb\"""#,
+        );
+        assert_lisp(
+            r#"(let ((print-escape-multibyte t)) (prin1-to-string "é1"))"#,
+            r#""\"\\xe9\\ 1\"""#,
+        );
+        assert_lisp(
+            r#"(let ((print-escape-nonascii t)) (prin1-to-string (string-to-unibyte "\377")))"#,
+            r#""\"\\377\"""#,
+        );
+        assert_lisp(
+            r#"(let ((print-gensym t)) (prin1-to-string (make-symbol "x")))"#,
+            r##""#:x""##,
+        );
+        assert_lisp(r#"(let ((float-output-format "%.2f")) (prin1-to-string 1.0))"#, r#""1.00""#);
+    }
+
+    #[test]
+    fn test_print_circle() {
+        assert_lisp(
+            r#"(let ((x (list 1 2))) (setcdr (cdr x) x) (prin1-to-string x))"#,
+            r#""(1 2 . #0)""#,
+        );
+        assert_lisp(r#"(let ((x (list 1 2))) (setcar x x) (prin1-to-string x))"#, r#""(#0 2)""#);
+        assert_lisp(
+            r#"(let ((x (list 1 2)) (print-circle t)) (setcdr (cdr x) x) (prin1-to-string x))"#,
+            r##""#1=(1 2 . #1#)""##,
+        );
+        assert_lisp(
+            r#"(let ((x (list 1)) (print-circle t)) (prin1-to-string (list x x)))"#,
+            r#""(#1=(1) #1#)""#,
+        );
+        assert_lisp(r#"(let ((x (list 1))) (prin1-to-string (list x x)))"#, r#""((1) (1))""#);
+    }
+
+    #[test]
+    fn test_print_streams() {
+        assert_lisp(
+            r#"(let ((chars nil)) (prin1 "a" #'(lambda (c) (setq chars (cons c chars)))) chars)"#,
+            r#"(34 97 34)"#,
+        );
+        assert_lisp(
+            r#"(let* ((chars nil) (standard-output #'(lambda (c) (setq chars (cons c chars)))))
+                 (princ 'ab) (terpri) (write-char ?c) (print 1) chars)"#,
+            r#"(10 49 10 99 10 98 97)"#,
+        );
+        assert_lisp(
+            r#"(let ((buf (get-buffer-create "print-test")))
+                 (princ "hi" buf) (prin1 "hi" buf) (set-buffer buf) (point))"#,
+            "7",
+        );
+    }
+}
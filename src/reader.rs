@@ -1,15 +1,22 @@
 //! Lisp reader that reads an object from a string.
 use crate::core::{
+    cons::Cons,
     env::{intern, sym},
     gc::Context,
-    object::{Object, Symbol},
+    object::{
+        HashTable, NIL, Object, ObjectType, RecordBuilder, Symbol, TRUE, char_to_int,
+        char_to_raw_byte, raw_byte_to_char,
+    },
 };
-use crate::fns;
+use crate::lisp::{CHAR_MODIFIER_MASK, CharBits};
+use crate::{alloc, fns};
 use num_bigint::BigInt;
+use rune_core::hashmap::{HashMap, HashSet};
 use rune_core::macros::list;
-use std::str;
+use std::borrow::Cow;
 use std::{fmt, iter::Peekable, str::CharIndices};
 use std::{fmt::Display, str::FromStr};
+use std::{ptr, str};
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -27,6 +34,12 @@ pub(crate) enum Error {
     UnknownMacroCharacter(char, usize),
     ParseInt(u8, usize),
     MalformedUnicdoe(usize),
+    InvalidStringProperty(usize),
+    InvalidEscape(usize),
+    InvalidRecord(usize),
+    InvalidBoolVector(usize),
+    UndefinedLabel(usize, usize),
+    InvalidByteCode(usize),
     EmptyStream,
 }
 
@@ -40,6 +53,12 @@ impl Display for Error {
             Error::ExtraCloseBracket(i) => write!(f, "Extra Closing brace: at {i}"),
             Error::UnexpectedChar(chr, i) => write!(f, "Unexpected character {chr}: at {i}"),
             Error::MalformedUnicdoe(i) => write!(f, "Malformed unicode: at {i}"),
+            Error::InvalidStringProperty(i) => write!(f, "Invalid string property list: at {i}"),
+            Error::InvalidEscape(i) => write!(f, "Invalid escape character syntax: at {i}"),
+            Error::InvalidRecord(i) => write!(f, "Invalid record or hash table syntax: at {i}"),
+            Error::InvalidBoolVector(i) => write!(f, "Invalid bool-vector syntax: at {i}"),
+            Error::UndefinedLabel(label, i) => write!(f, "Undefined label #{label}#: at {i}"),
+            Error::InvalidByteCode(i) => write!(f, "Invalid byte-code object: at {i}"),
             Error::EmptyStream => write!(f, "Empty Stream"),
             Error::ExtraItemInCdr(i) => write!(f, "Extra item in cdr: at {i}"),
             Error::MissingQuotedItem(i) => write!(f, "Missing element after quote: at {i}"),
@@ -63,6 +82,12 @@ impl Error {
             | Error::MissingStringDel(i)
             | Error::UnexpectedChar(_, i)
             | Error::MalformedUnicdoe(i)
+            | Error::InvalidStringProperty(i)
+            | Error::InvalidEscape(i)
+            | Error::InvalidRecord(i)
+            | Error::InvalidBoolVector(i)
+            | Error::UndefinedLabel(_, i)
+            | Error::InvalidByteCode(i)
             | Error::ExtraItemInCdr(i)
             | Error::ExtraCloseParen(i)
             | Error::ExtraCloseBracket(i)
@@ -73,6 +98,19 @@ impl Error {
         }
     }
 
+    /// True if the error could go away if there was more text after the end
+    /// of the input.
+    pub(crate) fn is_incomplete(&self) -> bool {
+        matches!(
+            self,
+            Error::MissingCloseParen(_)
+                | Error::MissingCloseBracket(_)
+                | Error::MissingStringDel(_)
+                | Error::MissingQuotedItem(_)
+                | Error::EmptyStream
+        )
+    }
+
     pub(crate) fn update_pos(&mut self, offset: usize) {
         if let Some(pos) = self.mut_pos() {
             *pos += offset;
@@ -91,7 +129,7 @@ enum Token<'a> {
     Unquote(usize),
     Splice(usize),
     Sharp(usize),
-    QuestionMark(usize, char),
+    QuestionMark(usize, i64),
     Ident(&'a str),
     String(&'a str),
 }
@@ -108,7 +146,12 @@ impl Display for Token<'_> {
             Token::Unquote(_) => write!(f, ","),
             Token::Splice(_) => write!(f, ",@"),
             Token::Sharp(_) => write!(f, "#"),
-            Token::QuestionMark(_, chr) => write!(f, "?{chr}"),
+            Token::QuestionMark(_, code) => {
+                match u32::try_from(*code).ok().and_then(char::from_u32) {
+                    Some(chr) => write!(f, "?{chr}"),
+                    None => write!(f, "?{code}"),
+                }
+            }
             Token::Ident(x) => write!(f, "{x}"),
             Token::String(x) => write!(f, "\"{x}\""),
         }
@@ -210,53 +253,140 @@ impl<'a> Tokenizer<'a> {
     }
 
     fn read_quoted_char(&mut self, idx: usize) -> Result<Token<'a>> {
-        match self.iter.next() {
-            Some((start, item)) => {
-                if item == '\\' {
-                    let Token::Ident(tok) = self.get_symbol(start, item) else { unreachable!() };
-                    let Some(chr) = tok.chars().nth(1) else {
-                        return Err(Error::MissingQuotedItem(start));
-                    };
-                    if chr == 'u' || chr == 'x' {
-                        match u32::from_str_radix(&tok[2..], 16) {
-                            Ok(digits) => match char::from_u32(digits) {
-                                Some(c) => Ok(Token::QuestionMark(start, c)),
-                                None => Err(Error::MalformedUnicdoe(start)),
-                            },
-                            Err(_) => Err(Error::MalformedUnicdoe(start)),
-                        }
-                    } else if tok.chars().count() == 2 {
-                        let new = match chr {
-                            'a' => '\u{07}',
-                            'b' => '\u{08}',
-                            'd' => '\u{7F}',
-                            'e' => '\u{1B}',
-                            'f' => '\u{0C}',
-                            'n' => '\n',
-                            'r' => '\r',
-                            's' => ' ',
-                            't' => '\t',
-                            'v' => '\u{0B}',
-                            c => c,
-                        };
-                        Ok(Token::QuestionMark(start, new))
-                    } else {
-                        // TODO implement keycode parsing
-                        Ok(Token::QuestionMark(start, '\0'))
-                    }
-                } else {
-                    match self.iter.peek() {
-                        Some((i, chr)) if symbol_char(*chr) && *chr != '?' => {
-                            Err(Error::UnexpectedChar(*chr, *i)) // ?aa
-                        }
-                        _ => Ok(Token::QuestionMark(idx, item)), // ?a
-                    }
+        let token = match self.iter.next() {
+            Some((start, '\\')) => Token::QuestionMark(start, self.read_char_escape(start)?),
+            Some((_, item)) => Token::QuestionMark(idx, char_to_int(item)), // ?a
+            None => return Err(Error::MissingQuotedItem(idx)),
+        };
+        match self.iter.peek() {
+            Some((i, chr)) if symbol_char(*chr) && *chr != '?' => {
+                Err(Error::UnexpectedChar(*chr, *i)) // ?aa
+            }
+            _ => Ok(token),
+        }
+    }
+
+    /// Read the escape sequence following a `\` in character syntax. The
+    /// returned code includes any modifier bits, so `\M-a` is `a` with the meta
+    /// bit set.
+    fn read_char_escape(&mut self, start: usize) -> Result<i64> {
+        let Some((_, chr)) = self.iter.next() else {
+            return Err(Error::MissingQuotedItem(start));
+        };
+        let code = match chr {
+            'a' => 0x07,
+            'b' => 0x08,
+            'd' => 0x7F,
+            'e' => 0x1B,
+            'f' => 0x0C,
+            'n' => 0x0A,
+            'r' => 0x0D,
+            't' => 0x09,
+            'v' => 0x0B,
+            // `\s` is a space unless it is the super modifier `\s-`
+            's' if self.iter.peek().is_none_or(|x| x.1 != '-') => 0x20,
+            's' | 'A' | 'C' | 'H' | 'M' | 'S' => {
+                if self.iter.next_if(|x| x.1 == '-').is_none() {
+                    return Err(Error::InvalidEscape(start));
+                }
+                let code = self.read_modified_char(start)?;
+                match chr {
+                    's' => code | CharBits::Super as i64,
+                    'A' => code | CharBits::Alt as i64,
+                    'H' => code | CharBits::Hyper as i64,
+                    'M' => code | CharBits::Meta as i64,
+                    'S' => code | CharBits::Shift as i64,
+                    _ => control_char(code),
                 }
             }
-            None => Err(Error::MissingQuotedItem(idx)),
+            '^' => control_char(self.read_modified_char(start)?),
+            'x' => self.read_hex_escape(start, usize::MAX)?,
+            'u' => self.read_hex_escape(start, 4)?,
+            'U' => self.read_hex_escape(start, 8)?,
+            '0'..='7' => {
+                let mut code = i64::from(chr as u8 - b'0');
+                for _ in 0..2 {
+                    let Some((_, digit)) = self.iter.next_if(|x| x.1.is_digit(8)) else { break };
+                    code = code * 8 + i64::from(digit as u8 - b'0');
+                }
+                code
+            }
+            'N' => {
+                if self.iter.next_if(|x| x.1 == '{').is_none() {
+                    return Err(Error::InvalidEscape(start));
+                }
+                let beg = self.cur_pos();
+                let end = self.skip_till(|c| c == '}');
+                if self.iter.next().is_none() {
+                    return Err(Error::InvalidEscape(start));
+                }
+                match char_from_name(&self.slice[beg..end]) {
+                    Some(chr) => char_to_int(chr),
+                    None => return Err(Error::InvalidEscape(start)),
+                }
+            }
+            c => char_to_int(c),
+        };
+        Ok(code)
+    }
+
+    /// Read the character that a modifier such as `\C-` or `\M-` applies to.
+    fn read_modified_char(&mut self, start: usize) -> Result<i64> {
+        match self.iter.next() {
+            Some((_, '\\')) => self.read_char_escape(start),
+            Some((_, chr)) => Ok(char_to_int(chr)),
+            None => Err(Error::MissingQuotedItem(start)),
+        }
+    }
+
+    /// Read up to `max` hex digits of a `\x`, `\u` or `\U` escape.
+    fn read_hex_escape(&mut self, start: usize, max: usize) -> Result<i64> {
+        let beg = self.cur_pos();
+        let mut count = 0;
+        let end = self.skip_till(|c| {
+            count += 1;
+            count > max || !c.is_ascii_hexdigit()
+        });
+        match u32::from_str_radix(&self.slice[beg..end], 16) {
+            Ok(digits) if char::from_u32(digits).is_some() => Ok(digits.into()),
+            _ => Err(Error::MalformedUnicdoe(start)),
         }
     }
 
+    /// Skip the text following `#@COUNT`. The character that terminates the
+    /// count is included in it, and `#@00` skips to the end of the input. This
+    /// is used in compiled files to hide docstrings from the reader.
+    fn skip_counted(&mut self) {
+        let beg = self.cur_pos();
+        let end = self.skip_till(|c| !c.is_ascii_digit());
+        let digits = &self.slice[beg..end];
+        let skip_end = match digits.parse::<usize>() {
+            _ if digits == "00" => usize::MAX,
+            Ok(count) => end + count,
+            Err(_) => end,
+        };
+        while self.iter.next_if(|x| x.0 < skip_end).is_some() {}
+    }
+
+    /// Read the name of a symbol that follows a reader macro such as `#:`. The
+    /// name is empty if the next character can't start a symbol.
+    fn read_symbol_name(&mut self) -> &'a str {
+        match self.iter.next_if(|x| symbol_char(x.1)) {
+            Some((beg, chr)) => match self.get_symbol(beg, chr) {
+                Token::Ident(name) => name,
+                _ => unreachable!(),
+            },
+            None => "",
+        }
+    }
+
+    /// Read the decimal number that follows a reader macro such as `#&`.
+    fn read_decimal(&mut self) -> Option<usize> {
+        let beg = self.cur_pos();
+        let end = self.skip_till(|c| !c.is_ascii_digit());
+        self.slice[beg..end].parse().ok()
+    }
+
     fn read_char(&mut self) -> Option<char> {
         self.iter.next().map(|x| x.1)
     }
@@ -276,6 +406,10 @@ impl<'a> Iterator for Tokenizer<'a> {
             '\'' => Ok(Token::Quote(idx)),
             ',' => Ok(self.get_macro_char(idx)),
             '`' => Ok(Token::Backquote(idx)),
+            '#' if self.iter.next_if(|x| x.1 == '@').is_some() => {
+                self.skip_counted();
+                return self.next();
+            }
             '#' => Ok(Token::Sharp(idx)),
             '?' => self.read_quoted_char(idx),
             '"' => self.get_string(idx),
@@ -286,7 +420,8 @@ impl<'a> Iterator for Tokenizer<'a> {
     }
 }
 
-fn intern_symbol<'ob>(symbol: &str, cx: &'ob Context) -> Symbol<'ob> {
+/// Remove the escapes from a symbol name.
+fn symbol_name(symbol: &str) -> Cow<'_, str> {
     let mut escaped = false;
     let is_not_escape = |c: &char| {
         if escaped {
@@ -300,13 +435,16 @@ fn intern_symbol<'ob>(symbol: &str, cx: &'ob Context) -> Symbol<'ob> {
         }
     };
     if symbol.contains('\\') {
-        let escaped_slice: String = symbol.chars().filter(is_not_escape).collect();
-        intern(escaped_slice.as_str(), cx)
+        Cow::Owned(symbol.chars().filter(is_not_escape).collect())
     } else {
-        intern(symbol, cx)
+        Cow::Borrowed(symbol)
     }
 }
 
+fn intern_symbol<'ob>(symbol: &str, cx: &'ob Context) -> Symbol<'ob> {
+    intern(&symbol_name(symbol), cx)
+}
+
 /// Parse a symbol from a string. This will either by a true symbol or a number
 /// literal.
 fn parse_symbol<'a>(slice: &str, cx: &'a Context) -> Object<'a> {
@@ -323,32 +461,130 @@ fn parse_symbol<'a>(slice: &str, cx: &'a Context) -> Object<'a> {
 }
 
 /// process escape characters in the string slice and return the resulting
-/// string.
+/// string. Octal and hex escapes below 256 are raw bytes, so a string that
+/// only contains those and ASCII is unibyte.
 fn unescape_string<'a>(string: &str, cx: &'a Context) -> Object<'a> {
-    let mut escaped = false;
-    let unescape = |c: char| {
-        if escaped {
-            escaped = false;
-            // TODO: Handle unicode, hex, and octal escapes
-            match c {
-                'n' => Some('\n'),
-                't' => Some('\t'),
-                'r' => Some('\r'),
-                '\n' | ' ' => None,
-                _ => Some(c),
+    let mut chars = Vec::with_capacity(string.len());
+    let mut multibyte = false;
+    let mut iter = string.chars().peekable();
+    while let Some(c) = iter.next() {
+        if c != '\\' {
+            multibyte |= !c.is_ascii();
+            chars.push(c);
+            continue;
+        }
+        let Some(c) = iter.next() else { break };
+        // Read up to `max` digits, continuing from `code`
+        let mut digits = |radix, max, mut code: u32| {
+            for _ in 0..max {
+                let Some(digit) = iter.peek().and_then(|x| x.to_digit(radix)) else { break };
+                code = code * radix + digit;
+                iter.next();
             }
-        } else if c == '\\' {
-            escaped = true;
-            None
-        } else {
-            Some(c)
+            code
+        };
+        let chr = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\u{07}',
+            'b' => '\u{08}',
+            'd' => '\u{7F}',
+            'e' => '\u{1B}',
+            'f' => '\u{0C}',
+            's' => ' ',
+            'v' => '\u{0B}',
+            '\n' | ' ' => continue,
+            '0'..='7' | 'x' => {
+                let code = match c.to_digit(8) {
+                    Some(first) => digits(8, 2, first),
+                    None => digits(16, 8, 0),
+                };
+                match u8::try_from(code) {
+                    Ok(byte) => raw_byte_to_char(byte),
+                    Err(_) => {
+                        multibyte = true;
+                        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                }
+            }
+            'u' | 'U' => {
+                multibyte = true;
+                let code = digits(16, if c == 'u' { 4 } else { 8 }, 0);
+                char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            'N' if iter.next_if_eq(&'{').is_some() => {
+                multibyte = true;
+                let name: String = iter.by_ref().take_while(|x| *x != '}').collect();
+                char_from_name(&name).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            _ => c,
+        };
+        chars.push(chr);
+    }
+    if multibyte || chars.iter().all(char::is_ascii) {
+        let mut new = cx.string_with_capacity(string.len());
+        new.extend(chars);
+        cx.add(new)
+    } else {
+        let bytes = chars.into_iter().map(|x| char_to_raw_byte(x).unwrap_or(x as u8));
+        cx.add(bytes.collect::<Vec<u8>>())
+    }
+}
+
+/// Find a character by the name used in a `\N{NAME}` escape. This is either the
+/// Unicode name of the character or its code point written as `U+XXXX`.
+fn char_from_name(name: &str) -> Option<char> {
+    // names can be split over multiple lines
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    match name.strip_prefix("U+") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+        None => unicode_names2::character(&name),
+    }
+}
+
+/// Apply the control modifier to `code`. Letters and the characters `@[\]^_`
+/// have ASCII control equivalents, `?` becomes DEL, and everything else gets
+/// the control bit.
+fn control_char(code: i64) -> i64 {
+    let modifiers = CHAR_MODIFIER_MASK as i64;
+    let base = code & !modifiers;
+    if base == i64::from(b'?') {
+        0o177 | (code & modifiers)
+    } else if base >= 0o200 {
+        code | CharBits::Ctl as i64
+    } else if (0o101..=0o132).contains(&(code & 0o137)) || (0o100..=0o137).contains(&base) {
+        code & (0o37 | !0o177)
+    } else {
+        code | CharBits::Ctl as i64
+    }
+}
+
+/// Replace every reference to `placeholder` inside of `tree` with `object`.
+fn substitute<'ob>(tree: Object<'ob>, placeholder: Object<'ob>, object: Object<'ob>) {
+    let swap = |x: Object<'ob>| if x.ptr_eq(placeholder) { object } else { x };
+    let mut seen: HashSet<*const u8> = HashSet::default();
+    let mut stack = vec![tree];
+    while let Some(obj) = stack.pop() {
+        let cells = match obj.untag() {
+            ObjectType::Cons(cons) => {
+                if seen.insert(ptr::from_ref(cons).cast()) {
+                    // conses created by the reader are always mutable
+                    cons.set_car(swap(cons.car())).unwrap();
+                    cons.set_cdr(swap(cons.cdr())).unwrap();
+                    stack.extend([cons.cdr(), cons.car()]);
+                }
+                continue;
+            }
+            ObjectType::Vec(vec) if seen.insert(ptr::from_ref(vec).cast()) => vec.try_mut(),
+            ObjectType::Record(rec) if seen.insert(ptr::from_ref(rec).cast()) => rec.try_mut(),
+            _ => continue,
+        };
+        for cell in cells.into_iter().flatten() {
+            cell.set(swap(cell.get()));
+            stack.push(cell.get());
         }
-    };
-    let mut new = cx.string_with_capacity(string.len());
-    for c in string.chars().filter_map(unescape) {
-        new.push(c);
     }
-    cx.add(new)
 }
 
 /// Return true if `chr` is a valid symbol character.
@@ -374,6 +610,12 @@ struct Reader<'a, 'ob> {
     tokens: Tokenizer<'a>,
     /// New objects are allocated in the context.
     cx: &'ob Context<'ob>,
+    /// Objects labeled with `#N=`, along with whether they have been
+    /// referenced by `#N#`. While the labeled object is being read the label
+    /// refers to a placeholder.
+    labels: HashMap<usize, (Object<'ob>, bool)>,
+    /// The value of `#$`.
+    load_file_name: Object<'ob>,
 }
 
 impl<'a, 'ob> Reader<'a, 'ob> {
@@ -469,35 +711,200 @@ impl<'a, 'ob> Reader<'a, 'ob> {
                 }
                 None => Err(Error::MissingQuotedItem(pos)),
             },
+            Some('(') => self.read_propertized_string(pos),
+            Some('s') => match self.tokens.read_char() {
+                Some('(') => self.read_record(pos),
+                _ => Err(Error::InvalidRecord(pos)),
+            },
+            Some('&') => self.read_bool_vector(pos),
+            Some('[') => self.read_byte_code(pos),
+            Some('$') => Ok(self.load_file_name),
+            Some('#') => Ok(intern("", self.cx).into()),
+            // Rune has no shorthands, so this is just a symbol
+            Some('_') => Ok(intern_symbol(self.tokens.read_symbol_name(), self.cx).into()),
+            Some(':') => {
+                let name = symbol_name(self.tokens.read_symbol_name());
+                Ok(Symbol::new_uninterned(&name, self.cx).into())
+            }
             Some('b') => self.read_radix(pos, 2),
             Some('o') => self.read_radix(pos, 8),
             Some('x') => self.read_radix(pos, 16),
             Some(chr) if chr.is_ascii_digit() => {
-                let mut radix = (chr as u8) - b'0';
+                let mut number = usize::from((chr as u8) - b'0');
+                // The digit where the number became too large to be a radix
+                let mut radix_overflow = None;
                 loop {
                     match self.tokens.read_char() {
                         Some('r') => break,
+                        Some('=') => return self.read_labeled(number, pos),
+                        Some('#') => return self.read_label_ref(number, pos),
                         Some(chr) if chr.is_ascii_digit() => {
-                            match radix
+                            match number
                                 .checked_mul(10)
-                                .and_then(|r| r.checked_add(chr as u8 - b'0'))
+                                .and_then(|r| r.checked_add(usize::from(chr as u8 - b'0')))
                             {
-                                Some(r) => radix = r,
-                                // TODO: Better error for radix overflow
+                                Some(n) => number = n,
                                 None => return Err(Error::UnknownMacroCharacter(chr, pos)),
                             }
+                            if number > u8::MAX.into() {
+                                radix_overflow.get_or_insert(chr);
+                            }
                         }
                         Some(chr) => return Err(Error::UnknownMacroCharacter(chr, pos)),
                         None => return Err(Error::MissingQuotedItem(pos)),
                     }
                 }
-                self.read_radix(pos, radix)
+                match radix_overflow {
+                    // TODO: Better error for radix overflow
+                    Some(chr) => Err(Error::UnknownMacroCharacter(chr, pos)),
+                    None => self.read_radix(pos, number as u8),
+                }
             }
             Some(chr) => Err(Error::UnknownMacroCharacter(chr, pos)),
             None => Err(Error::MissingQuotedItem(pos)),
         }
     }
 
+    /// Read a string with text properties, written as `#("text" START END PLIST ...)`.
+    fn read_propertized_string(&mut self, pos: usize) -> Result<Object<'ob>> {
+        let invalid = Error::InvalidStringProperty(pos);
+        let list = self.read_list(pos)?;
+        let elements: Vec<_> = list.as_list().map_err(|_| invalid)?.flatten().collect();
+        let Some(ObjectType::String(string)) = elements.first().map(|x| x.untag()) else {
+            return Err(invalid);
+        };
+        for chunk in elements[1..].chunks(3) {
+            let &[start, end, plist] = chunk else { return Err(invalid) };
+            let (Ok(start), Ok(end)) = (usize::try_from(start), usize::try_from(end)) else {
+                return Err(invalid);
+            };
+            if start > end || end > string.len() {
+                return Err(invalid);
+            }
+            let mut props = string.textprops_mut(self.cx).map_err(|_| invalid)?;
+            props.set_properties(start, end, plist);
+        }
+        Ok(elements[0])
+    }
+
+    /// Read the object labeled by `#N=`. References to the label inside of
+    /// the object are read as a placeholder and replaced once it is complete.
+    fn read_labeled(&mut self, label: usize, pos: usize) -> Result<Object<'ob>> {
+        let placeholder = Cons::new(NIL, NIL, self.cx).into();
+        self.labels.insert(label, (placeholder, false));
+        let obj = match self.tokens.next() {
+            Some(token) => self.read_sexp(token?)?,
+            None => return Err(Error::MissingQuotedItem(pos)),
+        };
+        let referenced = self.labels.insert(label, (obj, false)).is_some_and(|x| x.1);
+        if referenced {
+            substitute(obj, placeholder, obj);
+        }
+        Ok(obj)
+    }
+
+    /// Read a reference to a previous label, written as `#N#`.
+    fn read_label_ref(&mut self, label: usize, pos: usize) -> Result<Object<'ob>> {
+        match self.labels.get_mut(&label) {
+            Some((obj, referenced)) => {
+                *referenced = true;
+                Ok(*obj)
+            }
+            None => Err(Error::UndefinedLabel(label, pos)),
+        }
+    }
+
+    /// Read a record or hash table literal, written as `#s(TYPE SLOTS...)` or
+    /// `#s(hash-table PROPERTY VALUE...)`. The only hash table property that
+    /// matters is `data`, since all tables use `equal` for now.
+    fn read_record(&mut self, pos: usize) -> Result<Object<'ob>> {
+        let invalid = Error::InvalidRecord(pos);
+        let list = self.read_list(pos)?;
+        let elements: Vec<_> = list.as_list().map_err(|_| invalid)?.flatten().collect();
+        let Some(&type_) = elements.first() else { return Err(invalid) };
+        if type_ == sym::HASH_TABLE {
+            let mut table = HashTable::default();
+            for chunk in elements[1..].chunks(2) {
+                let &[prop, value] = chunk else { return Err(invalid) };
+                if prop == intern("data", self.cx) {
+                    let data: Vec<_> = value.as_list().map_err(|_| invalid)?.flatten().collect();
+                    for pair in data.chunks(2) {
+                        let &[key, value] = pair else { return Err(invalid) };
+                        table.insert(key, value);
+                    }
+                }
+            }
+            Ok(self.cx.add(table))
+        } else {
+            let mut record = self.cx.vec_with_capacity(elements.len());
+            record.extend_from_slice(&elements);
+            Ok(self.cx.add(RecordBuilder(record)))
+        }
+    }
+
+    /// Read a byte-code function literal, written as `#[ARGS CODE CONSTANTS
+    /// DEPTH ...]`. This is how compiled functions appear in `.elc` files.
+    fn read_byte_code(&mut self, pos: usize) -> Result<Object<'ob>> {
+        let invalid = Error::InvalidByteCode(pos);
+        let vec = self.read_vec(pos)?;
+        let ObjectType::Vec(vec) = vec.untag() else { unreachable!("read_vec returns a vector") };
+        let elements = vec.to_vec();
+        let [args, code, constants, depth, rest @ ..] = &*elements else {
+            return Err(invalid);
+        };
+        let (ObjectType::Int(args), ObjectType::Vec(constants), ObjectType::Int(depth)) =
+            (args.untag(), constants.untag(), depth.untag())
+        else {
+            return Err(invalid);
+        };
+        let code = match code.untag() {
+            ObjectType::ByteString(code) => code,
+            // Code that only contains ASCII is read as a multibyte string
+            ObjectType::String(code) if code.is_ascii() => {
+                let ObjectType::ByteString(code) = self.cx.add(code.as_bytes().to_vec()).untag()
+                else {
+                    unreachable!("byte vector was not a byte string")
+                };
+                code
+            }
+            _ => return Err(invalid),
+        };
+        let depth = usize::try_from(depth).map_err(|_| invalid)?;
+        let docstring = rest.first().copied();
+        let interactive = rest.get(1).copied();
+        match alloc::make_byte_code(
+            args,
+            code,
+            constants,
+            depth,
+            docstring,
+            interactive,
+            &[],
+            self.cx,
+        ) {
+            Ok(func) => Ok(func.into()),
+            Err(_) => Err(invalid),
+        }
+    }
+
+    /// Read a bool-vector, written as `#&LENGTH"BITS"`. Bool-vectors are not
+    /// implemented yet, so this returns a vector of `t` and `nil`.
+    fn read_bool_vector(&mut self, pos: usize) -> Result<Object<'ob>> {
+        let invalid = Error::InvalidBoolVector(pos);
+        let len = self.tokens.read_decimal().ok_or(invalid)?;
+        let Some(Ok(Token::String(string))) = self.tokens.next() else { return Err(invalid) };
+        let bytes: Vec<u8> = match unescape_string(string, self.cx).untag() {
+            ObjectType::ByteString(bytes) => bytes.inner().to_vec(),
+            ObjectType::String(string) => string.chars().map(|x| x as u8).collect(),
+            _ => unreachable!("unescaped string was not a string"),
+        };
+        if bytes.len() * 8 < len {
+            return Err(invalid);
+        }
+        let bits = (0..len).map(|i| if bytes[i / 8] & (1 << (i % 8)) == 0 { NIL } else { TRUE });
+        Ok(self.cx.add(bits.collect::<Vec<_>>()))
+    }
+
     fn read_sexp(&mut self, token: Token<'a>) -> Result<Object<'ob>> {
         match token {
             Token::OpenParen(i) => self.read_list(i),
@@ -509,7 +916,7 @@ impl<'a, 'ob> Reader<'a, 'ob> {
             Token::Splice(i) => self.quote_item(i, sym::SPLICE),
             Token::Backquote(i) => self.quote_item(i, sym::BACKQUOTE),
             Token::Sharp(i) => self.read_sharp(i),
-            Token::QuestionMark(_, c) => Ok((c as i64).into()),
+            Token::QuestionMark(_, c) => Ok(c.into()),
             Token::Ident(x) => Ok(parse_symbol(x, self.cx)),
             Token::String(x) => Ok(unescape_string(x, self.cx)),
         }
@@ -519,7 +926,17 @@ impl<'a, 'ob> Reader<'a, 'ob> {
 /// read a lisp object from `slice`. Return the object and index of next
 /// remaining character in the slice.
 pub(crate) fn read<'ob>(slice: &str, cx: &'ob Context) -> Result<(Object<'ob>, usize)> {
-    let mut reader = Reader { tokens: Tokenizer::new(slice), cx };
+    read_for_load(slice, NIL, cx)
+}
+
+/// Like [`read`], but `#$` reads as `load_file_name`.
+pub(crate) fn read_for_load<'ob>(
+    slice: &str,
+    load_file_name: Object<'ob>,
+    cx: &'ob Context,
+) -> Result<(Object<'ob>, usize)> {
+    let mut reader =
+        Reader { tokens: Tokenizer::new(slice), cx, labels: HashMap::default(), load_file_name };
     match reader.tokens.next() {
         Some(Ok(t)) => reader.read_sexp(t).map(|x| (x, reader.tokens.cur_pos())),
         Some(Err(e)) => Err(e),
@@ -598,6 +1015,31 @@ mod test {
         assert_error("?", Error::MissingQuotedItem(0), cx);
     }
 
+    #[test]
+    fn test_read_char_escape() {
+        let roots = &RootSet::default();
+        let cx = &Context::new(roots);
+        check_reader!(24, "?\\C-x", cx);
+        check_reader!(9, "?\\^I", cx);
+        check_reader!(127, "?\\C-?", cx);
+        check_reader!(0x800_0061, "?\\M-a", cx);
+        check_reader!(0x800_0001, "?\\C-\\M-a", cx);
+        check_reader!(0x400_0025, "?\\C-%", cx);
+        check_reader!(0x200_0061, "?\\S-a", cx);
+        check_reader!(0x80_0061, "?\\s-a", cx);
+        check_reader!(0x40_0061, "?\\A-a", cx);
+        check_reader!(0x100_0061, "?\\H-a", cx);
+        check_reader!(32, "?\\s", cx);
+        check_reader!(65, "?\\101", cx);
+        check_reader!(40, "?\\(", cx);
+        check_reader!(0xE9, "?\\N{LATIN SMALL LETTER E WITH ACUTE}", cx);
+        check_reader!(0x1F600, "?\\N{U+1F600}", cx);
+        check_reader!("\u{E9}", r#""\N{latin small letter e with acute}""#, cx);
+        assert_error("?\\N{NOT A CHARACTER}", Error::InvalidEscape(1), cx);
+        assert_error("?\\Ma", Error::InvalidEscape(1), cx);
+        assert_error("?\\C-", Error::MissingQuotedItem(1), cx);
+    }
+
     #[test]
     fn read_bool() {
         let roots = &RootSet::default();
@@ -637,6 +1079,10 @@ mod test {
 baz""#,
             cx
         );
+        check_reader!("\u{7}\u{1B} A", r#""\a\e\s\x41""#, cx);
+        check_reader!(vec![b'a', 0xFF, 0x80], r#""a\377\x80""#, cx);
+        let raw = raw_byte_to_char(0xFF);
+        check_reader!(format!("\u{E9}{raw}"), r#""\u00e9\377""#, cx);
     }
 
     #[test]
@@ -684,6 +1130,81 @@ baz""#,
         assert_error("#", Error::MissingQuotedItem(0), cx);
         assert_error("#'", Error::MissingQuotedItem(0), cx);
         assert_error("#a", Error::UnknownMacroCharacter('a', 0), cx);
+        check_reader!("abc", "#(\"abc\" 0 1 (face bold))", cx);
+        assert_error("#(1 2)", Error::InvalidStringProperty(0), cx);
+        assert_error("#(\"abc\" 0 4 nil)", Error::InvalidStringProperty(0), cx);
+        assert_error("#(\"abc\" 0 1)", Error::InvalidStringProperty(0), cx);
+        check_reader!(intern("", cx), "##", cx);
+        check_reader!(intern("foo", cx), "#_foo", cx);
+        check_reader!(list!(1, 2; cx), "(1 #@4 foo 2)", cx);
+        check_reader!(1, "#@5 foo 1", cx);
+        assert_error("#@00 foo", Error::EmptyStream, cx);
+        check_reader!(false, "#$", cx);
+        let vec: Vec<Object> = vec![TRUE, NIL, TRUE];
+        check_reader!(vec, "#&3\"\\5\"", cx);
+        assert_error("#&9\"a\"", Error::InvalidBoolVector(0), cx);
+        let func = read("#[0 \"\\300\\207\" [1] 1]", cx).unwrap().0;
+        assert!(matches!(func.untag(), ObjectType::ByteFn(_)));
+        let func = read("#[0 \"\" [] 0 \"Doc.\" \"p\"]", cx).unwrap().0;
+        assert_eq!(func.to_string(), "#[0 [] [] 0 \"Doc.\" \"p\"]");
+        assert_error("#[0 1]", Error::InvalidByteCode(0), cx);
+    }
+
+    #[test]
+    fn read_uninterned() {
+        let roots = &RootSet::default();
+        let cx = &Context::new(roots);
+        let obj = read("#:foo", cx).unwrap().0;
+        let ObjectType::Symbol(sym) = obj.untag() else { panic!("expected symbol") };
+        assert_eq!(sym.name(), "foo");
+        assert_ne!(sym, intern("foo", cx));
+        let obj = read("#:", cx).unwrap().0;
+        let ObjectType::Symbol(sym) = obj.untag() else { panic!("expected symbol") };
+        assert_eq!(sym.name(), "");
+    }
+
+    #[test]
+    fn read_labels() {
+        let roots = &RootSet::default();
+        let cx = &Context::new(roots);
+        let obj = read("#1=(a . #1#)", cx).unwrap().0;
+        let ObjectType::Cons(cons) = obj.untag() else { panic!("expected cons") };
+        assert_eq!(cons.car(), intern("a", cx));
+        assert!(cons.cdr().ptr_eq(obj));
+
+        let obj = read("(#1=(x) #1# [#1#])", cx).unwrap().0;
+        let list: Vec<_> = obj.as_list().unwrap().flatten().collect();
+        assert!(list[0].ptr_eq(list[1]));
+        let ObjectType::Vec(vec) = list[2].untag() else { panic!("expected vector") };
+        assert!(vec[0].get().ptr_eq(list[0]));
+
+        let obj = read("#2=[1 #2#]", cx).unwrap().0;
+        let ObjectType::Vec(vec) = obj.untag() else { panic!("expected vector") };
+        assert!(vec[1].get().ptr_eq(obj));
+        assert_error("(#1=a #2#)", Error::UndefinedLabel(2, 6), cx);
+    }
+
+    #[test]
+    fn read_records() {
+        let roots = &RootSet::default();
+        let cx = &Context::new(roots);
+        let obj = read("#s(foo 1 \"bar\")", cx).unwrap().0;
+        let ObjectType::Record(record) = obj.untag() else { panic!("expected record") };
+        assert_eq!(record.len(), 3);
+        assert_eq!(record[0].get(), intern("foo", cx));
+        assert_eq!(record[2].get(), "bar");
+
+        let obj = read("#s(hash-table size 2 test equal data (a 1 \"b\" 2))", cx).unwrap().0;
+        let ObjectType::HashTable(table) = obj.untag() else { panic!("expected hash table") };
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(intern("a", cx).into()), Some(1.into()));
+        // Tables hash by identity, so look up the string key by position
+        let (key, value) = table.get_index(1).unwrap();
+        assert_eq!(key, "b");
+        assert_eq!(value, 2);
+        assert_error("#s()", Error::InvalidRecord(0), cx);
+        assert_error("#s[foo]", Error::InvalidRecord(0), cx);
+        assert_error("#s(hash-table data (a))", Error::InvalidRecord(0), cx);
     }
 
     #[test]
@@ -0,0 +1,1136 @@
+//! Emacs regular expressions.
+//!
+//! Patterns are parsed into a tree, which is then compiled into a small
+//! program for a backtracking matcher, the same way regex-emacs.c in GNU Emacs
+//! works. This lets us support the constructs that depend on the state of the
+//! buffer, such as syntax classes (`\sw`), symbol boundaries (`\_<`) and point
+//! (`\=`), which no general purpose regex crate knows about.
+use crate::syntax::{SyntaxClass, standard_syntax};
+use anyhow::{Result, bail};
+use std::fmt;
+
+/// The bounds of each group of a match as byte offsets into the haystack, or
+/// `None` for groups that did not participate in the match. Group 0 is the
+/// whole match.
+pub(crate) type Match = Vec<Option<(usize, usize)>>;
+
+/// Errors in the syntax of a regexp. The messages are the same as the ones
+/// Emacs uses for `invalid-regexp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RegexError {
+    BadPattern,
+    BadClass,
+    TrailingBackslash,
+    BadBackref,
+    UnmatchedBracket,
+    UnmatchedParen,
+    UnmatchedBrace,
+    BadInterval,
+    BadRepeat,
+    TooBig,
+    UnmatchedCloseParen,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            RegexError::BadPattern => "Invalid regular expression",
+            RegexError::BadClass => "Invalid character class name",
+            RegexError::TrailingBackslash => "Trailing backslash",
+            RegexError::BadBackref => "Invalid back reference",
+            RegexError::UnmatchedBracket => "Unmatched [ or [^",
+            RegexError::UnmatchedParen => "Unmatched ( or \\(",
+            RegexError::UnmatchedBrace => "Unmatched \\{",
+            RegexError::BadInterval => "Invalid content of \\{\\}",
+            RegexError::BadRepeat => "Invalid preceding regular expression",
+            RegexError::TooBig => "Regular expression too big",
+            RegexError::UnmatchedCloseParen => "Unmatched ) or \\)",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+/// The largest count allowed in `\{n,m\}`.
+const MAX_REPEAT: u32 = 0xFFFF;
+/// The largest number of instructions a compiled pattern can have.
+const MAX_PROGRAM: usize = 1 << 20;
+/// The largest number of backtracking entries before the matcher gives up.
+const MAX_STACK: usize = 1 << 22;
+
+/// Character classes that can appear in brackets, like `[[:alpha:]]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Alnum,
+    Alpha,
+    Ascii,
+    Blank,
+    Cntrl,
+    Digit,
+    Graph,
+    Lower,
+    Multibyte,
+    Nonascii,
+    Print,
+    Punct,
+    Space,
+    Unibyte,
+    Upper,
+    Word,
+    Xdigit,
+}
+
+impl CharClass {
+    fn from_name(name: &str) -> Option<Self> {
+        let class = match name {
+            "alnum" => Self::Alnum,
+            "alpha" => Self::Alpha,
+            "ascii" => Self::Ascii,
+            "blank" => Self::Blank,
+            "cntrl" => Self::Cntrl,
+            "digit" => Self::Digit,
+            "graph" => Self::Graph,
+            "lower" => Self::Lower,
+            "multibyte" => Self::Multibyte,
+            "nonascii" => Self::Nonascii,
+            "print" => Self::Print,
+            "punct" => Self::Punct,
+            "space" => Self::Space,
+            "unibyte" => Self::Unibyte,
+            "upper" => Self::Upper,
+            "word" => Self::Word,
+            "xdigit" => Self::Xdigit,
+            _ => return None,
+        };
+        Some(class)
+    }
+
+    fn matches(self, c: char, input: &Input) -> bool {
+        match self {
+            Self::Alnum => c.is_alphanumeric(),
+            Self::Alpha => c.is_alphabetic(),
+            Self::Ascii | Self::Unibyte => c.is_ascii(),
+            Self::Nonascii | Self::Multibyte => !c.is_ascii(),
+            Self::Blank => {
+                c == '\t'
+                    || (c.is_whitespace()
+                        && !matches!(c, '\n'..='\r' | '\u{85}' | '\u{2028}' | '\u{2029}'))
+            }
+            Self::Cntrl => c < ' ',
+            Self::Digit => c.is_ascii_digit(),
+            Self::Xdigit => c.is_ascii_hexdigit(),
+            Self::Graph if c.is_ascii() => c.is_ascii_graphic(),
+            Self::Graph => !c.is_whitespace() && !c.is_control(),
+            Self::Print if c.is_ascii() => c == ' ' || c.is_ascii_graphic(),
+            Self::Print => !c.is_control(),
+            // When folding case, these match any character that has case
+            Self::Lower | Self::Upper if input.fold => c.is_lowercase() || c.is_uppercase(),
+            Self::Lower => c.is_lowercase(),
+            Self::Upper => c.is_uppercase(),
+            Self::Punct if c.is_ascii() => c.is_ascii_punctuation(),
+            Self::Punct => input.syntax_of(c) != SyntaxClass::Word,
+            Self::Space => input.syntax_of(c) == SyntaxClass::Whitespace,
+            Self::Word => input.syntax_of(c) == SyntaxClass::Word,
+        }
+    }
+}
+
+/// A bracket expression like `[a-z_]` or `[^[:space:]]`.
+#[derive(Debug, Clone, Default)]
+struct CharSet {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+    classes: Vec<CharClass>,
+}
+
+impl CharSet {
+    fn matches(&self, c: char, input: &Input) -> bool {
+        let contains = |c: char| {
+            self.ranges.iter().any(|&(beg, end)| (beg..=end).contains(&c))
+                || self.classes.iter().any(|class| class.matches(c, input))
+        };
+        let found = contains(c) || (input.fold && case_variants(c).any(contains));
+        found != self.negated
+    }
+}
+
+/// A test that consumes a single character.
+#[derive(Debug, Clone)]
+enum Test {
+    Char(char),
+    /// `.`, which matches anything but a newline.
+    Any,
+    Set(Box<CharSet>),
+    /// `\sC` or `\SC` when negated. An unknown syntax designator never
+    /// matches.
+    Syntax(Option<SyntaxClass>, bool),
+    /// `\cC` or `\CC` when negated.
+    Category(char, bool),
+}
+
+impl Test {
+    fn matches(&self, c: char, input: &Input) -> bool {
+        match self {
+            Test::Char(x) if input.fold => canon(c) == canon(*x),
+            Test::Char(x) => c == *x,
+            Test::Any => c != '\n',
+            Test::Set(set) => set.matches(c, input),
+            Test::Syntax(class, negated) => (Some(input.syntax_of(c)) == *class) != *negated,
+            Test::Category(cat, negated) => has_category(c, *cat) != *negated,
+        }
+    }
+}
+
+/// Zero width assertions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Assert {
+    /// `^`
+    LineStart,
+    /// `$`
+    LineEnd,
+    /// `` \` ``
+    TextStart,
+    /// `\'`
+    TextEnd,
+    /// `\=`
+    Point,
+    /// `\b`
+    WordBound,
+    /// `\B`
+    NotWordBound,
+    /// `\<`
+    WordStart,
+    /// `\>`
+    WordEnd,
+    /// `\_<`
+    SymbolStart,
+    /// `\_>`
+    SymbolEnd,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Test(Test),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    /// A group, which is shy if it has no number.
+    Group(Option<usize>, Box<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+    Backref(usize),
+    Assert(Assert),
+}
+
+struct Parser {
+    pattern: Vec<char>,
+    pos: usize,
+    /// The highest group number used so far.
+    groups: usize,
+    /// The groups that have not been closed yet, which can't be referred to.
+    open: Vec<usize>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.pattern.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let found = self.at(s);
+        if found {
+            self.pos += s.chars().count();
+        }
+        found
+    }
+
+    fn at(&self, s: &str) -> bool {
+        let len = s.chars().count();
+        self.pattern
+            .get(self.pos..self.pos + len)
+            .is_some_and(|x| x.iter().copied().eq(s.chars()))
+    }
+
+    /// True if the parser is at the end of an alternative.
+    fn at_alt_end(&self) -> bool {
+        self.peek().is_none() || self.at("\\|") || self.at("\\)")
+    }
+
+    fn parse_alt(&mut self) -> Result<Node, RegexError> {
+        let mut alts = vec![self.parse_concat()?];
+        while self.eat("\\|") {
+            alts.push(self.parse_concat()?);
+        }
+        Ok(if alts.len() == 1 { alts.pop().unwrap() } else { Node::Alt(alts) })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, RegexError> {
+        let mut items: Vec<Node> = Vec::new();
+        while !self.at_alt_end() {
+            let c = self.next().unwrap();
+            // Repetition operators are literal at the start of an
+            // alternative, or right after a `^` there.
+            let can_repeat = !matches!(items.last(), None | Some(Node::Assert(Assert::LineStart)));
+            let node = match c {
+                '^' if items.is_empty() => Node::Assert(Assert::LineStart),
+                '$' if self.at_alt_end() => Node::Assert(Assert::LineEnd),
+                '*' | '+' | '?' if can_repeat => {
+                    let greedy = !self.eat("?");
+                    let (min, max) = match c {
+                        '*' => (0, None),
+                        '+' => (1, None),
+                        _ => (0, Some(1)),
+                    };
+                    let node = Box::new(items.pop().unwrap());
+                    items.push(Node::Repeat { node, min, max, greedy });
+                    continue;
+                }
+                '.' => Node::Test(Test::Any),
+                '[' => Node::Test(Test::Set(Box::new(self.parse_set()?))),
+                '\\' => match self.next().ok_or(RegexError::TrailingBackslash)? {
+                    '(' => self.parse_group()?,
+                    '{' => {
+                        if !can_repeat {
+                            return Err(RegexError::BadRepeat);
+                        }
+                        let (min, max) = self.parse_interval()?;
+                        let node = Box::new(items.pop().unwrap());
+                        items.push(Node::Repeat { node, min, max, greedy: true });
+                        continue;
+                    }
+                    c @ '1'..='9' => {
+                        let group = c as usize - '0' as usize;
+                        if group > self.groups || self.open.contains(&group) {
+                            return Err(RegexError::BadBackref);
+                        }
+                        Node::Backref(group)
+                    }
+                    'w' => Node::Test(Test::Syntax(Some(SyntaxClass::Word), false)),
+                    'W' => Node::Test(Test::Syntax(Some(SyntaxClass::Word), true)),
+                    c @ ('s' | 'S') => {
+                        let class = self.next().ok_or(RegexError::BadPattern)?;
+                        Node::Test(Test::Syntax(SyntaxClass::from_designator(class), c == 'S'))
+                    }
+                    c @ ('c' | 'C') => {
+                        let cat = self.next().ok_or(RegexError::BadPattern)?;
+                        Node::Test(Test::Category(cat, c == 'C'))
+                    }
+                    '`' => Node::Assert(Assert::TextStart),
+                    '\'' => Node::Assert(Assert::TextEnd),
+                    '=' => Node::Assert(Assert::Point),
+                    'b' => Node::Assert(Assert::WordBound),
+                    'B' => Node::Assert(Assert::NotWordBound),
+                    '<' => Node::Assert(Assert::WordStart),
+                    '>' => Node::Assert(Assert::WordEnd),
+                    '_' => match self.next() {
+                        Some('<') => Node::Assert(Assert::SymbolStart),
+                        Some('>') => Node::Assert(Assert::SymbolEnd),
+                        _ => return Err(RegexError::BadPattern),
+                    },
+                    c => Node::Test(Test::Char(c)),
+                },
+                c => Node::Test(Test::Char(c)),
+            };
+            items.push(node);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items),
+        })
+    }
+
+    /// Parse a group after the opening `\(`.
+    fn parse_group(&mut self) -> Result<Node, RegexError> {
+        let number = if self.eat("?") {
+            let number = self.parse_number();
+            if !self.eat(":") {
+                return Err(RegexError::BadPattern);
+            }
+            match number {
+                // `\(?0:` is not allowed
+                Some(0) => return Err(RegexError::BadPattern),
+                Some(n) => {
+                    let n = n as usize;
+                    self.groups = self.groups.max(n);
+                    Some(n)
+                }
+                None => None,
+            }
+        } else {
+            self.groups += 1;
+            Some(self.groups)
+        };
+        self.open.extend(number);
+        let inner = self.parse_alt()?;
+        if !self.eat("\\)") {
+            return Err(RegexError::UnmatchedParen);
+        }
+        if number.is_some() {
+            self.open.pop();
+        }
+        Ok(Node::Group(number, Box::new(inner)))
+    }
+
+    /// Parse a decimal number, if there is one.
+    fn parse_number(&mut self) -> Option<u32> {
+        let mut number: Option<u32> = None;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+            self.pos += 1;
+            number = Some(number.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+        }
+        number
+    }
+
+    /// Parse the bounds of an interval after the opening `\{`.
+    fn parse_interval(&mut self) -> Result<(u32, Option<u32>), RegexError> {
+        let min = self.parse_number();
+        let max = if self.eat(",") { self.parse_number() } else { Some(min.unwrap_or(0)) };
+        if self.peek().is_none() {
+            return Err(RegexError::UnmatchedBrace);
+        }
+        if !self.eat("\\}") {
+            return Err(RegexError::BadInterval);
+        }
+        let min = min.unwrap_or(0);
+        if max.is_some_and(|max| max < min) {
+            return Err(RegexError::BadInterval);
+        }
+        if max.unwrap_or(min) > MAX_REPEAT {
+            return Err(RegexError::TooBig);
+        }
+        Ok((min, max))
+    }
+
+    /// Parse a bracket expression after the opening `[`.
+    fn parse_set(&mut self) -> Result<CharSet, RegexError> {
+        let mut set = CharSet { negated: self.eat("^"), ..CharSet::default() };
+        // A `]` at the start is a literal
+        if self.eat("]") {
+            set.ranges.push((']', ']'));
+        }
+        loop {
+            let c = self.next().ok_or(RegexError::UnmatchedBracket)?;
+            match c {
+                ']' => return Ok(set),
+                '[' if self.peek() == Some(':') => {
+                    if let Some(class) = self.parse_class()? {
+                        set.classes.push(class);
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            if self.peek() == Some('-') && self.pattern.get(self.pos + 1).is_some_and(|x| *x != ']')
+            {
+                self.pos += 1;
+                let end = self.next().unwrap();
+                // Reversed ranges are empty
+                if c <= end {
+                    set.ranges.push((c, end));
+                }
+            } else {
+                set.ranges.push((c, c));
+            }
+        }
+    }
+
+    /// Parse a character class like `[:alpha:]` after the opening `[`. If
+    /// this is not a character class, the parser is left unchanged and the
+    /// `[` is a literal.
+    fn parse_class(&mut self) -> Result<Option<CharClass>, RegexError> {
+        let start = self.pos + 1;
+        let Some(len) = self.pattern[start..].iter().position(|c| *c == ':' || *c == ']') else {
+            return Ok(None);
+        };
+        let end = start + len;
+        if !(self.pattern[end] == ':' && self.pattern.get(end + 1) == Some(&']')) {
+            return Ok(None);
+        }
+        let name: String = self.pattern[start..end].iter().collect();
+        let class = CharClass::from_name(&name).ok_or(RegexError::BadClass)?;
+        self.pos = end + 2;
+        Ok(Some(class))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    /// Consume a character that passes the test.
+    Test(Test),
+    /// Try the first branch, and then the second one if that fails.
+    Split(usize, usize),
+    Jump(usize),
+    /// Store the current position in a register.
+    Save(usize),
+    /// Fail if the position has not changed since it was saved in the
+    /// register. This stops loops whose body can match the empty string.
+    Progress(usize),
+    Assert(Assert),
+    Backref(usize),
+    Match,
+}
+
+struct Compiler {
+    prog: Vec<Inst>,
+    /// The number of registers used so far.
+    regs: usize,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.prog.len() >= MAX_PROGRAM {
+            return Err(RegexError::TooBig);
+        }
+        self.prog.push(inst);
+        Ok(self.prog.len() - 1)
+    }
+
+    fn split(&mut self, at: usize, next: usize, other: usize, greedy: bool) {
+        self.prog[at] = if greedy { Inst::Split(next, other) } else { Inst::Split(other, next) };
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), RegexError> {
+        match node {
+            Node::Empty => {}
+            Node::Test(test) => {
+                self.push(Inst::Test(test.clone()))?;
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.compile(node)?;
+                }
+            }
+            Node::Alt(alts) => {
+                let mut jumps = Vec::new();
+                let (last, rest) = alts.split_last().unwrap();
+                for alt in rest {
+                    let split = self.push(Inst::Split(0, 0))?;
+                    self.compile(alt)?;
+                    jumps.push(self.push(Inst::Jump(0))?);
+                    self.prog[split] = Inst::Split(split + 1, self.prog.len());
+                }
+                self.compile(last)?;
+                let end = self.prog.len();
+                for jump in jumps {
+                    self.prog[jump] = Inst::Jump(end);
+                }
+            }
+            Node::Group(Some(n), node) => {
+                self.push(Inst::Save(2 * n))?;
+                self.compile(node)?;
+                self.push(Inst::Save(2 * n + 1))?;
+            }
+            Node::Group(None, node) => self.compile(node)?,
+            Node::Repeat { node, min, max, greedy } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.push(Inst::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        let end = self.prog.len();
+                        for split in splits {
+                            self.split(split, split + 1, end, *greedy);
+                        }
+                    }
+                    None => {
+                        let reg = self.regs;
+                        self.regs += 1;
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.push(Inst::Save(reg))?;
+                        self.compile(node)?;
+                        self.push(Inst::Progress(reg))?;
+                        self.push(Inst::Jump(split))?;
+                        let end = self.prog.len();
+                        self.split(split, split + 1, end, *greedy);
+                    }
+                }
+            }
+            Node::Backref(n) => {
+                self.push(Inst::Backref(*n))?;
+            }
+            Node::Assert(assert) => {
+                self.push(Inst::Assert(*assert))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The text a regexp is matched against, along with the state of the buffer
+/// that some constructs depend on.
+pub(crate) struct Input<'a> {
+    hay: &'a str,
+    /// Text that continues `hay`, for a buffer whose gap splits the text in
+    /// two. Byte offsets past the end of `hay` point into it.
+    rest: &'a str,
+    /// Matches can't extend past this byte offset. Assertions like `\'` and
+    /// `\b` still look at the whole haystack.
+    limit: usize,
+    /// The byte offset of point, for `\=`.
+    point: Option<usize>,
+    /// Whether to ignore case, from `case-fold-search`.
+    fold: bool,
+    syntax: &'a dyn Fn(char) -> SyntaxClass,
+}
+
+impl<'a> Input<'a> {
+    pub(crate) fn new(hay: &'a str) -> Self {
+        Self::split(hay, "")
+    }
+
+    /// Match against `hay` followed by `rest`, without copying them together.
+    pub(crate) fn split(hay: &'a str, rest: &'a str) -> Self {
+        let limit = hay.len() + rest.len();
+        Self { hay, rest, limit, point: None, fold: false, syntax: &standard_syntax }
+    }
+
+    fn len(&self) -> usize {
+        self.hay.len() + self.rest.len()
+    }
+
+    /// The text between the byte offsets `beg` and `end`.
+    fn chars(&self, beg: usize, end: usize) -> impl Iterator<Item = char> + use<'a> {
+        let split = self.hay.len();
+        let before = &self.hay[beg.min(split)..end.min(split)];
+        let after = &self.rest[beg.max(split) - split..end.max(split) - split];
+        before.chars().chain(after.chars())
+    }
+
+    /// The first byte offset at or after `pos` where `needle` could start
+    /// without going past the limit. Near the gap this can be a position that
+    /// doesn't match, because the needle might continue after it.
+    fn find(&self, pos: usize, needle: &str) -> Option<usize> {
+        let split = self.hay.len();
+        if pos < split {
+            if let Some(idx) = self.hay[pos..self.limit.min(split)].find(needle) {
+                return Some(pos + idx);
+            }
+            if self.limit <= split {
+                return None;
+            }
+            let mut start = split.saturating_sub(needle.len() - 1).max(pos);
+            while !self.hay.is_char_boundary(start) {
+                start += 1;
+            }
+            if start < split {
+                return Some(start);
+            }
+        }
+        let pos = pos.max(split);
+        self.rest[pos - split..self.limit - split].find(needle).map(|idx| pos + idx)
+    }
+
+    pub(crate) fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub(crate) fn point(mut self, point: usize) -> Self {
+        self.point = Some(point);
+        self
+    }
+
+    pub(crate) fn fold(mut self, fold: bool) -> Self {
+        self.fold = fold;
+        self
+    }
+
+    /// Use `syntax` to look up the syntax classes of characters instead of
+    /// the standard syntax table.
+    pub(crate) fn syntax(mut self, syntax: &'a dyn Fn(char) -> SyntaxClass) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    fn syntax_of(&self, c: char) -> SyntaxClass {
+        (self.syntax)(c)
+    }
+
+    /// The character at `pos` that can be consumed by a match.
+    fn next(&self, pos: usize) -> Option<char> {
+        self.chars(pos, self.limit).next()
+    }
+
+    fn char_after(&self, pos: usize) -> Option<char> {
+        self.chars(pos, self.len()).next()
+    }
+
+    fn char_before(&self, pos: usize) -> Option<char> {
+        match pos.checked_sub(self.hay.len()) {
+            Some(rest) if rest > 0 => self.rest[..rest].chars().next_back(),
+            _ => self.hay[..pos].chars().next_back(),
+        }
+    }
+
+    fn is_word(&self, c: Option<char>) -> bool {
+        c.is_some_and(|c| self.syntax_of(c) == SyntaxClass::Word)
+    }
+
+    fn is_symbol(&self, c: Option<char>) -> bool {
+        c.is_some_and(|c| matches!(self.syntax_of(c), SyntaxClass::Word | SyntaxClass::Symbol))
+    }
+
+    fn check(&self, assert: Assert, pos: usize) -> bool {
+        let (before, after) = (self.char_before(pos), self.char_after(pos));
+        match assert {
+            Assert::LineStart => before.is_none_or(|c| c == '\n'),
+            Assert::LineEnd => after.is_none_or(|c| c == '\n'),
+            Assert::TextStart => pos == 0,
+            Assert::TextEnd => pos == self.len(),
+            Assert::Point => self.point == Some(pos),
+            Assert::WordBound => {
+                before.is_none() || after.is_none() || self.is_word(before) != self.is_word(after)
+            }
+            Assert::NotWordBound => {
+                before.is_some() && after.is_some() && self.is_word(before) == self.is_word(after)
+            }
+            Assert::WordStart => self.is_word(after) && !self.is_word(before),
+            Assert::WordEnd => self.is_word(before) && !self.is_word(after),
+            Assert::SymbolStart => self.is_symbol(after) && !self.is_symbol(before),
+            Assert::SymbolEnd => self.is_symbol(before) && !self.is_symbol(after),
+        }
+    }
+}
+
+/// Entries on the matcher's backtracking stack.
+enum Frame {
+    /// Resume at `pc` with the position `pos`.
+    Retry { pc: usize, pos: usize },
+    /// Restore a register to its old value.
+    Restore { reg: usize, value: Option<usize> },
+}
+
+/// A compiled Emacs regexp.
+#[derive(Debug, Clone)]
+pub(crate) struct Regexp {
+    prog: Vec<Inst>,
+    /// The number of groups, not counting the whole match.
+    groups: usize,
+    regs: usize,
+    /// A string every match has to start with, used to skip ahead quickly
+    /// when searching.
+    prefix: String,
+    /// Find the longest match instead of the first one, as POSIX requires.
+    posix: bool,
+}
+
+impl Regexp {
+    pub(crate) fn new(pattern: &str) -> Result<Self, RegexError> {
+        let mut parser =
+            Parser { pattern: pattern.chars().collect(), pos: 0, groups: 0, open: Vec::new() };
+        let node = parser.parse_alt()?;
+        if parser.peek().is_some() {
+            // parse_alt only stops early at a `\)` without an opening `\(`
+            return Err(RegexError::UnmatchedCloseParen);
+        }
+        Self::compile(&node, parser.groups)
+    }
+
+    /// A regexp that matches `string` literally.
+    pub(crate) fn literal(string: &str) -> Self {
+        let node = Node::Concat(string.chars().map(|c| Node::Test(Test::Char(c))).collect());
+        Self::compile(&node, 0).expect("literal strings should compile")
+    }
+
+    /// Make this regexp find the longest match, like `posix-search-forward`.
+    pub(crate) fn posix(mut self) -> Self {
+        self.posix = true;
+        self
+    }
+
+    fn compile(node: &Node, groups: usize) -> Result<Self, RegexError> {
+        let regs = 2 * (groups + 1);
+        let mut compiler = Compiler { prog: Vec::new(), regs };
+        compiler.push(Inst::Save(0))?;
+        compiler.compile(node)?;
+        compiler.push(Inst::Save(1))?;
+        compiler.push(Inst::Match)?;
+        let prefix = match node {
+            Node::Concat(nodes) => nodes
+                .iter()
+                .map_while(|node| match node {
+                    Node::Test(Test::Char(c)) => Some(*c),
+                    _ => None,
+                })
+                .collect(),
+            Node::Test(Test::Char(c)) => c.to_string(),
+            _ => String::new(),
+        };
+        Ok(Self { prog: compiler.prog, groups, regs: compiler.regs, prefix, posix: false })
+    }
+
+    /// Match the regexp starting exactly at the byte offset `start`.
+    pub(crate) fn match_at(&self, input: &Input, start: usize) -> Result<Option<Match>> {
+        let mut regs = vec![None; self.regs];
+        let mut stack = Vec::new();
+        let mut best: Option<Vec<Option<usize>>> = None;
+        let (mut pc, mut pos) = (0, start);
+        loop {
+            let ok = match &self.prog[pc] {
+                Inst::Test(test) => match input.next(pos) {
+                    Some(c) if test.matches(c, input) => {
+                        pos += c.len_utf8();
+                        pc += 1;
+                        true
+                    }
+                    _ => false,
+                },
+                Inst::Split(first, second) => {
+                    stack.push(Frame::Retry { pc: *second, pos });
+                    pc = *first;
+                    true
+                }
+                Inst::Jump(to) => {
+                    pc = *to;
+                    true
+                }
+                Inst::Save(reg) => {
+                    stack.push(Frame::Restore { reg: *reg, value: regs[*reg] });
+                    regs[*reg] = Some(pos);
+                    pc += 1;
+                    true
+                }
+                Inst::Progress(reg) => {
+                    pc += 1;
+                    regs[*reg] != Some(pos)
+                }
+                Inst::Assert(assert) => {
+                    pc += 1;
+                    input.check(*assert, pos)
+                }
+                Inst::Backref(group) => match (regs[2 * group], regs[2 * group + 1]) {
+                    (Some(beg), Some(end)) => match backref(input, input.chars(beg, end), pos) {
+                        Some(end) => {
+                            pos = end;
+                            pc += 1;
+                            true
+                        }
+                        None => false,
+                    },
+                    _ => false,
+                },
+                Inst::Match => {
+                    if !self.posix {
+                        return Ok(Some(self.groups(&regs)));
+                    }
+                    if best.as_ref().is_none_or(|best| best[1] < regs[1]) {
+                        best = Some(regs.clone());
+                    }
+                    // Keep looking for a longer match
+                    false
+                }
+            };
+            if ok {
+                if stack.len() > MAX_STACK {
+                    bail!("Stack overflow in regexp matcher");
+                }
+                continue;
+            }
+            loop {
+                match stack.pop() {
+                    None => return Ok(best.map(|regs| self.groups(&regs))),
+                    Some(Frame::Restore { reg, value }) => regs[reg] = value,
+                    Some(Frame::Retry { pc: next_pc, pos: next_pos }) => {
+                        pc = next_pc;
+                        pos = next_pos;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Find the first match that starts at or after the byte offset `from`.
+    pub(crate) fn search(&self, input: &Input, from: usize) -> Result<Option<Match>> {
+        let mut pos = from;
+        while pos <= input.limit {
+            if !self.prefix.is_empty() && !input.fold {
+                match input.find(pos, &self.prefix) {
+                    Some(start) => pos = start,
+                    None => return Ok(None),
+                }
+            }
+            if let Some(found) = self.match_at(input, pos)? {
+                return Ok(Some(found));
+            }
+            pos += input.next(pos).map_or(1, char::len_utf8);
+        }
+        Ok(None)
+    }
+
+    /// Find the match that starts last, at or before the byte offset `from`
+    /// but not before `min`.
+    pub(crate) fn search_backward(
+        &self,
+        input: &Input,
+        from: usize,
+        min: usize,
+    ) -> Result<Option<Match>> {
+        let mut pos = from;
+        loop {
+            if let Some(found) = self.match_at(input, pos)? {
+                return Ok(Some(found));
+            }
+            if pos <= min {
+                return Ok(None);
+            }
+            pos -= input.char_before(pos).map_or(1, char::len_utf8);
+        }
+    }
+
+    fn groups(&self, regs: &[Option<usize>]) -> Match {
+        (0..=self.groups)
+            .map(|i| match (regs[2 * i], regs[2 * i + 1]) {
+                (Some(beg), Some(end)) => Some((beg, end)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Match the text of a group again at `pos`, returning the end of the match.
+fn backref(input: &Input, text: impl Iterator<Item = char>, pos: usize) -> Option<usize> {
+    let mut end = pos;
+    for c in text {
+        let next = input.next(end)?;
+        let same = if input.fold { canon(next) == canon(c) } else { next == c };
+        if !same {
+            return None;
+        }
+        end += next.len_utf8();
+    }
+    Some(end)
+}
+
+/// The canonical case of `c`, used to compare characters when folding case.
+fn canon(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    single(c.to_lowercase()).unwrap_or(c)
+}
+
+/// The other cases of `c` that it matches when folding case.
+fn case_variants(c: char) -> impl Iterator<Item = char> {
+    let lower = single(c.to_lowercase());
+    let upper = single(c.to_uppercase());
+    lower.into_iter().chain(upper).filter(move |x| *x != c)
+}
+
+/// The only item of `iter`, if it has exactly one.
+fn single(mut iter: impl Iterator<Item = char>) -> Option<char> {
+    let first = iter.next();
+    if iter.next().is_some() { None } else { first }
+}
+
+/// Whether `c` is in the character category `cat`, as used by `\cC`. This
+/// follows the categories of the standard category table.
+fn has_category(c: char, cat: char) -> bool {
+    let code = c as u32;
+    let is_han = matches!(code, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF);
+    let is_hiragana = matches!(code, 0x3040..=0x309F);
+    let is_katakana = matches!(code, 0x30A0..=0x30FF | 0x31F0..=0x31FF);
+    let is_hangul = matches!(code, 0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF);
+    let is_greek = matches!(code, 0x370..=0x3FF | 0x1F00..=0x1FFF);
+    let is_cyrillic = matches!(code, 0x400..=0x52F);
+    let is_hebrew = matches!(code, 0x590..=0x5FF);
+    let is_arabic = matches!(code, 0x600..=0x6FF | 0x750..=0x77F);
+    let is_combining = matches!(code, 0x300..=0x36F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F);
+    match cat {
+        'a' => (' '..='~').contains(&c),
+        'l' => matches!(code, 0x80..=0x24F | 0x1E00..=0x1EFF),
+        'g' | 'G' => is_greek,
+        'y' | 'Y' => is_cyrillic,
+        'w' => is_hebrew,
+        'b' => is_arabic,
+        't' => matches!(code, 0xE00..=0xE7F),
+        'o' => matches!(code, 0xE80..=0xEFF),
+        'q' => matches!(code, 0xF00..=0xFFF),
+        'e' => matches!(code, 0x1200..=0x139F),
+        'v' => matches!(code, 0x1EA0..=0x1EF9),
+        'h' => is_hangul,
+        'c' | 'C' => is_han,
+        'j' => is_han || is_hiragana || is_katakana,
+        'H' => is_hiragana,
+        'K' => is_katakana,
+        'k' => matches!(code, 0xFF61..=0xFF9F),
+        'A' => matches!(code, 0xFF10..=0xFF19 | 0xFF21..=0xFF3A | 0xFF41..=0xFF5A),
+        '^' => is_combining,
+        '.' => !is_combining,
+        'R' => is_hebrew || is_arabic,
+        'L' => c.is_alphabetic() && !(is_hebrew || is_arabic),
+        '|' => is_han || is_hiragana || is_katakana || is_hangul,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assert_elprop, library::elprop::arb_custom_string};
+    use proptest::prelude::*;
+
+    fn find(pattern: &str, hay: &str) -> Option<Match> {
+        let re = Regexp::new(pattern).unwrap();
+        re.search(&Input::new(hay), 0).unwrap()
+    }
+
+    fn whole(pattern: &str, hay: &str) -> Option<(usize, usize)> {
+        find(pattern, hay).and_then(|m| m[0])
+    }
+
+    #[test]
+    fn lisp_regex() {
+        assert_eq!(whole("foo", "a foo"), Some((2, 5)));
+        assert_eq!(whole("\\foo", "foo"), Some((0, 3)));
+        assert_eq!(find("\\(foo\\)", "foo"), Some(vec![Some((0, 3)), Some((0, 3))]));
+        assert_eq!(whole("(foo)", "a(foo)"), Some((1, 6)));
+        assert_eq!(whole("a\\|b|c", "b|c"), Some((0, 3)));
+        assert_eq!(whole("\\`a", "ba"), None);
+        assert_eq!(whole("a\\'", "ab a"), Some((3, 4)));
+        assert_eq!(whole("[[:word:]]+", "-ab1-"), Some((1, 4)));
+        assert_eq!(whole("[[:word:]_]+", "-a_b-"), Some((1, 4)));
+    }
+
+    #[test]
+    fn repetition() {
+        assert_eq!(whole("ab*", "abbbc"), Some((0, 4)));
+        assert_eq!(whole("ab*?", "abbbc"), Some((0, 1)));
+        assert_eq!(whole("ab+?", "abbbc"), Some((0, 2)));
+        assert_eq!(whole("*a", "x*a"), Some((1, 3)));
+        assert_eq!(whole("^*a", "*a"), Some((0, 2)));
+        assert_eq!(whole("a\\{2,3\\}", "aaaa"), Some((0, 3)));
+        assert_eq!(whole("a\\{2\\}", "a aa"), Some((2, 4)));
+        assert_eq!(whole("\\(a*\\)*b", "aab"), Some((0, 3)));
+        assert_eq!(Regexp::new("a\\{3,2\\}").unwrap_err(), RegexError::BadInterval);
+    }
+
+    #[test]
+    fn split_input() {
+        let search = |pattern: &str, hay: &str, rest: &str| {
+            let re = Regexp::new(pattern).unwrap();
+            re.search(&Input::split(hay, rest), 0).unwrap().map(|found| found[0].unwrap())
+        };
+        assert_eq!(search("lo wo", "hello", " world"), Some((3, 8)));
+        assert_eq!(search("o w", "hello", " world"), Some((4, 7)));
+        assert_eq!(search("\\(o\\).*\\1", "hello", " world"), Some((4, 8)));
+        assert_eq!(search("\\bworld", "hello ", "world"), Some((6, 11)));
+        assert_eq!(search("d\\'", "hello ", "world"), Some((10, 11)));
+        assert_eq!(search("xyz", "hello ", "world"), None);
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(find("\\(?:a\\)\\(b\\)", "ab"), Some(vec![Some((0, 2)), Some((1, 2))]));
+        assert_eq!(
+            find("\\(?2:a\\)\\(b\\)", "ab"),
+            Some(vec![Some((0, 2)), None, Some((0, 1)), Some((1, 2))])
+        );
+        assert_eq!(whole("\\(a\\|b\\)\\1", "abba"), Some((1, 3)));
+        assert_eq!(Regexp::new("\\(a\\1\\)").unwrap_err(), RegexError::BadBackref);
+        assert_eq!(Regexp::new("\\(a").unwrap_err(), RegexError::UnmatchedParen);
+        assert_eq!(Regexp::new("a\\)").unwrap_err(), RegexError::UnmatchedCloseParen);
+        assert_eq!(Regexp::new("[a").unwrap_err(), RegexError::UnmatchedBracket);
+        assert_eq!(Regexp::new("a\\").unwrap_err(), RegexError::TrailingBackslash);
+    }
+
+    #[test]
+    fn sets() {
+        assert_eq!(whole("[]a]+", "x]a]"), Some((1, 4)));
+        assert_eq!(whole("[^a-c]", "abcd"), Some((3, 4)));
+        assert_eq!(whole("[a-]+", "x-a-"), Some((1, 4)));
+        assert_eq!(whole("[[:digit:][:space:]]+", "a1 2b"), Some((1, 4)));
+        assert_eq!(whole("[[:alpha]+", "x:a"), Some((1, 3)));
+        assert_eq!(Regexp::new("[[:foo:]]").unwrap_err(), RegexError::BadClass);
+    }
+
+    #[test]
+    fn syntax() {
+        assert_eq!(whole("\\sw+", "-foo-"), Some((1, 4)));
+        assert_eq!(whole("\\s-+", "a \tb"), Some((1, 3)));
+        assert_eq!(whole("\\s_", "a-b"), Some((1, 2)));
+        assert_eq!(whole("\\Sw+", "ab-+c"), Some((2, 4)));
+        assert_eq!(whole("\\w+", "(ab)"), Some((1, 3)));
+        assert_eq!(whole("\\cg", "aβ"), Some((1, 3)));
+    }
+
+    #[test]
+    fn assertions() {
+        assert_eq!(whole("\\bb", "ab b"), Some((3, 4)));
+        assert_eq!(whole("\\Bb", "b ab"), Some((3, 4)));
+        assert_eq!(whole("\\<b\\w*\\>", "ab bc"), Some((3, 5)));
+        assert_eq!(whole("\\_<b\\_>", "a-b b"), Some((4, 5)));
+        assert_eq!(whole("^b", "ab\nb"), Some((3, 4)));
+        assert_eq!(whole("a$", "ab\na"), Some((3, 4)));
+        assert_eq!(whole("a^", "a^"), Some((0, 2)));
+        let re = Regexp::new("a\\=").unwrap();
+        let found = re.search(&Input::new("aaa").point(2), 0).unwrap();
+        assert_eq!(found.and_then(|m| m[0]), Some((1, 2)));
+    }
+
+    #[test]
+    fn case_fold() {
+        let re = Regexp::new("ab[c-d]\\(x\\)\\1").unwrap();
+        let found = re.search(&Input::new("-ABDxX").fold(true), 0).unwrap();
+        assert_eq!(found.and_then(|m| m[0]), Some((1, 6)));
+        assert!(re.search(&Input::new("-ABDxX"), 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn posix() {
+        let re = Regexp::new("a\\|ab").unwrap();
+        assert_eq!(re.search(&Input::new("ab"), 0).unwrap().unwrap()[0], Some((0, 1)));
+        let re = re.posix();
+        assert_eq!(re.search(&Input::new("ab"), 0).unwrap().unwrap()[0], Some((0, 2)));
+    }
+
+    const PATTERNS: &[&str] = &[
+        "a+b*",
+        "\\(a\\|b\\)+",
+        "\\(a*\\)\\(b\\)?c",
+        "\\(.\\)\\1",
+        "[^ ]+",
+        "[[:space:]]+",
+        "\\sw+",
+        "\\s_\\|\\s.",
+        "\\<\\w",
+        "\\w\\>",
+        "\\_<[a-c]+\\_>",
+        "\\bc",
+        "\\Ba",
+        "^a\\|c$",
+        "\\`.\\|.\\'",
+        "\\(?:ab\\)\\{1,2\\}",
+        "\\(?3:b\\)\\(c\\)",
+        "A\\(B\\)",
+        "a*?b",
+    ];
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_string_match() {
+        proptest!(|(
+            pattern in prop::sample::select(PATTERNS),
+            string in arb_custom_string("[abcABC _(-]{0,12}"),
+        )| {
+            assert_elprop!["(and (string-match {:?} {}) (match-data))", pattern, string];
+        });
+    }
+}
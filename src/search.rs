@@ -1,46 +1,452 @@
 //! Search utilities.
-use crate::core::{
-    cons::Cons,
-    env::Env,
-    gc::{Context, Rt},
-    object::{List, NIL, Object, ObjectType, OptionalFlag},
+use crate::{
+    core::{
+        env::{Env, sym},
+        gc::{Context, Rt},
+        object::{CharTable, List, NIL, Object, ObjectType, OptionalFlag, Position, TRUE},
+    },
+    eval::EvalError,
+    fns::slice_into_list,
+    regex_emacs::{Input, Match, Regexp},
+    syntax::{SyntaxTable, current_table},
 };
 use anyhow::{Result, bail, ensure};
 use fallible_iterator::FallibleIterator;
-use fancy_regex::Regex;
+use rune_core::macros::list;
 use rune_macros::defun;
+use text_buffer::Buffer as TextBuffer;
+
+/// The bounds of the groups of a match as 0-based character positions, or
+/// `None` for groups that did not participate in the match.
+type Groups = Vec<Option<(usize, usize)>>;
+
+/// Convert the groups of a match to character positions. `to_char` converts
+/// a byte offset into the haystack to a character position.
+fn match_groups(found: &Match, to_char: impl Fn(usize) -> usize) -> Groups {
+    found
+        .iter()
+        .map(|group| group.map(|(beg, end)| (to_char(beg), to_char(end))))
+        .collect()
+}
+
+/// Compile REGEXP, signaling `invalid-regexp` if it is malformed.
+fn compile_regexp(regexp: &str, env: &mut Rt<Env>, cx: &Context) -> Result<Regexp> {
+    Regexp::new(regexp).map_err(|err| {
+        let data = list![cx.add(err.to_string()); cx];
+        EvalError::signal(sym::INVALID_REGEXP.into(), data, env).into()
+    })
+}
+
+/// Whether searches should ignore case, from `case-fold-search`.
+fn case_fold(env: &Rt<Env>, cx: &Context) -> bool {
+    // The default value is t, so an unbound variable means to fold case too
+    env.var(sym::CASE_FOLD_SEARCH, cx).is_none_or(|x| !x.is_nil())
+}
+
+/// Set the match data to `groups`, adding `offset` to each position. Returns
+/// the start of the match.
+fn set_match_groups<'ob>(
+    groups: &Groups,
+    offset: usize,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Object<'ob> {
+    // Unmatched groups at the end are left out
+    let len = groups.iter().rposition(Option::is_some).map_or(0, |x| x + 1);
+    let mut all: Vec<Object> = Vec::new();
+    for group in &groups[..len] {
+        match group {
+            Some((beg, end)) => {
+                all.push((beg + offset).into());
+                all.push((end + offset).into());
+            }
+            None => all.extend([NIL, NIL]),
+        }
+    }
+    env.match_data.set(slice_into_list(&all, None, cx));
+    all.first().copied().unwrap_or(NIL)
+}
 
 #[defun]
 fn string_match<'ob>(
     regexp: &str,
     string: &str,
     start: Option<i64>,
-    _inhibit_modify: OptionalFlag,
+    inhibit_modify: Option<Object>,
     env: &mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    // TODO: implement inhibit-modify
-    let re = Regex::new(&lisp_regex_to_rust(regexp))?;
-
-    let start = start.unwrap_or(0) as usize;
-    if let Some(matches) = re.captures_iter(&string[start..]).next() {
-        let mut all: Vec<Object> = Vec::new();
-        let matches = matches?;
-        let mut groups = matches.iter();
-        // TODO: match data should be char position, not byte
-        while let Some(Some(group)) = groups.next() {
-            all.push(group.start().into());
-            all.push(group.end().into());
+    let re = compile_regexp(regexp, env, cx)?;
+    let table = SyntaxTable::new(current_table(env, cx));
+    let syntax = |c: char| table.class(c);
+    let input = Input::new(string).fold(case_fold(env, cx)).syntax(&syntax);
+
+    // START counts characters, and from the end if it is negative
+    let len = string.chars().count() as i64;
+    let start = match start.unwrap_or(0) {
+        x if x < 0 => len + x,
+        x => x,
+    };
+    ensure!((0..=len).contains(&start), "Args out of range: {string:?}, {start}");
+    let start = char_to_byte(string, start as usize);
+    let Some(found) = re.search(&input, start)? else { return Ok(NIL) };
+    let groups = match_groups(&found, |byte| string[..byte].chars().count());
+    if inhibit_modify.is_some_and(|x| !x.is_nil()) {
+        return Ok(groups[0].map(|(beg, _)| beg).into());
+    }
+    Ok(set_match_groups(&groups, 0, env, cx))
+}
+
+fn char_to_byte(string: &str, pos: usize) -> usize {
+    string.char_indices().nth(pos).map_or(string.len(), |(idx, _)| idx)
+}
+
+/// A view of part of a buffer's text that the regex engine can run on. Byte
+/// offsets into it count from the start of `hay` and continue into `rest`.
+pub(crate) struct BufferText<'a> {
+    text: &'a TextBuffer,
+    pub(crate) hay: &'a str,
+    /// The text after the gap, when the gap is inside the range. This is
+    /// always empty for a view made with [`Self::new`].
+    rest: &'a str,
+    /// The character position of the start of `hay`.
+    pub(crate) start: usize,
+    /// The byte position of the start of `hay` in the buffer, including the
+    /// gap.
+    base: usize,
+    /// The byte position of the start of `rest` in the buffer.
+    rest_base: usize,
+}
+
+impl<'a> BufferText<'a> {
+    /// A contiguous view of the text between `beg` and `end`. The gap is moved
+    /// out of the way first, so text is only copied when the gap is inside the
+    /// range, instead of copying the whole buffer.
+    pub(crate) fn new(text: &'a mut TextBuffer, beg: usize, end: usize) -> Self {
+        text.move_gap_out_of(beg..end);
+        let view = Self::split(text, beg, end);
+        debug_assert!(view.rest.is_empty());
+        view
+    }
+
+    /// A view of the text between `beg` and `end` that leaves the gap where
+    /// it is, so the text can be in two halves. Only [`Regexp`] can run on
+    /// this, through [`Self::input`].
+    fn split(text: &'a TextBuffer, beg: usize, end: usize) -> Self {
+        let (hay, rest) = text.slice(beg..end);
+        let base = text.char_to_byte(beg);
+        let rest_base = if rest.is_empty() {
+            base + hay.len()
+        } else {
+            // The gap starts where `hay` ends
+            text.char_to_byte(text.byte_to_char(base + hay.len()))
+        };
+        Self { text, hay, rest, start: beg, base, rest_base }
+    }
+
+    fn input(&self) -> Input<'a> {
+        Input::split(self.hay, self.rest)
+    }
+
+    /// The byte offset into the haystack of the character position `pos`.
+    pub(crate) fn byte(&self, pos: usize) -> usize {
+        let (a, b) = self.text.slice(self.start..pos);
+        a.len() + b.len()
+    }
+
+    /// The character position of the byte offset `byte` into the haystack.
+    fn char(&self, byte: usize) -> usize {
+        match byte.checked_sub(self.hay.len()) {
+            Some(rest) if rest > 0 => self.text.byte_to_char(self.rest_base + rest),
+            _ => self.text.byte_to_char(self.base + byte),
+        }
+    }
+}
+
+/// A search in the current buffer, shared by `re-search-forward`,
+/// `search-forward` and the other buffer search functions.
+struct BufferSearch<'ob> {
+    /// The string that was searched for, used in the error message.
+    pattern: Object<'ob>,
+    re: Regexp,
+    forward: bool,
+    fold: bool,
+    /// The syntax table of the buffer being searched.
+    table: &'ob CharTable,
+}
+
+impl<'ob> BufferSearch<'ob> {
+    fn new(
+        pattern: Object<'ob>,
+        literal: bool,
+        forward: bool,
+        posix: bool,
+        env: &mut Rt<Env>,
+        cx: &'ob Context,
+    ) -> Result<Self> {
+        let string: &str = pattern.try_into()?;
+        let re = if literal { Regexp::literal(string) } else { compile_regexp(string, env, cx)? };
+        let re = if posix { re.posix() } else { re };
+        let table = current_table(env, cx);
+        Ok(Self { pattern, re, forward, fold: case_fold(env, cx), table })
+    }
+
+    /// Search COUNT times from point, without going past BOUND. On success
+    /// point is moved to the end of the match (or the start when searching
+    /// backward), and the match data is set. See `re-search-forward` for the
+    /// meaning of the arguments.
+    fn run(
+        &self,
+        bound: Option<Position>,
+        noerror: Option<Object>,
+        count: Option<i64>,
+        env: &mut Rt<Env>,
+        cx: &'ob Context,
+    ) -> Result<Object<'ob>> {
+        let count = count.unwrap_or(1);
+        let forward = self.forward == (count >= 0);
+        let buffer = env.current_buffer.get();
+        let point = buffer.text.cursor().chars();
+        if count == 0 {
+            return Ok((point + 1).into());
+        }
+        let (begv, zv) = (buffer.begv(), buffer.zv());
+        let lim = match bound {
+            Some(Position(bound)) => {
+                let bound = bound.saturating_sub(1);
+                if (forward && bound < point) || (!forward && bound > point) {
+                    bail!("Invalid search bound (wrong side of point)");
+                }
+                bound.clamp(begv, zv)
+            }
+            None if forward => zv,
+            None => begv,
+        };
+
+        let text = BufferText::split(&buffer.text, begv, zv);
+        let mut pos = point;
+        let mut found = None;
+        for _ in 0..count.unsigned_abs() {
+            found = self.find(&text, point, pos, lim, forward)?;
+            let Some(groups) = &found else { break };
+            let (beg, end) = groups[0].unwrap();
+            pos = if forward { end } else { beg };
+        }
+
+        match found {
+            Some(groups) => {
+                env.current_buffer.get_mut().text.set_cursor(pos);
+                set_match_groups(&groups, 1, env, cx);
+                Ok((pos + 1).into())
+            }
+            None if noerror.is_none_or(|x| x.is_nil()) => {
+                let data = list![self.pattern; cx];
+                Err(EvalError::signal(sym::SEARCH_FAILED.into(), data, env).into())
+            }
+            None => {
+                if noerror != Some(TRUE) {
+                    env.current_buffer.get_mut().text.set_cursor(lim);
+                }
+                Ok(NIL)
+            }
         }
-        let match_data = crate::fns::slice_into_list(&all, None, cx);
-        env.match_data.set(match_data);
-        let head: &Cons = match_data.try_into().unwrap();
-        Ok(head.car())
-    } else {
-        Ok(NIL)
+    }
+
+    /// Find one match between `pos` and `lim`.
+    fn find(
+        &self,
+        text: &BufferText,
+        point: usize,
+        pos: usize,
+        lim: usize,
+        forward: bool,
+    ) -> Result<Option<Groups>> {
+        let table = SyntaxTable::new(self.table);
+        let syntax = |c: char| table.class(c);
+        let input = text.input().fold(self.fold).point(text.byte(point)).syntax(&syntax);
+        // The match can't extend past the limit when searching forward, or
+        // past the starting position when searching backward.
+        let found = if forward {
+            self.re.search(&input.limit(text.byte(lim)), text.byte(pos))?
+        } else {
+            let from = text.byte(pos);
+            self.re.search_backward(&input.limit(from), from, text.byte(lim))?
+        };
+        Ok(found.map(|found| match_groups(&found, |byte| text.char(byte))))
     }
 }
 
+/// Search forward from point for regular expression REGEXP.
+/// Set point to the end of the occurrence found, and return point.
+/// An optional second argument bounds the search; it is a buffer position.
+///   The match found must not end after that position.  A value of nil
+///   means search to the end of the accessible portion of the buffer.
+/// Optional third argument, if t, means if fail just return nil (no error).
+///   If not nil and not t, move to limit of search and return nil.
+/// Optional fourth argument COUNT, if a positive number, means to search
+///   for COUNT successive occurrences.  If COUNT is negative, search
+///   backward, instead of forward, for -COUNT occurrences.  A value of
+///   nil means the same as 1.
+#[defun]
+fn re_search_forward<'ob>(
+    regexp: Object<'ob>,
+    bound: Option<Position>,
+    noerror: Option<Object>,
+    count: Option<i64>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    BufferSearch::new(regexp, false, true, false, env, cx)?.run(bound, noerror, count, env, cx)
+}
+
+/// Search backward from point for regular expression REGEXP.
+/// This function is almost identical to `re-search-forward', except that
+/// by default it searches backward instead of forward, and the sign of
+/// COUNT also indicates exactly the opposite searching direction.
+/// The match found must not end after point, and BOUND is the earliest
+/// position where it may start.
+#[defun]
+fn re_search_backward<'ob>(
+    regexp: Object<'ob>,
+    bound: Option<Position>,
+    noerror: Option<Object>,
+    count: Option<i64>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    BufferSearch::new(regexp, false, false, false, env, cx)?.run(bound, noerror, count, env, cx)
+}
+
+/// Search forward from point for STRING.
+/// Set point to the end of the occurrence found, and return point.
+/// The optional arguments are the same as for `re-search-forward'.
+#[defun]
+fn search_forward<'ob>(
+    string: Object<'ob>,
+    bound: Option<Position>,
+    noerror: Option<Object>,
+    count: Option<i64>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    BufferSearch::new(string, true, true, false, env, cx)?.run(bound, noerror, count, env, cx)
+}
+
+/// Search backward from point for STRING.
+/// Set point to the beginning of the occurrence found, and return point.
+/// The optional arguments are the same as for `re-search-backward'.
+#[defun]
+fn search_backward<'ob>(
+    string: Object<'ob>,
+    bound: Option<Position>,
+    noerror: Option<Object>,
+    count: Option<i64>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    BufferSearch::new(string, true, false, false, env, cx)?.run(bound, noerror, count, env, cx)
+}
+
+/// Search forward from point for match for REGEXP according to Posix rules.
+/// Find the longest match in accord with Posix regular expression rules.
+/// The optional arguments are the same as for `re-search-forward'.
+#[defun]
+fn posix_search_forward<'ob>(
+    regexp: Object<'ob>,
+    bound: Option<Position>,
+    noerror: Option<Object>,
+    count: Option<i64>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    BufferSearch::new(regexp, false, true, true, env, cx)?.run(bound, noerror, count, env, cx)
+}
+
+/// Search backward from point for match for REGEXP according to Posix rules.
+/// Find the longest match in accord with Posix regular expression rules.
+/// The optional arguments are the same as for `re-search-backward'.
+#[defun]
+fn posix_search_backward<'ob>(
+    regexp: Object<'ob>,
+    bound: Option<Position>,
+    noerror: Option<Object>,
+    count: Option<i64>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    BufferSearch::new(regexp, false, false, true, env, cx)?.run(bound, noerror, count, env, cx)
+}
+
+/// Return t if text after point matches regular expression REGEXP.
+/// By default, this function modifies the match data that
+/// `match-beginning', `match-end' and `match-data' access.  If
+/// INHIBIT-MODIFY is non-nil, the match data will not be modified.
+#[defun]
+fn looking_at(
+    regexp: &str,
+    inhibit_modify: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<bool> {
+    let re = compile_regexp(regexp, env, cx)?;
+    let fold = case_fold(env, cx);
+    let table = SyntaxTable::new(current_table(env, cx));
+    let syntax = |c: char| table.class(c);
+    let buffer = env.current_buffer.get();
+    let point = buffer.text.cursor().chars();
+    let (begv, zv) = (buffer.begv(), buffer.zv());
+    let text = BufferText::split(&buffer.text, begv, zv);
+    let start = text.byte(point);
+    let input = text.input().fold(fold).point(start).syntax(&syntax);
+    let Some(found) = re.match_at(&input, start)? else { return Ok(false) };
+    let groups = match_groups(&found, |byte| text.char(byte));
+    if inhibit_modify.is_none_or(|x| x.is_nil()) {
+        set_match_groups(&groups, 1, env, cx);
+    }
+    Ok(true)
+}
+
+/// Return non-nil if text before point matches regular expression REGEXP.
+/// Like `looking-at' except matches before point, and is slower.
+/// LIMIT if non-nil speeds up the search by specifying a minimum
+/// starting position, to avoid checking matches that would start
+/// before LIMIT.
+///
+/// If GREEDY is non-nil, extend the match backwards as far as
+/// possible, stopping when a single additional previous character
+/// cannot be part of a match for REGEXP.
+#[defun]
+fn looking_back(
+    regexp: &str,
+    limit: Option<Position>,
+    greedy: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<bool> {
+    // The match has to end at point
+    let re = compile_regexp(&format!("\\(?:{regexp}\\)\\="), env, cx)?;
+    let fold = case_fold(env, cx);
+    let table = SyntaxTable::new(current_table(env, cx));
+    let syntax = |c: char| table.class(c);
+    let buffer = env.current_buffer.get();
+    let point = buffer.text.cursor().chars();
+    let (begv, zv) = (buffer.begv(), buffer.zv());
+    let limit = limit.map_or(begv, |Position(x)| x.saturating_sub(1).clamp(begv, point));
+    let text = BufferText::split(&buffer.text, begv, zv);
+    let end = text.byte(point);
+    let input = text.input().fold(fold).point(end).limit(end).syntax(&syntax);
+    let min = text.byte(limit);
+    let found = if greedy.is_some_and(|x| !x.is_nil()) {
+        re.search(&input, min)?
+    } else {
+        re.search_backward(&input, end, min)?
+    };
+    let Some(found) = found else { return Ok(false) };
+    let groups = match_groups(&found, |byte| text.char(byte));
+    set_match_groups(&groups, 1, env, cx);
+    Ok(true)
+}
+
 #[defun]
 fn replace_match(
     newtext: &str,
@@ -64,9 +470,8 @@ fn replace_match(
     let Some(beg) = match_data.next()? else { bail!(sub_err()) };
     let Some(end) = match_data.next()? else { bail!(sub_err()) };
 
-    // TODO: match data should be char position, not byte
-    let beg: usize = beg.try_into()?;
-    let end: usize = end.try_into()?;
+    let beg = char_to_byte(string, beg.try_into()?);
+    let end = char_to_byte(string, end.try_into()?);
 
     // replace the range beg..end in string with newtext
     let mut new_string = String::new();
@@ -88,41 +493,6 @@ fn regexp_quote(string: &str) -> String {
     quoted
 }
 
-pub(crate) fn lisp_regex_to_rust(regexp: &str) -> String {
-    let mut norm_regex = String::new();
-    let mut chars = regexp.char_indices();
-    while let Some((idx, ch)) = chars.next() {
-        match ch {
-            // Invert the escaping of parens. i.e. \( => ( and ( => \(
-            '(' | ')' | '{' | '}' => {
-                norm_regex.push('\\');
-                norm_regex.push(ch);
-            }
-            '\\' => match chars.next() {
-                Some((_, c @ '('..=')' | c @ '{' | c @ '}')) => norm_regex.push(c),
-                Some((_, '`')) => norm_regex += "\\A",
-                Some((_, '\'')) => norm_regex += "\\z",
-                Some((_, c)) => {
-                    norm_regex.push('\\');
-                    norm_regex.push(c);
-                }
-                None => norm_regex.push('\\'),
-            },
-            '[' => {
-                let word = "[:word:]";
-                if regexp[idx..].starts_with(word) {
-                    chars.nth(word.len() - 2);
-                    norm_regex.push_str("a-zA-Z");
-                } else {
-                    norm_regex.push('[');
-                }
-            }
-            c => norm_regex.push(c),
-        }
-    }
-    norm_regex
-}
-
 #[defun]
 fn match_data<'ob>(
     integer: OptionalFlag,
@@ -148,13 +518,13 @@ fn set_match_data<'ob>(list: List, _reseat: OptionalFlag, env: &mut Rt<Env>) ->
 #[defun]
 fn match_beginning<'ob>(subexp: usize, env: &Rt<Env>, cx: &'ob Context) -> Result<Object<'ob>> {
     let list = env.match_data.bind(cx).as_list()?;
-    Ok(list.fallible().nth(subexp)?.unwrap_or_default())
+    Ok(list.fallible().nth(subexp * 2)?.unwrap_or_default())
 }
 
 #[defun]
 fn match_end<'ob>(subexp: usize, env: &Rt<Env>, cx: &'ob Context) -> Result<Object<'ob>> {
     let list = env.match_data.bind(cx).as_list()?;
-    Ok(list.fallible().nth(subexp + 1)?.unwrap_or_default())
+    Ok(list.fallible().nth(subexp * 2 + 1)?.unwrap_or_default())
 }
 
 #[defun]
@@ -172,23 +542,88 @@ fn match_data__translate(n: i64, env: &Rt<Env>, cx: &Context) -> Result<()> {
     Ok(())
 }
 
+defsym!(SEARCH_FAILED);
+defsym!(INVALID_REGEXP);
+defvar!(CASE_FOLD_SEARCH, true);
+
 #[cfg(test)]
 mod test {
     use crate::core::gc::RootSet;
+    use crate::interpreter::assert_lisp;
     use rune_core::macros::root;
 
     use super::*;
 
     #[test]
-    fn lisp_regex() {
-        assert_eq!(lisp_regex_to_rust("foo"), "foo");
-        assert_eq!(lisp_regex_to_rust("\\foo"), "\\foo");
-        assert_eq!(lisp_regex_to_rust("\\(foo\\)"), "(foo)");
-        assert_eq!(lisp_regex_to_rust("(foo)"), "\\(foo\\)");
-        assert_eq!(lisp_regex_to_rust("\\`"), "\\A");
-        assert_eq!(lisp_regex_to_rust("\\'"), "\\z");
-        assert_eq!(lisp_regex_to_rust("[[:word:]]"), "[a-zA-Z]");
-        assert_eq!(lisp_regex_to_rust("[[:word:]_]"), "[a-zA-Z_]");
+    fn test_string_match() {
+        assert_lisp(r#"(string-match "b\\(.\\)" "abcabc" 2)"#, "4");
+        assert_lisp(r#"(progn (string-match "ñ\\(o\\)" "añaño") (match-end 1))"#, "5");
+        assert_lisp(r#"(progn (string-match "x\\|\\(y\\)" "ax") (match-data))"#, "(1 2)");
+        assert_lisp(r#"(string-match "\\_<b" "a-b b")"#, "4");
+        assert_lisp(r#"(let ((case-fold-search nil)) (string-match "A" "a"))"#, "nil");
+        assert_lisp(r#"(condition-case nil (string-match "\\(" "") (error 'invalid))"#, "invalid");
+    }
+
+    #[test]
+    fn test_search_forward() {
+        assert_lisp(
+            r#"(progn (insert "foo bar baz") (goto-char 1) (list (re-search-forward "ba\\(.\\)") (point) (match-beginning 0) (match-end 1)))"#,
+            "(8 8 5 8)",
+        );
+        assert_lisp(
+            r#"(progn (insert "ñaño") (goto-char 1) (list (re-search-forward "ño") (match-beginning 0)))"#,
+            "(5 3)",
+        );
+        assert_lisp(
+            r#"(progn (insert "abcabc") (goto-char 1) (list (search-forward "c" 3 t) (search-forward "c" 5 t) (point) (search-forward "z" nil 'move) (point)))"#,
+            "(nil 4 4 nil 7)",
+        );
+        assert_lisp(
+            r#"(progn (insert "a1a2a3") (goto-char 1) (list (re-search-forward "a[0-9]" nil nil 2) (re-search-forward "a[0-9]" nil nil -1)))"#,
+            "(5 3)",
+        );
+        assert_lisp(r#"(condition-case nil (search-forward "zz") (error 'failed))"#, "failed");
+        assert_lisp(
+            r#"(progn (insert "abcd") (goto-char 1) (narrow-to-region 1 3) (search-forward "c" nil t))"#,
+            "nil",
+        );
+        // The insertion leaves the gap after the "!", so matches span it
+        assert_lisp(
+            r#"(progn (insert "hello world") (goto-char 6) (insert "!") (goto-char 1) (list (search-forward "o! w") (match-beginning 0) (re-search-backward "l+\\(o\\)!") (match-end 1) (looking-at "lo! \\(w\\)") (match-beginning 1)))"#,
+            "(9 5 4 6 t 8)",
+        );
+    }
+
+    #[test]
+    fn test_search_backward() {
+        assert_lisp(
+            r#"(progn (insert "abcabc") (list (search-backward "bc") (point) (match-end 0)))"#,
+            "(5 5 7)",
+        );
+        assert_lisp(
+            r#"(progn (insert "abcabc") (list (re-search-backward "a\\(b\\)" 3 t) (point) (re-search-backward "a" 3 t)))"#,
+            "(4 4 nil)",
+        );
+        assert_lisp(
+            r#"(progn (insert "xaaay") (goto-char 2) (list (posix-search-forward "a\\|aa") (progn (goto-char 2) (re-search-forward "a\\|aa"))))"#,
+            "(4 3)",
+        );
+    }
+
+    #[test]
+    fn test_looking_at() {
+        assert_lisp(
+            r#"(progn (insert "hello") (goto-char 1) (list (looking-at "hel") (looking-at "el") (match-end 0)))"#,
+            "(t nil 4)",
+        );
+        assert_lisp(
+            r#"(progn (insert "hello world") (list (looking-back "wor?ld") (looking-back "hello") (match-beginning 0)))"#,
+            "(t nil 7)",
+        );
+        assert_lisp(
+            r#"(progn (insert "aaab") (goto-char 4) (looking-back "a+" nil t) (match-beginning 0))"#,
+            "1",
+        );
     }
 
     #[test]
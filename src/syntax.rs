@@ -0,0 +1,1652 @@
+//! Syntax tables and the functions that parse text with them.
+use crate::{
+    core::{
+        cons::Cons,
+        env::{Env, sym},
+        gc::{Context, Rt},
+        object::{
+            CharTable, CharTableInner, IntoObject, List, NIL, Object, ObjectType, Position, Symbol,
+            TRUE, TagType, int_to_char,
+        },
+    },
+    eval::EvalError,
+    fns::slice_into_list,
+    intervals::{IntervalTree, textget},
+    search::BufferText,
+};
+use anyhow::{Result, anyhow, bail};
+use rune_core::macros::list;
+use rune_macros::defun;
+
+/// The syntax class of a character, which determines how it is treated by
+/// the regex engine and the other functions that parse text. The order
+/// matches the codes Emacs uses in raw syntax descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SyntaxClass {
+    Whitespace,
+    Punct,
+    Word,
+    Symbol,
+    Open,
+    Close,
+    Quote,
+    String,
+    Math,
+    Escape,
+    CharQuote,
+    Comment,
+    EndComment,
+    Inherit,
+    CommentFence,
+    StringFence,
+}
+
+/// The designator characters of the syntax classes, indexed by their code.
+const DESIGNATORS: [char; 16] =
+    [' ', '.', 'w', '_', '(', ')', '\'', '"', '$', '\\', '/', '<', '>', '@', '!', '|'];
+
+impl SyntaxClass {
+    /// The class for a syntax designator character, such as `w` for word
+    /// constituents or `-` for whitespace.
+    pub(crate) fn from_designator(c: char) -> Option<Self> {
+        let c = if c == '-' { ' ' } else { c };
+        let code = DESIGNATORS.iter().position(|x| *x == c)?;
+        Self::from_code(code)
+    }
+
+    fn from_code(code: usize) -> Option<Self> {
+        let class = match code {
+            0 => Self::Whitespace,
+            1 => Self::Punct,
+            2 => Self::Word,
+            3 => Self::Symbol,
+            4 => Self::Open,
+            5 => Self::Close,
+            6 => Self::Quote,
+            7 => Self::String,
+            8 => Self::Math,
+            9 => Self::Escape,
+            10 => Self::CharQuote,
+            11 => Self::Comment,
+            12 => Self::EndComment,
+            13 => Self::Inherit,
+            14 => Self::CommentFence,
+            15 => Self::StringFence,
+            _ => return None,
+        };
+        Some(class)
+    }
+
+    fn designator(self) -> char {
+        DESIGNATORS[self as usize]
+    }
+}
+
+/// The syntax class of `c` in the standard syntax table.
+pub(crate) fn standard_syntax(c: char) -> SyntaxClass {
+    match c {
+        ' ' | '\t' | '\n' | '\r' | '\x0c' => SyntaxClass::Whitespace,
+        // Other control characters should not be whitespace
+        '\0'..=' ' | '\x7f' => SyntaxClass::Punct,
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '$' | '%' => SyntaxClass::Word,
+        '(' | '[' | '{' => SyntaxClass::Open,
+        ')' | ']' | '}' => SyntaxClass::Close,
+        '"' => SyntaxClass::String,
+        '\\' => SyntaxClass::Escape,
+        '_' | '-' | '+' | '*' | '/' | '&' | '|' | '<' | '>' | '=' => SyntaxClass::Symbol,
+        '.' | ',' | ';' | ':' | '?' | '!' | '#' | '@' | '~' | '^' | '\'' | '`' => {
+            SyntaxClass::Punct
+        }
+        // All other characters are word constituents, except the ones Unicode
+        // considers to be spaces or punctuation.
+        c if c.is_whitespace() => SyntaxClass::Whitespace,
+        c if c.is_alphanumeric() => SyntaxClass::Word,
+        c if is_unicode_punct(c) => SyntaxClass::Punct,
+        _ => SyntaxClass::Word,
+    }
+}
+
+fn is_unicode_punct(c: char) -> bool {
+    matches!(c,
+        '\u{a1}'..='\u{bf}' | '\u{d7}' | '\u{f7}'
+        | '\u{2010}'..='\u{2027}' | '\u{2030}'..='\u{205e}'
+        | '\u{3001}'..='\u{3003}' | '\u{3008}'..='\u{3011}')
+}
+
+// Flags of a syntax descriptor, stored above the class in its code
+const COMSTART_FIRST: i64 = 1 << 16;
+const COMSTART_SECOND: i64 = 1 << 17;
+const COMEND_FIRST: i64 = 1 << 18;
+const COMEND_SECOND: i64 = 1 << 19;
+const PREFIX: i64 = 1 << 20;
+const STYLE_B: i64 = 1 << 21;
+const NESTED: i64 = 1 << 22;
+const STYLE_C: i64 = 1 << 23;
+
+/// The flag characters of a syntax descriptor string and their bits.
+const FLAGS: [(char, i64); 8] = [
+    ('1', COMSTART_FIRST),
+    ('2', COMSTART_SECOND),
+    ('3', COMEND_FIRST),
+    ('4', COMEND_SECOND),
+    ('p', PREFIX),
+    ('b', STYLE_B),
+    ('n', NESTED),
+    ('c', STYLE_C),
+];
+
+/// The syntax of a character: its class, matching parenthesis and flags.
+/// In syntax tables this is stored as a raw syntax descriptor, a cons of the
+/// code and the matching character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Syntax {
+    class: SyntaxClass,
+    matching: Option<char>,
+    flags: i64,
+}
+
+impl Syntax {
+    fn new(class: SyntaxClass) -> Self {
+        Self { class, matching: None, flags: 0 }
+    }
+
+    /// The entry for `c` in the standard syntax table.
+    fn standard(c: char) -> Self {
+        let matching = match c {
+            '(' => Some(')'),
+            ')' => Some('('),
+            '[' => Some(']'),
+            ']' => Some('['),
+            '{' => Some('}'),
+            '}' => Some('{'),
+            _ => None,
+        };
+        Self { matching, ..Self::new(standard_syntax(c)) }
+    }
+
+    /// Parse a syntax descriptor like `". 23"`. Returns `None` for the
+    /// inherit class `@`, which is represented by nil.
+    fn parse(descriptor: &str) -> Result<Option<Self>> {
+        let mut chars = descriptor.chars();
+        let Some(designator) = chars.next() else { return Ok(None) };
+        let Some(class) = SyntaxClass::from_designator(designator) else {
+            bail!("Invalid syntax description letter: {designator}");
+        };
+        if class == SyntaxClass::Inherit {
+            return Ok(None);
+        }
+        let matching = chars.next().filter(|c| *c != ' ');
+        let mut flags = 0;
+        for c in chars {
+            if let Some((_, bit)) = FLAGS.iter().find(|(flag, _)| *flag == c) {
+                flags |= bit;
+            }
+        }
+        Ok(Some(Self { class, matching, flags }))
+    }
+
+    /// Decode a raw syntax descriptor. Anything other than a cons of a code
+    /// and a matching character means whitespace.
+    fn from_object(obj: Object) -> Self {
+        let ObjectType::Cons(cons) = obj.untag() else {
+            return Self::new(SyntaxClass::Whitespace);
+        };
+        let ObjectType::Int(code) = cons.car().untag() else {
+            return Self::new(SyntaxClass::Whitespace);
+        };
+        let class = SyntaxClass::from_code((code & 0xFFFF) as usize);
+        Self {
+            class: class.unwrap_or(SyntaxClass::Whitespace),
+            matching: char::try_from(cons.cdr()).ok(),
+            flags: code & !0xFFFF,
+        }
+    }
+
+    fn to_object<'ob>(self, cx: &'ob Context) -> Object<'ob> {
+        let code = self.class as i64 | self.flags;
+        let matching = match self.matching {
+            Some(c) => cx.add(c),
+            None => NIL,
+        };
+        Cons::new(code, matching, cx).into()
+    }
+
+    fn has(self, flag: i64) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// The comment style of a single character comment delimiter.
+    fn style(self) -> u8 {
+        u8::from(self.has(STYLE_B)) | (u8::from(self.has(STYLE_C)) << 1)
+    }
+}
+
+/// The comment style of a two character comment starter.
+fn start_style(first: Syntax, second: Syntax) -> u8 {
+    second.style() | (u8::from(first.has(STYLE_C)) << 1)
+}
+
+/// The comment style of a two character comment ender.
+fn end_style(first: Syntax, second: Syntax) -> u8 {
+    first.style() | (u8::from(second.has(STYLE_C)) << 1)
+}
+
+/// The syntax table used to look up the syntax of characters, along with the
+/// text properties of the buffer when `parse-sexp-lookup-properties` is
+/// non-nil.
+pub(crate) struct SyntaxTable<'a> {
+    table: &'a CharTable,
+    props: Option<&'a IntervalTree<'static>>,
+}
+
+impl<'a> SyntaxTable<'a> {
+    pub(crate) fn new(table: &'a CharTable) -> Self {
+        Self { table, props: None }
+    }
+
+    fn get(table: &CharTable, c: char) -> Syntax {
+        Syntax::from_object(table.get(c as usize))
+    }
+
+    /// The syntax class of `c`, ignoring text properties.
+    pub(crate) fn class(&self, c: char) -> SyntaxClass {
+        Self::get(self.table, c).class
+    }
+
+    /// The syntax of `c` at the 0-based position `pos`. A `syntax-table`
+    /// property there can override the syntax table.
+    fn at(&self, c: char, pos: usize) -> Syntax {
+        if let Some(props) = self.props
+            && let Some(node) = props.find(pos + 1)
+        {
+            let prop = textget(*node.val, sym::SYNTAX_TABLE.into()).unwrap_or(NIL);
+            match prop.untag() {
+                ObjectType::CharTable(table) => return Self::get(table, c),
+                ObjectType::Cons(_) => return Syntax::from_object(prop),
+                _ => {}
+            }
+        }
+        Self::get(self.table, c)
+    }
+}
+
+/// The standard syntax table, which is the parent of the tables made by
+/// `make-syntax-table` and the one used by buffers without their own.
+pub(crate) fn standard_table<'ob>(env: &mut Rt<Env>, cx: &'ob Context) -> &'ob CharTable {
+    if let ObjectType::CharTable(table) = env.standard_syntax_table.bind(cx).untag() {
+        return table;
+    }
+    let word = Syntax::new(SyntaxClass::Word).to_object(cx);
+    let table = CharTableInner::new(Some(word)).into_obj(cx).untag();
+    // Characters that aren't covered here are word constituents
+    let chars = ('\0'..='\u{7f}').chain('\u{80}'..='\u{3011}');
+    for c in chars.filter(|c| c.is_ascii() || standard_syntax(*c) != SyntaxClass::Word) {
+        table.set(c as usize, Syntax::standard(c).to_object(cx));
+    }
+    env.standard_syntax_table.set(Object::from(table.tag()));
+    table
+}
+
+/// The syntax table of the current buffer.
+pub(crate) fn current_table<'ob>(env: &mut Rt<Env>, cx: &'ob Context) -> &'ob CharTable {
+    match cx.bind(env.current_buffer.get().syntax_table()).untag() {
+        ObjectType::CharTable(table) => table,
+        _ => standard_table(env, cx),
+    }
+}
+
+fn var_is_set(var: Symbol, env: &Rt<Env>, cx: &Context) -> bool {
+    env.var(var, cx).is_some_and(|x| !x.is_nil())
+}
+
+/// A position in the text being scanned, as both a byte offset into the
+/// haystack and a 0-based character position in the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pos {
+    byte: usize,
+    char: usize,
+}
+
+/// Scans the accessible portion of the current buffer using its syntax
+/// table. This is the engine behind `scan-lists`, `parse-partial-sexp` and
+/// the other motion functions.
+struct Scanner<'a> {
+    text: BufferText<'a>,
+    syntax: SyntaxTable<'a>,
+    end: Pos,
+    /// The value of `parse-sexp-ignore-comments`.
+    ignore_comments: bool,
+}
+
+impl Scanner<'_> {
+    fn begin(&self) -> Pos {
+        Pos { byte: 0, char: self.text.start }
+    }
+
+    /// The position of the 0-based character position `pos`, clamped to the
+    /// accessible portion of the buffer.
+    fn pos(&self, pos: usize) -> Pos {
+        let pos = pos.clamp(self.text.start, self.end.char);
+        Pos { byte: self.text.byte(pos), char: pos }
+    }
+
+    fn after(&self, pos: Pos) -> Option<(char, Syntax)> {
+        let c = self.text.hay[pos.byte..].chars().next()?;
+        Some((c, self.syntax.at(c, pos.char)))
+    }
+
+    fn before(&self, pos: Pos) -> Option<(char, Syntax)> {
+        let c = self.text.hay[..pos.byte].chars().next_back()?;
+        Some((c, self.syntax.at(c, pos.char - 1)))
+    }
+
+    fn syntax_after(&self, pos: Pos) -> Syntax {
+        self.after(pos)
+            .map_or(Syntax::new(SyntaxClass::Whitespace), |(_, syntax)| syntax)
+    }
+
+    fn next(&self, pos: Pos) -> Pos {
+        let len = self.text.hay[pos.byte..].chars().next().map_or(0, char::len_utf8);
+        Pos { byte: pos.byte + len, char: pos.char + 1 }
+    }
+
+    fn prev(&self, pos: Pos) -> Pos {
+        let len = self.text.hay[..pos.byte].chars().next_back().map_or(0, char::len_utf8);
+        Pos { byte: pos.byte - len, char: pos.char - 1 }
+    }
+
+    /// True if the character at `pos` is escaped by an odd number of escape
+    /// characters.
+    fn char_quoted(&self, pos: Pos, stop: Pos) -> bool {
+        let mut quoted = false;
+        let mut pos = pos;
+        while pos.char > stop.char {
+            let Some((_, syntax)) = self.before(pos) else { break };
+            if !matches!(syntax.class, SyntaxClass::Escape | SyntaxClass::CharQuote) {
+                break;
+            }
+            quoted = !quoted;
+            pos = self.prev(pos);
+        }
+        quoted
+    }
+
+    /// Move over COUNT words, like `forward-word`. Returns the new position
+    /// and whether all of the words were found.
+    fn scan_words(&self, mut pos: Pos, count: i64) -> (Pos, bool) {
+        let is_word =
+            |x: Option<(char, Syntax)>| x.is_some_and(|(_, s)| s.class == SyntaxClass::Word);
+        for _ in 0..count.unsigned_abs() {
+            if count > 0 {
+                while !is_word(self.after(pos)) {
+                    if pos == self.end {
+                        return (pos, false);
+                    }
+                    pos = self.next(pos);
+                }
+                while is_word(self.after(pos)) {
+                    pos = self.next(pos);
+                }
+            } else {
+                while !is_word(self.before(pos)) {
+                    if pos == self.begin() {
+                        return (pos, false);
+                    }
+                    pos = self.prev(pos);
+                }
+                while is_word(self.before(pos)) {
+                    pos = self.prev(pos);
+                }
+            }
+        }
+        (pos, true)
+    }
+
+    /// Scan forward from `pos`, which is just after a comment starter, to
+    /// the end of the comment, stopping at `end`. `style` is `None` for generic comments
+    /// delimited by comment fences, and `nesting` is the depth of nesting
+    /// for nestable comments. Returns the position after the comment ender,
+    /// or the end of the text as an error if the comment isn't terminated.
+    fn forward_comment(
+        &self,
+        mut pos: Pos,
+        end: Pos,
+        style: Option<u8>,
+        nesting: &mut Option<i64>,
+    ) -> Result<Pos, Pos> {
+        loop {
+            if pos.char >= end.char {
+                return Err(pos);
+            }
+            let (_, syntax) = self.after(pos).unwrap();
+            let here = pos;
+            pos = self.next(pos);
+            let Some(style) = style else {
+                if syntax.class == SyntaxClass::CommentFence {
+                    return Ok(pos);
+                }
+                continue;
+            };
+            match syntax.class {
+                SyntaxClass::EndComment if syntax.style() == style => match nesting {
+                    Some(depth) if *depth > 1 => *depth -= 1,
+                    _ => return Ok(pos),
+                },
+                SyntaxClass::Comment if syntax.style() == style => {
+                    if let Some(depth) = nesting {
+                        *depth += 1;
+                    }
+                }
+                _ => {}
+            }
+            if pos.char >= end.char {
+                continue;
+            }
+            let (_, second) = self.after(pos).unwrap();
+            if syntax.has(COMEND_FIRST)
+                && second.has(COMEND_SECOND)
+                && end_style(syntax, second) == style
+                && !self.char_quoted(here, self.begin())
+            {
+                pos = self.next(pos);
+                match nesting {
+                    Some(depth) if *depth > 1 => *depth -= 1,
+                    _ => return Ok(pos),
+                }
+            } else if let Some(depth) = nesting
+                && syntax.has(COMSTART_FIRST)
+                && second.has(COMSTART_SECOND)
+                && start_style(syntax, second) == style
+            {
+                pos = self.next(pos);
+                *depth += 1;
+            }
+        }
+    }
+
+    /// Find the start of the comment whose ender starts at `end`. Comments
+    /// can't be parsed backward reliably, so this parses forward from the
+    /// start of the text instead.
+    fn comment_start(&self, end: Pos, style: Option<u8>) -> Option<Pos> {
+        let mut state = ParseState::new();
+        self.parse(self.begin(), end, &mut state, &StopAt::default());
+        match state.inside {
+            Inside::Comment { style: found, .. } if found == style => {
+                state.start.map(|x| self.pos(x))
+            }
+            _ => None,
+        }
+    }
+
+    /// Scan COUNT lists or sexps starting at `from`, like `scan-lists`.
+    /// Returns `None` if the end of the text was reached between sexps.
+    fn scan_lists(
+        &self,
+        from: Pos,
+        mut count: i64,
+        mut depth: i64,
+        sexp: bool,
+    ) -> Result<Option<Pos>, ScanError> {
+        use SyntaxClass as S;
+        let min_depth = depth.min(0);
+        let mut pos = from;
+        let mut last_good = from;
+        let unbalanced = |last_good: Pos, pos: Pos| ScanError {
+            message: "Unbalanced parentheses",
+            beg: last_good.char,
+            end: pos.char,
+        };
+        let premature = |beg: Pos, end: Pos| ScanError {
+            message: "Containing expression ends prematurely",
+            beg: beg.char,
+            end: end.char,
+        };
+        while count > 0 {
+            loop {
+                let Some((c, syntax)) = self.after(pos) else {
+                    if depth != 0 {
+                        return Err(unbalanced(last_good, pos));
+                    }
+                    return Ok(None);
+                };
+                let here = pos;
+                pos = self.next(pos);
+                if depth == min_depth {
+                    last_good = here;
+                }
+                let mut class = syntax.class;
+                let mut style = (class == S::Comment).then_some(syntax.style());
+                let mut nesting = syntax.has(NESTED).then_some(1);
+                if let Some((_, second)) = self.after(pos)
+                    && self.ignore_comments
+                    && syntax.has(COMSTART_FIRST)
+                    && second.has(COMSTART_SECOND)
+                {
+                    class = S::Comment;
+                    style = Some(start_style(syntax, second));
+                    nesting = (syntax.has(NESTED) || second.has(NESTED)).then_some(1);
+                    pos = self.next(pos);
+                } else if syntax.has(PREFIX) {
+                    continue;
+                }
+                match class {
+                    S::Escape | S::CharQuote | S::Word | S::Symbol => {
+                        if matches!(class, S::Escape | S::CharQuote) {
+                            if pos == self.end {
+                                return Err(unbalanced(last_good, pos));
+                            }
+                            pos = self.next(pos);
+                        }
+                        if depth != 0 || !sexp {
+                            continue;
+                        }
+                        // This word counts as a sexp, so skip to its end
+                        pos = self
+                            .skip_symbol(pos, self.end)
+                            .map_err(|end| unbalanced(last_good, end))?;
+                        break;
+                    }
+                    S::Comment | S::CommentFence if self.ignore_comments => {
+                        let style = if class == S::Comment { style } else { None };
+                        match self.forward_comment(pos, self.end, style, &mut nesting) {
+                            Ok(end) => pos = end,
+                            Err(end) if depth == 0 => {
+                                pos = end;
+                                break;
+                            }
+                            Err(end) => return Err(unbalanced(last_good, end)),
+                        }
+                    }
+                    S::Open => {
+                        depth += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    S::Close => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                        if depth < min_depth {
+                            return Err(premature(here, pos));
+                        }
+                    }
+                    S::String | S::StringFence => {
+                        loop {
+                            let Some((c2, syntax)) = self.after(pos) else {
+                                return Err(unbalanced(last_good, pos));
+                            };
+                            let ends = match class {
+                                S::String => c2 == c && syntax.class == S::String,
+                                _ => syntax.class == S::StringFence,
+                            };
+                            if ends {
+                                break;
+                            }
+                            if matches!(syntax.class, S::Escape | S::CharQuote) {
+                                pos = self.next(pos);
+                            }
+                            pos = self.next(pos);
+                        }
+                        pos = self.next(pos);
+                        if depth == 0 && sexp {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            count -= 1;
+        }
+
+        let stop = self.begin();
+        while count < 0 {
+            loop {
+                if pos == stop {
+                    if depth != 0 {
+                        return Err(unbalanced(last_good, pos));
+                    }
+                    return Ok(None);
+                }
+                pos = self.prev(pos);
+                let (c, syntax) = self.after(pos).unwrap();
+                if depth == min_depth {
+                    last_good = pos;
+                }
+                let mut class = syntax.class;
+                let mut style = (class == S::EndComment).then_some(syntax.style());
+                // Check for a two character comment ender
+                if pos != stop
+                    && self.ignore_comments
+                    && syntax.has(COMEND_SECOND)
+                    && let Some((_, first)) = self.before(pos)
+                    && first.has(COMEND_FIRST)
+                    && !self.char_quoted(self.prev(pos), stop)
+                {
+                    class = S::EndComment;
+                    style = Some(end_style(first, syntax));
+                    pos = self.prev(pos);
+                }
+                // Quoting turns anything except a comment ender into a word
+                if class != S::EndComment && self.char_quoted(pos, stop) {
+                    pos = self.prev(pos);
+                    class = S::Word;
+                } else if syntax.has(PREFIX) {
+                    continue;
+                }
+                match class {
+                    S::Word | S::Symbol | S::Escape | S::CharQuote => {
+                        if depth != 0 || !sexp {
+                            continue;
+                        }
+                        // This word counts as a sexp, so skip to its start
+                        while pos != stop {
+                            let mut prev = self.prev(pos);
+                            let quoted = self.char_quoted(prev, stop);
+                            if quoted {
+                                prev = self.prev(prev);
+                            }
+                            let class = self.syntax_after(prev).class;
+                            if !quoted && !matches!(class, S::Word | S::Symbol | S::Quote) {
+                                break;
+                            }
+                            pos = prev;
+                        }
+                        break;
+                    }
+                    S::Close => {
+                        depth += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    S::Open => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                        if depth < min_depth {
+                            return Err(premature(pos, self.next(pos)));
+                        }
+                    }
+                    S::EndComment if self.ignore_comments => {
+                        if let Some(start) = self.comment_start(pos, style) {
+                            pos = start;
+                        }
+                    }
+                    S::CommentFence | S::StringFence => {
+                        if class == S::CommentFence && !self.ignore_comments {
+                            continue;
+                        }
+                        loop {
+                            if pos == stop {
+                                return Err(unbalanced(last_good, pos));
+                            }
+                            pos = self.prev(pos);
+                            if !self.char_quoted(pos, stop) && self.syntax_after(pos).class == class
+                            {
+                                break;
+                            }
+                        }
+                        if class == S::StringFence && depth == 0 && sexp {
+                            break;
+                        }
+                    }
+                    S::String => {
+                        loop {
+                            if pos == stop {
+                                return Err(unbalanced(last_good, pos));
+                            }
+                            pos = self.prev(pos);
+                            let (c2, syntax) = self.after(pos).unwrap();
+                            if c2 == c && syntax.class == S::String && !self.char_quoted(pos, stop)
+                            {
+                                break;
+                            }
+                        }
+                        if depth == 0 && sexp {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            count += 1;
+        }
+        Ok(Some(pos))
+    }
+
+    /// Skip over the rest of a symbol, stopping at `end`. Returns `Err` if
+    /// the text ends right after an escape character.
+    fn skip_symbol(&self, mut pos: Pos, end: Pos) -> Result<Pos, Pos> {
+        use SyntaxClass as S;
+        while pos.char < end.char {
+            let (_, syntax) = self.after(pos).unwrap();
+            match syntax.class {
+                S::Escape | S::CharQuote => {
+                    pos = self.next(pos);
+                    if pos.char >= end.char {
+                        return Err(pos);
+                    }
+                }
+                S::Word | S::Symbol | S::Quote => {}
+                _ => break,
+            }
+            pos = self.next(pos);
+        }
+        Ok(pos)
+    }
+
+    /// Parse from `from` to `end`, updating `state`, like
+    /// `parse-partial-sexp`. Returns the position where parsing stopped.
+    fn parse(&self, from: Pos, end: Pos, state: &mut ParseState, stop: &StopAt) -> Pos {
+        use SyntaxClass as S;
+        let mut pos = from;
+        let in_range = |pos: Pos| pos.char < end.char;
+        loop {
+            match state.inside {
+                Inside::Comment { style, ref mut nesting } => {
+                    match self.forward_comment(pos, end, style, nesting) {
+                        Ok(after) => pos = after,
+                        Err(after) => return after,
+                    }
+                    state.inside = Inside::Code;
+                    state.start = None;
+                    if stop.comment == Some(CommentStop::CommentOrString) {
+                        return pos;
+                    }
+                }
+                Inside::String(term) => {
+                    if state.quoted && in_range(pos) {
+                        state.quoted = false;
+                        pos = self.next(pos);
+                    }
+                    loop {
+                        if !in_range(pos) {
+                            return pos;
+                        }
+                        let (c, syntax) = self.after(pos).unwrap();
+                        let ends = match term {
+                            Some(term) => c == term && syntax.class == S::String,
+                            None => syntax.class == S::StringFence,
+                        };
+                        pos = self.next(pos);
+                        if ends {
+                            break;
+                        }
+                        if matches!(syntax.class, S::Escape | S::CharQuote) {
+                            if !in_range(pos) {
+                                state.quoted = true;
+                                return pos;
+                            }
+                            pos = self.next(pos);
+                        }
+                    }
+                    state.inside = Inside::Code;
+                    state.start = None;
+                    state.level().prev = state.level().last;
+                    if stop.comment == Some(CommentStop::CommentOrString) {
+                        return pos;
+                    }
+                }
+                Inside::Code => {}
+            }
+            if state.quoted {
+                // Resume in the middle of a symbol, after an escape
+                state.quoted = false;
+                if !in_range(pos) {
+                    state.quoted = true;
+                    return pos;
+                }
+                pos = match self.skip_symbol(self.next(pos), end) {
+                    Ok(after) => after,
+                    Err(after) => {
+                        state.quoted = true;
+                        return after;
+                    }
+                };
+                state.level().prev = state.level().last;
+            }
+            if !in_range(pos) {
+                return pos;
+            }
+            let here = pos;
+            let (c, syntax) = self.after(pos).unwrap();
+            pos = self.next(pos);
+            if in_range(pos)
+                && syntax.has(COMSTART_FIRST)
+                && let Some((_, second)) = self.after(pos)
+                && second.has(COMSTART_SECOND)
+            {
+                let nested = syntax.has(NESTED) || second.has(NESTED);
+                let style = Some(start_style(syntax, second));
+                state.inside = Inside::Comment { style, nesting: nested.then_some(1) };
+                state.start = Some(here.char);
+                pos = self.next(pos);
+                if stop.comment.is_some() {
+                    return pos;
+                }
+                continue;
+            }
+            if syntax.has(PREFIX) {
+                continue;
+            }
+            match syntax.class {
+                S::Escape | S::CharQuote | S::Word | S::Symbol => {
+                    if stop.before_sexp {
+                        return here;
+                    }
+                    state.level().last = Some(here.char);
+                    if matches!(syntax.class, S::Escape | S::CharQuote) {
+                        if !in_range(pos) {
+                            state.quoted = true;
+                            return pos;
+                        }
+                        pos = self.next(pos);
+                    }
+                    pos = match self.skip_symbol(pos, end) {
+                        Ok(after) => after,
+                        Err(after) => {
+                            state.quoted = true;
+                            return after;
+                        }
+                    };
+                    state.level().prev = state.level().last;
+                }
+                S::Comment | S::CommentFence => {
+                    let (style, nesting) = match syntax.class {
+                        S::Comment => (Some(syntax.style()), syntax.has(NESTED).then_some(1)),
+                        _ => (None, None),
+                    };
+                    state.inside = Inside::Comment { style, nesting };
+                    state.start = Some(here.char);
+                    if stop.comment.is_some() {
+                        return pos;
+                    }
+                }
+                S::Open => {
+                    if stop.before_sexp {
+                        return here;
+                    }
+                    state.depth += 1;
+                    state.level().last = Some(here.char);
+                    state.levels.push(Level::default());
+                    state.opens.push(here.char);
+                    if stop.depth == Some(state.depth) {
+                        return pos;
+                    }
+                }
+                S::Close => {
+                    state.depth -= 1;
+                    state.min_depth = state.min_depth.min(state.depth);
+                    if state.levels.len() > 1 {
+                        state.levels.pop();
+                    }
+                    state.opens.pop();
+                    state.level().prev = state.level().last;
+                    if stop.depth == Some(state.depth) {
+                        return pos;
+                    }
+                }
+                S::String | S::StringFence => {
+                    if stop.before_sexp {
+                        return here;
+                    }
+                    state.level().last = Some(here.char);
+                    state.inside = Inside::String((syntax.class == S::String).then_some(c));
+                    state.start = Some(here.char);
+                    if stop.comment == Some(CommentStop::CommentOrString) {
+                        return pos;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The error signaled by `scan-lists` when the parentheses don't balance.
+/// The positions are 0-based.
+struct ScanError {
+    message: &'static str,
+    beg: usize,
+    end: usize,
+}
+
+impl ScanError {
+    fn signal(self, env: &mut Rt<Env>, cx: &Context) -> anyhow::Error {
+        let data = list![cx.add(self.message), self.beg as i64 + 1, self.end as i64 + 1; cx];
+        EvalError::signal(sym::SCAN_ERROR.into(), data, env).into()
+    }
+}
+
+/// Where the parser is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Inside {
+    Code,
+    /// In a string ended by the given character, or by a string fence if
+    /// it's `None`.
+    String(Option<char>),
+    /// In a comment of the given style, or a generic comment if the style is
+    /// `None`. `nesting` is the depth of nestable comments.
+    Comment {
+        style: Option<u8>,
+        nesting: Option<i64>,
+    },
+}
+
+/// The sexps at one level of parentheses.
+#[derive(Debug, Clone, Copy, Default)]
+struct Level {
+    /// The start of the last sexp that was started.
+    last: Option<usize>,
+    /// The start of the last complete sexp.
+    prev: Option<usize>,
+}
+
+/// The state of `parse-partial-sexp`. Positions are 0-based.
+#[derive(Debug, Clone)]
+struct ParseState {
+    depth: i64,
+    min_depth: i64,
+    inside: Inside,
+    /// Whether the last character was an escape.
+    quoted: bool,
+    /// The start of the current comment or string.
+    start: Option<usize>,
+    /// The positions of the open parentheses, outermost first.
+    opens: Vec<usize>,
+    levels: Vec<Level>,
+}
+
+impl ParseState {
+    fn new() -> Self {
+        Self {
+            depth: 0,
+            min_depth: 0,
+            inside: Inside::Code,
+            quoted: false,
+            start: None,
+            opens: Vec::new(),
+            levels: vec![Level::default()],
+        }
+    }
+
+    fn level(&mut self) -> &mut Level {
+        self.levels.last_mut().unwrap()
+    }
+
+    /// Restore the state from the list returned by an earlier call to
+    /// `parse-partial-sexp`.
+    fn from_list(list: Object) -> Result<Self> {
+        let mut state = Self::new();
+        let list: List = list.try_into()?;
+        let elems: Vec<Object> = list.elements().collect::<Result<_, _>>()?;
+        let elem = |i: usize| elems.get(i).copied().unwrap_or(NIL);
+        let int = |obj: Object| match obj.untag() {
+            ObjectType::Int(x) => Some(x),
+            _ => None,
+        };
+        state.depth = int(elem(0)).unwrap_or(0);
+        state.min_depth = state.depth;
+        let comment = elem(4);
+        let style = match elem(7).untag() {
+            ObjectType::Int(x) => Some(x as u8),
+            ObjectType::NIL => Some(0),
+            _ => None,
+        };
+        state.inside = match elem(3).untag() {
+            ObjectType::NIL if comment.is_nil() => Inside::Code,
+            ObjectType::NIL => Inside::Comment { style, nesting: int(comment) },
+            ObjectType::Int(c) => Inside::String(int_to_char(c).ok()),
+            _ => Inside::String(None),
+        };
+        state.quoted = !elem(5).is_nil();
+        state.start = usize::try_from(elem(8)).ok().map(|x| x.saturating_sub(1));
+        if let Ok(opens) = List::try_from(elem(9)) {
+            for open in opens.elements() {
+                state.opens.push(usize::try_from(open?)?.saturating_sub(1));
+                state.levels.push(Level::default());
+            }
+        }
+        Ok(state)
+    }
+
+    fn into_list<'ob>(self, scanner: &Scanner, end: Pos, cx: &'ob Context) -> Object<'ob> {
+        let pos = |x: Option<usize>| x.map_or(NIL, |x| cx.add(x as i64 + 1));
+        let string = match self.inside {
+            Inside::String(Some(c)) => cx.add(c),
+            Inside::String(None) => TRUE,
+            _ => NIL,
+        };
+        let (comment, style) = match self.inside {
+            Inside::Comment { style, nesting } => {
+                let comment = nesting.map_or(TRUE, |x| cx.add(x));
+                let style = match style {
+                    None => sym::SYNTAX_TABLE.into(),
+                    Some(0) => NIL,
+                    Some(x) => cx.add(x as i64),
+                };
+                (comment, style)
+            }
+            _ => (NIL, NIL),
+        };
+        let opens: Vec<Object> = self.opens.iter().map(|x| cx.add(*x as i64 + 1)).collect();
+        // The syntax of the last character if it could start a two
+        // character comment delimiter
+        let prev_syntax = match scanner.before(end) {
+            Some((_, syntax))
+                if self.inside == Inside::Code
+                    && (syntax.has(COMSTART_FIRST) || syntax.has(COMEND_FIRST)) =>
+            {
+                cx.add(syntax.class as i64 | syntax.flags)
+            }
+            _ => NIL,
+        };
+        let level = self.levels.last().copied().unwrap_or_default();
+        let state = [
+            cx.add(self.depth),
+            pos(self.opens.last().copied()),
+            pos(level.prev),
+            string,
+            comment,
+            cx.add(self.quoted),
+            cx.add(self.min_depth),
+            style,
+            pos(self.start),
+            slice_into_list(&opens, None, cx),
+            prev_syntax,
+        ];
+        slice_into_list(&state, None, cx)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommentStop {
+    /// Stop after the start of a comment.
+    Comment,
+    /// Stop after the start or end of a comment or string.
+    CommentOrString,
+}
+
+/// When `parse-partial-sexp` should stop before reaching the end.
+#[derive(Debug, Default)]
+struct StopAt {
+    depth: Option<i64>,
+    before_sexp: bool,
+    comment: Option<CommentStop>,
+}
+
+/// Run `f` with a scanner over the accessible portion of the current buffer,
+/// and the 0-based position of point.
+fn with_scanner<T>(env: &mut Rt<Env>, cx: &Context, f: impl FnOnce(&Scanner, Pos) -> T) -> T {
+    let table = current_table(env, cx);
+    let ignore_comments = var_is_set(sym::PARSE_SEXP_IGNORE_COMMENTS, env, cx);
+    let lookup_props = var_is_set(sym::PARSE_SEXP_LOOKUP_PROPERTIES, env, cx);
+    let data = &mut **env.current_buffer.get_mut();
+    let (begv, zv) = (data.begv(), data.zv());
+    let point = data.text.cursor().chars();
+    let text = BufferText::new(&mut data.text, begv, zv);
+    let props = lookup_props.then_some(&data.textprops);
+    let end = Pos { byte: text.hay.len(), char: zv };
+    let scanner = Scanner { text, syntax: SyntaxTable { table, props }, end, ignore_comments };
+    let point = scanner.pos(point);
+    f(&scanner, point)
+}
+
+fn set_point(pos: usize, env: &mut Rt<Env>) {
+    env.current_buffer.get_mut().text.set_cursor(pos);
+}
+
+/// Return t if OBJECT is a syntax table.
+/// Currently, any char-table counts as a syntax table.
+#[defun]
+fn syntax_table_p(object: Object) -> bool {
+    matches!(object.untag(), ObjectType::CharTable(_))
+}
+
+/// Return the current syntax table.
+/// This is the one specified by the current buffer.
+#[defun]
+fn syntax_table<'ob>(env: &mut Rt<Env>, cx: &'ob Context) -> &'ob CharTable {
+    current_table(env, cx)
+}
+
+/// Return the standard syntax table.
+/// This is the one used for new buffers.
+#[defun]
+fn standard_syntax_table<'ob>(env: &mut Rt<Env>, cx: &'ob Context) -> &'ob CharTable {
+    standard_table(env, cx)
+}
+
+/// Select a new syntax table for the current buffer.
+/// One argument, a syntax table.
+#[defun]
+fn set_syntax_table<'ob>(table: &'ob CharTable, env: &mut Rt<Env>) -> &'ob CharTable {
+    let buffer = env.current_buffer.buf_ref;
+    env.current_buffer.track(buffer);
+    env.current_buffer.get_mut().set_syntax_table(table.tag().into());
+    table
+}
+
+/// Return a new syntax table.
+/// Create a syntax table which inherits from OLDTABLE (if non-nil) or
+/// from `standard-syntax-table' otherwise.
+#[defun]
+fn make_syntax_table<'ob>(
+    oldtable: Option<&'ob CharTable>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> &'ob CharTable {
+    let parent = oldtable.unwrap_or_else(|| standard_table(env, cx));
+    let table = CharTableInner::new(None).into_obj(cx).untag();
+    table.set_parent(Some(parent));
+    table
+}
+
+/// Convert a syntax descriptor STRING into a raw syntax descriptor.
+/// STRING should be a string of the form allowed as argument of
+/// `modify-syntax-entry'.  The return value is a raw syntax descriptor: a
+/// cons cell (CODE . MATCHING-CHAR) which can be used, for example, as
+/// the value of a `syntax-table' text property.
+#[defun]
+fn string_to_syntax<'ob>(string: &str, cx: &'ob Context) -> Result<Object<'ob>> {
+    Ok(Syntax::parse(string)?.map_or(NIL, |syntax| syntax.to_object(cx)))
+}
+
+/// Set syntax for character CHAR according to string NEWENTRY.
+/// The syntax is changed only for table SYNTAX-TABLE, which defaults to
+/// the current buffer's syntax table.
+/// CHAR may be a cons (MIN . MAX), in which case, syntaxes of all characters
+/// in the range MIN to MAX are changed.
+#[defun]
+fn modify_syntax_entry<'ob>(
+    character: Object,
+    newentry: &str,
+    syntax_table: Option<&'ob CharTable>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<()> {
+    let table = match syntax_table {
+        Some(table) => table,
+        None => current_table(env, cx),
+    };
+    let (min, max): (char, char) = match character.untag() {
+        ObjectType::Cons(range) => (range.car().try_into()?, range.cdr().try_into()?),
+        _ => {
+            let c = character.try_into()?;
+            (c, c)
+        }
+    };
+    let entry = Syntax::parse(newentry)?.map_or(NIL, |syntax| syntax.to_object(cx));
+    for c in min..=max {
+        table.set(c as usize, entry);
+    }
+    Ok(())
+}
+
+/// Return the syntax code of CHARACTER, described by a character.
+/// For example, if CHARACTER is a word constituent, the
+/// character `w' (119) is returned.
+/// The characters that correspond to various syntax codes
+/// are listed in the documentation of `modify-syntax-entry'.
+#[defun]
+pub(crate) fn char_syntax(character: char, env: &mut Rt<Env>, cx: &Context) -> char {
+    SyntaxTable::new(current_table(env, cx)).class(character).designator()
+}
+
+/// Return the character that represents the syntax class SYNTAX.
+#[defun]
+fn syntax_class_to_char(syntax: usize) -> Result<char> {
+    let class = SyntaxClass::from_code(syntax).ok_or_else(|| anyhow!("Invalid syntax class"))?;
+    Ok(class.designator())
+}
+
+/// Return the matching parenthesis of CHARACTER, or nil if none.
+#[defun]
+fn matching_paren(character: char, env: &mut Rt<Env>, cx: &Context) -> Option<char> {
+    let syntax = SyntaxTable::get(current_table(env, cx), character);
+    match syntax.class {
+        SyntaxClass::Open | SyntaxClass::Close => syntax.matching,
+        _ => None,
+    }
+}
+
+/// Move point forward ARG words (backward if ARG is negative).
+/// If ARG is omitted or nil, move point forward one word.
+/// Normally returns t.
+/// If an edge of the buffer is reached, point is left there and the
+/// function returns nil.
+#[defun]
+pub(crate) fn forward_word(arg: Option<i64>, env: &mut Rt<Env>, cx: &Context) -> bool {
+    let (pos, found) =
+        with_scanner(env, cx, |scan, point| scan.scan_words(point, arg.unwrap_or(1)));
+    set_point(pos.char, env);
+    found
+}
+
+/// Move point over characters whose syntax is in SYNTAX, forward or
+/// backward. Returns the distance traveled.
+fn skip_syntax(
+    syntax: &str,
+    lim: Option<Position>,
+    forward: bool,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<i64> {
+    let (negated, syntax) = match syntax.strip_prefix('^') {
+        Some(rest) => (true, rest),
+        None => (false, syntax),
+    };
+    let classes = syntax
+        .chars()
+        .map(|c| {
+            SyntaxClass::from_designator(c)
+                .ok_or_else(|| anyhow!("Invalid syntax description letter: {c}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let skip = |x: Option<(char, Syntax)>| {
+        x.is_some_and(|(_, syntax)| classes.contains(&syntax.class) != negated)
+    };
+    let (start, pos) = with_scanner(env, cx, |scan, point| {
+        let lim = scan.pos(
+            lim.map_or(if forward { usize::MAX } else { 0 }, |Position(x)| x.saturating_sub(1)),
+        );
+        let mut pos = point;
+        if forward {
+            while pos.char < lim.char && skip(scan.after(pos)) {
+                pos = scan.next(pos);
+            }
+        } else {
+            while pos.char > lim.char && skip(scan.before(pos)) {
+                pos = scan.prev(pos);
+            }
+        }
+        (point, pos)
+    });
+    set_point(pos.char, env);
+    Ok(pos.char as i64 - start.char as i64)
+}
+
+/// Move point forward across chars in specified syntax classes.
+/// SYNTAX is a string of syntax code characters.
+/// Stop before a char whose syntax is not in SYNTAX, or at position LIM.
+/// If SYNTAX starts with ^, skip characters whose syntax is NOT in SYNTAX.
+/// This function returns the distance traveled, either zero or positive.
+#[defun]
+fn skip_syntax_forward(
+    syntax: &str,
+    lim: Option<Position>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<i64> {
+    skip_syntax(syntax, lim, true, env, cx)
+}
+
+/// Move point backward across chars in specified syntax classes.
+/// SYNTAX is a string of syntax code characters.
+/// Stop on reaching a char whose syntax is not in SYNTAX, or at position LIM.
+/// If SYNTAX starts with ^, skip characters whose syntax is NOT in SYNTAX.
+/// This function returns either zero or a negative number, and the absolute value
+/// of this is the distance traveled.
+#[defun]
+fn skip_syntax_backward(
+    syntax: &str,
+    lim: Option<Position>,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<i64> {
+    skip_syntax(syntax, lim, false, env, cx)
+}
+
+/// Move point over the characters described by STRING, forward or backward.
+/// Returns the distance traveled.
+fn skip_chars(string: &str, lim: Option<Position>, forward: bool, env: &mut Rt<Env>) -> i64 {
+    let (negated, string) = match string.strip_prefix('^') {
+        Some(rest) => (true, rest),
+        None => (false, string),
+    };
+    // TODO: support character classes like `[:alpha:]'
+    let chars: Vec<char> = string.chars().collect();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let mut c = chars[i];
+        i += 1;
+        if c == '\\' {
+            let Some(&quoted) = chars.get(i) else { break };
+            c = quoted;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'-') && i + 1 < chars.len() {
+            let mut end = chars[i + 1];
+            i += 2;
+            if end == '\\' && i < chars.len() {
+                end = chars[i];
+                i += 1;
+            }
+            ranges.push(c..=end);
+        } else {
+            ranges.push(c..=c);
+        }
+    }
+    let skip = |c: Option<char>| {
+        c.is_some_and(|c| ranges.iter().any(|range| range.contains(&c)) != negated)
+    };
+    let buf = env.current_buffer.get_mut();
+    let (begv, zv) = (buf.begv(), buf.zv());
+    let start = buf.text.cursor().chars();
+    let lim = lim
+        .map_or(if forward { zv } else { begv }, |Position(x)| x.saturating_sub(1))
+        .clamp(begv, zv);
+    let mut pos = start;
+    if forward {
+        while pos < lim && skip(buf.text.char_at(pos)) {
+            pos += 1;
+        }
+    } else {
+        while pos > lim && skip(buf.text.char_at(pos - 1)) {
+            pos -= 1;
+        }
+    }
+    buf.text.set_cursor(pos);
+    pos as i64 - start as i64
+}
+
+/// Move point forward, stopping before a char not in STRING, or at pos LIM.
+/// STRING is like the inside of a `[...]' in a regular expression
+/// except that `]' is never special and `\\' quotes `^', `-' or `\\'
+/// (but not at the end of a range; quoting is never needed there).
+/// Thus, with arg "a-zA-Z", this skips letters stopping before first nonletter.
+/// With arg "^a-zA-Z", skips nonletters stopping before first letter.
+///
+/// Returns the distance traveled, either zero or positive.
+#[defun]
+pub(crate) fn skip_chars_forward(string: &str, lim: Option<Position>, env: &mut Rt<Env>) -> i64 {
+    skip_chars(string, lim, true, env)
+}
+
+/// Move point backward, stopping after a char not in STRING, or at pos LIM.
+/// See `skip-chars-forward' for details.
+/// Returns the distance traveled, either zero or negative.
+#[defun]
+pub(crate) fn skip_chars_backward(string: &str, lim: Option<Position>, env: &mut Rt<Env>) -> i64 {
+    skip_chars(string, lim, false, env)
+}
+
+/// Move point backward over any number of chars with prefix syntax.
+/// This includes chars with expression prefix syntax class (\\=') and those with
+/// the prefix syntax flag (p).
+#[defun]
+fn backward_prefix_chars(env: &mut Rt<Env>, cx: &Context) {
+    let pos = with_scanner(env, cx, |scan, point| {
+        let mut pos = point;
+        while let Some((_, syntax)) = scan.before(pos)
+            && (syntax.class == SyntaxClass::Quote || syntax.has(PREFIX))
+            && !scan.char_quoted(scan.prev(pos), scan.begin())
+        {
+            pos = scan.prev(pos);
+        }
+        pos
+    });
+    set_point(pos.char, env);
+}
+
+fn scan<'ob>(
+    from: usize,
+    count: i64,
+    depth: i64,
+    sexp: bool,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let found = with_scanner(env, cx, |scan, _| {
+        scan.scan_lists(scan.pos(from.saturating_sub(1)), count, depth, sexp)
+    });
+    match found {
+        Ok(pos) => Ok(pos.map_or(NIL, |pos| cx.add(pos.char as i64 + 1))),
+        Err(err) => Err(err.signal(env, cx)),
+    }
+}
+
+/// Scan from character number FROM by COUNT lists.
+/// Scan forward if COUNT is positive, backward if COUNT is negative.
+/// Return the character number of the position thus found.
+///
+/// A \"list\", in this context, refers to a balanced parenthetical
+/// grouping, as determined by the syntax table.
+///
+/// If DEPTH is nonzero, treat that as the paren depth.  Scanning stops
+/// when the depth becomes zero.
+///
+/// If the beginning or end of (the accessible part of) the buffer is reached
+/// and the depth is not zero, an error is signaled.
+/// If the depth is zero, nil is returned.
+///
+/// If `parse-sexp-ignore-comments' is non-nil, comments are treated as
+/// whitespace.
+#[defun]
+fn scan_lists<'ob>(
+    Position(from): Position,
+    count: i64,
+    depth: i64,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    scan(from, count, depth, false, env, cx)
+}
+
+/// Scan from character number FROM by COUNT balanced expressions.
+/// If COUNT is negative, scan backwards.
+/// Returns the character number of the position thus found.
+///
+/// If the beginning or end of (the accessible part of) the buffer is reached
+/// in the middle of a parenthetical grouping, an error is signaled.
+/// If the beginning or end is reached between groupings
+/// but before count is used up, nil is returned.
+#[defun]
+fn scan_sexps<'ob>(
+    Position(from): Position,
+    count: i64,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    scan(from, count, 0, true, env, cx)
+}
+
+/// Parse Lisp syntax starting at FROM until TO; return status of parse at TO.
+/// Parsing stops at TO or when certain criteria are met;
+///  point is set to where parsing stops.
+///
+/// If OLDSTATE is omitted or nil, parsing assumes that FROM is the
+///  beginning of a function.  If not, OLDSTATE should be the state at
+///  FROM.
+///
+/// Value is a list of elements describing final state of parsing:
+///  0. depth in parens.
+///  1. character address of start of innermost containing list; nil if none.
+///  2. character address of start of last complete sexp terminated.
+///  3. non-nil if inside a string.
+///     (it is the character that will terminate the string,
+///      or t if the string should be terminated by a generic string delimiter.)
+///  4. nil if outside a comment, t if inside a non-nestable comment,
+///     else an integer (the current comment nesting depth).
+///  5. t if following a quote character.
+///  6. the minimum paren-depth encountered during this scan.
+///  7. style of comment, if any.
+///  8. character address of start of comment or string; nil if not in one.
+///  9. List of positions of currently open parens, outermost first.
+/// 10. When the last position scanned holds the first character of a
+///     (potential) two character construct, the syntax of that position,
+///     otherwise nil.
+///
+/// If third arg TARGETDEPTH is non-nil, parsing stops if the depth
+/// in parentheses becomes equal to TARGETDEPTH.
+/// Fourth arg STOPBEFORE non-nil means stop when we come to
+///  any character that starts a sexp.
+/// Sixth arg COMMENTSTOP non-nil means stop after the start of a comment.
+///  If it is the symbol `syntax-table', stop after the start of a comment or a
+///  string, or after end of a comment or a string.
+#[defun]
+#[expect(clippy::too_many_arguments)]
+fn parse_partial_sexp<'ob>(
+    Position(from): Position,
+    Position(to): Position,
+    targetdepth: Option<i64>,
+    stopbefore: Option<Object>,
+    oldstate: Option<Object>,
+    commentstop: Option<Object>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    if to < from {
+        bail!("End position is smaller than start position");
+    }
+    let stop = StopAt {
+        depth: targetdepth,
+        before_sexp: stopbefore.is_some_and(|x| !x.is_nil()),
+        comment: match commentstop {
+            Some(x) if x == sym::SYNTAX_TABLE => Some(CommentStop::CommentOrString),
+            Some(x) if !x.is_nil() => Some(CommentStop::Comment),
+            _ => None,
+        },
+    };
+    let (state, pos) = with_scanner(env, cx, |scan, _| -> Result<_> {
+        let from = scan.pos(from.saturating_sub(1));
+        let to = scan.pos(to.saturating_sub(1));
+        let mut state = match oldstate {
+            Some(old) if !old.is_nil() => ParseState::from_list(old)?,
+            _ => ParseState::new(),
+        };
+        let pos = scan.parse(from, to, &mut state, &stop);
+        Ok((state.into_list(scan, pos, cx), pos))
+    })?;
+    set_point(pos.char, env);
+    Ok(state)
+}
+
+defsym!(SCAN_ERROR);
+defvar!(PARSE_SEXP_IGNORE_COMMENTS);
+defvar!(PARSE_SEXP_LOOKUP_PROPERTIES);
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_syntax_table() {
+        assert_lisp(r#"(list (char-syntax ?a) (char-syntax ?\() (char-syntax ? ))"#, "(119 40 32)");
+        assert_lisp(r#"(string-to-syntax ". 23b")"#, "(2490369)");
+        assert_lisp(r#"(string-to-syntax "()")"#, "(4 . 41)");
+        assert_lisp(r#"(string-to-syntax "@")"#, "nil");
+        assert_lisp(
+            r#"(let ((table (make-syntax-table)))
+                 (modify-syntax-entry ?a "." table)
+                 (set-syntax-table table)
+                 (list (char-syntax ?a) (char-syntax ?b) (eq (syntax-table) table)))"#,
+            "(46 119 t)",
+        );
+        assert_lisp(r#"(matching-paren ?\[)"#, "93");
+    }
+
+    #[test]
+    fn test_motion() {
+        assert_lisp(
+            r#"(progn (insert "foo bar-baz") (goto-char 1) (list (forward-word 2) (point) (forward-word 5) (point)))"#,
+            "(t 8 nil 12)",
+        );
+        assert_lisp(
+            r#"(progn (insert "  foo") (goto-char 1) (list (skip-syntax-forward " ") (skip-syntax-forward "^w" 2) (point)))"#,
+            "(2 0 3)",
+        );
+        assert_lisp(
+            r#"(progn (insert "ab cd") (list (skip-syntax-backward "w_") (point)))"#,
+            "(-2 4)",
+        );
+        assert_lisp(
+            r#"(progn (insert "abc123 x") (goto-char 1) (list (skip-chars-forward "a-z") (skip-chars-forward "^ " 6) (point)))"#,
+            "(3 2 6)",
+        );
+        assert_lisp(
+            r#"(progn (insert "x-^-y") (list (skip-chars-backward "y\\^\\-") (point)))"#,
+            "(-4 2)",
+        );
+    }
+
+    #[test]
+    fn test_scan_lists() {
+        assert_lisp(r#"(progn (insert "(a (b) \"c)\") d") (scan-lists 1 1 0))"#, "13");
+        assert_lisp(r#"(progn (insert "(a (b) c) d") (scan-lists 10 -1 0))"#, "1");
+        assert_lisp(r#"(progn (insert "(a (b) c)") (scan-lists 5 1 1))"#, "7");
+        assert_lisp(
+            r#"(progn (insert "foo-bar (x)") (list (scan-sexps 1 1) (scan-sexps 12 -1)))"#,
+            "(8 9)",
+        );
+        assert_lisp(r#"(progn (insert "a") (scan-sexps 2 1))"#, "nil");
+        assert_lisp(
+            r#"(progn (insert "(a b") (condition-case nil (scan-lists 1 1 0) (error 'unbalanced)))"#,
+            "unbalanced",
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_lisp(
+            r#"(let ((table (make-syntax-table)) (parse-sexp-ignore-comments t))
+                 (modify-syntax-entry ?\; "<" table)
+                 (modify-syntax-entry ?\n ">" table)
+                 (set-syntax-table table)
+                 (insert "(a ;)\n b)")
+                 (list (scan-lists 1 1 0) (scan-sexps 10 -1)))"#,
+            "(10 1)",
+        );
+        assert_lisp(
+            r#"(let ((table (make-syntax-table)) (parse-sexp-ignore-comments t))
+                 (modify-syntax-entry ?/ ". 124b" table)
+                 (modify-syntax-entry ?* ". 23" table)
+                 (modify-syntax-entry ?\n "> b" table)
+                 (set-syntax-table table)
+                 (insert "a /* ) */ (b) // )\n c")
+                 (list (scan-sexps 3 1) (scan-sexps 20 -1)))"#,
+            "(14 11)",
+        );
+    }
+
+    #[test]
+    fn test_parse_partial_sexp() {
+        assert_lisp(
+            r#"(progn (insert "(a (b \"c") (list (parse-partial-sexp 1 9) (point)))"#,
+            r#"((2 4 5 34 nil nil 0 nil 7 (1 4) nil) 9)"#,
+        );
+        assert_lisp(
+            r#"(progn (insert "(a) (b)") (parse-partial-sexp 1 8))"#,
+            "(0 nil 5 nil nil nil 0 nil nil nil nil)",
+        );
+        assert_lisp(
+            r#"(progn (insert "(a (b))") (list (nth 0 (parse-partial-sexp 1 8 1)) (point)))"#,
+            "(1 2)",
+        );
+        assert_lisp(
+            r#"(let ((table (make-syntax-table)))
+                 (modify-syntax-entry ?\; "<" table)
+                 (modify-syntax-entry ?\n ">" table)
+                 (set-syntax-table table)
+                 (insert "(a ; b\nc")
+                 (let ((state (parse-partial-sexp 1 7)))
+                   (list (nth 4 state) (nth 8 state) (nth 4 (parse-partial-sexp 7 9 nil nil state)))))"#,
+            "(t 4 nil)",
+        );
+    }
+
+    #[test]
+    fn test_syntax_table_property() {
+        assert_lisp(
+            r#"(let ((parse-sexp-lookup-properties t))
+                 (insert "a(b")
+                 (put-text-property 2 3 'syntax-table (string-to-syntax "w") nil)
+                 (list (scan-sexps 1 1) (progn (goto-char 1) (forward-word) (point))))"#,
+            "(4 4)",
+        );
+    }
+}
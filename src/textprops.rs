@@ -4,14 +4,17 @@ use crate::{
         env::Env,
         error::{Type, TypeError},
         gc::{Context, Rt, Slot},
-        object::{Gc, ListType, NIL, Object, ObjectType, WithLifetime},
+        object::{Gc, LispOverlay, ListType, NIL, Object, ObjectType, Position, WithLifetime},
     },
     fns::eq,
-    intervals::textget,
+    intervals::{IntervalTree, textget},
+    overlay::overlay_property,
+    undo,
 };
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Result, bail};
 use rune_core::macros::list;
 use rune_macros::defun;
+use std::ops::Range;
 
 use crate::core::object::BufferData;
 
@@ -72,40 +75,50 @@ pub fn add_properties<'ob>(
     Ok(obj_i)
 }
 
-/// Helper function to safely modify buffer data.
+/// Helper function to safely modify the text properties of an object.
 ///
-/// Takes an object (buffer or nil) and environment, and applies the given function
-/// to the buffer's data. Handles both current buffer and other buffers safely.
+/// Takes an object (buffer, string or nil) and environment, and applies the
+/// given function to the object's property tree along with the range of valid
+/// positions in it. Buffers use 1-based positions and strings 0-based indices.
+/// Handles both current buffer and other buffers safely.
 ///
 /// # Arguments
-/// * `object` - The buffer object to modify (or nil for current buffer)
+/// * `object` - The buffer or string to modify (or nil for current buffer)
 /// * `env` - The environment containing buffer state
-/// * `func` - Function to apply to the buffer's data
+/// * `func` - Function to apply to the property tree
 ///
 /// # Returns
 /// Result containing the return value from func or an error
-fn modify_buffer_data<'ob, T>(
+fn modify_text_properties<'ob, T>(
     object: Object<'ob>,
-    env: &'ob mut Rt<Env>,
-    func: impl FnOnce(&mut BufferData) -> Result<T>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+    func: impl FnOnce(&mut IntervalTree<'ob>, Range<usize>) -> Result<T>,
 ) -> Result<T> {
-    if object.is_nil() {
-        let data = env.current_buffer.get_mut();
-        func(data)
-    } else {
-        let current_buf = env.current_buffer.buf_ref;
-        if let Some(b) = object.buffer() {
-            if b == current_buf {
-                let data = env.current_buffer.get_mut();
-                func(data)
-            } else {
-                let mut open_buf = b.lock()?;
-                func(open_buf.get_mut())
-            }
-        } else {
-            Err(anyhow!(TypeError::new(Type::BufferOrString, object.untag())))
+    let mut open_buf;
+    let data: &mut BufferData = match object.untag() {
+        // The properties live in our heap, so the buffer needs to be traced with it
+        ObjectType::NIL => {
+            let buffer = env.current_buffer.buf_ref;
+            env.current_buffer.track(buffer);
+            env.current_buffer.get_mut()
         }
-    }
+        ObjectType::Buffer(b) if b == env.current_buffer.buf_ref => {
+            env.current_buffer.track(b);
+            env.current_buffer.get_mut()
+        }
+        ObjectType::Buffer(b) => {
+            env.current_buffer.track(b);
+            open_buf = b.lock()?;
+            open_buf.get_mut()
+        }
+        ObjectType::String(string) => {
+            return func(&mut *string.textprops_mut(cx)?, 0..string.len());
+        }
+        obj => bail!(TypeError::new(Type::BufferOrString, obj)),
+    };
+    let bounds = data.point_min()..data.point_max();
+    func(data.textprops_with_lifetime(), bounds)
 }
 
 /// Return the list of properties of the character at POSITION in OBJECT.
@@ -123,7 +136,7 @@ fn modify_buffer_data<'ob, T>(
 /// form, use the `describe-text-properties' command.
 #[defun]
 pub fn text_properties_at<'ob>(
-    position: usize,
+    Position(position): Position,
     object: Object<'ob>,
     env: &'ob mut Rt<Env>,
 ) -> Result<Object<'ob>> {
@@ -133,8 +146,10 @@ pub fn text_properties_at<'ob>(
         let obj = object.untag();
         match obj {
             ObjectType::Buffer(buf) => &buf.lock().unwrap().textprops,
-            ObjectType::String(_str) => {
-                todo!()
+            ObjectType::String(string) => {
+                let Some(tree) = string.textprops() else { return Ok(NIL) };
+                let props = tree.find(position).map(|a| *a.val).unwrap_or(NIL);
+                return Ok(unsafe { props.with_lifetime() });
             }
             _ => {
                 bail!(TypeError::new(Type::BufferOrString, obj))
@@ -155,12 +170,12 @@ pub fn text_properties_at<'ob>(
 /// may be non-nil.
 #[defun]
 pub fn get_text_property<'ob>(
-    position: usize,
+    Position(position): Position,
     prop: Object<'ob>,
     object: Object<'ob>,
     env: &'ob mut Rt<Env>,
 ) -> Result<Object<'ob>> {
-    let props = text_properties_at(position, object, env)?;
+    let props = text_properties_at(Position(position), object, env)?;
     // TODO see lookup_char_property, should also lookup
     // 1. category
     // 2. char_property_alias_alist
@@ -168,26 +183,65 @@ pub fn get_text_property<'ob>(
     textget(props, prop)
 }
 
+/// The value of PROP in the overlay with the highest precedence at POSITION,
+/// together with that overlay. Strings don't have overlays.
+fn overlay_char_property<'ob>(
+    position: usize,
+    prop: Object,
+    object: Object,
+    env: &Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Option<(Object<'ob>, &'ob LispOverlay)>> {
+    let pos = position.saturating_sub(1);
+    match object.untag() {
+        ObjectType::NIL => Ok(overlay_property(env.current_buffer.get(), pos, prop, cx)),
+        ObjectType::Buffer(buffer) => {
+            env.with_buffer(buffer, |buffer| overlay_property(buffer, pos, prop, cx))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Like `get-char-property', but with extra overlay information.
+/// The value is a cons cell.  Its car is the return value of `get-char-property'
+/// with the same arguments--that is, the value of POSITION's property
+/// PROP in OBJECT.  Its cdr is the overlay in which the property was
+/// found, or nil, if it was found as a text property or not found at all.
 #[defun]
-#[allow(unused)]
 pub fn get_char_property_and_overlay<'ob>(
-    position: usize,
+    Position(position): Position,
     prop: Object<'ob>,
     object: Object<'ob>,
     env: &'ob mut Rt<Env>,
+    cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    todo!()
+    if let Some((value, overlay)) = overlay_char_property(position, prop, object, env, cx)? {
+        return Ok(Cons::new(value, overlay, cx).into());
+    }
+    let value = get_text_property(Position(position), prop, object, env)?;
+    Ok(Cons::new(value, NIL, cx).into())
 }
 
+/// Return the value of POSITION's property PROP, in OBJECT.
+/// Both overlay properties and text properties are checked.
+/// OBJECT is optional and defaults to the current buffer.
+/// If POSITION is at the end of OBJECT, the value is nil.
+/// If OBJECT is a buffer, then overlay properties are considered as well as
+/// text properties.
+/// If OBJECT is a window, then that window's buffer is used, but window-specific
+/// overlays are considered only if they are associated with OBJECT.
 #[defun]
-#[allow(unused)]
 pub fn get_char_property<'ob>(
-    position: usize,
+    Position(position): Position,
     prop: Object<'ob>,
     object: Object<'ob>,
     env: &'ob mut Rt<Env>,
+    cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    todo!()
+    if let Some((value, _)) = overlay_char_property(position, prop, object, env, cx)? {
+        return Ok(value);
+    }
+    get_text_property(Position(position), prop, object, env)
 }
 
 // TODO also missing `next-char-property-change` and 3 other similar functions.
@@ -200,22 +254,59 @@ pub fn get_char_property<'ob>(
 /// markers).  If OBJECT is a string, START and END are 0-based indices into it.
 #[defun]
 pub fn put_text_property<'ob>(
-    start: usize,
-    end: usize,
+    Position(start): Position,
+    Position(end): Position,
     property: Object<'ob>,
     value: Object<'ob>,
     object: Object<'ob>,
     env: &mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<()> {
+    let in_current = match object.untag() {
+        ObjectType::NIL => true,
+        ObjectType::Buffer(b) => *env.current_buffer == *b,
+        _ => false,
+    };
+    if in_current {
+        record_property_change(start, end, property, value, env, cx)?;
+    }
     let prop = list!(property, value; cx);
     let prop = Slot::new(prop);
-    modify_buffer_data(object, env, |data| {
-        let tree = &mut data.textprops_with_lifetime();
+    modify_text_properties(object, env, cx, |tree, _| {
         tree.insert(start, end, prop, cx);
         Ok(())
     })
 }
+/// Record the old values of `property` between `start` and `end` in the undo
+/// list of the current buffer, wherever they differ from `value`.
+fn record_property_change(
+    start: usize,
+    end: usize,
+    property: Object,
+    value: Object,
+    env: &mut Rt<Env>,
+    cx: &Context,
+) -> Result<()> {
+    let mut changes = Vec::new();
+    for (range, plist) in env.current_buffer.get().textprops.iter(start, end) {
+        let old = textget(cx.bind(plist), property)?;
+        if !eq(old, value) {
+            changes.push((range, old));
+        }
+    }
+    for (range, old) in changes {
+        undo::record_property_change(
+            &mut env.current_buffer,
+            range.start,
+            range.end,
+            property,
+            old,
+            cx,
+        );
+    }
+    Ok(())
+}
+
 /// Return the position of next property change.
 /// Scans characters forward from POSITION in OBJECT till it finds
 /// a change in some text property, then returns the position of the change.
@@ -230,16 +321,15 @@ pub fn put_text_property<'ob>(
 /// past position LIMIT; return LIMIT if nothing is found before LIMIT.
 #[defun]
 pub fn next_property_change<'ob>(
-    position: usize,
+    Position(position): Position,
     object: Object<'ob>,
-    limit: Option<usize>,
+    limit: Option<Position>,
     env: &'ob mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    modify_buffer_data(object, env, |data| -> Result<Object<'ob>> {
-        let point_max = data.text.len_chars() + 1;
-        let end = limit.unwrap_or(point_max);
-        let tree = data.textprops_with_lifetime();
+    let limit = limit.map(|x| x.0);
+    modify_text_properties(object, env, cx, |tree, bounds| -> Result<Object<'ob>> {
+        let end = limit.unwrap_or(bounds.end);
         // NOTE this can be optimized
         tree.clean();
         let prop = tree.tree.find_intersect_min(position..end);
@@ -283,17 +373,16 @@ pub fn next_property_change<'ob>(
 /// past position LIMIT; return LIMIT if nothing is found before LIMIT.
 #[defun]
 pub fn next_single_property_change<'ob>(
-    position: usize,
+    Position(position): Position,
     prop: Object<'ob>,
     object: Object<'ob>,
-    limit: Option<usize>,
+    limit: Option<Position>,
     env: &'ob mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    modify_buffer_data(object, env, |data| -> Result<Object<'ob>> {
-        let point_max = data.text.len_chars() + 1;
-        let end = limit.unwrap_or(point_max);
-        let tree = data.textprops_with_lifetime();
+    let limit = limit.map(|x| x.0);
+    modify_text_properties(object, env, cx, |tree, bounds| -> Result<Object<'ob>> {
+        let end = limit.unwrap_or(bounds.end);
         // NOTE this can be optimized
         tree.clean();
         let iter = tree.iter(position, end);
@@ -330,17 +419,16 @@ pub fn next_single_property_change<'ob>(
 /// back past position LIMIT; return LIMIT if nothing is found until LIMIT.
 #[defun]
 pub fn previous_property_change<'ob>(
-    position: usize,
+    Position(position): Position,
     object: Object<'ob>,
-    limit: Option<usize>,
+    limit: Option<Position>,
     env: &'ob mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    modify_buffer_data(object, env, |data| -> Result<Object<'ob>> {
-        let point_min = 1;
-        let start = limit.unwrap_or(point_min);
+    let limit = limit.map(|x| x.0);
+    modify_text_properties(object, env, cx, |tree, bounds| -> Result<Object<'ob>> {
+        let start = limit.unwrap_or(bounds.start);
         let end = position;
-        let tree = data.textprops_with_lifetime();
         // NOTE this can be optimized
         tree.clean();
         let prop = tree.tree.find_intersect_max(start..end);
@@ -388,17 +476,16 @@ pub fn previous_property_change<'ob>(
 /// past position LIMIT; return LIMIT if nothing is found before LIMIT.
 #[defun]
 pub fn previous_single_property_change<'ob>(
-    position: usize,
+    Position(position): Position,
     prop: Object<'ob>,
     object: Object<'ob>,
-    limit: Option<usize>,
+    limit: Option<Position>,
     env: &'ob mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    modify_buffer_data(object, env, |data| -> Result<Object<'ob>> {
-        let point_min = 1;
-        let start = limit.unwrap_or(point_min);
-        let tree = data.textprops_with_lifetime();
+    let limit = limit.map(|x| x.0);
+    modify_text_properties(object, env, cx, |tree, bounds| -> Result<Object<'ob>> {
+        let start = limit.unwrap_or(bounds.start);
         // NOTE this can be optimized
         tree.clean();
         let iter = tree.iter_reverse(start, position);
@@ -430,14 +517,14 @@ pub fn previous_single_property_change<'ob>(
 /// the designated part of OBJECT.
 #[defun]
 pub fn set_text_properties<'ob>(
-    start: usize,
-    end: usize,
+    Position(start): Position,
+    Position(end): Position,
     properties: Object<'ob>,
     object: Object<'ob>,
     env: &mut Rt<Env>,
+    cx: &'ob Context,
 ) -> Result<()> {
-    modify_buffer_data(object, env, |data| -> Result<()> {
-        let tree = data.textprops_with_lifetime();
+    modify_text_properties(object, env, cx, |tree, _| -> Result<()> {
         tree.set_properties(start, end, properties);
         Ok(())
     })
@@ -455,15 +542,14 @@ pub fn set_text_properties<'ob>(
 /// Use `set-text-properties' if you want to remove all text properties.
 #[defun]
 pub fn remove_text_properties<'ob>(
-    start: usize,
-    end: usize,
+    Position(start): Position,
+    Position(end): Position,
     properties: Object<'ob>,
     object: Object<'ob>,
     env: &mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<()> {
-    modify_buffer_data(object, env, |data| -> Result<()> {
-        let tree = data.textprops_with_lifetime();
+    modify_text_properties(object, env, cx, |tree, _| -> Result<()> {
         tree.delete(start, end, list![properties; cx])
     })
 }
@@ -476,14 +562,14 @@ pub fn remove_text_properties<'ob>(
 /// Return t if any property was actually removed, nil otherwise.
 #[defun]
 pub fn remove_list_of_text_properties<'ob>(
-    start: usize,
-    end: usize,
+    Position(start): Position,
+    Position(end): Position,
     list_of_properties: Object<'ob>,
     object: Object<'ob>,
     env: &mut Rt<Env>,
+    cx: &'ob Context,
 ) -> Result<()> {
-    modify_buffer_data(object, env, |data| -> Result<()> {
-        let tree = data.textprops_with_lifetime();
+    modify_text_properties(object, env, cx, |tree, _| -> Result<()> {
         tree.delete(start, end, list_of_properties)
     })
 }
@@ -496,16 +582,15 @@ pub fn remove_list_of_text_properties<'ob>(
 /// markers).  If OBJECT is a string, START and END are 0-based indices into it.
 #[defun]
 pub fn text_properties_any<'ob>(
-    start: usize,
-    end: usize,
+    Position(start): Position,
+    Position(end): Position,
     property: Object<'ob>,
     value: Object<'ob>,
     object: Object<'ob>,
     env: &mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    modify_buffer_data(object, env, |data| -> Result<Object<'ob>> {
-        let tree = data.textprops_with_lifetime();
+    modify_text_properties(object, env, cx, |tree, _| -> Result<Object<'ob>> {
         let iter = tree.iter(start, end);
         for (interval, props) in iter {
             let val = textget(props, property)?;
@@ -525,16 +610,15 @@ pub fn text_properties_any<'ob>(
 /// markers).  If OBJECT is a string, START and END are 0-based indices into it.
 #[defun]
 pub fn text_properties_not_all<'ob>(
-    start: usize,
-    end: usize,
+    Position(start): Position,
+    Position(end): Position,
     property: Object<'ob>,
     value: Object<'ob>,
     object: Object<'ob>,
     env: &mut Rt<Env>,
     cx: &'ob Context,
 ) -> Result<Object<'ob>> {
-    modify_buffer_data(object, env, |data| -> Result<Object<'ob>> {
-        let tree = data.textprops_with_lifetime();
+    modify_text_properties(object, env, cx, |tree, _| -> Result<Object<'ob>> {
         let iter = tree.iter(start, end);
         for (interval, props) in iter {
             let val = textget(props, property)?;
@@ -598,32 +682,32 @@ mod tests {
         let a = cx.add(a);
 
         // Add properties at different ranges
-        put_text_property(0, 5, a, cx.add(1), buf, env, cx)?;
-        put_text_property(5, 10, a, cx.add(2), buf, env, cx)?;
-        put_text_property(15, 20, a, cx.add(3), buf, env, cx)?;
+        put_text_property(Position(0), Position(5), a, cx.add(1), buf, env, cx)?;
+        put_text_property(Position(5), Position(10), a, cx.add(2), buf, env, cx)?;
+        put_text_property(Position(15), Position(20), a, cx.add(3), buf, env, cx)?;
 
         // Test property changes
-        let change = next_property_change(0, buf, None, env, cx)?;
+        let change = next_property_change(Position(0), buf, None, env, cx)?;
         assert_eq!(change, cx.add(5)); // Change at end of first range
 
-        let change = next_property_change(5, buf, None, env, cx)?;
+        let change = next_property_change(Position(5), buf, None, env, cx)?;
         assert_eq!(change, cx.add(10)); // Change at end of second range
 
-        let change = next_property_change(10, buf, None, env, cx)?;
+        let change = next_property_change(Position(10), buf, None, env, cx)?;
         assert_eq!(change, cx.add(15)); // Change at start of third range
 
-        let change = next_property_change(15, buf, None, env, cx)?;
+        let change = next_property_change(Position(15), buf, None, env, cx)?;
         assert_eq!(change, cx.add(20)); // Change at end of third range
 
         // Test with limit
-        let change = next_property_change(0, buf, Some(8), env, cx)?;
+        let change = next_property_change(Position(0), buf, Some(Position(8)), env, cx)?;
         assert_eq!(change, cx.add(5)); // Change within limit
 
-        let change = next_property_change(5, buf, Some(8), env, cx)?;
+        let change = next_property_change(Position(5), buf, Some(Position(8)), env, cx)?;
         assert_eq!(change, cx.add(8)); // Limit reached
 
         // Test no change case
-        let change = next_property_change(20, buf, None, env, cx)?;
+        let change = next_property_change(Position(20), buf, None, env, cx)?;
         assert!(change.is_nil()); // No changes after last property
 
         BUFFERS.lock().unwrap().clear();
@@ -652,40 +736,40 @@ mod tests {
         let b = cx.add(b);
 
         // Add properties at different ranges
-        put_text_property(1, 5, a, cx.add(1), buf, env, cx)?;
-        put_text_property(5, 8, a, cx.add(2), buf, env, cx)?;
-        put_text_property(10, 15, b, cx.add(4), buf, env, cx)?;
-        put_text_property(15, 20, a, cx.add(3), buf, env, cx)?;
+        put_text_property(Position(1), Position(5), a, cx.add(1), buf, env, cx)?;
+        put_text_property(Position(5), Position(8), a, cx.add(2), buf, env, cx)?;
+        put_text_property(Position(10), Position(15), b, cx.add(4), buf, env, cx)?;
+        put_text_property(Position(15), Position(20), a, cx.add(3), buf, env, cx)?;
 
         // Test property changes for :a
-        let change = next_single_property_change(1, a, buf, None, env, cx)?;
+        let change = next_single_property_change(Position(1), a, buf, None, env, cx)?;
         assert_eq!(change, cx.add(5)); // Change at end of first range
 
-        let change = next_single_property_change(5, a, buf, None, env, cx)?;
+        let change = next_single_property_change(Position(5), a, buf, None, env, cx)?;
         assert_eq!(change, cx.add(8)); // Change at end of second range
 
-        let change = next_single_property_change(10, a, buf, None, env, cx)?;
+        let change = next_single_property_change(Position(10), a, buf, None, env, cx)?;
         assert_eq!(change, cx.add(15)); // Change at start of third range
 
-        let change = next_single_property_change(15, a, buf, None, env, cx)?;
+        let change = next_single_property_change(Position(15), a, buf, None, env, cx)?;
         assert_eq!(change, cx.add(20)); // Change at end of third range
 
         // Test with limit
-        let change = next_single_property_change(1, a, buf, Some(8), env, cx)?;
+        let change = next_single_property_change(Position(1), a, buf, Some(Position(8)), env, cx)?;
         assert_eq!(change, cx.add(5)); // Change within limit
 
-        let change = next_single_property_change(5, a, buf, Some(8), env, cx)?;
+        let change = next_single_property_change(Position(5), a, buf, Some(Position(8)), env, cx)?;
         assert_eq!(change, cx.add(8)); // Limit reached
 
         // Test no change case
-        let change = next_single_property_change(20, a, buf, None, env, cx)?;
+        let change = next_single_property_change(Position(20), a, buf, None, env, cx)?;
         assert!(change.is_nil()); // No changes after last property
 
         // Test property changes for :b
-        let change = next_single_property_change(1, b, buf, None, env, cx)?;
+        let change = next_single_property_change(Position(1), b, buf, None, env, cx)?;
         assert_eq!(change, cx.add(10)); // Change at start of b range
 
-        let change = next_single_property_change(10, b, buf, None, env, cx)?;
+        let change = next_single_property_change(Position(10), b, buf, None, env, cx)?;
         assert_eq!(change, cx.add(15)); // Change at end of b range
 
         BUFFERS.lock().unwrap().clear();
@@ -710,23 +794,23 @@ mod tests {
         let b = cx.add(b);
 
         // Add properties
-        put_text_property(1, 5, a, cx.add(1), buf, env, cx)?;
-        put_text_property(5, 10, b, cx.add(2), buf, env, cx)?;
+        put_text_property(Position(1), Position(5), a, cx.add(1), buf, env, cx)?;
+        put_text_property(Position(5), Position(10), b, cx.add(2), buf, env, cx)?;
 
         // Remove property :a
-        remove_text_properties(1, 10, a, buf, env, cx)?;
+        remove_text_properties(Position(1), Position(10), a, buf, env, cx)?;
 
         // Verify :a was removed
-        let props = text_properties_at(3, buf, env)?;
+        let props = text_properties_at(Position(3), buf, env)?;
         assert!(props.is_nil());
 
         // Verify :b remains
-        let props = text_properties_at(5, buf, env)?;
+        let props = text_properties_at(Position(5), buf, env)?;
         let val = plist_get(props, b)?;
         assert_eq!(val, cx.add(2));
 
         // Try removing non-existent property
-        remove_text_properties(0, 10, a, buf, env, cx)?;
+        remove_text_properties(Position(0), Position(10), a, buf, env, cx)?;
 
         BUFFERS.lock().unwrap().clear();
         Ok(())
@@ -740,13 +824,13 @@ mod tests {
         root!(env, new(Env), cx);
 
         let buf = get_buffer_create(cx.add("test_text_properties_at"), None, cx)?;
-        let n = text_properties_at(0, buf, env)?;
+        let n = text_properties_at(Position(0), buf, env)?;
         assert!(n.is_nil());
 
         let a = intern(":a", cx);
         let a = cx.add(a);
-        put_text_property(0, 1, a, cx.add(3), buf, env, cx)?;
-        let n = text_properties_at(0, buf, env)?;
+        put_text_property(Position(0), Position(1), a, cx.add(3), buf, env, cx)?;
+        let n = text_properties_at(Position(0), buf, env)?;
         let val = plist_get(n, a)?;
         assert!(eq(val, cx.add(3)));
 
@@ -2,11 +2,12 @@
 use crate::core::{
     env::{Env, sym},
     gc::{Context, Rt},
-    object::Object,
+    object::{Gc, List, Object, ObjectType},
 };
+use anyhow::{Result, bail};
 use rune_core::macros::list;
 use rune_macros::defun;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 defvar!(CURRENT_TIME_LIST, true);
 
@@ -16,7 +17,12 @@ fn current_time<'ob>(cx: &'ob Context, env: &Rt<Env>) -> Object<'ob> {
         env.vars.get(sym::CURRENT_TIME_LIST).unwrap() == &sym::TRUE,
         "current-time-list is nil"
     );
-    let duration = SystemTime::now()
+    time_to_lisp(SystemTime::now(), cx)
+}
+
+/// Convert `time` to a Lisp timestamp of the form (HIGH LOW USEC PSEC).
+pub(crate) fn time_to_lisp<'ob>(time: SystemTime, cx: &'ob Context) -> Object<'ob> {
+    let duration = time
         .duration_since(SystemTime::UNIX_EPOCH)
         .expect("System time is before the epoch");
 
@@ -27,3 +33,35 @@ fn current_time<'ob>(cx: &'ob Context, env: &Rt<Env>) -> Object<'ob> {
 
     list![high, low, micros, 0; cx]
 }
+
+/// Convert a Lisp timestamp to a time. This accepts a number of seconds or a
+/// list of the form (HIGH LOW USEC PSEC), where the trailing elements are
+/// optional.
+pub(crate) fn lisp_to_time(time: Object) -> Result<SystemTime> {
+    let since_epoch = match time.untag() {
+        ObjectType::Int(secs) => Duration::from_secs(u64::try_from(secs)?),
+        ObjectType::Float(secs) => Duration::try_from_secs_f64(**secs)?,
+        ObjectType::Cons(cons) => {
+            let int = |obj: Object| -> Result<i64> { Ok(Gc::<i64>::try_from(obj)?.untag()) };
+            let high = int(cons.car())?;
+            let (low, usec, psec) = match cons.cdr().untag() {
+                // (HIGH . LOW)
+                ObjectType::Int(low) => (low, 0, 0),
+                _ => {
+                    let rest = List::try_from(cons.cdr())?.elements();
+                    let mut parts = [0; 3];
+                    for (part, elem) in parts.iter_mut().zip(rest) {
+                        *part = int(elem?)?;
+                    }
+                    let [low, usec, psec] = parts;
+                    (low, usec, psec)
+                }
+            };
+            let secs = u64::try_from((high << 16) + low)?;
+            let nanos = u64::try_from(usec * 1000 + psec / 1000)?;
+            Duration::from_secs(secs) + Duration::from_nanos(nanos)
+        }
+        _ => bail!("Invalid time specification"),
+    };
+    Ok(SystemTime::UNIX_EPOCH + since_epoch)
+}
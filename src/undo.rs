@@ -0,0 +1,312 @@
+//! Undo recording.
+//!
+//! Each buffer keeps its changes in the buffer-local variable
+//! `buffer-undo-list`, using the same entries as Emacs:
+//!
+//! - `(BEG . END)`: text was inserted between BEG and END
+//! - `(TEXT . POSITION)`: TEXT was deleted at POSITION. If POSITION is negative,
+//!   point was at the end of the deleted text.
+//! - `(nil PROPERTY VALUE BEG . END)`: a text property was changed; VALUE is
+//!   the old value
+//! - `(MARKER . ADJUSTMENT)`: MARKER was moved ADJUSTMENT characters
+//! - `POSITION`: point was at POSITION before the change
+//! - `nil`: an undo boundary
+//!
+//! If the list is `t`, undo information is not recorded. All positions in the
+//! list are 1-based.
+use crate::core::{
+    cons::Cons,
+    env::{CurrentBuffer, Env, sym},
+    gc::{Context, Rt},
+    object::{Gc, LispBuffer, NIL, Object, ObjectType, OpenBuffer, Position, TRUE},
+};
+use crate::editfns::{delete_text, goto_char, insert_object};
+use crate::textprops::put_text_property;
+use anyhow::{Result, bail, ensure};
+use rune_macros::defun;
+
+fn undo_list<'ob>(buffer: &CurrentBuffer, cx: &'ob Context) -> Object<'ob> {
+    buffer.local_var(sym::BUFFER_UNDO_LIST).map_or(NIL, |x| cx.bind(x))
+}
+
+fn push_undo(buffer: &mut CurrentBuffer, entry: Object, cx: &Context) {
+    let list = undo_list(buffer, cx);
+    buffer.set_local_var(sym::BUFFER_UNDO_LIST, Cons::new(entry, list, cx).into());
+}
+
+fn is_recording(buffer: &CurrentBuffer, cx: &Context) -> bool {
+    undo_list(buffer, cx) != TRUE
+}
+
+/// Record where point was at the last boundary, if this change is the first
+/// one after it and is somewhere else. Undoing the change group will then
+/// restore point to where it was.
+fn record_point(buffer: &mut CurrentBuffer, beg: usize, cx: &Context) {
+    let at_boundary = match undo_list(buffer, cx).untag() {
+        ObjectType::Cons(cons) => cons.car().is_nil(),
+        _ => true,
+    };
+    if at_boundary
+        && let Some(point) = buffer.get().undo_point
+        && point != beg
+    {
+        push_undo(buffer, cx.add(point as i64 + 1), cx);
+    }
+}
+
+/// Record that `len` characters were inserted at the 0-based position `beg`.
+pub(crate) fn record_insert(buffer: &mut CurrentBuffer, beg: usize, len: usize, cx: &Context) {
+    if len == 0 || !is_recording(buffer, cx) {
+        return;
+    }
+    record_point(buffer, beg, cx);
+    let (beg, end) = (beg as i64 + 1, (beg + len) as i64 + 1);
+    // If this directly follows the last insertion, extend it instead
+    if let ObjectType::Cons(list) = undo_list(buffer, cx).untag()
+        && let ObjectType::Cons(last) = list.car().untag()
+        && let (ObjectType::Int(_), ObjectType::Int(last_end)) =
+            (last.car().untag(), last.cdr().untag())
+        && last_end == beg
+        && last.set_cdr(cx.add(end)).is_ok()
+    {
+        return;
+    }
+    push_undo(buffer, Cons::new(beg, end, cx).into(), cx);
+}
+
+/// Record that `text` is about to be deleted from the 0-based position `beg`.
+/// This has to be called before the text is removed.
+pub(crate) fn record_delete(buffer: &mut CurrentBuffer, beg: usize, text: &str, cx: &Context) {
+    if text.is_empty() || !is_recording(buffer, cx) {
+        return;
+    }
+    record_point(buffer, beg, cx);
+    let end = beg + text.chars().count();
+    let point = buffer.get().text.cursor().chars();
+    let pos = if point == end { -(beg as i64 + 1) } else { beg as i64 + 1 };
+    push_undo(buffer, Cons::new(text, pos, cx).into(), cx);
+}
+
+/// Record that the text property `prop` had the value `value` between the
+/// buffer positions `beg` and `end` before it was changed.
+pub(crate) fn record_property_change(
+    buffer: &mut CurrentBuffer,
+    beg: usize,
+    end: usize,
+    prop: Object,
+    value: Object,
+    cx: &Context,
+) {
+    if !is_recording(buffer, cx) {
+        return;
+    }
+    let range = Cons::new(beg as i64, end as i64, cx);
+    let entry = Cons::new(NIL, Cons::new(prop, Cons::new(value, range, cx), cx), cx);
+    push_undo(buffer, entry.into(), cx);
+}
+
+/// Mark a boundary between units of undo.
+/// An undo command will stop at this point, but another undo command will
+/// undo to the previous boundary.
+#[defun]
+fn undo_boundary(env: &mut Rt<Env>, cx: &Context) {
+    let buffer = &mut env.current_buffer;
+    match undo_list(buffer, cx).untag() {
+        ObjectType::Cons(list) if !list.car().is_nil() => push_undo(buffer, NIL, cx),
+        ObjectType::Cons(_) | ObjectType::NIL => {}
+        // undo is disabled
+        _ => return,
+    }
+    let point = buffer.get().text.cursor().chars();
+    buffer.get_mut().undo_point = Some(point);
+}
+
+/// Make BUFFER stop keeping undo information.
+/// No argument or nil as argument means do this for the current buffer.
+#[defun]
+fn buffer_disable_undo(buffer: Option<Gc<&LispBuffer>>, env: &mut Rt<Env>) -> Result<()> {
+    set_undo_list(buffer, env, |_| Some(TRUE))
+}
+
+/// Start keeping undo information for buffer BUFFER.
+/// No argument or nil as argument means do this for the current buffer.
+#[defun]
+fn buffer_enable_undo(buffer: Option<Gc<&LispBuffer>>, env: &mut Rt<Env>) -> Result<()> {
+    set_undo_list(buffer, env, |list| (list == Some(TRUE)).then_some(NIL))
+}
+
+/// Replace the undo list of `buffer` with the result of `func`, if any. Only
+/// constants are stored, so other buffers can be updated without tracing them.
+fn set_undo_list(
+    buffer: Option<Gc<&LispBuffer>>,
+    env: &mut Rt<Env>,
+    func: impl Fn(Option<Object>) -> Option<Object<'static>>,
+) -> Result<()> {
+    let update = |b: &mut OpenBuffer| {
+        if let Some(new) = func(b.local_var(sym::BUFFER_UNDO_LIST)) {
+            b.set_local_var(sym::BUFFER_UNDO_LIST, new);
+        }
+    };
+    match buffer {
+        Some(buffer) => env.with_buffer_mut(buffer.untag(), update),
+        None => {
+            update(env.current_buffer.get_mut());
+            Ok(())
+        }
+    }
+}
+
+/// Undo N records from the front of the list LIST.
+/// Return what remains of the list. Undoing a change records the reverse
+/// change in the undo list, so the undo itself can be undone.
+#[defun]
+fn primitive_undo<'ob>(
+    n: i64,
+    list: Object<'ob>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let mut list = list;
+    for _ in 0..n {
+        while let ObjectType::Cons(cons) = list.untag() {
+            list = cons.cdr();
+            let next = cons.car();
+            match next.untag() {
+                // boundary
+                ObjectType::NIL => break,
+                ObjectType::Int(_) => goto_char(next, env)?,
+                ObjectType::Cons(entry) => list = undo_entry(entry, list, env, cx)?,
+                _ => bail!("Unrecognized entry in undo list {next}"),
+            }
+        }
+    }
+    let point = env.current_buffer.get().text.cursor().chars();
+    env.current_buffer.get_mut().undo_point = Some(point);
+    Ok(list)
+}
+
+/// Undo a single compound entry. Returns the rest of the undo list, since
+/// deletions consume the marker adjustments that follow them.
+fn undo_entry<'ob>(
+    entry: &'ob Cons,
+    mut list: Object<'ob>,
+    env: &mut Rt<Env>,
+    cx: &'ob Context,
+) -> Result<Object<'ob>> {
+    let in_range = |env: &Rt<Env>, beg: i64, end: i64| -> Result<()> {
+        let buffer = env.current_buffer.get();
+        let (min, max) = (buffer.point_min() as i64, buffer.point_max() as i64);
+        ensure!(
+            min <= beg && end <= max,
+            "Changes to be undone are outside visible portion of buffer"
+        );
+        Ok(())
+    };
+    match (entry.car().untag(), entry.cdr().untag()) {
+        // (t . TIME) records the first change after visiting a file
+        (ObjectType::TRUE, _) => {}
+        // (nil PROPERTY VALUE BEG . END)
+        (ObjectType::NIL, ObjectType::Cons(args)) => {
+            let prop_args: &Cons = args.cdr().try_into()?;
+            let range: &Cons = prop_args.cdr().try_into()?;
+            let (beg, end): (i64, i64) = (range.car().try_into()?, range.cdr().try_into()?);
+            in_range(env, beg, end)?;
+            let (prop, value) = (args.car(), prop_args.car());
+            put_text_property(
+                Position(beg as usize),
+                Position(end as usize),
+                prop,
+                value,
+                NIL,
+                env,
+                cx,
+            )?;
+        }
+        // (BEG . END)
+        (ObjectType::Int(beg), ObjectType::Int(end)) => {
+            in_range(env, beg, end)?;
+            goto_char(cx.add(beg), env)?;
+            delete_text(&mut env.current_buffer, beg as usize, end as usize, cx)?;
+        }
+        // (TEXT . POSITION)
+        (ObjectType::String(text), ObjectType::Int(pos)) => {
+            let abs_pos = pos.abs();
+            in_range(env, abs_pos, abs_pos)?;
+            // Marker adjustments recorded with the deletion are only applied if
+            // all of them still point at the deletion.
+            let mut adjustments = Vec::new();
+            let mut valid = true;
+            while let ObjectType::Cons(cons) = list.untag()
+                && let ObjectType::Cons(adj) = cons.car().untag()
+                && let (ObjectType::Marker(marker), ObjectType::Int(offset)) =
+                    (adj.car().untag(), adj.cdr().untag())
+            {
+                list = cons.cdr();
+                let here = marker.buffer().is_some_and(|b| *env.current_buffer == *b);
+                if here && marker.position() == Some(abs_pos as usize - 1) {
+                    adjustments.push((marker, offset));
+                } else {
+                    valid = false;
+                }
+            }
+            goto_char(cx.add(abs_pos), env)?;
+            insert_object(&mut env.current_buffer, text.into(), cx)?;
+            if pos > 0 {
+                goto_char(cx.add(pos), env)?;
+            }
+            if valid {
+                for (marker, offset) in adjustments {
+                    let position = marker.position().unwrap_or(0) as i64 - offset;
+                    let position = usize::try_from(position).unwrap_or(0);
+                    env.current_buffer.get_mut().set_marker(marker, position);
+                }
+            }
+        }
+        // (MARKER . ADJUSTMENT)
+        (ObjectType::Marker(marker), ObjectType::Int(offset)) => {
+            if let (Some(buffer), Some(pos)) = (marker.buffer(), marker.position()) {
+                let position = usize::try_from(pos as i64 - offset).unwrap_or(0);
+                env.with_buffer_mut(buffer, |b| b.set_marker(marker, position))?;
+            }
+        }
+        // TODO: support (apply FUN-NAME . ARGS)
+        (ObjectType::Symbol(s), _) if s == sym::APPLY => {
+            bail!("apply entries in undo lists are not supported")
+        }
+        _ => bail!("Unrecognized entry in undo list {entry}"),
+    }
+    Ok(list)
+}
+
+defvar!(BUFFER_UNDO_LIST);
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::assert_lisp;
+
+    #[test]
+    fn test_record_changes() {
+        assert_lisp("(progn (insert \"ab\") (insert \"cd\") buffer-undo-list)", "((1 . 5))");
+        assert_lisp(
+            "(progn (insert \"abcd\") (undo-boundary) (delete-region 2 4) buffer-undo-list)",
+            "((\"bc\" . 2) 5 nil (1 . 5))",
+        );
+        assert_lisp(
+            "(progn (insert \"abc\") (put-text-property 1 3 'face 'bold nil) (car buffer-undo-list))",
+            "(nil face nil 1 . 3)",
+        );
+        assert_lisp("(progn (buffer-disable-undo) (insert \"ab\") buffer-undo-list)", "t");
+    }
+
+    #[test]
+    fn test_primitive_undo() {
+        assert_lisp(
+            "(progn (insert \"abcd\") (undo-boundary) (delete-region 2 4) (primitive-undo 1 buffer-undo-list) (list (point-max) (point)))",
+            "(5 5)",
+        );
+        assert_lisp(
+            "(progn (insert \"ab\") (undo-boundary) (insert \"cd\") (primitive-undo 1 buffer-undo-list) (point-max))",
+            "3",
+        );
+    }
+}